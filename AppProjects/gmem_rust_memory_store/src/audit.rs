@@ -0,0 +1,84 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::record::MemoryRecord;
+use crate::timestamp::now_iso;
+
+/// 审计日志记录的变更类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOp {
+    /// 添加（包括导入）
+    Add,
+    /// 修改已有记录（如重新生成 ID、tidy 规范化）
+    Update,
+    /// 软删除
+    Delete,
+    /// 永久删除
+    Purge,
+}
+
+/// 审计日志中的一行（JSONL）
+/// 只记录文本的哈希而不是原文，审计日志里不会出现记忆内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// 变更时间（ISO 8601）
+    pub timestamp: String,
+    /// 变更类型
+    pub op: AuditOp,
+    /// 记忆 ID
+    pub id: String,
+    /// 记忆文本的哈希（`fnv1a64:<16 位十六进制>`）
+    pub text_hash: String,
+}
+
+/// 计算记忆文本的哈希（FNV-1a 64 位，跨版本、跨平台稳定）
+///
+/// # 参数
+/// * `text` - 记忆文本
+///
+/// # 返回
+/// `fnv1a64:<16 位十六进制>`
+pub fn text_hash(text: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("fnv1a64:{:016x}", hash)
+}
+
+/// 向审计日志追加一批记录的变更
+///
+/// # 参数
+/// * `path` - 审计日志路径（目录不存在时自动创建）
+/// * `op` - 变更类型
+/// * `records` - 被变更的记录
+///
+/// # 返回
+/// 写入结果
+pub fn append_audit_entries<'a>(path: &Path, op: AuditOp, records: impl IntoIterator<Item = &'a MemoryRecord>) -> io::Result<()> {
+    let timestamp = now_iso();
+    let mut lines = String::new();
+    for record in records {
+        let entry = AuditEntry {
+            timestamp: timestamp.clone(),
+            op,
+            id: record.id.clone(),
+            text_hash: text_hash(&record.text),
+        };
+        lines.push_str(&serde_json::to_string(&entry).map_err(io::Error::other)?);
+        lines.push('\n');
+    }
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    // 一次写入整批记录，减少并发追加时行交错的可能
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(lines.as_bytes())
+}
@@ -0,0 +1,135 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use chrono::Local;
+
+/// 写入前备份（`backup_before_write`）默认保留的备份数量
+pub const DEFAULT_BACKUP_KEEP: usize = 5;
+
+/// 为文件创建带时间戳的备份
+/// 备份文件名格式：<原文件名>.<YYYYMMDDTHHMMSS>.bak
+///
+/// # 参数
+/// * `path` - 要备份的文件
+/// * `backup_dir` - 备份目录（可选，默认与原文件同目录）
+///
+/// # 返回
+/// 备份文件路径
+pub fn backup_file(path: &Path, backup_dir: Option<&Path>) -> io::Result<PathBuf> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("Not a file path: {}", path.display()))
+    })?;
+
+    let dir = match backup_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    fs::create_dir_all(&dir)?;
+
+    let stamp = Local::now().format("%Y%m%dT%H%M%S");
+    let mut backup_path = dir.join(format!("{}.{}.bak", file_name.to_string_lossy(), stamp));
+    // 同一秒内重复备份时追加序号，避免覆盖已有备份
+    let mut n = 1;
+    while backup_path.exists() {
+        backup_path = dir.join(format!("{}.{}-{}.bak", file_name.to_string_lossy(), stamp, n));
+        n += 1;
+    }
+
+    fs::copy(path, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// 删除多余的旧备份，只保留最近的 `keep` 个（按修改时间判断新旧）
+/// 只处理 `backup_file` 在原文件同目录生成的 `<原文件名>.*.bak`
+///
+/// # 参数
+/// * `path` - 被备份的文件
+/// * `keep` - 保留的备份数量
+///
+/// # 返回
+/// 删除的备份数量
+pub fn prune_backups(path: &Path, keep: usize) -> io::Result<usize> {
+    let file_name = match path.file_name() {
+        Some(name) => format!("{}.", name.to_string_lossy()),
+        None => return Ok(0),
+    };
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut backups: Vec<(std::time::SystemTime, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(&dir)?.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&file_name) && name.ends_with(".bak") {
+            let modified = entry.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH);
+            backups.push((modified, entry.path()));
+        }
+    }
+    if backups.len() <= keep {
+        return Ok(0);
+    }
+
+    // 最新的排在前面，删除第 keep 个之后的备份
+    backups.sort_by(|a, b| b.cmp(a));
+    let mut removed = 0;
+    for (_, old) in backups.into_iter().skip(keep) {
+        fs::remove_file(old)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// 永久删除日志（purge journal）的文件名前缀，完整文件名为 `.purge-journal-<时间>.json`
+pub const PURGE_JOURNAL_PREFIX: &str = ".purge-journal-";
+
+/// 默认保留的永久删除日志数量
+pub const DEFAULT_PURGE_JOURNAL_KEEP: usize = 20;
+
+/// 生成新的永久删除日志路径（时间精确到毫秒，同名时追加序号）
+///
+/// # 参数
+/// * `dir` - 记忆目录
+///
+/// # 返回
+/// 日志文件路径（尚未创建）
+pub fn purge_journal_path(dir: &Path) -> PathBuf {
+    let stamp = Local::now().format("%Y%m%dT%H%M%S%3f");
+    let mut path = dir.join(format!("{}{}.json", PURGE_JOURNAL_PREFIX, stamp));
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("{}{}-{}.json", PURGE_JOURNAL_PREFIX, stamp, n));
+        n += 1;
+    }
+    path
+}
+
+/// 删除多余的旧永久删除日志，只保留最近的 `keep` 个（文件名中的时间可直接按字典序比较）
+///
+/// # 参数
+/// * `dir` - 记忆目录
+/// * `keep` - 保留的日志数量
+///
+/// # 返回
+/// 删除的日志数量
+pub fn prune_purge_journals(dir: &Path, keep: usize) -> io::Result<usize> {
+    let mut journals: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.starts_with(PURGE_JOURNAL_PREFIX) && name.ends_with(".json")
+        })
+        .map(|entry| entry.path())
+        .collect();
+    if journals.len() <= keep {
+        return Ok(0);
+    }
+
+    journals.sort_by(|a, b| b.cmp(a));
+    let mut removed = 0;
+    for old in journals.into_iter().skip(keep) {
+        fs::remove_file(old)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
@@ -1,83 +1,66 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::env;
+use gmem_rust_memory_store::backup::backup_file;
+use gmem_rust_memory_store::config::{load_config, get_config_file_path, get_memory_path};
+use gmem_rust_memory_store::{confirm, parse_records};
+use gmem_rust_memory_store::{CATEGORY_FILE_SUFFIX, LEGACY_CATEGORY_FILE_SUFFIX};
 
-/// 从.env.toml文件中读取memory_path配置
-fn read_memory_path() -> String {
-    // 尝试从多个相对位置读取配置文件
-    let config_paths = [
-        "./config/.env.toml",
-        "./bin/config/.env.toml",
-        "../config/.env.toml",
-        "../../config/.env.toml",
-        "../../../config/.env.toml",
-        "../../../../config/.env.toml",
-        "./GmemWorker/bin/config/.env.toml",
-        "../GmemWorker/bin/config/.env.toml",
-        "../../GmemWorker/bin/config/.env.toml"
-    ];
-    
-    for config_path in &config_paths {
-        if Path::new(config_path).exists() {
-            println!("读取配置文件: {}", config_path);
-            
-            // 读取配置文件内容
-            if let Ok(content) = fs::read_to_string(config_path) {
-                // 简单解析memory_path行
-                for line in content.lines() {
-                    let line = line.trim();
-                    if line.starts_with("memory_path = ") {
-                        // 提取配置值
-                        let value = line.strip_prefix("memory_path = ").unwrap_or("");
-                        // 处理引号和环境变量
-                        let value = value.trim_matches('"');
-                        
-                        // 处理环境变量或默认值格式
-                        if value.contains("|") {
-                            let parts: Vec<&str> = value.split('|').collect();
-                            for part in parts {
-                                let part = part.trim();
-                                if part.starts_with("%") && part.ends_with("%") {
-                                    // 尝试获取环境变量
-                                    let env_var = part.trim_matches('%');
-                                    if let Ok(env_value) = env::var(env_var) {
-                                        return env_value;
-                                    }
-                                } else if !part.is_empty() {
-                                    // 使用默认值
-                                    let default_value = part.trim_matches('"');
-                                    // 检查默认值是否也是环境变量格式
-                                    if default_value.starts_with("%") && default_value.ends_with("%") {
-                                        let env_var = default_value.trim_matches('%');
-                                        if let Ok(env_value) = env::var(env_var) {
-                                            return env_value;
-                                        }
-                                    }
-                                    return default_value.to_string();
-                                }
-                            }
-                        } else if value.starts_with("%") && value.ends_with("%") {
-                            // 尝试获取环境变量
-                            let env_var = value.trim_matches('%');
-                            if let Ok(env_value) = env::var(env_var) {
-                                return env_value;
-                            }
-                        } else {
-                            return value.to_string();
-                        }
-                    }
-                }
+/// 清理选项
+struct CleanOptions {
+    /// 跳过确认提示
+    yes: bool,
+    /// 删除前不备份
+    no_backup: bool,
+    /// 只列出将被删除的文件和记录数，不删除
+    dry_run: bool,
+}
+
+/// 查找要清理的记忆文件
+///
+/// # 参数
+/// * `memory_path` - 记忆目录或单个记忆文件
+///
+/// # 返回
+/// 匹配的记忆文件列表
+fn find_memory_files(memory_path: &str) -> std::io::Result<Vec<PathBuf>> {
+    let path = Path::new(memory_path);
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let file_path = entry?.path();
+        if file_path.is_file() {
+            let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+            if file_name.ends_with(CATEGORY_FILE_SUFFIX) || file_name.ends_with(LEGACY_CATEGORY_FILE_SUFFIX) {
+                files.push(file_path);
             }
         }
     }
-    
-    // 默认路径
-    println!("未找到配置文件，使用默认路径");
-    "E:\\GmemWorkerHome".to_string()
+    files.sort();
+    Ok(files)
 }
 
-/// 清理所有记忆文件
-fn clean_all_memories(memory_path: &str) -> std::io::Result<()> {
+/// 统计记忆文件中的活跃记录数
+///
+/// # 参数
+/// * `path` - 记忆文件路径
+///
+/// # 返回
+/// 活跃记录数，文件无法解析时返回错误
+fn count_active_records(path: &Path) -> std::io::Result<usize> {
+    let raw = fs::read_to_string(path)?;
+    if raw.trim().is_empty() {
+        return Ok(0);
+    }
+    let records = parse_records(&raw)?;
+    Ok(records.iter().filter(|r| r.deleted_at.is_none()).count())
+}
+
+/// 清理所有记忆文件（默认先确认并备份）
+fn clean_all_memories(memory_path: &str, options: &CleanOptions) -> std::io::Result<()> {
     let path = Path::new(memory_path);
     
     if !path.exists() {
@@ -85,43 +68,150 @@ fn clean_all_memories(memory_path: &str) -> std::io::Result<()> {
         return Ok(());
     }
     
-    if path.is_dir() {
-        // 清理目录中的所有记忆文件
-        println!("清理目录中的记忆文件: {}", memory_path);
-        
-        let entries = fs::read_dir(path)?;
-        for entry in entries {
-            let entry = entry?;
-            let file_path = entry.path();
-            
-            // 检查是否是记忆文件
-            if file_path.is_file() {
-                let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
-                if file_name.contains("-global-gmem-recoder.json") || file_name == "default-global-gmem-recoder.json" {
-                    println!("删除记忆文件: {}", file_path.display());
-                    fs::remove_file(file_path)?;
-                }
+    let files = find_memory_files(memory_path)?;
+    if files.is_empty() {
+        println!("没有找到记忆文件: {}", memory_path);
+        return Ok(());
+    }
+
+    println!("将删除以下 {} 个记忆文件:", files.len());
+    let mut total_active = 0;
+    for file in &files {
+        match count_active_records(file) {
+            Ok(count) => {
+                total_active += count;
+                println!("  - {} ({} 条活跃记忆)", file.display(), count);
             }
+            Err(e) => println!("  - {} (无法解析: {})", file.display(), e),
+        }
+    }
+    println!("共 {} 条活跃记忆将被删除", total_active);
+
+    if options.dry_run {
+        println!("[预览模式] 未删除任何文件");
+        return Ok(());
+    }
+
+    if !options.yes && !confirm("确认删除以上记忆文件？") {
+        println!("已取消，未删除任何文件");
+        return Ok(());
+    }
+
+    for file in &files {
+        if !options.no_backup {
+            let backup = backup_file(file, None)?;
+            println!("已备份: {} -> {}", file.display(), backup.display());
         }
-    } else {
-        // 清理单个记忆文件
-        println!("清理记忆文件: {}", memory_path);
-        fs::remove_file(path)?;
+        println!("删除记忆文件: {}", file.display());
+        fs::remove_file(file)?;
     }
     
     println!("清理完成!");
     Ok(())
 }
 
+/// 通过配置模块解析要清理的记忆路径（与其他工具一致，支持环境变量与 `|` 备选值）
+///
+/// # 参数
+/// * `config_path` - 配置文件路径（None 表示默认配置文件）
+///
+/// # 返回
+/// 记忆路径
+fn resolve_memory_path(config_path: Option<&str>) -> String {
+    let config = load_config(config_path);
+    println!("读取配置文件: {}", get_config_file_path(config_path));
+    get_memory_path(&config)
+}
+
 fn main() {
-    // 读取memory_path配置
-    let memory_path = read_memory_path();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = CleanOptions {
+        yes: args.iter().any(|a| a == "--yes" || a == "-y"),
+        no_backup: args.iter().any(|a| a == "--no-backup"),
+        dry_run: args.iter().any(|a| a == "--dry-run"),
+    };
+
+    let memory_path = resolve_memory_path(None);
     
     // 清理所有记忆
-    match clean_all_memories(&memory_path) {
+    match clean_all_memories(&memory_path, &options) {
         Ok(_) => println!("记忆清理成功!"),
         Err(e) => println!("记忆清理失败: {}", e),
     }
     
     println!("操作完成!");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在目录中写入一个分类文件
+    fn write_category(dir: &Path, category: &str, content: &str) -> PathBuf {
+        let path = dir.join(format!("{}{}", category, CATEGORY_FILE_SUFFIX));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// 列出目录中的备份文件
+    fn backups(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir).unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "bak"))
+            .collect()
+    }
+
+    #[test]
+    fn files_are_backed_up_before_removal() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_category(dir.path(), "rust", "[]");
+        let options = CleanOptions { yes: true, no_backup: false, dry_run: false };
+
+        clean_all_memories(dir.path().to_str().unwrap(), &options).unwrap();
+
+        assert!(!file.exists());
+        let saved = backups(dir.path());
+        assert_eq!(saved.len(), 1);
+        assert_eq!(fs::read_to_string(&saved[0]).unwrap(), "[]");
+
+        let file = write_category(dir.path(), "git", "[]");
+        let options = CleanOptions { yes: true, no_backup: true, dry_run: false };
+        clean_all_memories(dir.path().to_str().unwrap(), &options).unwrap();
+        assert!(!file.exists());
+        assert_eq!(backups(dir.path()).len(), 1);
+    }
+
+    #[test]
+    fn memory_path_matches_config_resolution() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("config.toml");
+        let memory_dir = dir.path().join("memories");
+        fs::write(&config_file, format!(
+            "memory_path = {:?}\n",
+            format!("%GMEM_CLEANALL_TEST_UNSET%|{}", memory_dir.display())
+        )).unwrap();
+        let config_path = config_file.to_str().unwrap();
+
+        let resolved = resolve_memory_path(Some(config_path));
+        assert_eq!(resolved, get_memory_path(&load_config(Some(config_path))));
+        assert_eq!(resolved, memory_dir.to_string_lossy());
+    }
+
+    #[test]
+    fn dry_run_lists_files_without_deleting() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_category(dir.path(), "rust", r#"[
+            {"id": "m_1", "text": "kept", "tags": [], "keywords": [], "created_at": "2024-01-01T00:00:00.000Z", "updated_at": "2024-01-01T00:00:00.000Z"},
+            {"id": "m_2", "text": "gone", "tags": [], "keywords": [], "created_at": "2024-01-01T00:00:00.000Z", "updated_at": "2024-01-01T00:00:00.000Z", "deleted_at": "2024-01-02T00:00:00.000Z"}
+        ]"#);
+        let memory_path = dir.path().to_str().unwrap();
+
+        assert_eq!(find_memory_files(memory_path).unwrap(), vec![file.clone()]);
+        assert_eq!(count_active_records(&file).unwrap(), 1);
+
+        let options = CleanOptions { yes: true, no_backup: true, dry_run: true };
+        clean_all_memories(memory_path, &options).unwrap();
+        assert!(file.exists());
+        assert!(backups(dir.path()).is_empty());
+    }
+}
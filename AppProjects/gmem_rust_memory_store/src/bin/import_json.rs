@@ -2,6 +2,7 @@ use std::fs;
 use std::path::PathBuf;
 use serde_json;
 use gmem_rust_memory_store::record::MemoryRecord;
+use gmem_rust_memory_store::parse_records;
 use gmem_rust_memory_store::config::{load_config, get_memory_path};
 
 /// 读取JSON记忆文件
@@ -15,7 +16,7 @@ fn read_json_file(file_path: &str) -> Result<Vec<MemoryRecord>, String> {
     let content = fs::read_to_string(file_path)
         .map_err(|e| format!("无法读取文件: {}", e))?;
     
-    let records: Vec<MemoryRecord> = serde_json::from_str(&content)
+    let records = parse_records(&content)
         .map_err(|e| format!("JSON解析失败: {}", e))?;
     
     Ok(records)
@@ -1,6 +1,7 @@
 use std::fs;
-use std::process::{Command, Stdio};
 use serde_json;
+use gmem_rust_memory_store::{load_config, LockType, MemoryStore, SOURCE_IMPORT_JSON};
+use gmem_rust_memory_store::import_batch::{print_failure_report, ImportFailure};
 
 // JSON记忆导入工具
 // 功能：读取JSON格式的记忆文件，批量导入到记忆系统中
@@ -29,89 +30,43 @@ fn read_json_file(file_path: &str) -> Result<Vec<MemoryRecord>, String> {
     Ok(records)
 }
 
-/// 转换标签为逗号分隔的字符串
-pub fn tags_to_string(tags: &[String]) -> String {
-    tags.join(", ")
-}
-
-/// 导入记忆到系统
-pub fn import_memory(text: &str, tags: &[String]) -> Result<(), String> {
-    // 构建JSON请求
-    let tags_str = tags_to_string(tags);
-    let request = serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": 1,
-        "method": "tools/call",
-        "params": {
-            "name": "add_memory",
-            "arguments": {
-                "text": text,
-                "tags": tags_str
-            }
-        }
-    });
-    
-    let json_payload = serde_json::to_string(&request)
-        .map_err(|e| format!("JSON序列化失败: {}", e))?;
-    
-    // 调用gmemory_mcp_server.exe
-    let mcp_server_path = "V:/git_data/GmemWorker/GmemWorker/bin/gmemory_mcp_server.exe";
-    let bin_dir = "V:/git_data/GmemWorker/GmemWorker/bin";
-    
-    let mut cmd = Command::new(mcp_server_path)
-        .current_dir(bin_dir)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("执行命令失败: {}", e))?;
-    
-    // 写入输入
-    if let Some(stdin) = &mut cmd.stdin {
-        std::io::Write::write_all(stdin, json_payload.as_bytes())
-            .map_err(|e| format!("写入输入失败: {}", e))?;
-    }
-    
-    // 等待命令执行完成
-    let output = cmd.wait_with_output()
-        .map_err(|e| format!("等待命令执行失败: {}", e))?;
-    
-    if output.status.success() {
-        Ok(())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("导入失败: {}", stderr))
-    }
-}
-
 /// 批量导入记忆
-fn import_memories(records: &[MemoryRecord]) -> (usize, usize, usize) {
-    let mut success_count = 0;
-    let mut fail_count = 0;
+/// 所有记忆一次加锁、一次写入；单条失败（如文本为空）不会中断批量导入，失败项收集后在最后统一列出
+///
+/// # 返回
+/// (成功数, 跳过数, 失败项)
+fn import_memories(store: &MemoryStore, records: &[MemoryRecord]) -> Result<(usize, usize, Vec<ImportFailure>), String> {
+    let mut indexes = Vec::new();
+    let mut items = Vec::new();
     let mut skip_count = 0;
-    
+
     for (index, record) in records.iter().enumerate() {
-        println!("-------------------------------------");
-        println!("导入记忆 {} / {}", index + 1, records.len());
-        
         // 检查是否已删除
         if record.deleted_at.is_some() {
             println!("跳过已删除的记忆: {}", record.id);
             skip_count += 1;
             continue;
         }
-        
-        // 导入记忆
-        match import_memory(&record.text, &record.tags) {
+        indexes.push(index);
+        items.push((record.text.clone(), record.tags.clone()));
+    }
+
+    let results = store.add_many_with_source(&items, Some(SOURCE_IMPORT_JSON))
+        .map_err(|e| format!("写入记忆文件失败: {}", e))?;
+
+    let mut success_count = 0;
+    let mut failures = Vec::new();
+    for (index, result) in indexes.into_iter().zip(results) {
+        match result {
             Ok(_) => success_count += 1,
             Err(e) => {
                 println!("导入失败: {}", e);
-                fail_count += 1;
+                failures.push(ImportFailure { index: index + 1, title: records[index].id.clone(), error: e.to_string() });
             }
         }
     }
-    
-    (success_count, fail_count, skip_count)
+
+    Ok((success_count, skip_count, failures))
 }
 
 fn main() {
@@ -137,12 +92,64 @@ fn main() {
     println!("找到 {} 条记忆", records.len());
     println!("=====================================");
     
-    let (success, fail, skip) = import_memories(&records);
+    let config = load_config(None);
+    let store = MemoryStore::new(config.memory_path.as_deref(), Some(LockType::Cli)).apply_config(&config);
+    if let Err(e) = store.ensure_storage() {
+        println!("错误: {}", e);
+        std::process::exit(1);
+    }
+    println!("记忆文件: {}", store.get_memory_path().display());
+
+    let (success, skip, failures) = match import_memories(&store, &records) {
+        Ok(counts) => counts,
+        Err(e) => {
+            println!("错误: {}", e);
+            std::process::exit(1);
+        }
+    };
     
     println!("=====================================");
     println!("导入完成!");
     println!("成功: {}", success);
-    println!("失败: {}", fail);
+    println!("失败: {}", failures.len());
     println!("跳过: {}", skip);
     println!("总计: {}", records.len());
-}
\ No newline at end of file
+    
+    print_failure_report(&failures);
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, text: &str) -> MemoryRecord {
+        MemoryRecord {
+            id: id.to_string(),
+            text: text.to_string(),
+            tags: vec!["imported".to_string()],
+            _created_at: "2000-01-01T00:00:00.000Z".to_string(),
+            _updated_at: "2000-01-01T00:00:00.000Z".to_string(),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn failed_item_is_reported_and_the_rest_still_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path().join("memory.json").to_str(), Some(LockType::Cli));
+        // 第 2 项文本为空，添加时被拒绝
+        let records = vec![record("m_1", "first"), record("m_2", "   "), record("m_3", "third"), record("m_4", "fourth")];
+
+        let (success, skip, failures) = import_memories(&store, &records).unwrap();
+        assert_eq!((success, skip), (3, 0));
+        assert_eq!(failures.len(), 1);
+        assert_eq!((failures[0].index, failures[0].title.as_str()), (2, "m_2"));
+        assert!(!failures[0].error.is_empty());
+
+        let texts: Vec<String> = store.load().unwrap().into_iter().map(|r| r.text).collect();
+        assert_eq!(texts, vec!["first", "third", "fourth"]);
+    }
+}
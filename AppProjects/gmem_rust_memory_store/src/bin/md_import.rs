@@ -2,6 +2,8 @@ use std::fs;
 use std::path::Path;
 use std::process::{Command, Stdio};
 use serde_json;
+use gmem_rust_memory_store::file_source;
+use gmem_rust_memory_store::import_batch::{check_add_response, print_failure_report, ImportFailure};
 
 // MD文件解析工具
 // 功能：读取MD文件，解析标题层级，提取内容，批量导入为记忆
@@ -156,7 +158,7 @@ pub fn generate_tags(section: &MdSection, sections: &[MdSection]) -> Vec<String>
 }
 
 /// 导入记忆到系统
-pub fn import_memory(text: &str, tags: &[String]) -> Result<(), String> {
+pub fn import_memory(text: &str, tags: &[String], source: &str) -> Result<(), String> {
     // 构建JSON请求
     let tags_str = tags.join(", ");
     let request = serde_json::json!({
@@ -167,7 +169,8 @@ pub fn import_memory(text: &str, tags: &[String]) -> Result<(), String> {
             "name": "add_memory",
             "arguments": {
                 "text": text,
-                "tags": tags_str
+                "tags": tags_str,
+                "source": source
             }
         }
     });
@@ -197,18 +200,13 @@ pub fn import_memory(text: &str, tags: &[String]) -> Result<(), String> {
     let output = cmd.wait_with_output()
         .map_err(|e| format!("等待命令执行失败: {}", e))?;
     
-    let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
     
     if !output.status.success() {
         return Err(format!("命令执行失败: {}", stderr));
     }
     
-    if stdout.contains("error") {
-        return Err(format!("添加记忆失败: {}", stdout));
-    }
-    
-    Ok(())
+    check_add_response(&String::from_utf8_lossy(&output.stdout))
 }
 
 fn main() {
@@ -271,8 +269,10 @@ fn main() {
     println!("=====================================");
     
     // 导入记忆
+    let source = file_source("md", md_file);
+    // 单个章节失败不会中断批量导入，失败项收集后在最后统一列出
     let mut success_count = 0;
-    let mut fail_count = 0;
+    let mut failures = Vec::new();
     let remove_lock_path = "V:/git_data/GmemWorker/AppProjects/gmem_rust_memory_store/target/debug/remove_lock.exe";
     
     for (i, section) in sections.iter().enumerate() {
@@ -313,14 +313,14 @@ fn main() {
         let tags = generate_tags(section, &sections);
         
         // 导入记忆
-        match import_memory(&memory_text, &tags) {
+        match import_memory(&memory_text, &tags, &source) {
             Ok(_) => {
                 println!("导入成功");
                 success_count += 1;
             }
             Err(e) => {
                 println!("导入失败: {}", e);
-                fail_count += 1;
+                failures.push(ImportFailure { index: i + 1, title: section.title.clone(), error: e });
             }
         }
         
@@ -331,6 +331,11 @@ fn main() {
     println!("=====================================");
     println!("导入完成!");
     println!("成功: {}", success_count);
-    println!("失败: {}", fail_count);
+    println!("失败: {}", failures.len());
     println!("总章节: {}", sections.len());
+    
+    print_failure_report(&failures);
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
 }
\ No newline at end of file
@@ -3,11 +3,14 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use gmem_rust_memory_store::config::{load_config, Config};
 
 /// 记忆整理定时器工具（常驻版本）
 /// 功能：常驻系统，按指定间隔自动运行记忆整理工具，确保没有重复定义的规则，每条规则都在正确分类下
 /// 使用方法：
 ///   - organize_timer.exe <间隔小时数>  : 常驻模式，每30分钟检查一次，间隔小时数后执行整理
+///   - organize_timer.exe <间隔小时数> --no-run-on-start : 常驻模式，首次整理等待一个完整间隔
+///   - organize_timer.exe <间隔小时数> --config <file> : 使用指定的配置文件（同时传给记忆整理工具）
 ///   - organize_timer.exe once        : 单次执行模式，执行一次整理后退出（可与常驻模式共存）
 
 /// 获取当前可执行文件所在目录
@@ -137,6 +140,33 @@ fn get_lock_file() -> String {
     }
 }
 
+/// 读取命令行中 `--config` 指定的配置文件
+///
+/// # 参数
+/// * `args` - 完整的命令行参数
+///
+/// # 返回
+/// 配置文件路径，未指定时返回 None（使用可执行文件目录下的 config/.env.toml）
+fn config_path_arg(args: &[String]) -> Option<&str> {
+    args.iter()
+        .position(|a| a == "--config")
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+/// 判断常驻模式启动时是否立即整理
+/// `--no-run-on-start` 优先，否则读取配置中的 `organize_run_on_start`（未设置时为是）
+///
+/// # 参数
+/// * `args` - 完整的命令行参数
+/// * `config` - 配置结构体
+///
+/// # 返回
+/// 是否在启动时整理
+fn run_on_start(args: &[String], config: &Config) -> bool {
+    !args.iter().any(|a| a == "--no-run-on-start") && config.organize_run_on_start.unwrap_or(true)
+}
+
 /// 展开环境变量
 ///
 /// # 参数
@@ -225,6 +255,24 @@ fn get_current_timestamp() -> u64 {
         .as_secs()
 }
 
+/// 计算判断是否到期时使用的上次运行时间
+/// 不在启动时整理时，把定时器启动时间视为一次运行，首次整理至少等待一个完整间隔
+///
+/// # 参数
+/// * `last_run_time` - 时间戳文件中的上次运行时间
+/// * `started_at` - 定时器启动时间
+/// * `run_on_start` - 是否允许启动时立即整理
+///
+/// # 返回
+/// 生效的上次运行时间
+fn effective_last_run_time(last_run_time: Option<u64>, started_at: u64, run_on_start: bool) -> Option<u64> {
+    if run_on_start {
+        last_run_time
+    } else {
+        Some(last_run_time.unwrap_or(0).max(started_at))
+    }
+}
+
 /// 检查是否需要运行整理
 ///
 /// # 参数
@@ -246,9 +294,12 @@ fn should_run_organize(last_run_time: Option<u64>, interval_hours: u64) -> bool
 
 /// 运行记忆整理工具
 ///
+/// # 参数
+/// * `config_path` - 传给记忆整理工具的配置文件（可选）
+///
 /// # 返回
 /// 操作结果
-fn run_organize_tool() -> Result<(), String> {
+fn run_organize_tool(config_path: Option<&str>) -> Result<(), String> {
     let tool_path = get_gmemory_store_path();
     let exe_dir = get_exe_dir();
     
@@ -258,6 +309,7 @@ fn run_organize_tool() -> Result<(), String> {
     
     let output = Command::new(&tool_path)
         .current_dir(&exe_dir)
+        .args(config_path.map(|path| ["--config", path]).iter().flatten())
         .arg("--direct-organize")
         .output()
         .map_err(|e| format!("执行记忆整理工具失败: {}", e))?;
@@ -271,9 +323,35 @@ fn run_organize_tool() -> Result<(), String> {
     
     println!("{}", stdout);
     
+    record_stats_snapshot(&tool_path, &exe_dir, config_path);
+    
     Ok(())
 }
 
+/// 整理完成后记录一次统计快照（追加到记忆目录的 stats-history.csv）
+/// 快照只用于观察记忆库增长，失败时仅打印警告，不影响整理结果
+///
+/// # 参数
+/// * `tool_path` - GmemoryStore.exe 路径
+/// * `exe_dir` - 工作目录
+/// * `config_path` - 传给记忆整理工具的配置文件（可选）
+fn record_stats_snapshot(tool_path: &Path, exe_dir: &Path, config_path: Option<&str>) {
+    match Command::new(tool_path)
+        .current_dir(exe_dir)
+        .args(config_path.map(|path| ["--config", path]).iter().flatten())
+        .arg("stats-snapshot")
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            print!("{}", String::from_utf8_lossy(&output.stdout));
+        }
+        Ok(output) => {
+            println!("警告: 记录统计快照失败: {}", String::from_utf8_lossy(&output.stderr));
+        }
+        Err(e) => println!("警告: 记录统计快照失败: {}", e),
+    }
+}
+
 /// 获取格式化的当前时间
 ///
 /// # 返回
@@ -286,9 +364,12 @@ fn get_formatted_time() -> String {
 
 /// 单次执行模式
 ///
+/// # 参数
+/// * `config_path` - 传给记忆整理工具的配置文件（可选）
+///
 /// # 返回
 /// 操作结果
-fn run_once() -> Result<(), String> {
+fn run_once(config_path: Option<&str>) -> Result<(), String> {
     println!("========================================");
     println!("记忆整理工具（单次执行模式）");
     println!("========================================");
@@ -301,7 +382,7 @@ fn run_once() -> Result<(), String> {
         println!("执行单次整理...\n");
     }
     
-    match run_organize_tool() {
+    match run_organize_tool(config_path) {
         Ok(_) => {
             let timestamp_file = get_timestamp_file();
             let current_time = get_current_timestamp();
@@ -323,8 +404,11 @@ fn run_once() -> Result<(), String> {
 ///
 /// # 参数
 /// * `interval_hours` - 整理间隔小时数
-fn run_daemon(interval_hours: u64) {
+/// * `run_on_start` - 没有时间戳文件或已到期时，是否在启动后立即整理
+/// * `config_path` - 传给记忆整理工具的配置文件（可选）
+fn run_daemon(interval_hours: u64, run_on_start: bool, config_path: Option<&str>) {
     let check_interval_minutes = 30u64;
+    let started_at = get_current_timestamp();
     let timestamp_file = get_timestamp_file();
     let lock_file = get_lock_file();
     
@@ -345,6 +429,11 @@ fn run_daemon(interval_hours: u64) {
     println!("========================================");
     println!("整理间隔: {} 小时", interval_hours);
     println!("检查间隔: {} 分钟", check_interval_minutes);
+    if run_on_start {
+        println!("启动时整理: 是（没有时间戳文件或已到期时立即整理）");
+    } else {
+        println!("启动时整理: 否（首次整理在 {} 小时后）", interval_hours);
+    }
     println!("时间戳文件: {}", timestamp_file);
     println!("锁文件: {}", lock_file);
     println!("========================================");
@@ -352,13 +441,13 @@ fn run_daemon(interval_hours: u64) {
     println!("========================================\n");
     
     loop {
-        let last_run_time = get_last_run_time(&timestamp_file);
+        let last_run_time = effective_last_run_time(get_last_run_time(&timestamp_file), started_at, run_on_start);
         
         if should_run_organize(last_run_time, interval_hours) {
             println!("\n[{}] 检查结果: 需要运行记忆整理", get_formatted_time());
             println!("========================================");
             
-            match run_organize_tool() {
+            match run_organize_tool(config_path) {
                 Ok(_) => {
                     let current_time = get_current_timestamp();
                     if let Err(e) = save_current_time(&timestamp_file, current_time) {
@@ -402,24 +491,28 @@ fn main() {
         println!("========================================");
         println!("使用方法:");
         println!("  1. 单次执行模式: organize_timer.exe once");
-        println!("  2. 常驻模式:   organize_timer.exe <间隔小时数>");
+        println!("  2. 常驻模式:   organize_timer.exe <间隔小时数> [--no-run-on-start] [--config <file>]");
         println!("");
         println!("示例:");
         println!("  organize_timer.exe once          # 执行一次整理后退出");
         println!("  organize_timer.exe 24            # 常驻运行，每24小时整理一次");
         println!("  organize_timer.exe 12            # 常驻运行，每12小时整理一次");
+        println!("  organize_timer.exe 24 --no-run-on-start  # 启动时不整理，24小时后首次整理");
         println!("");
         println!("说明:");
         println!("  - 'once' 模式可以在常驻定时器运行时使用");
         println!("  - 常驻模式会创建锁文件防止重复运行");
+        println!("  - 常驻模式默认启动时立即整理，可用 --no-run-on-start 或配置 organize_run_on_start = false 关闭");
+        println!("  - --config 指定配置文件，同时传给记忆整理工具");
         println!("========================================");
         std::process::exit(1);
     }
     
     let first_arg = &args[1];
+    let config_path = config_path_arg(&args);
     
     if first_arg.to_lowercase() == "once" {
-        if let Err(e) = run_once() {
+        if let Err(e) = run_once(config_path) {
             println!("错误: {}", e);
             std::process::exit(1);
         }
@@ -434,6 +527,50 @@ fn main() {
             }
         };
         
-        run_daemon(interval_hours);
+        let config = load_config(config_path);
+        run_daemon(interval_hours, run_on_start(&args, &config), config_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_run_on_start_waits_for_the_interval_without_a_timestamp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let timestamp_file = dir.path().join("last_organize_time.txt");
+        let last_run_time = get_last_run_time(timestamp_file.to_str().unwrap());
+        assert_eq!(last_run_time, None);
+        let now = get_current_timestamp();
+
+        // 默认启动时立即整理
+        assert!(should_run_organize(effective_last_run_time(last_run_time, now, true), 24));
+        // --no-run-on-start：启动时间视为一次运行，一个完整间隔后才整理
+        assert!(!should_run_organize(effective_last_run_time(last_run_time, now, false), 24));
+        assert!(should_run_organize(effective_last_run_time(last_run_time, now - 24 * 3600, false), 24));
+
+        // 时间戳文件中更晚的运行时间仍然生效
+        assert_eq!(effective_last_run_time(Some(now), now - 3600, false), Some(now));
+    }
+
+    #[test]
+    fn run_on_start_is_read_through_the_config_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("timer.toml");
+        // 行内注释和多余空白都由 TOML 解析处理
+        fs::write(&config_file, "organize_run_on_start   =   false   # 首次整理等待一个间隔\n").unwrap();
+        let args: Vec<String> = ["organize_timer", "24", "--config", config_file.to_str().unwrap()]
+            .iter().map(|a| a.to_string()).collect();
+
+        assert_eq!(config_path_arg(&args), config_file.to_str());
+        let config = load_config(config_path_arg(&args));
+        assert!(!run_on_start(&args, &config));
+
+        let default_config = Config { organize_run_on_start: None, ..Config::default() };
+        assert!(run_on_start(&args[..2], &default_config));
+        let mut no_run: Vec<String> = args[..2].to_vec();
+        no_run.push("--no-run-on-start".to_string());
+        assert!(!run_on_start(&no_run, &Config::default()));
     }
 }
@@ -1,7 +1,10 @@
 use std::fs;
 use std::path::Path;
-use std::process::{Command, Stdio};
-use serde_json;
+use gmem_rust_memory_store::{file_source, get_write_buffer, load_config, LockType, MemoryStore};
+use gmem_rust_memory_store::import_batch::{print_failure_report, ImportFailure};
+
+/// 未配置 write_buffer_size 时，导入使用的缓冲写入批大小
+const IMPORT_WRITE_BUFFER_SIZE: usize = 500;
 
 /// TXT文件导入工具
 /// 功能：读取TXT格式的规则文件，按章节导入为记忆
@@ -136,77 +139,21 @@ fn generate_tags(section: &Section, _file_name: &str) -> Vec<String> {
     tags
 }
 
-/// 导入记忆到系统
-///
-/// # 参数
-/// * `text` - 记忆文本
-/// * `tags` - 标签列表
-///
-/// # 返回
-/// 操作结果
-pub fn import_memory(text: &str, tags: &[String]) -> Result<(), String> {
-    let tags_str = tags.join(", ");
-    let request = serde_json::json!({
-        "jsonrpc": "2.0",
-        "id": 1,
-        "method": "tools/call",
-        "params": {
-            "name": "add_memory",
-            "arguments": {
-                "text": text,
-                "tags": tags_str
-            }
-        }
-    });
-    
-    let json_payload = serde_json::to_string(&request)
-        .map_err(|e| format!("JSON序列化失败: {}", e))?;
-    
-    let mcp_server_path = "V:/git_data/GmemWorker/GmemWorker/bin/gmemory_mcp_server.exe";
-    let bin_dir = "V:/git_data/GmemWorker/GmemWorker/bin";
-    
-    let mut cmd = Command::new(mcp_server_path)
-        .current_dir(bin_dir)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("执行命令失败: {}", e))?;
-    
-    if let Some(stdin) = &mut cmd.stdin {
-        std::io::Write::write_all(stdin, json_payload.as_bytes())
-            .map_err(|e| format!("写入输入失败: {}", e))?;
-    }
-    
-    let output = cmd.wait_with_output()
-        .map_err(|e| format!("等待命令执行失败: {}", e))?;
-    
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    
-    if !output.status.success() {
-        return Err(format!("命令执行失败: {}", stderr));
-    }
-    
-    if stdout.contains("error") {
-        return Err(format!("添加记忆失败: {}", stdout));
-    }
-    
-    Ok(())
-}
-
 /// 批量导入章节
+/// 通过缓冲写入逐条添加，攒满一批（或结束时）才加锁写入一次；
+/// 单个章节失败不会中断批量导入，失败项收集后在最后统一列出
 ///
 /// # 参数
+/// * `store` - 启用了缓冲写入的记忆存储
 /// * `sections` - 章节列表
 /// * `file_name` - 文件名
 ///
 /// # 返回
-/// (成功数, 失败数)
-fn import_sections(sections: &[Section], file_name: &str) -> (usize, usize) {
+/// (成功数, 失败项)；写入缓冲区失败时返回错误
+fn import_sections(store: &MemoryStore, sections: &[Section], file_name: &str) -> std::io::Result<(usize, Vec<ImportFailure>)> {
     let mut success_count = 0;
-    let mut fail_count = 0;
-    let _remove_lock_path = "V:/git_data/GmemWorker/AppProjects/gmem_rust_memory_store/target/release/remove_lock.exe";
+    let mut failures = Vec::new();
+    let source = file_source("txt", file_name);
     
     for (index, section) in sections.iter().enumerate() {
         println!("-------------------------------------");
@@ -218,30 +165,22 @@ fn import_sections(sections: &[Section], file_name: &str) -> (usize, usize) {
         
         println!("标签: {}", tags.join(", "));
         
-        let lock_file = "E:/GmemWorkerHome/.copilot-memory.lock";
-        
-        if Path::new(lock_file).exists() {
-            println!("发现锁文件,尝试删除...");
-            if let Err(e) = std::fs::remove_file(lock_file) {
-                println!("警告: 删除锁文件失败: {}", e);
-            } else {
-                println!("锁文件删除成功!");
-            }
-        }
-        
-        match import_memory(&text, &tags) {
+        match store.add_memory_with_source(&text, Some(tags), None, Some(&source)) {
             Ok(_) => {
                 println!("✓ 导入成功");
                 success_count += 1;
             }
             Err(e) => {
                 println!("✗ 导入失败: {}", e);
-                fail_count += 1;
+                failures.push(ImportFailure { index: index + 1, title: section.title.clone(), error: e.to_string() });
             }
         }
     }
     
-    (success_count, fail_count)
+    let flushed = store.flush()?;
+    println!("写入 {} 条缓冲的记忆", flushed);
+    
+    Ok((success_count, failures))
 }
 
 fn main() {
@@ -274,11 +213,35 @@ fn main() {
     println!("找到 {} 个章节", sections.len());
     println!("=====================================");
     
-    let (success, fail) = import_sections(&sections, file_name);
+    // 导入总是使用缓冲写入，配置了 write_buffer_size 时使用配置的批大小
+    let config = load_config(None);
+    let store = MemoryStore::new(config.memory_path.as_deref(), Some(LockType::Cli)).apply_config(&config);
+    let store = match get_write_buffer(&config) {
+        Some(_) => store,
+        None => store.with_write_buffer(IMPORT_WRITE_BUFFER_SIZE, None),
+    };
+    if let Err(e) = store.ensure_storage() {
+        println!("错误: {}", e);
+        std::process::exit(1);
+    }
+    println!("记忆文件: {}", store.get_memory_path().display());
+    
+    let (success, failures) = match import_sections(&store, &sections, file_name) {
+        Ok(counts) => counts,
+        Err(e) => {
+            println!("错误: 写入记忆文件失败: {}", e);
+            std::process::exit(1);
+        }
+    };
     
     println!("=====================================");
     println!("导入完成!");
     println!("成功: {}", success);
-    println!("失败: {}", fail);
+    println!("失败: {}", failures.len());
     println!("总计: {}", sections.len());
+    
+    print_failure_report(&failures);
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
 }
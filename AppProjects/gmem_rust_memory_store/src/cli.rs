@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 use std::io::{self, Write};
-use crate::store::MemoryStore;
-use crate::compress::compress_deterministic;
+use crate::store::{MemoryStore, Bm25Corpus, ListOptions, ListSort, RankingMode, SearchOptions, TagMatch, elapsed_ms, highlight_matches, is_temp, DEFAULT_HIGHLIGHT_MARKERS, migrate_legacy_file, namespace_tag, resolve_lock_path, DEFAULT_AUTO_CUTOFF_RATIO};
+use crate::compress::{compress_with_profile, BudgetUnit, CompressOptions};
+use crate::direct_organize::{recategorize, migrate_filenames, merge_stores, RecategorizeResult};
+use crate::record::{MemoryRecord, StatsSnapshot, SOURCE_CLI};
 
 /// 解析的命令结构
 #[derive(Debug)]
@@ -19,8 +21,17 @@ pub struct Parsed {
 /// # 返回
 /// 解析后的命令结构，如果输入为空则返回 None
 pub fn parse(line: &str) -> Option<Parsed> {
-    let tokens = tokenize(line.trim());
-    
+    parse_tokens(&tokenize(line.trim()))
+}
+
+/// 解析已经分好词的命令参数（如进程参数，不再经过引号处理）
+///
+/// # 参数
+/// * `tokens` - 命令及其参数
+///
+/// # 返回
+/// 解析后的命令结构，如果没有参数则返回 None
+pub fn parse_tokens(tokens: &[String]) -> Option<Parsed> {
     if tokens.is_empty() {
         return None;
     }
@@ -99,7 +110,7 @@ fn tokenize(line: &str) -> Vec<String> {
 ///
 /// # 返回
 /// IO 错误（如果有）
-pub fn run_repl(store: MemoryStore, debug_mode: bool, version: &str) -> io::Result<()> {
+pub fn run_repl(store: &MemoryStore, debug_mode: bool, version: &str) -> io::Result<()> {
     let stdin = io::stdin();
     let mut stdout = io::stdout();
 
@@ -118,8 +129,11 @@ pub fn run_repl(store: MemoryStore, debug_mode: bool, version: &str) -> io::Resu
     }
     println!();
 
+    let config = crate::config::load_config(None);
+    let prompt = crate::config::get_prompt(&config);
+
     loop {
-        print!(" > ");
+        print!("{}", prompt);
         stdout.flush()?;
 
         let mut line = String::new();
@@ -142,7 +156,7 @@ pub fn run_repl(store: MemoryStore, debug_mode: bool, version: &str) -> io::Resu
 
         match parse(line) {
             Some(parsed) => {
-                if let Err(e) = execute_command(&store, &parsed) {
+                if let Err(e) = execute_command(store, &parsed) {
                     println!("Error: {}", e);
                 }
             }
@@ -165,41 +179,214 @@ pub fn execute_command(store: &MemoryStore, parsed: &Parsed) -> io::Result<()> {
     match parsed.cmd.as_str() {
         "add" => {
             let text = parsed.args.join(" ");
-            let tags = parsed.opts.get("tags")
+            let mut tags: Option<Vec<String>> = parsed.opts.get("tags")
                 .map(|t| t.split(',').map(|s| s.trim().to_string()).collect());
+            if let Some(ns) = namespace_opt(parsed) {
+                tags.get_or_insert_with(Vec::new).push(namespace_tag(ns));
+            }
 
-            let rec = store.add_memory(&text, tags)?;
+            let rec = store.add_memory_with_source(&text, tags, None, Some(SOURCE_CLI))?;
             println!("✅ Added {}", rec.id);
         }
         "search" => {
-            let query = parsed.args.join(" ");
+            let query = query_with_switches(parsed, &SEARCH_SWITCHES);
             let limit = parsed.opts.get("limit")
                 .and_then(|l| l.parse().ok());
 
-            let hits = store.search(&query, limit)?;
-            if hits.is_empty() {
+            let explain = parsed.opts.contains_key("explain");
+            let highlight = parsed.opts.contains_key("highlight");
+
+            // --tag 与 --tags-all 相同：记录需带有全部标签
+            let tags_all = parsed.opts.get("tags-all").or_else(|| parsed.opts.get("tag"));
+            let (tags, tag_match) = match (tags_all, parsed.opts.get("tags-any")) {
+                (Some(_), Some(_)) => {
+                    println!("Use either --tag/--tags-all or --tags-any, not both");
+                    return Ok(());
+                }
+                (Some(t), None) => (split_tags(t), TagMatch::All),
+                (None, Some(t)) => (split_tags(t), TagMatch::Any),
+                (None, None) => (Vec::new(), TagMatch::Any),
+            };
+            let ranking = match parsed.opts.get("ranking") {
+                Some(name) => match RankingMode::from_name(name) {
+                    Some(ranking) => Some(ranking),
+                    None => {
+                        println!("Unknown ranking: {} (expected linear or bm25)", name);
+                        return Ok(());
+                    }
+                },
+                None => None,
+            };
+            let options = SearchOptions {
+                limit,
+                namespace: namespace_opt(parsed).map(str::to_string),
+                tags,
+                tag_match,
+                expand_implications: parsed.opts.contains_key("implied"),
+                source: parsed.opts.get("source")
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty()),
+                exclude_temp: exclude_temp_opt(parsed),
+                within: parsed.opts.get("within").cloned(),
+                auto_cutoff: parsed.opts.contains_key("auto-cutoff").then_some(DEFAULT_AUTO_CUTOFF_RATIO),
+                include_deleted: parsed.opts.contains_key("include-deleted"),
+                after: parsed.opts.get("after").cloned(),
+                before: parsed.opts.get("before").cloned(),
+                ranking,
+                offset: parsed.opts.get("offset").and_then(|o| o.parse().ok()),
+            };
+
+            let (hits, profile) = store.search_with_profile(&query, &options)?;
+            if parsed.opts.contains_key("profile") {
+                eprintln!(
+                    "[profile] load: {:.3} ms, score: {:.3} ms, sort: {:.3} ms",
+                    profile.load_ms, profile.score_ms, profile.sort_ms
+                );
+            }
+            if parsed.opts.contains_key("ids-only") {
+                // 每行一个 ID，便于在管道中交给 delete/purge
+                for hit in &hits {
+                    println!("{}", hit.id);
+                }
+            } else if hits.is_empty() && profile.total_hits > 0 {
+                println!("No results at offset {} ({} matches)", options.offset.unwrap_or(0), profile.total_hits);
+            } else if hits.is_empty() {
                 println!("No results found");
             } else {
+                let records: HashMap<String, MemoryRecord> = if explain {
+                    store.load()?.into_iter().map(|r| (r.id.clone(), r)).collect()
+                } else {
+                    HashMap::new()
+                };
+                // BM25 的明细需要和搜索时相同的语料统计
+                let corpus = (explain && ranking.unwrap_or(store.ranking()) == RankingMode::Bm25).then(|| {
+                    let corpus_records: Vec<MemoryRecord> = records.values()
+                        .filter(|r| options.include_deleted || r.deleted_at.is_none())
+                        .cloned()
+                        .collect();
+                    Bm25Corpus::build(&corpus_records, &query)
+                });
+                let preview_len = if parsed.opts.contains_key("full") {
+                    None
+                } else {
+                    crate::config::get_result_preview_len(&crate::config::load_config(None))
+                };
                 for (i, hit) in hits.iter().enumerate() {
                     let tag_str = if hit.tags.is_empty() {
                         String::new()
                     } else {
                         format!(" [{}]", hit.tags.join(", "))
                     };
-                    println!("{}. {}{} (score: {:.1})", i + 1, hit.text, tag_str, hit.score);
+                    let deleted_str = hit.deleted_at.as_deref()
+                        .map(|at| format!(" [deleted {}]", at))
+                        .unwrap_or_default();
+                    let text = match preview_len {
+                        Some(max) => preview_text(&hit.text, max),
+                        None => hit.text.clone(),
+                    };
+                    // 先截取再高亮，避免截断落在标记中间
+                    let text = if highlight {
+                        let (open, close) = DEFAULT_HIGHLIGHT_MARKERS;
+                        highlight_matches(&text, &query, open, close)
+                    } else {
+                        text
+                    };
+                    println!("{}. {}{}{} (score: {:.1})", options.offset.unwrap_or(0) + i + 1, text, tag_str, deleted_str, hit.score);
+                    if let Some(r) = records.get(&hit.id) {
+                        let mut b = store.score_with(r, &query, corpus.as_ref());
+                        if options.within.is_some() {
+                            b.tag_score = 0.0;
+                        }
+                        let text_str = if corpus.is_some() {
+                            format!("{} hits, bm25 = {:.1}", b.text_hits, b.text_score)
+                        } else {
+                            format!("{} x {} = {:.1}", b.text_hits, store.scoring_weights().text_hit, b.text_score)
+                        };
+                        println!(
+                            "   text: {}, tags: {:.1}, keywords: {:.1}, recency: {:.1} => {:.1}",
+                            text_str, b.tag_score, b.keyword_score, b.recency, b.total()
+                        );
+                    }
+                }
+                let offset = options.offset.unwrap_or(0);
+                if offset > 0 || profile.total_hits > hits.len() {
+                    println!("Showing {}-{} of {}", offset + 1, offset + hits.len(), profile.total_hits);
                 }
             }
         }
+        "stats" if parsed.opts.contains_key("export-stats") => {
+            println!("{}", StatsSnapshot::CSV_HEADER);
+            for snapshot in store.read_stats_history()? {
+                println!("{}", snapshot.to_csv_line());
+            }
+        }
+        "stats-snapshot" => {
+            let snapshot = store.record_stats_snapshot()?;
+            println!(
+                "✅ Recorded snapshot: total {}, active {}, deleted {}, tags {} ({})",
+                snapshot.total, snapshot.active, snapshot.deleted, snapshot.distinct_tags,
+                store.stats_history_path().display()
+            );
+        }
         "stats" => {
-            let stats = store.compute_stats()?;
+            let stats = store.compute_stats_in_namespace(namespace_opt(parsed))?;
             println!("Total: {}, Active: {}, Deleted: {}", stats.total, stats.active, stats.deleted);
-            if !stats.tags.is_empty() {
+            if parsed.opts.contains_key("by-source") {
+                let mut sources: Vec<(&String, &usize)> = stats.sources.iter().collect();
+                sources.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                println!("\nSources:");
+                for (source, count) in sources {
+                    println!("  - {}: {}", source, count);
+                }
+            } else if !stats.tags.is_empty() {
                 println!("\nTags:");
                 for (tag, count) in stats.tags.iter().take(10) {
                     println!("  - {}: {}", tag, count);
                 }
             }
         }
+        "tag-stats" => {
+            let limit = parsed.opts.get("limit")
+                .and_then(|l| l.parse().ok())
+                .unwrap_or(10);
+
+            let mut pairs: Vec<((String, String), usize)> = store.tag_cooccurrence()?.into_iter().collect();
+            if pairs.is_empty() {
+                println!("No co-occurring tags found");
+            } else {
+                pairs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                println!("Top tag pairs:");
+                for ((a, b), count) in pairs.iter().take(limit) {
+                    println!("  - {} + {}: {}", a, b, count);
+                }
+            }
+        }
+        "check-categories" => {
+            let config = crate::config::load_config(None);
+            let unmapped = store.unmapped_tags(&config)?;
+            if unmapped.is_empty() {
+                println!("✅ Every tag in the store has a category_mapping entry");
+                return Ok(());
+            }
+            println!("{} tags have no category_mapping entry:", unmapped.len());
+            for t in &unmapped {
+                let hint = t.similar_key.as_deref()
+                    .map(|key| format!(" (similar to mapped tag '{}', typo?)", key))
+                    .unwrap_or_default();
+                println!("  - {}: {} memories, {} fall to default{}", t.tag, t.count, t.default_count, hint);
+            }
+            // 可直接粘贴到 [category_mapping] 下的建议：相近的键沿用其分类，否则以标签名作为分类
+            let mapping = config.category_mapping.clone().unwrap_or_default();
+            println!();
+            println!("Suggested [category_mapping] entries:");
+            for t in unmapped.iter().filter(|t| t.default_count > 0) {
+                let category = t.similar_key.as_ref()
+                    .and_then(|key| mapping.get(key))
+                    .cloned()
+                    .unwrap_or_else(|| t.tag.clone());
+                println!("{} = \"{}\"", toml_key(&t.tag), category);
+            }
+        }
         "logs" => {
             println!("Logs command:");
             println!("  logs show - 显示最近的日志");
@@ -220,70 +407,486 @@ pub fn execute_command(store: &MemoryStore, parsed: &Parsed) -> io::Result<()> {
             println!("  Max size: 1MB per file");
             println!("  Rotation: Enabled");
         }
+        "list" => {
+            let sort_by = match parsed.opts.get("sort") {
+                Some(name) => match ListSort::from_name(name) {
+                    Some(sort) => Some(sort),
+                    None => {
+                        println!("Unknown sort: {} (expected created or updated)", name);
+                        return Ok(());
+                    }
+                },
+                None => None,
+            };
+            let options = ListOptions {
+                limit: Some(parsed.opts.get("limit").and_then(|l| l.parse().ok()).unwrap_or(store.default_limit())),
+                offset: parsed.opts.get("offset").and_then(|o| o.parse().ok()).unwrap_or(0),
+                include_deleted: parsed.opts.contains_key("deleted"),
+                tag: parsed.opts.get("tag").filter(|t| !t.is_empty()).cloned(),
+                sort_by,
+                // 按时间排序时默认从新到旧，--asc 改为从旧到新
+                descending: sort_by.is_some() && !parsed.opts.contains_key("asc"),
+            };
+            let (records, total) = store.list(&options)?;
+            if records.is_empty() {
+                println!("No memories found");
+                return Ok(());
+            }
+            let preview_len = crate::config::get_result_preview_len(&crate::config::load_config(None)).unwrap_or(usize::MAX);
+            for r in &records {
+                let timestamp = match sort_by {
+                    Some(ListSort::Updated) => &r.updated_at,
+                    _ => &r.created_at,
+                };
+                let tag_str = if r.tags.is_empty() {
+                    String::new()
+                } else {
+                    format!(" [{}]", r.tags.join(", "))
+                };
+                let deleted_str = if r.deleted_at.is_some() { " [deleted]" } else { "" };
+                println!("{}  {}  {}{}{}", r.id, timestamp, preview_text(&r.text, preview_len.min(80)), tag_str, deleted_str);
+            }
+            println!("Showing {}-{} of {}", options.offset + 1, options.offset + records.len(), total);
+        }
+        "get" => {
+            let id = match parsed.args.first() {
+                Some(id) => id,
+                None => {
+                    println!("Usage: get <id> [--category NAME]");
+                    return Ok(());
+                }
+            };
+            let record = match parsed.opts.get("category").filter(|c| !c.is_empty()) {
+                Some(category) => store.get_by_id_in_category(id, category)?,
+                None => store.get_by_id(id)?,
+            };
+            match record {
+                Some(r) => {
+                    println!("id: {}", r.id);
+                    println!("text: {}", r.text);
+                    println!("tags: {}", r.tags.join(", "));
+                    println!("keywords: {}", r.keywords.join(", "));
+                    println!("created_at: {}", r.created_at);
+                    println!("updated_at: {}", r.updated_at);
+                    println!("deleted_at: {}", r.deleted_at.as_deref().unwrap_or("-"));
+                    println!("source: {}", r.source.as_deref().unwrap_or("-"));
+                    if let Some(key) = &r.idempotency_key {
+                        println!("idempotency_key: {}", key);
+                    }
+                }
+                None => println!("❌ Memory not found: {}", id),
+            }
+        }
         "whereiscfg" => {
             let config_path = crate::config::get_config_file_path(None);
             println!("Current config file path:");
             println!("{}", config_path);
         }
+        "move" | "copy" => {
+            // `--new-id` 是开关，放在 id 前面时会吞掉 id
+            let id = parsed.args.first().or_else(|| parsed.opts.get("new-id").filter(|v| !v.is_empty()));
+            let (id, to) = match (id, parsed.opts.get("to")) {
+                (Some(id), Some(to)) if !to.is_empty() => (id, to),
+                _ => {
+                    println!("Usage: {} <id> --to <memory-path> [--new-id]", parsed.cmd);
+                    return Ok(());
+                }
+            };
+            let config = crate::config::load_config(None);
+            let target = MemoryStore::new(Some(to), Some(store.get_lock_type())).apply_config(&config);
+            target.ensure_storage()?;
+            let keep_id = !parsed.opts.contains_key("new-id");
+            let result = if parsed.cmd == "move" {
+                store.move_to(id, &target, keep_id)?
+            } else {
+                store.copy_to(id, &target, keep_id)?
+            };
+            match result {
+                Some(record) if parsed.cmd == "move" => println!("✅ Moved {} to {} as {}", id, target.get_memory_path().display(), record.id),
+                Some(record) => println!("✅ Copied {} to {} as {}", id, target.get_memory_path().display(), record.id),
+                None => println!("❌ Memory not found: {}", id),
+            }
+        }
         "delete" => {
             if parsed.args.is_empty() {
-                println!("Usage: delete <id>");
+                println!("Usage: delete <id> [<id> ...] (use - to read ids from stdin)");
                 return Ok(());
             }
-            let id = &parsed.args[0];
-            if store.soft_delete(id)? {
-                println!("✅ Deleted {}", id);
-            } else {
-                println!("❌ Memory not found: {}", id);
+            // `-` 表示从标准输入读取 ID（每行一个，可配合 `search --ids-only`）
+            let mut ids: Vec<String> = Vec::new();
+            for arg in &parsed.args {
+                if arg == "-" {
+                    for line in io::stdin().lines() {
+                        let line = line?;
+                        let line = line.trim();
+                        if !line.is_empty() {
+                            ids.push(line.to_string());
+                        }
+                    }
+                } else {
+                    ids.push(arg.clone());
+                }
+            }
+            let id_refs: Vec<&str> = ids.iter().map(String::as_str).collect();
+            let results = store.soft_delete_many(&id_refs)?;
+            for (id, found) in ids.iter().zip(&results) {
+                if *found {
+                    println!("✅ Deleted {}", id);
+                } else {
+                    println!("❌ Memory not found: {}", id);
+                }
+            }
+            if ids.len() > 1 {
+                let deleted = results.iter().filter(|found| **found).count();
+                println!("Deleted {} of {} ids ({} not found)", deleted, ids.len(), ids.len() - deleted);
             }
         }
         "purge" => {
-            let id = parsed.opts.get("id").map(|s| s.as_str());
-            let tag = parsed.opts.get("tag").map(|s| s.as_str());
-            let match_text = parsed.opts.get("text").map(|s| s.as_str());
-            
-            let purged = store.purge(id, tag, match_text)?;
-            println!("✅ Purged {} memories", purged);
+            let confirmed = parsed.opts.contains_key("yes")
+                || confirm("Permanently delete the matching memories? This cannot be undone.");
+            if !confirmed {
+                println!("Purge cancelled");
+                return Ok(());
+            }
+
+            let journal = parsed.opts.contains_key("journal");
+            let journal_path = if parsed.opts.contains_key("deleted") {
+                let (purged, journal_path) = store.purge_deleted_journaled(journal)?;
+                println!("✅ Permanently removed {} soft-deleted memories", purged);
+                journal_path
+            } else {
+                let id = parsed.opts.get("id").map(|s| s.as_str());
+                let tag = parsed.opts.get("tag").map(|s| s.as_str());
+                let match_text = parsed.opts.get("text").map(|s| s.as_str());
+
+                let (purged, journal_path) = store.purge_journaled(id, tag, match_text, journal)?;
+                println!("✅ Purged {} memories", purged);
+                journal_path
+            };
+            if let Some(path) = journal_path {
+                println!("Journal: {} (undo with: unpurge {})", path.display(), path.display());
+            }
+        }
+        "unpurge" => {
+            let path = match parsed.args.first() {
+                Some(path) => path,
+                None => {
+                    println!("Usage: unpurge <journal-file>");
+                    return Ok(());
+                }
+            };
+            let restored = store.restore_purge(path)?;
+            println!("✅ Restored {} memories from {} (existing ids skipped)", restored, path);
+        }
+        "tag" => {
+            match parsed.args.as_slice() {
+                [sub, from, to] if sub == "rename" => {
+                    let renamed = store.rename_tag(from, to)?;
+                    println!("✅ Renamed tag {} -> {} on {} memories", from.trim().to_lowercase(), to.trim().to_lowercase(), renamed);
+                }
+                _ => println!("Usage: tag rename <from> <to>"),
+            }
         }
         "compress" => {
-            let query = parsed.args.join(" ");
+            let query = query_with_switches(parsed, &COMPRESS_SWITCHES);
             let budget = parsed.opts.get("budget")
                 .and_then(|b| b.parse().ok())
                 .unwrap_or(2000);
             let limit = parsed.opts.get("limit")
                 .and_then(|l| l.parse().ok());
+            let budget_unit = match parsed.opts.get("budget-unit") {
+                Some(name) => match BudgetUnit::from_name(name) {
+                    Some(unit) => unit,
+                    None => {
+                        println!("Unknown budget unit: {} (expected chars, bytes or tokens)", name);
+                        return Ok(());
+                    }
+                },
+                None => BudgetUnit::default(),
+            };
 
-            let records = store.load()?;
-            let result = compress_deterministic(&records, &query, budget, limit);
-            
-            println!("--- Compressed Output ({} / {} chars) ---", result.used, result.budget);
+            let options = CompressOptions {
+                budget,
+                budget_unit,
+                limit,
+                fallback_recent: parsed.opts.contains_key("fallback-recent"),
+                group_by_tag: parsed.opts.contains_key("group-by-tag"),
+                group_by_category: parsed.opts.contains_key("group-by-category")
+                    .then(|| crate::config::load_config(None)),
+                trim_last: parsed.opts.contains_key("trim-last"),
+                disable_recency: parsed.opts.contains_key("no-recency"),
+                weights: *store.scoring_weights(),
+                dedup_threshold: parsed.opts.get("dedup-threshold")
+                    .and_then(|t| t.parse::<f64>().ok())
+                    .map(|t| t.clamp(0.0, 1.0)),
+            };
+
+            let started = std::time::Instant::now();
+            let mut records = store.load_active()?;
+            if exclude_temp_opt(parsed).unwrap_or(store.exclude_temp_by_default()) {
+                records.retain(|r| !is_temp(r));
+            }
+            let load_ms = elapsed_ms(started);
+            let (result, mut profile) = compress_with_profile(&records, &query, &options);
+            profile.load_ms = load_ms;
+            if parsed.opts.contains_key("profile") {
+                eprintln!(
+                    "[profile] load: {:.3} ms, score: {:.3} ms, truncate: {:.3} ms",
+                    profile.load_ms, profile.score_ms, profile.truncate_ms
+                );
+            }
+
+            if parsed.opts.contains_key("json") {
+                let json = serde_json::to_string_pretty(&result)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                println!("{}", json);
+                return Ok(());
+            }
+
+            println!("--- Compressed Output ({} / {} {}) ---", result.used, result.budget, budget_unit.name());
             println!("{}", result.markdown);
             println!("--- End ---");
         }
         "export" => {
-            let json = store.export_json()?;
-            println!("{}", json);
+            match parsed.opts.get("format").map(|s| s.as_str()) {
+                Some("md-dir") => {
+                    let out_dir = match parsed.opts.get("output") {
+                        Some(dir) if !dir.is_empty() => dir,
+                        _ => {
+                            println!("Usage: export --format md-dir --output <dir>");
+                            return Ok(());
+                        }
+                    };
+                    let config = crate::config::load_config(None);
+                    let files = store.export_markdown_by_category(&config, std::path::Path::new(out_dir))?;
+                    for file in &files {
+                        println!("  - {}", file.display());
+                    }
+                    println!("✅ Exported {} category files to {}", files.len(), out_dir);
+                }
+                Some("json") | None => {
+                    let json = store.export_json()?;
+                    println!("{}", json);
+                }
+                Some(other) => {
+                    println!("Unknown export format: {}. Supported: json, md-dir", other);
+                }
+            }
         }
         "import" => {
+            // `import --simulate <file>` / `import --strict <file>` 会被解析为选项的值
+            let swallowed = ["simulate", "strict"]
+                .iter()
+                .filter_map(|k| parsed.opts.get(*k))
+                .find(|v| !v.is_empty());
+            let file_path = match (parsed.args.first(), swallowed) {
+                (Some(path), _) => path,
+                (None, Some(path)) => path,
+                _ => {
+                    println!("Usage: import [--simulate] [--strict] <json_file>");
+                    return Ok(());
+                }
+            };
+            let json_data = std::fs::read_to_string(file_path)?;
+            let simulate = parsed.opts.contains_key("simulate");
+            let strict = parsed.opts.contains_key("strict");
+            let report = store.import_json_with_report(&json_data, simulate, strict)?;
+            if report.simulated {
+                println!("[simulate] Would import: {}, Skipped: {}, Failed: {}", report.success, report.skipped, report.failed);
+                for id in &report.skipped_ids {
+                    println!("  - duplicate id: {}", id);
+                }
+            } else {
+                println!("✅ Imported: {}, Skipped: {}, Failed: {}", report.success, report.skipped, report.failed);
+            }
+            for entry in &report.malformed {
+                println!("  - malformed record {}", entry);
+            }
+        }
+        "doctor" => {
+            println!("Memory file: {}", store.get_memory_path().display());
+            let records = store.load()?;
+            println!("✅ Loaded {} records", records.len());
+
+            let duplicates = store.find_duplicate_ids()?;
+            if duplicates.is_empty() {
+                println!("✅ No duplicate ids");
+            } else {
+                println!("⚠️  {} ids are shared by more than one record:", duplicates.len());
+                for id in &duplicates {
+                    println!("  - {}", id);
+                }
+                if parsed.opts.contains_key("fix-ids") {
+                    let regenerated = store.regenerate_duplicate_ids()?;
+                    println!("✅ Regenerated {} duplicate ids", regenerated);
+                } else {
+                    println!("Run `doctor --fix-ids` to give the duplicates new ids");
+                }
+            }
+
+            let empty = store.find_empty_text()?;
+            if empty.is_empty() {
+                println!("✅ No records with empty text");
+            } else {
+                println!("⚠️  {} records have empty text:", empty.len());
+                for id in &empty {
+                    println!("  - {}", id);
+                }
+                if parsed.opts.contains_key("drop-empty") {
+                    let dropped = store.purge_empty_text()?;
+                    println!("✅ Removed {} records with empty text", dropped);
+                } else {
+                    println!("Run `doctor --drop-empty` to remove them");
+                }
+            }
+        }
+        "migrate" => {
             if parsed.args.is_empty() {
-                println!("Usage: import <json_file>");
+                println!("Usage: migrate <json_file>");
                 return Ok(());
             }
             let file_path = &parsed.args[0];
-            let json_data = std::fs::read_to_string(file_path)?;
-            let (success, skipped, failed) = store.import_json(&json_data)?;
-            println!("✅ Imported: {}, Skipped: {}, Failed: {}", success, skipped, failed);
+            let (total, migrated) = migrate_legacy_file(std::path::Path::new(file_path))?;
+            if migrated == 0 {
+                println!("✅ {} already uses the snake_case schema ({} records)", file_path, total);
+            } else {
+                println!("✅ Migrated {} of {} records in {} to the snake_case schema", migrated, total, file_path);
+            }
+        }
+        "tidy" => {
+            let (total, changed) = store.tidy()?;
+            if changed == 0 {
+                println!("✅ All {} records are already tidy", total);
+            } else {
+                println!("✅ Tidied {} of {} records (tags, keywords, timestamps, field names)", changed, total);
+            }
+        }
+        "recategorize" => {
+            let id = match parsed.args.first() {
+                Some(id) => id,
+                None => {
+                    println!("Usage: recategorize <id>");
+                    return Ok(());
+                }
+            };
+            match recategorize(id)? {
+                RecategorizeResult::Moved { from, to } => println!("✅ Moved {} from {} to {}", id, from, to),
+                RecategorizeResult::Unchanged(category) => println!("{} is already in {}", id, category),
+                RecategorizeResult::NotFound => println!("Memory not found in any category file: {}", id),
+            }
+        }
+        "migrate-filenames" => {
+            let migrated = migrate_filenames()?;
+            if migrated.is_empty() {
+                println!("No category files use the old *-global-gmem-recoder.json name");
+            }
+            for (from, to) in &migrated {
+                println!("✅ Renamed {} -> {}", from.display(), to.display());
+            }
+        }
+        "split" => {
+            let config = crate::config::load_config(None);
+            let written = store.split_by_category(&config)?;
+            if written.is_empty() {
+                println!("No memories to split");
+            }
+            for (category, path, count) in &written {
+                println!("{}: {} memories -> {}", category, count, path.display());
+            }
+            let total: usize = written.iter().map(|(_, _, count)| count).sum();
+            println!("✅ Split {} memories into {} category files", total, written.len());
+        }
+        "merge-stores" => {
+            let (dir, output) = match (parsed.args.first(), parsed.args.get(1)) {
+                (Some(dir), Some(output)) => (dir, output),
+                _ => {
+                    println!("Usage: merge-stores <dir> <output>");
+                    return Ok(());
+                }
+            };
+            let report = merge_stores(std::path::Path::new(dir), std::path::Path::new(output))?;
+            for id in &report.conflicts {
+                println!("⚠️  id conflict with differing content, kept the most recently updated: {}", id);
+            }
+            println!("✅ Merged {} memories from {} category files into {} (duplicates dropped: {})",
+                report.records, report.files, output, report.duplicates);
+        }
+        "lock-status" => {
+            let lock_path = resolve_lock_path(store.get_memory_path());
+            match crate::lock::describe(&lock_path) {
+                Some(info) => {
+                    let pid = info.pid.map(|p| p.to_string()).unwrap_or_else(|| "unknown".to_string());
+                    let age = info.age_seconds.map(|a| format!("{}s", a)).unwrap_or_else(|| "unknown".to_string());
+                    let alive = match info.alive {
+                        Some(true) => "alive",
+                        Some(false) => "not running, lock is stale",
+                        None => "status unknown",
+                    };
+                    println!("Held: {}", lock_path.display());
+                    println!("  holder: {}", info.lock_type.as_deref().unwrap_or("unknown"));
+                    println!("  pid: {} ({})", pid, alive);
+                    println!("  created: {}", info.created_at.as_deref().unwrap_or("unknown"));
+                    println!("  age: {}", age);
+                }
+                None => println!("Not held ({})", lock_path.display()),
+            }
         }
         "help" => {
             println!("Available commands:");
-            println!("  add [--tags a,b,c] <text>      - Store a new memory");
-            println!("  search <query> [--limit N]     - Search memories");
-            println!("  delete <id>                    - Soft delete a memory");
+            println!("  add [--tags a,b,c] [--namespace NS] <text> - Store a new memory (NS adds a proj:NS tag)");
+            println!("  search <query> [--limit N] [--namespace NS] [--explain] - Search memories (--explain shows score breakdown)");
+            println!("         [--tags-any a,b | --tags-all a,b] - Only match memories with any/all of the tags");
+            println!("         [--tag a,b]             - Only match memories with all of the tags; with no query, list them newest first");
+            println!("         [--implied]             - Tag filters also match tags that imply them (tag_implications)");
+            println!("         [--profile]             - Print load/score/sort timings to stderr (also for compress)");
+            println!("         [--ids-only]            - Print only matching ids, one per line");
+            println!("         [--source SRC]          - Only match memories from a source (md matches md:<file>)");
+            println!("         [--no-temp]             - Skip memories tagged temp (also for compress)");
+            println!("         [--include-temp]        - Keep memories tagged temp even with exclude_temp_by_default (also for compress)");
+            println!("         [--within TAG]          - Only search memories tagged TAG, ranking by text without tag bonus");
+            println!("         [--auto-cutoff]         - Stop at the score cliff: drop hits below 30% of the top score (no default limit)");
+            println!("         [--include-deleted]     - Also search soft-deleted memories, marked [deleted <time>]");
+            println!("         [--full]                - Print whole texts instead of the first result_preview_len (200) characters");
+            println!("         [--highlight]           - Wrap the parts of each result that match the query in **...**");
+            println!("         [--offset N]            - Skip the first N hits (paging with --limit), footer shows \"Showing 11-20 of 47\"");
+            println!("         [--after DATE] [--before DATE] - Only match memories created in the range (RFC 3339 or YYYY-MM-DD, UTC+8)");
+            println!("         [--ranking linear|bm25] - Text relevance algorithm for this search (default: config ranking)");
+            println!("  delete <id> [<id> ...]         - Soft delete memories (- reads ids from stdin)");
             println!("  purge [--id ID] [--tag TAG] [--text TEXT] - Hard delete memories");
-            println!("  compress <query> [--budget N] [--limit N] - Compress memories");
-            println!("  stats                          - Show memory statistics");
+            println!("  purge --deleted                - Permanently remove all soft-deleted memories");
+            println!("        [--yes]                  - Skip the confirmation prompt (y/yes/是 confirm)");
+            println!("        [--journal]              - Save the removed memories to .purge-journal-<time>.json first (keeps purge_journal_keep)");
+            println!("  unpurge <journal-file>         - Restore memories from a purge journal, skipping ids that already exist");
+            println!("  tag rename <from> <to>         - Rename a tag on every memory (merges into <to> if already present)");
+            println!("  list [--tag T] [--deleted] [--limit N] [--offset M] - List memories in stored order (id, time, text, tags)");
+            println!("       [--sort created|updated] [--asc] - Sort by time, newest first unless --asc");
+            println!("  get <id> [--category NAME] - Show every field of one memory, including deleted ones");
+            println!("  copy <id> --to <memory-path> [--new-id] - Copy a memory into another store (keeps the id unless --new-id)");
+            println!("  move <id> --to <memory-path> [--new-id] - Copy a memory into another store, then soft delete it here");
+            println!("  compress <query> [--budget N] [--limit N] [--json] [--fallback-recent] - Compress memories");
+            println!("           [--group-by-tag]      - Group memories under ### <first tag> subheadings");
+            println!("           [--group-by-category] - Group memories under ### <category> subheadings (category_mapping), biggest total score first");
+            println!("           [--trim-last]         - Fill the leftover budget with the start of the next memory (ends with …)");
+            println!("           [--no-recency]        - Ignore recency when scoring so the output does not change from day to day");
+            println!("           [--budget-unit U]     - Measure the budget in chars (default), bytes or tokens (CJK char ~1, word ~1.3)");
+            println!("           [--dedup-threshold T] - Also drop paraphrases whose keyword overlap (Jaccard) is at least T (identical texts are always dropped)");
+            println!("  stats [--namespace NS] [--by-source] - Show memory statistics (--by-source counts per source)");
+            println!("  stats --export-stats           - Print the recorded stats history (stats-history.csv) as CSV");
+            println!("  stats-snapshot                 - Append current totals to stats-history.csv next to the memory file");
+            println!("  tag-stats [--limit N]          - Show most frequent co-occurring tag pairs");
+            println!("  check-categories               - List tags without a category_mapping entry and suggest mappings");
             println!("  export                         - Export all memories as JSON");
-            println!("  import <json_file>             - Import memories from JSON file");
+            println!("  export --format md-dir --output <dir> - Export one markdown file per category");
+            println!("  import [--simulate] [--strict] <json_file> - Import memories from JSON file (--simulate only reports)");
+            println!("                                 - Malformed records are counted as failed; --strict rejects the whole file");
+            println!("  doctor [--fix-ids] [--drop-empty] - Check the memory file (duplicate ids, empty text, parse errors)");
+            println!("  migrate <json_file>            - Rewrite a legacy camelCase memory file as snake_case");
+            println!("  tidy                           - Normalize tags, keywords, timestamps and field names of all records");
+            println!("  recategorize <id>              - Move one memory to the category file matching its tags");
+            println!("  migrate-filenames              - Rename old *-global-gmem-recoder.json category files to *-recorder.json");
+            println!("  split                          - Copy the store into per-category files, keeping ids and timestamps");
+            println!("  merge-stores <dir> <output>    - Merge the category files in <dir> into a single store file");
+            println!("  lock-status                    - Show who holds the store lock (cli, interactive or mcp), its age and whether the owner is alive");
             println!("  logs show                       - Show recent logs");
             println!("  logs clear                      - Clear all logs");
             println!("  logs status                     - Show logs status");
@@ -297,3 +900,240 @@ pub fn execute_command(store: &MemoryStore, parsed: &Parsed) -> io::Result<()> {
     }
     Ok(())
 }
+
+/// 判断用户的回答是否表示确认（y、yes、是，不区分大小写）
+///
+/// # 参数
+/// * `answer` - 用户输入
+///
+/// # 返回
+/// 是否为肯定回答
+pub fn is_affirmative(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes" | "是")
+}
+
+/// 询问用户是否继续
+///
+/// # 参数
+/// * `question` - 提示问题
+///
+/// # 返回
+/// 用户给出肯定回答时返回 true，输入结束或读取失败时返回 false
+pub fn confirm(question: &str) -> bool {
+    print!("{} [y/N]: ", question);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => false,
+        Ok(_) => is_affirmative(&answer),
+    }
+}
+
+/// search 命令中不带值的开关选项
+const SEARCH_SWITCHES: [&str; 10] = ["explain", "profile", "ids-only", "implied", "no-temp", "include-temp", "auto-cutoff", "include-deleted", "full", "highlight"];
+
+/// compress 命令中不带值的开关选项
+const COMPRESS_SWITCHES: [&str; 9] = ["json", "fallback-recent", "no-temp", "include-temp", "profile", "group-by-tag", "group-by-category", "trim-last", "no-recency"];
+
+/// 拼接查询文本
+/// 开关选项（如 `search --ids-only rust`）会把后面的词解析为选项值，这些词仍属于查询
+///
+/// # 参数
+/// * `parsed` - 解析后的命令
+/// * `switches` - 该命令不带值的开关选项
+///
+/// # 返回
+/// 查询文本
+fn query_with_switches(parsed: &Parsed, switches: &[&str]) -> String {
+    let mut words: Vec<&str> = switches.iter()
+        .filter_map(|flag| parsed.opts.get(*flag))
+        .map(String::as_str)
+        .filter(|w| !w.is_empty())
+        .collect();
+    words.extend(parsed.args.iter().map(String::as_str));
+    words.join(" ")
+}
+
+/// 读取 `--namespace` 选项（空值视为未指定）
+///
+/// # 参数
+/// * `parsed` - 解析后的命令
+///
+/// # 返回
+/// 命名空间名称（可选）
+fn namespace_opt(parsed: &Parsed) -> Option<&str> {
+    parsed.opts.get("namespace")
+        .map(|ns| ns.trim())
+        .filter(|ns| !ns.is_empty())
+}
+
+/// 读取 `--no-temp` / `--include-temp` 开关（同时给出时 `--no-temp` 优先）
+///
+/// # 参数
+/// * `parsed` - 解析后的命令
+///
+/// # 返回
+/// 是否排除临时记忆；都未指定时为 None，使用存储配置的 `exclude_temp_by_default`
+fn exclude_temp_opt(parsed: &Parsed) -> Option<bool> {
+    if parsed.opts.contains_key("no-temp") {
+        Some(true)
+    } else if parsed.opts.contains_key("include-temp") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// 拆分逗号分隔的标签列表
+///
+/// # 参数
+/// * `value` - 逗号分隔的标签字符串
+///
+/// # 返回
+/// 去除空白后的非空标签
+fn split_tags(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// 截取结果文本用于显示：超过 `max` 个字符时在词边界处截断并加省略号
+/// 截断点前半段内没有空白（如长串中文）时直接按字符截断
+///
+/// # 参数
+/// * `text` - 记忆文本
+/// * `max` - 最大字符数
+///
+/// # 返回
+/// 显示用的文本
+fn preview_text(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max).collect();
+    let at_word = match cut.rfind(char::is_whitespace) {
+        Some(pos) if cut[..pos].chars().count() >= max / 2 => &cut[..pos],
+        _ => cut.as_str(),
+    };
+    format!("{}…", at_word.trim_end())
+}
+
+/// 把标签写成 TOML 键：只含字母、数字、`-`、`_` 时用裸键，否则加引号
+///
+/// # 参数
+/// * `tag` - 标签
+///
+/// # 返回
+/// TOML 键
+fn toml_key(tag: &str) -> String {
+    if !tag.is_empty() && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        tag.to_string()
+    } else {
+        format!("{:?}", tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_tokens_keeps_process_args_intact() {
+        let parsed = parse_tokens(&tokens(&["add", "don't", "forget", "--tags", "a,b"])).unwrap();
+        assert_eq!(parsed.cmd, "add");
+        assert_eq!(parsed.args, vec!["don't", "forget"]);
+        assert_eq!(parsed.opts.get("tags").map(String::as_str), Some("a,b"));
+
+        let quoted = "say \"hi\" and 'bye'";
+        let parsed = parse_tokens(&tokens(&["add", quoted])).unwrap();
+        assert_eq!(parsed.args, vec![quoted]);
+    }
+
+    #[test]
+    fn parse_still_splits_repl_lines() {
+        let parsed = parse("search \"two words\" --limit 3").unwrap();
+        assert_eq!(parsed.cmd, "search");
+        assert_eq!(parsed.args, vec!["two words"]);
+        assert_eq!(parsed.opts.get("limit").map(String::as_str), Some("3"));
+        assert!(parse("   ").is_none());
+    }
+
+    #[test]
+    fn affirmative_answers_are_case_insensitive() {
+        for answer in ["y", "Y", "yes", "YES", "Yes\r\n", "  是 \n"] {
+            assert!(is_affirmative(answer), "{:?}", answer);
+        }
+        for answer in ["", "n", "no", "yep", "否", "y es"] {
+            assert!(!is_affirmative(answer), "{:?}", answer);
+        }
+    }
+
+    #[test]
+    fn preview_text_truncates_at_a_word_boundary() {
+        assert_eq!(preview_text("short note", 20), "short note");
+        assert_eq!(preview_text("alpha beta gamma delta", 13), "alpha beta…");
+        // 前半段没有空白时直接按字符截断
+        assert_eq!(preview_text("一二三四五六七八 九", 6), "一二三四五六…");
+        assert_eq!(preview_text("a bcdefghij", 8), "a bcdefg…");
+    }
+
+    #[test]
+    fn namespace_isolates_add_search_and_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path().join("memory.json").to_str(), Some(crate::lock::LockType::Cli));
+        for line in ["add --namespace foo --tags rust rust in foo", "add --namespace Bar rust in bar", "add rust everywhere"] {
+            execute_command(&store, &parse(line).unwrap()).unwrap();
+        }
+
+        let mut records = store.load().unwrap();
+        records[0].tags.sort();
+        assert_eq!(records[0].tags, vec!["proj:foo", "rust"]);
+        assert_eq!(records[1].tags, vec!["proj:bar"]);
+        assert!(records[2].tags.is_empty());
+
+        let texts = |ns: Option<&str>| -> Vec<String> {
+            store.search_in_namespace("rust", ns, None).unwrap().into_iter().map(|h| h.text).collect()
+        };
+        assert_eq!(texts(Some("foo")), vec!["rust in foo"]);
+        assert_eq!(texts(Some("bar")), vec!["rust in bar"]);
+        assert!(texts(Some("baz")).is_empty());
+        assert_eq!(texts(None).len(), 3);
+
+        let stats = store.compute_stats_in_namespace(Some("foo")).unwrap();
+        assert_eq!((stats.total, stats.active), (1, 1));
+        assert_eq!(stats.tags.get("rust"), Some(&1));
+        assert!(!stats.tags.contains_key("proj:bar"));
+        assert_eq!(store.compute_stats().unwrap().total, 3);
+    }
+
+    #[test]
+    fn split_writes_each_record_to_its_category_file_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path().join("memory.json").to_str(), Some(crate::lock::LockType::Cli));
+        for line in ["add --tags rust rust note", "add --tags git git note", "add --tags csdn blog note", "add untagged note"] {
+            execute_command(&store, &parse(line).unwrap()).unwrap();
+        }
+        let records = store.load().unwrap();
+
+        let written = store.split_by_category(&crate::config::Config::default()).unwrap();
+        let counts: Vec<(&str, usize)> = written.iter().map(|(category, _, count)| (category.as_str(), *count)).collect();
+        assert_eq!(counts, [("blog", 1), ("default", 1), ("git", 1), ("rust", 1)]);
+
+        for (category, text) in [("rust", "rust note"), ("git", "git note"), ("blog", "blog note"), ("default", "untagged note")] {
+            let path = crate::direct_organize::category_file_path(&dir.path().to_string_lossy(), category);
+            let split: Vec<crate::record::MemoryRecord> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let original = records.iter().find(|r| r.text == text).unwrap();
+            assert_eq!(split.len(), 1, "{}", category);
+            assert_eq!((&split[0].id, &split[0].created_at, &split[0].keywords), (&original.id, &original.created_at, &original.keywords));
+        }
+        // 存储本身不变
+        assert_eq!(store.load().unwrap().len(), 4);
+    }
+}
@@ -1,5 +1,145 @@
-use crate::record::{MemoryRecord, SearchHit, CompressResult};
-use crate::store::score_record;
+use std::collections::HashSet;
+use std::time::Instant;
+use crate::record::{MemoryRecord, SearchHit, CompressResult, CompressProfile, ScoringWeights};
+use crate::store::{score_breakdown, elapsed_ms, compare_hits};
+use crate::config::{Config, get_category_for_tags};
+#[cfg(feature = "llm")]
+use crate::config::{get_config_string, get_scoring_weights, DEFAULT_DEEPSEEK_API_BASE, DEFAULT_DEEPSEEK_MODEL};
+
+/// 压缩预算的计量单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BudgetUnit {
+    /// 字符数（中文一个字算 1）
+    #[default]
+    Chars,
+    /// UTF-8 字节数（中文一个字算 3）
+    Bytes,
+    /// 估算的 token 数：CJK 字符每个约 1 个，其他文本按空白分词、每个词约 1.3 个
+    ApproxTokens,
+}
+
+impl BudgetUnit {
+    /// 按名称解析（`chars`、`bytes`、`tokens`，不区分大小写）
+    ///
+    /// # 参数
+    /// * `name` - 单位名称
+    ///
+    /// # 返回
+    /// 计量单位，无法识别时返回 None
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "chars" => Some(BudgetUnit::Chars),
+            "bytes" => Some(BudgetUnit::Bytes),
+            "tokens" => Some(BudgetUnit::ApproxTokens),
+            _ => None,
+        }
+    }
+
+    /// 单位名称（用于输出）
+    pub fn name(&self) -> &'static str {
+        match self {
+            BudgetUnit::Chars => "chars",
+            BudgetUnit::Bytes => "bytes",
+            BudgetUnit::ApproxTokens => "tokens",
+        }
+    }
+
+    /// 按该单位计算文本的长度
+    ///
+    /// # 参数
+    /// * `text` - 文本
+    ///
+    /// # 返回
+    /// 长度
+    pub fn measure(&self, text: &str) -> usize {
+        match self {
+            BudgetUnit::Chars => text.chars().count(),
+            BudgetUnit::Bytes => text.len(),
+            BudgetUnit::ApproxTokens => approx_tokens(text),
+        }
+    }
+}
+
+/// 估算文本的 token 数：CJK 字符每个算 1 个并视为词的分隔，其余按空白分词，每个词算 1.3 个（向上取整）
+///
+/// # 参数
+/// * `text` - 文本
+///
+/// # 返回
+/// 估算的 token 数
+fn approx_tokens(text: &str) -> usize {
+    let mut cjk = 0usize;
+    let mut words = 0usize;
+    let mut in_word = false;
+    for c in text.chars() {
+        if is_cjk(c) {
+            cjk += 1;
+            in_word = false;
+        } else if c.is_whitespace() {
+            in_word = false;
+        } else if !in_word {
+            words += 1;
+            in_word = true;
+        }
+    }
+    cjk + (words * 13).div_ceil(10)
+}
+
+/// 判断字符是否属于 CJK 文字或全角标点
+///
+/// # 参数
+/// * `c` - 字符
+///
+/// # 返回
+/// 是否为 CJK 字符
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x303F | 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF
+        | 0xAC00..=0xD7AF | 0xF900..=0xFAFF | 0xFF00..=0xFFEF | 0x20000..=0x2FA1F)
+}
+
+/// 压缩选项
+#[derive(Debug, Clone)]
+pub struct CompressOptions {
+    /// 输出的最大长度（按 `budget_unit` 计，最小 200）
+    pub budget: usize,
+    /// 预算的计量单位（默认字符数）
+    pub budget_unit: BudgetUnit,
+    /// 考虑的最大记忆数（默认 25）
+    pub limit: Option<usize>,
+    /// 查询没有命中时，是否退回到最近的活跃记忆
+    pub fallback_recent: bool,
+    /// 是否按记忆的第一个标签分组，每组一个 `### <tag>` 小标题
+    pub group_by_tag: bool,
+    /// 按分类分组时使用的配置（分类由 `category_mapping` 决定，每组一个 `### <分类>` 小标题，
+    /// 组按组内分数之和排序）；设置后优先于 `group_by_tag`，None 表示不按分类分组
+    pub group_by_category: Option<Config>,
+    /// 预算放不下下一条记忆时，截取它的开头（以省略号结尾）填满剩余预算，而不是整条丢弃
+    pub trim_last: bool,
+    /// 评分时不计时效性加分，相同记录和查询的输出不随日期变化（便于快照测试）
+    pub disable_recency: bool,
+    /// 相关性评分权重
+    pub weights: ScoringWeights,
+    /// 关键词集合的 Jaccard 相似度达到该值时，把改写过的近似记忆也视为重复（None 表示只去掉文本相同的记忆）
+    pub dedup_threshold: Option<f64>,
+}
+
+impl Default for CompressOptions {
+    fn default() -> Self {
+        Self {
+            budget: 2000,
+            budget_unit: BudgetUnit::default(),
+            limit: None,
+            fallback_recent: false,
+            group_by_tag: false,
+            group_by_category: None,
+            trim_last: false,
+            disable_recency: false,
+            weights: ScoringWeights::default(),
+            dedup_threshold: None,
+        }
+    }
+}
 
 /// 确定性地将相关记忆压缩为预算约束的 markdown 块
 /// 使用确定性截断（无 LLM）- 包含记忆直到预算耗尽
@@ -7,39 +147,205 @@ use crate::store::score_record;
 /// # 参数
 /// * `records` - 记忆记录数组
 /// * `query` - 查找相关记忆的搜索查询
-/// * `budget` - 输出的最大字符数（最小 200）
+/// * `budget` - 输出的最大长度（按 `unit` 计，最小 200）
 /// * `limit` - 考虑的最大记忆数（默认 25）
+/// * `unit` - 预算的计量单位
 ///
 /// # 返回
-/// 带有 markdown 和元数据的 CompressResult
+/// 带有 markdown 和元数据的 CompressResult（`used` 与预算同一单位）
 pub fn compress_deterministic(
-    records: &Vec<MemoryRecord>,
+    records: &[MemoryRecord],
     query: &str,
     budget: usize,
     limit: Option<usize>,
+    unit: BudgetUnit,
 ) -> CompressResult {
-    let budget = budget.max(200);
-    let limit = limit.unwrap_or(25);
+    let options = CompressOptions {
+        budget,
+        budget_unit: unit,
+        limit,
+        ..CompressOptions::default()
+    };
+    compress_with_options(records, query, &options)
+}
+
+/// 按压缩选项确定性地压缩相关记忆
+///
+/// # 参数
+/// * `records` - 记忆记录数组
+/// * `query` - 查找相关记忆的搜索查询
+/// * `options` - 压缩选项
+///
+/// # 返回
+/// 带有 markdown 和元数据的 CompressResult
+pub fn compress_with_options(
+    records: &[MemoryRecord],
+    query: &str,
+    options: &CompressOptions,
+) -> CompressResult {
+    compress_with_profile(records, query, options).0
+}
 
-    let hits = search_records(records, query, Some(limit));
+/// 按压缩选项压缩相关记忆，并记录评分、截断阶段耗时
+/// 加载阶段由调用方计时并填入 `load_ms`
+///
+/// # 参数
+/// * `records` - 记忆记录数组
+/// * `query` - 查找相关记忆的搜索查询
+/// * `options` - 压缩选项
+///
+/// # 返回
+/// (CompressResult, 各阶段耗时)
+pub fn compress_with_profile(
+    records: &[MemoryRecord],
+    query: &str,
+    options: &CompressOptions,
+) -> (CompressResult, CompressProfile) {
+    let mut profile = CompressProfile::default();
+    let budget = options.budget.max(200);
+    let limit = options.limit.unwrap_or(25);
+
+    let started = Instant::now();
+    // 先去重再取前 limit 条，重复的记忆不占用名额
+    let mut hits = search_records(records, query, Some(records.len()), options.disable_recency, &options.weights);
+    if hits.is_empty() && options.fallback_recent {
+        hits = recent_hits(records, records.len());
+    }
+    let mut hits = dedup_hits(hits, options.dedup_threshold);
+    hits.truncate(limit.max(1));
+    profile.score_ms = elapsed_ms(started);
+
+    let started = Instant::now();
+    let result = render_markdown(hits, budget, options);
+    profile.truncate_ms = elapsed_ms(started);
+
+    (result, profile)
+}
 
+/// 把已有的搜索命中按排名压缩为 markdown 块，不再重新评分
+/// 用于调用方已经拿到命中、需要输出与之完全一致的场景
+///
+/// # 参数
+/// * `hits` - 按排名排列的搜索命中
+/// * `options` - 压缩选项（只使用预算、分组、截断和去重相关的选项）
+///
+/// # 返回
+/// 带有 markdown 和元数据的 CompressResult
+pub fn compress_hits(hits: Vec<SearchHit>, options: &CompressOptions) -> CompressResult {
+    let budget = options.budget.max(200);
+    render_markdown(dedup_hits(hits, options.dedup_threshold), budget, options)
+}
+
+/// 去掉重复的命中，保留排名靠前的一条
+/// 文本相同或规范化后（小写、合并空白）相同的视为重复；指定阈值时，关键词集合 Jaccard 相似度达到阈值的也视为重复
+///
+/// # 参数
+/// * `hits` - 按排名排列的搜索命中
+/// * `threshold` - 关键词相似度阈值（0-1，None 表示不按关键词合并）
+///
+/// # 返回
+/// 去重后的搜索命中
+fn dedup_hits(hits: Vec<SearchHit>, threshold: Option<f64>) -> Vec<SearchHit> {
+    let mut seen_texts: HashSet<String> = HashSet::new();
+    let mut kept_keywords: Vec<HashSet<String>> = Vec::new();
+    let mut kept: Vec<SearchHit> = Vec::new();
+    for h in hits {
+        let normalized = h.text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        if !seen_texts.insert(normalized) {
+            continue;
+        }
+        let keywords: HashSet<String> = h.keywords.iter().map(|k| k.to_lowercase()).collect();
+        if let Some(threshold) = threshold {
+            if kept_keywords.iter().any(|other| jaccard(&keywords, other) >= threshold) {
+                continue;
+            }
+        }
+        kept_keywords.push(keywords);
+        kept.push(h);
+    }
+    kept
+}
+
+/// 计算两个关键词集合的 Jaccard 相似度
+///
+/// # 参数
+/// * `a` - 关键词集合
+/// * `b` - 关键词集合
+///
+/// # 返回
+/// 交集大小除以并集大小（任一集合为空时为 0）
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let shared = a.intersection(b).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+/// 没有标签的记忆在分组输出中归入的小标题
+const UNTAGGED_GROUP: &str = "untagged";
+
+/// 截断记忆文本时追加的省略号
+const TRIM_ELLIPSIS: &str = "…";
+
+/// 截断后至少保留的文本长度（字节），剩余预算更少时不再截取
+const MIN_TRIMMED_TEXT: usize = 24;
+
+/// 生成 markdown 并按预算截断
+///
+/// # 参数
+/// * `hits` - 包含的搜索命中
+/// * `budget` - 输出的最大长度（按 `options.budget_unit` 计）
+/// * `options` - 压缩选项（计量单位、分组、截取最后一条）
+///
+/// # 返回
+/// 带有 markdown 和元数据的 CompressResult
+fn render_markdown(hits: Vec<SearchHit>, budget: usize, options: &CompressOptions) -> CompressResult {
     let mut lines: Vec<String> = Vec::new();
+    // 与 lines 一一对应：列表项对应的命中（标题、空行为 None），用于截取最后一条
+    let mut line_hits: Vec<Option<&SearchHit>> = Vec::new();
     lines.push("# Copilot Context (auto)".to_string());
     lines.push(String::new());
     lines.push("## Relevant memory".to_string());
+    line_hits.resize(lines.len(), None);
 
-    for h in &hits {
-        let tag_str = if h.tags.is_empty() {
-            String::new()
-        } else {
-            format!(" [{}]", h.tags.join(", "))
-        };
-        lines.push(format!("- ({}){} {}", h.id, tag_str, h.text));
+    if options.group_by_tag || options.group_by_category.is_some() {
+        // 按标签分组时，组按组内最相关记忆的排名排序；按分类分组时按组内分数之和排序。组内保持原有排名
+        let mut groups: Vec<(String, Vec<&SearchHit>)> = Vec::new();
+        for h in &hits {
+            let key = match &options.group_by_category {
+                Some(config) => get_category_for_tags(config, &h.tags),
+                None => h.tags.first().map(|t| t.to_string()).unwrap_or_else(|| UNTAGGED_GROUP.to_string()),
+            };
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, members)) => members.push(h),
+                None => groups.push((key, vec![h])),
+            }
+        }
+        if options.group_by_category.is_some() {
+            let total = |members: &[&SearchHit]| members.iter().map(|h| h.score).sum::<f64>();
+            groups.sort_by(|a, b| total(&b.1).partial_cmp(&total(&a.1)).unwrap_or(std::cmp::Ordering::Equal));
+        }
+        for (key, members) in groups {
+            lines.push(String::new());
+            lines.push(format!("### {}", key));
+            line_hits.extend([None, None]);
+            for h in members {
+                lines.push(bullet_line(h));
+                line_hits.push(Some(h));
+            }
+        }
+    } else {
+        for h in &hits {
+            lines.push(bullet_line(h));
+            line_hits.push(Some(h));
+        }
     }
 
+    let unit = options.budget_unit;
     let md = lines.join("\n") + "\n";
-    let md_len = md.len();
-    if md.len() <= budget {
+    let md_len = unit.measure(&md);
+    if md_len <= budget {
         return CompressResult {
             markdown: md,
             included: hits,
@@ -48,48 +354,173 @@ pub fn compress_deterministic(
         };
     }
 
+    // 逐行累加的长度不小于整体长度（估算 token 时每行单独向上取整），不会超出预算
+    // included 只保留实际放入预算的记忆（包括被截取的最后一条），按输出顺序排列
+    let newline = unit.measure("\n");
     let mut out: Vec<String> = Vec::new();
+    let mut included: Vec<SearchHit> = Vec::new();
     let mut size = 0;
-    for line in &lines {
-        if size + line.len() + 1 > budget {
+    for (line, hit) in lines.iter().zip(&line_hits) {
+        let line_len = unit.measure(line);
+        if size + line_len + newline > budget {
+            // 已恰好用满预算时 remaining 为 0，不再截取
+            let remaining = budget.saturating_sub(size + newline);
+            if let (true, Some(h), true) = (options.trim_last, hit, remaining > 0) {
+                if let Some(trimmed) = trimmed_bullet_line(h, remaining, unit) {
+                    out.push(trimmed);
+                    included.push((*h).clone());
+                }
+            }
             break;
         }
         out.push(line.clone());
-        size += line.len() + 1;
+        if let Some(h) = hit {
+            included.push((*h).clone());
+        }
+        size += line_len + newline;
+    }
+    // 截断后不保留没有内容的小标题
+    while out.last().is_some_and(|l| l.is_empty() || l.starts_with("### ")) {
+        out.pop();
     }
 
     let md2 = out.join("\n") + "\n";
-    let md2_len = md2.len();
+    let md2_len = unit.measure(&md2);
     CompressResult {
         markdown: md2,
-        included: hits,
+        included,
         budget,
         used: md2_len,
     }
 }
 
-/// 使用 LLM 压缩记忆（需要 llm feature）
-/// 此功能是预留的，需要外部 LLM 服务支持
+/// 生成一条记忆的列表项
+///
+/// # 参数
+/// * `h` - 搜索命中
+///
+/// # 返回
+/// `- (id) [tags] text` 格式的列表项
+fn bullet_line(h: &SearchHit) -> String {
+    let tag_str = if h.tags.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", h.tags.join(", "))
+    };
+    format!("- ({}){} {}", h.id, tag_str, h.text)
+}
+
+/// 截取记忆文本的开头，生成不超过指定长度的列表项（以省略号结尾）
+/// 优先在句子结尾处截断，句子边界太靠前时按字符截断
+///
+/// # 参数
+/// * `h` - 搜索命中
+/// * `max_len` - 列表项的最大长度（按 `unit` 计，不含换行）
+/// * `unit` - 长度的计量单位
+///
+/// # 返回
+/// 截取后的列表项；剩余长度放不下有意义的片段时返回 None
+fn trimmed_bullet_line(h: &SearchHit, max_len: usize, unit: BudgetUnit) -> Option<String> {
+    let prefix_len = bullet_line(h).len() - h.text.len();
+    let line = bullet_line(h);
+    let room = max_len.checked_sub(unit.measure(&line[..prefix_len]) + unit.measure(TRIM_ELLIPSIS))?;
+
+    // 找到按该单位不超过剩余长度的最长前缀（长度随前缀单调不减，可以二分）
+    let boundaries: Vec<usize> = h.text.char_indices().map(|(i, _)| i).skip(1).chain([h.text.len()]).collect();
+    let fits = boundaries.partition_point(|end| unit.measure(&h.text[..*end]) <= room);
+    let cut = if fits == 0 { 0 } else { boundaries[fits - 1] };
+    if cut < MIN_TRIMMED_TEXT {
+        return None;
+    }
+    let head = &h.text[..cut];
+    let sentence_end = head
+        .char_indices()
+        .rev()
+        .find(|(_, c)| matches!(c, '.' | '!' | '?' | '。' | '！' | '？'))
+        .map(|(i, c)| i + c.len_utf8())
+        .filter(|end| *end >= cut / 2);
+    let head = &head[..sentence_end.unwrap_or(cut)];
+
+    Some(format!("{}{}{}", &line[..prefix_len], head.trim_end(), TRIM_ELLIPSIS))
+}
+
+/// LLM 压缩失败的原因
+#[cfg(feature = "llm")]
+#[derive(Debug)]
+pub enum LlmError {
+    /// 请求没有发出或响应无法读取
+    Http(reqwest::Error),
+    /// 接口返回非 200 状态码
+    Status {
+        /// HTTP 状态码
+        status: u16,
+        /// 响应正文（通常包含接口给出的错误说明）
+        body: String,
+    },
+    /// 响应中没有生成的内容
+    EmptyResponse,
+}
+
+#[cfg(feature = "llm")]
+impl std::fmt::Display for LlmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LlmError::Http(e) => write!(f, "DeepSeek request failed: {}", e),
+            LlmError::Status { status, body } => write!(f, "DeepSeek returned HTTP {}: {}", status, body),
+            LlmError::EmptyResponse => write!(f, "DeepSeek response contained no content"),
+        }
+    }
+}
+
+#[cfg(feature = "llm")]
+impl std::error::Error for LlmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LlmError::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "llm")]
+impl From<reqwest::Error> for LlmError {
+    fn from(e: reqwest::Error) -> Self {
+        LlmError::Http(e)
+    }
+}
+
+/// 使用 DeepSeek 压缩记忆（需要 llm feature）
+/// 密钥、接口地址和模型读取自配置（`deepseek_api_key`、`deepseek_api_base`、`deepseek_model`），
+/// 没有配置密钥时退回 `compress_deterministic`。生成内容超过预算时按字符边界截断
 ///
 /// # 参数
 /// * `records` - 记忆记录数组
 /// * `query` - 查找相关记忆的搜索查询
 /// * `budget` - 输出的最大字符数（最小 200）
 /// * `limit` - 考虑的最大记忆数（默认 25）
+/// * `config` - 配置结构体
 ///
 /// # 返回
 /// 带有 markdown 和元数据的 CompressResult
 #[cfg(feature = "llm")]
 pub async fn compress_with_llm(
-    records: &Vec<MemoryRecord>,
+    records: &[MemoryRecord],
     query: &str,
     budget: usize,
     limit: Option<usize>,
-) -> Result<CompressResult, Box<dyn std::error::Error>> {
+    config: &Config,
+) -> Result<CompressResult, LlmError> {
+    let api_key = config.deepseek_api_key.as_deref().map(str::trim).unwrap_or("");
+    if api_key.is_empty() {
+        return Ok(compress_deterministic(records, query, budget, limit, BudgetUnit::Chars));
+    }
+    let api_base = get_config_string(&config.deepseek_api_base, DEFAULT_DEEPSEEK_API_BASE);
+    let model = get_config_string(&config.deepseek_model, DEFAULT_DEEPSEEK_MODEL);
+
     let budget = budget.max(200);
     let limit = limit.unwrap_or(25);
 
-    let hits = search_records(records, query, Some(limit));
+    let hits = search_records(records, query, Some(limit), false, &get_scoring_weights(config));
 
     let context: String = hits
         .iter()
@@ -104,23 +535,41 @@ pub async fn compress_with_llm(
 
     let client = reqwest::Client::new();
     let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", "Bearer YOUR_API_KEY")
+        .post(format!("{}/chat/completions", api_base.trim_end_matches('/')))
+        .bearer_auth(api_key)
         .json(&serde_json::json!({
-            "model": "gpt-3.5-turbo",
+            "model": model,
             "messages": [{"role": "user", "content": prompt}],
-            "max_tokens": budget / 2,
+            // 一个 token 至少对应一个字符，按预算字符数限制 token 数即可
+            "max_tokens": budget,
+            "stream": false,
         }))
         .send()
         .await?;
 
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(LlmError::Status { status: status.as_u16(), body });
+    }
+
     let json: serde_json::Value = response.json().await?;
-    let markdown = json["choices"][0]["message"]["content"]
+    let mut markdown = json["choices"][0]["message"]["content"]
         .as_str()
-        .unwrap_or("# Copilot Context (LLM)\n\n无法生成压缩内容")
-        .to_string();
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .ok_or(LlmError::EmptyResponse)?;
+
+    // 预算和提示词一样按字符计，截断在字符边界上
+    let unit = BudgetUnit::Chars;
+    if unit.measure(&markdown) > budget {
+        let room = budget.saturating_sub(unit.measure(TRIM_ELLIPSIS));
+        let cut = markdown.char_indices().nth(room).map_or(markdown.len(), |(i, _)| i);
+        markdown.truncate(cut);
+        markdown.push_str(TRIM_ELLIPSIS);
+    }
 
-    let used = markdown.len();
+    let used = unit.measure(&markdown);
     Ok(CompressResult {
         markdown,
         included: hits,
@@ -129,19 +578,50 @@ pub async fn compress_with_llm(
     })
 }
 
+/// 获取最近更新的活跃记忆（查询无命中时的退回结果，分数为 0）
+///
+/// # 参数
+/// * `records` - 记忆记录数组
+/// * `limit` - 返回的最大数量
+///
+/// # 返回
+/// 按 updated_at 降序排列的搜索命中数组
+pub fn recent_hits(records: &[MemoryRecord], limit: usize) -> Vec<SearchHit> {
+    let mut active: Vec<&MemoryRecord> = records.iter().filter(|r| r.deleted_at.is_none()).collect();
+    active.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    active.into_iter()
+        .take(std::cmp::max(1, limit))
+        .map(|r| SearchHit {
+            id: r.id.clone(),
+            text: r.text.clone(),
+            tags: r.tags.clone(),
+            keywords: r.keywords.clone(),
+            created_at: r.created_at.clone(),
+            updated_at: r.updated_at.clone(),
+            source: r.source.clone(),
+            deleted_at: None,
+            score: 0.0,
+        })
+        .collect()
+}
+
 /// 搜索记录（内部辅助函数）
 ///
 /// # 参数
 /// * `records` - 记忆记录数组
 /// * `query` - 搜索查询
 /// * `limit` - 返回的最大结果数
+/// * `disable_recency` - 是否不计时效性加分
+/// * `weights` - 评分权重
 ///
 /// # 返回
 /// 按分数降序排列的搜索命中数组
 fn search_records(
-    records: &Vec<MemoryRecord>,
+    records: &[MemoryRecord],
     query: &str,
     limit: Option<usize>,
+    disable_recency: bool,
+    weights: &ScoringWeights,
 ) -> Vec<SearchHit> {
     let mut hits: Vec<SearchHit> = Vec::new();
     let limit = limit.unwrap_or(10);
@@ -150,7 +630,12 @@ fn search_records(
         if r.deleted_at.is_some() {
             continue;
         }
-        let score = score_record(r, query);
+        let breakdown = score_breakdown(r, query, weights);
+        let score = if disable_recency {
+            breakdown.total() - breakdown.recency
+        } else {
+            breakdown.total()
+        };
         if score <= 0.0 {
             continue;
         }
@@ -161,10 +646,198 @@ fn search_records(
             keywords: r.keywords.clone(),
             created_at: r.created_at.clone(),
             updated_at: r.updated_at.clone(),
+            source: r.source.clone(),
+            deleted_at: None,
             score,
         });
     }
 
-    hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    hits.sort_by(compare_hits);
     hits.into_iter().take(std::cmp::max(1, limit)).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造测试记录（时间戳固定，便于得到确定的输出）
+    fn record(id: &str, text: &str, tags: &[&str]) -> MemoryRecord {
+        MemoryRecord {
+            id: id.to_string(),
+            text: text.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            keywords: crate::keywords::extract_keywords(text),
+            created_at: "2024-01-01T00:00:00.000Z".to_string(),
+            updated_at: "2024-01-01T00:00:00.000Z".to_string(),
+            deleted_at: None,
+            idempotency_key: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn compress_result_json_round_trips() {
+        let records = vec![
+            record("m_1", "rust lock files keep writers apart", &["rust"]),
+            record("m_2", "rust search ranks memories", &["rust", "search"]),
+            record("m_3", "unrelated note", &[]),
+        ];
+        let result = compress_with_options(&records, "rust", &CompressOptions::default());
+        assert_eq!(result.included.len(), 2);
+
+        let json = serde_json::to_string_pretty(&result).unwrap();
+        let parsed: CompressResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.markdown, result.markdown);
+        assert_eq!(parsed.budget, result.budget);
+        assert_eq!(parsed.used, result.used);
+        let ids = |hits: &[SearchHit]| hits.iter().map(|h| (h.id.clone(), h.score)).collect::<Vec<_>>();
+        assert_eq!(ids(&parsed.included), ids(&result.included));
+    }
+
+    #[test]
+    fn no_match_query_falls_back_to_recent_only_when_enabled() {
+        let mut newer = record("m_new", "newer note", &[]);
+        newer.updated_at = "2024-03-01T00:00:00.000Z".to_string();
+        let mut deleted = record("m_gone", "deleted note", &[]);
+        deleted.updated_at = "2024-04-01T00:00:00.000Z".to_string();
+        deleted.deleted_at = Some(deleted.updated_at.clone());
+        let records = vec![record("m_old", "older note", &[]), newer, deleted];
+
+        let off = compress_with_options(&records, "nothing matches", &CompressOptions::default());
+        assert!(off.included.is_empty());
+
+        let on = compress_with_options(&records, "nothing matches", &CompressOptions {
+            fallback_recent: true,
+            limit: Some(1),
+            ..CompressOptions::default()
+        });
+        let ids: Vec<&str> = on.included.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["m_new"]);
+        assert!(on.markdown.contains("newer note"));
+        assert!(!on.markdown.contains("deleted note"));
+    }
+
+    #[test]
+    fn duplicates_do_not_take_limit_slots() {
+        let records = vec![
+            record("m_1", "rust lock files keep writers apart", &["rust"]),
+            record("m_2", "Rust lock files  keep writers apart", &["rust"]),
+            record("m_3", "rust search ranks memories", &[]),
+        ];
+
+        let result = compress_with_options(&records, "rust", &CompressOptions {
+            limit: Some(2),
+            disable_recency: true,
+            ..CompressOptions::default()
+        });
+        let ids: Vec<&str> = result.included.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["m_1", "m_3"]);
+    }
+
+    #[test]
+    fn trim_last_includes_head_of_a_hit_longer_than_the_budget() {
+        let long_text = format!("rust {}", "lock files keep concurrent writers apart. ".repeat(20));
+        let records = vec![record("m_long", &long_text, &["rust"])];
+        let options = CompressOptions {
+            budget: 200,
+            trim_last: true,
+            ..CompressOptions::default()
+        };
+        let result = compress_with_options(&records, "rust", &options);
+
+        assert!(result.used <= result.budget);
+        assert_eq!(result.included.len(), 1);
+        let bullet = result.markdown.lines().last().unwrap();
+        assert!(bullet.starts_with("- (m_long) [rust] rust lock files"));
+        assert!(bullet.ends_with(TRIM_ELLIPSIS));
+
+        let dropped = compress_with_options(&records, "rust", &CompressOptions { trim_last: false, ..options });
+        assert!(dropped.included.is_empty());
+    }
+
+    #[test]
+    fn trim_last_stops_when_the_budget_is_exactly_full() {
+        // 标题三行共 45 个字符（含换行），每条列表项 154 个字符加换行，第一条恰好用满 200
+        let records = vec![
+            record("m_a", &format!("rust {}", "a".repeat(134)), &["rust"]),
+            record("m_b", &format!("rust {}", "b".repeat(134)), &["rust"]),
+        ];
+        let options = CompressOptions {
+            budget: 200,
+            trim_last: true,
+            ..CompressOptions::default()
+        };
+        let result = compress_with_options(&records, "rust", &options);
+
+        assert_eq!(result.used, 200);
+        assert_eq!(result.included.len(), 1);
+        assert!(!result.markdown.contains(TRIM_ELLIPSIS));
+    }
+
+    #[test]
+    fn group_by_tag_adds_subheadings_and_drops_empty_trailing_heading() {
+        let records = vec![
+            record("m_rust", "rust lock notes and rust ranking", &["rust"]),
+            record("m_git", &format!("git {}", "history rewriting advice ".repeat(12)), &["git"]),
+        ];
+        let options = CompressOptions {
+            group_by_tag: true,
+            ..CompressOptions::default()
+        };
+
+        let full = compress_with_options(&records, "rust git", &options);
+        assert!(full.markdown.contains("### rust\n- (m_rust)"));
+        assert!(full.markdown.contains("### git\n- (m_git)"));
+        assert!(full.markdown.find("### rust").unwrap() < full.markdown.find("### git").unwrap());
+
+        // 第二组的小标题放得下，但它唯一的列表项放不下
+        let truncated = compress_with_options(&records, "rust git", &CompressOptions { budget: 200, ..options });
+        assert!(truncated.used <= truncated.budget);
+        assert!(truncated.markdown.contains("### rust"));
+        assert!(!truncated.markdown.contains("### git"));
+        let last = truncated.markdown.lines().last().unwrap();
+        assert!(last.starts_with("- (m_rust)"));
+    }
+
+    #[test]
+    fn disable_recency_output_matches_snapshot() {
+        let records = vec![
+            record("m_1", "rust lock notes", &["rust"]),
+            record("m_2", "rust search and rust ranking", &["rust", "search"]),
+            record("m_3", "unrelated note", &[]),
+        ];
+        let options = CompressOptions {
+            disable_recency: true,
+            ..CompressOptions::default()
+        };
+        let result = compress_with_options(&records, "rust", &options);
+
+        assert_eq!(
+            result.markdown,
+            "# Copilot Context (auto)\n\
+             \n\
+             ## Relevant memory\n\
+             - (m_2) [rust, search] rust search and rust ranking\n\
+             - (m_1) [rust] rust lock notes\n"
+        );
+    }
+
+    #[test]
+    fn budget_units_measure_cjk_text_and_bound_used() {
+        let text = "记忆 store";
+        assert_eq!(BudgetUnit::Chars.measure(text), 8);
+        assert_eq!(BudgetUnit::Bytes.measure(text), 12);
+        // 2 个 CJK 字符 + 1 个英文词（1.3 向上取整为 2）
+        assert_eq!(BudgetUnit::ApproxTokens.measure(text), 4);
+
+        let records: Vec<MemoryRecord> = (0..30)
+            .map(|i| record(&format!("m_{:02}", i), &format!("rust 锁文件让并发写入互不干扰，第 {} 条", i), &["rust"]))
+            .collect();
+        for unit in [BudgetUnit::Chars, BudgetUnit::Bytes, BudgetUnit::ApproxTokens] {
+            let result = compress_deterministic(&records, "rust", 200, None, unit);
+            assert_eq!(result.used, unit.measure(&result.markdown), "unit {}", unit.name());
+            assert!(result.used <= result.budget, "unit {}", unit.name());
+            assert!(!result.included.is_empty());
+        }
+    }
+}
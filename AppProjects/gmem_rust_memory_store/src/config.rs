@@ -1,6 +1,26 @@
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
 use serde::{Deserialize, Serialize};
+use crate::record::ScoringWeights;
+use crate::store::Bm25Params;
+
+lazy_static::lazy_static! {
+    /// 进程级配置文件路径覆盖（由 `--config` 设置），未传入路径的 `load_config(None)` 会使用它
+    static ref CONFIG_PATH_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// 设置进程级配置文件路径，之后所有 `load_config(None)` 都读取该文件
+///
+/// # 参数
+/// * `config_path` - 配置文件路径（相对路径基于当前目录）
+pub fn set_config_path(config_path: &str) {
+    let resolved = resolve_config_path(Some(config_path));
+    if let Ok(mut guard) = CONFIG_PATH_OVERRIDE.write() {
+        *guard = Some(resolved);
+    }
+}
 
 /// 配置文件结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +29,10 @@ pub struct Config {
     pub project_name: Option<String>,
     /// DeepSeek API 密钥
     pub deepseek_api_key: Option<String>,
+    /// DeepSeek API 地址（请求发往 `<地址>/chat/completions`）
+    pub deepseek_api_base: Option<String>,
+    /// LLM 压缩使用的 DeepSeek 模型
+    pub deepseek_model: Option<String>,
     /// 记忆文件路径
     pub memory_path: Option<String>,
     /// 备份格式
@@ -33,6 +57,62 @@ pub struct Config {
     pub debug_enabled: Option<bool>,
     /// 记忆分类映射（标签到分类的映射）
     pub category_mapping: Option<std::collections::HashMap<String, String>>,
+    /// 默认标签（每次添加记忆时自动合并）
+    pub default_tags: Option<Vec<String>>,
+    /// 记忆 ID 前缀（多个存储联合使用时区分来源）
+    pub id_prefix: Option<String>,
+    /// 写入时是否按创建时间和 ID 排序记录（便于审阅差异）
+    pub sort_on_write: Option<bool>,
+    /// MCP 搜索单次返回的最大结果数（客户端请求的 limit 会被截断到该值）
+    pub mcp_max_search_limit: Option<usize>,
+    /// 未指定 limit 时搜索返回的结果数（CLI、MCP 和库接口共用）
+    pub search_default_limit: Option<usize>,
+    /// 记忆文本的最大长度（字符，0 或未设置表示不限制）
+    pub max_text_len: Option<usize>,
+    /// 文本超长时的处理方式：truncate（截断并打上 truncated 标签）或 reject（拒绝添加）
+    pub max_text_mode: Option<String>,
+    /// 标签蕴含关系（标签 -> 它隐含的标签），添加记忆时自动补全
+    pub tag_implications: Option<std::collections::HashMap<String, Vec<String>>>,
+    /// 搜索和压缩时是否默认排除带 temp 标签的临时记忆
+    pub exclude_temp_by_default: Option<bool>,
+    /// 交互式命令行（REPL）的输入提示符
+    pub prompt: Option<String>,
+    /// 启动时清理的过期锁文件最大年龄（秒），超过此年龄的锁视为崩溃残留
+    pub lock_max_age_seconds: Option<u64>,
+    /// 审计日志路径（JSONL，记录每次添加、修改、删除、永久删除；相对路径相对于记忆文件目录，未设置时不记录）
+    pub audit_log: Option<String>,
+    /// 常驻整理定时器（organize_timer）启动时没有到期也立即整理一次；为 false 时首次整理等待一个完整间隔
+    pub organize_run_on_start: Option<bool>,
+    /// 把 memory_path 当作目录存储：记录按分类分散在目录中的分类文件里，读写接口与单文件存储一致
+    pub directory_store: Option<bool>,
+    /// 每次重写记忆文件前先备份原文件（`<文件名>.<时间>.bak`）
+    pub backup_before_write: Option<bool>,
+    /// 写入前备份保留的数量
+    pub backup_keep: Option<usize>,
+    /// `purge --journal` 生成的永久删除日志保留的数量（超过后删除最旧的）
+    pub purge_journal_keep: Option<usize>,
+    /// 缓冲写入攒够多少条记忆后写入一次（0 或未设置表示每次添加立即写入）
+    pub write_buffer_size: Option<usize>,
+    /// 缓冲写入的刷新间隔（秒），缓冲区非空时由后台线程按间隔写入（0 表示只在攒满或退出时写入）
+    pub write_buffer_flush_secs: Option<u64>,
+    /// 命令行 search 每条结果显示的最大字符数（0 表示不截断）
+    pub result_preview_len: Option<usize>,
+    /// 文本中查询词每次命中的得分
+    pub score_text_hit: Option<f64>,
+    /// 每个匹配标签的得分
+    pub score_tag_hit: Option<f64>,
+    /// 每个匹配的提取关键词的得分
+    pub score_keyword_hit: Option<f64>,
+    /// 时效性加分的最大值
+    pub score_recency_max: Option<f64>,
+    /// 时效性加分减半所需的天数（两倍该天数后为 0）
+    pub score_recency_halflife_days: Option<f64>,
+    /// 搜索的文本相关性算法：linear（每次命中固定加分）或 bm25
+    pub ranking: Option<String>,
+    /// BM25 词频饱和度 k1
+    pub bm25_k1: Option<f64>,
+    /// BM25 长度归一化强度 b（0-1）
+    pub bm25_b: Option<f64>,
 }
 
 impl Default for Config {
@@ -63,6 +143,8 @@ impl Default for Config {
         Self {
             project_name: Some("global-memory-rule".to_string()),
             deepseek_api_key: None,
+            deepseek_api_base: Some(DEFAULT_DEEPSEEK_API_BASE.to_string()),
+            deepseek_model: Some(DEFAULT_DEEPSEEK_MODEL.to_string()),
             memory_path: None,
             backup_format: Some("markdown".to_string()),
             backup_interval: Some(7200000),
@@ -75,10 +157,160 @@ impl Default for Config {
             logs_level: Some("info".to_string()),
             debug_enabled: Some(false),
             category_mapping: Some(category_mapping),
+            default_tags: Some(Vec::new()),
+            id_prefix: Some("m".to_string()),
+            sort_on_write: Some(false),
+            mcp_max_search_limit: Some(DEFAULT_MCP_MAX_SEARCH_LIMIT),
+            search_default_limit: Some(DEFAULT_SEARCH_LIMIT),
+            max_text_len: Some(0),
+            max_text_mode: Some("truncate".to_string()),
+            tag_implications: Some(std::collections::HashMap::new()),
+            exclude_temp_by_default: Some(false),
+            prompt: Some(DEFAULT_PROMPT.to_string()),
+            lock_max_age_seconds: Some(300),
+            audit_log: None,
+            organize_run_on_start: Some(true),
+            directory_store: Some(false),
+            backup_before_write: Some(false),
+            backup_keep: Some(crate::backup::DEFAULT_BACKUP_KEEP),
+            purge_journal_keep: Some(crate::backup::DEFAULT_PURGE_JOURNAL_KEEP),
+            write_buffer_size: Some(0),
+            write_buffer_flush_secs: Some(DEFAULT_WRITE_BUFFER_FLUSH_SECS),
+            result_preview_len: Some(DEFAULT_RESULT_PREVIEW_LEN),
+            score_text_hit: None,
+            score_tag_hit: None,
+            score_keyword_hit: None,
+            score_recency_max: None,
+            score_recency_halflife_days: None,
+            ranking: Some("linear".to_string()),
+            bm25_k1: None,
+            bm25_b: None,
         }
     }
 }
 
+/// DeepSeek API 默认地址
+pub const DEFAULT_DEEPSEEK_API_BASE: &str = "https://api.deepseek.com";
+
+/// LLM 压缩默认使用的 DeepSeek 模型
+pub const DEFAULT_DEEPSEEK_MODEL: &str = "deepseek-chat";
+
+/// MCP 搜索默认的最大结果数
+pub const DEFAULT_MCP_MAX_SEARCH_LIMIT: usize = 200;
+
+/// 获取 MCP 搜索的最大结果数
+///
+/// # 参数
+/// * `config` - 配置结构体
+///
+/// # 返回
+/// 最大结果数（未配置时为 200，最小为 1）
+pub fn get_mcp_max_search_limit(config: &Config) -> usize {
+    config.mcp_max_search_limit.unwrap_or(DEFAULT_MCP_MAX_SEARCH_LIMIT).max(1)
+}
+
+/// 命令行 search 结果默认显示的最大字符数
+pub const DEFAULT_RESULT_PREVIEW_LEN: usize = 200;
+
+/// 获取命令行 search 结果显示的最大字符数
+///
+/// # 参数
+/// * `config` - 配置结构体
+///
+/// # 返回
+/// 最大字符数，配置为 0 时返回 None（不截断）
+pub fn get_result_preview_len(config: &Config) -> Option<usize> {
+    Some(config.result_preview_len.unwrap_or(DEFAULT_RESULT_PREVIEW_LEN)).filter(|len| *len > 0)
+}
+
+/// 获取相关性评分权重
+///
+/// # 参数
+/// * `config` - 配置结构体
+///
+/// # 返回
+/// 评分权重（未配置的项使用默认值）
+pub fn get_scoring_weights(config: &Config) -> ScoringWeights {
+    let defaults = ScoringWeights::default();
+    ScoringWeights {
+        text_hit: config.score_text_hit.unwrap_or(defaults.text_hit),
+        tag_hit: config.score_tag_hit.unwrap_or(defaults.tag_hit),
+        keyword_hit: config.score_keyword_hit.unwrap_or(defaults.keyword_hit),
+        recency_max: config.score_recency_max.unwrap_or(defaults.recency_max),
+        recency_halflife_days: config.score_recency_halflife_days.unwrap_or(defaults.recency_halflife_days),
+    }
+}
+
+/// 获取 BM25 参数
+///
+/// # 参数
+/// * `config` - 配置结构体
+///
+/// # 返回
+/// BM25 参数（未配置时 k1 = 1.2、b = 0.75，b 限制在 0-1）
+pub fn get_bm25_params(config: &Config) -> Bm25Params {
+    let defaults = Bm25Params::default();
+    Bm25Params {
+        k1: config.bm25_k1.unwrap_or(defaults.k1).max(0.0),
+        b: config.bm25_b.unwrap_or(defaults.b).clamp(0.0, 1.0),
+    }
+}
+
+/// 缓冲写入默认的刷新间隔（秒）
+pub const DEFAULT_WRITE_BUFFER_FLUSH_SECS: u64 = 5;
+
+/// 获取缓冲写入设置
+///
+/// # 参数
+/// * `config` - 配置结构体
+///
+/// # 返回
+/// (攒够多少条后写入, 刷新间隔)；`write_buffer_size` 未配置或为 0 时返回 None（不缓冲）
+pub fn get_write_buffer(config: &Config) -> Option<(usize, Option<Duration>)> {
+    let size = config.write_buffer_size.filter(|size| *size > 0)?;
+    let secs = config.write_buffer_flush_secs.unwrap_or(DEFAULT_WRITE_BUFFER_FLUSH_SECS);
+    Some((size, Some(Duration::from_secs(secs)).filter(|interval| !interval.is_zero())))
+}
+
+/// 未指定 limit 时搜索默认返回的结果数
+pub const DEFAULT_SEARCH_LIMIT: usize = 10;
+
+/// 获取搜索的默认结果数
+///
+/// # 参数
+/// * `config` - 配置结构体
+///
+/// # 返回
+/// 默认结果数（未配置时为 10，最小为 1）
+pub fn get_search_default_limit(config: &Config) -> usize {
+    config.search_default_limit.unwrap_or(DEFAULT_SEARCH_LIMIT).max(1)
+}
+
+/// REPL 默认的输入提示符
+pub const DEFAULT_PROMPT: &str = " > ";
+
+/// 获取 REPL 的输入提示符
+///
+/// # 参数
+/// * `config` - 配置结构体
+///
+/// # 返回
+/// 输入提示符（未配置时为 ` > `）
+pub fn get_prompt(config: &Config) -> &str {
+    config.prompt.as_deref().unwrap_or(DEFAULT_PROMPT)
+}
+
+/// 获取配置的默认标签
+///
+/// # 参数
+/// * `config` - 配置结构体
+///
+/// # 返回
+/// 默认标签列表（未配置时为空）
+pub fn get_default_tags(config: &Config) -> Vec<String> {
+    config.default_tags.clone().unwrap_or_default()
+}
+
 /// 加载配置文件
 ///
 /// # 参数
@@ -130,8 +362,11 @@ fn create_default_config(config_file: &Path) {
 # 项目名称
 project_name = "global-memory-rule"
 
-# DeepSeek API 密钥（可选：启用 LLM 压缩功能）
+# DeepSeek API 密钥（可选：启用 LLM 压缩功能，为空时 LLM 压缩退回确定性压缩）
 deepseek_api_key = ""
+# DeepSeek API 地址和模型（LLM 压缩请求 <地址>/chat/completions）
+deepseek_api_base = "https://api.deepseek.com"
+deepseek_model = "deepseek-chat"
 
 # 记忆文件路径（支持相对路径或绝对路径）
 memory_path = "E:\\GmemWorkerHome"
@@ -157,9 +392,82 @@ logs_dir = "logs/debug"
 logs_max_size = 1048576
 logs_level = "info"
 
-# Debug配置
+# Debug配置（开启后每次释放锁会在锁文件目录的 .lock-history 中追加一行持有记录）
 debug_enabled = false
 
+# 默认标签（每次添加记忆时自动合并，例如 ["source:cli"]）
+default_tags = []
+
+# 记忆 ID 前缀（多个存储联合使用时保持 ID 全局唯一，例如 "proj"）
+id_prefix = "m"
+
+# 写入时按创建时间和 ID 排序记录，使记忆文件的 git 差异稳定
+sort_on_write = false
+
+# MCP 搜索单次返回的最大结果数，防止客户端请求过大的 limit（search_memory/recall/compress_memory 共用）
+mcp_max_search_limit = 200
+
+# 未指定 limit 时搜索返回的结果数（命令行 search 和 MCP search_memory/recall/compress_memory 共用）
+search_default_limit = 10
+
+# 记忆文本的最大长度（字符），0 表示不限制
+max_text_len = 0
+
+# 文本超长时的处理方式：truncate（截断并打上 truncated 标签）或 reject（拒绝添加）
+max_text_mode = "truncate"
+
+# 搜索和压缩时默认排除带 temp 标签的临时记忆（相当于总是加上 --no-temp）
+exclude_temp_by_default = false
+
+# 交互式命令行（REPL）的输入提示符
+prompt = " > "
+
+# 启动时自动清理超过此年龄（秒）的锁文件，避免崩溃残留的锁拖慢第一次写入
+lock_max_age_seconds = 300
+
+# 审计日志（JSONL，每次添加、修改、删除、永久删除追加一行：时间、操作、ID、文本哈希，不含原文）
+# audit_log = "audit.jsonl"
+
+# 常驻整理定时器启动时是否立即整理（false 时首次整理等待一个完整间隔，相当于 --no-run-on-start）
+organize_run_on_start = true
+
+# 把 memory_path 当作目录存储：记忆按 category_mapping 分散保存到 {分类}-global-gmem-recorder.json，
+# add/search/delete 等命令直接读写这些分类文件（为 false 时 memory_path 是单个 JSON 文件）
+directory_store = false
+
+# 每次重写记忆文件前先把原文件备份为 <文件名>.<时间>.bak，只保留最近 backup_keep 个（默认关闭，每次写入多一次复制）
+backup_before_write = false
+backup_keep = 5
+
+# purge --journal 在记忆目录中写 .purge-journal-<时间>.json（可用 unpurge 恢复），日志会累积，只保留最近 purge_journal_keep 个
+purge_journal_keep = 20
+
+# 缓冲写入：添加的记忆先留在内存中，攒满 write_buffer_size 条、空闲 write_buffer_flush_secs 秒或进程正常退出时一次性写入
+# （0 表示关闭，每次添加立即写入；进程崩溃或被强制结束时会丢失尚未写入的记忆）
+write_buffer_size = 0
+write_buffer_flush_secs = 5
+
+# 命令行 search 每条结果显示的最大字符数（按词边界截断并以 … 结尾，--full 显示全文；0 表示不截断）
+result_preview_len = 200
+
+# 相关性评分权重：文本每次命中、每个匹配标签、每个匹配关键词的得分，
+# 以及时效性加分的最大值和减半天数（线性衰减，两倍天数后为 0）
+# score_text_hit = 5.0
+# score_tag_hit = 8.0
+# score_keyword_hit = 6.0
+# score_recency_max = 5.0
+# score_recency_halflife_days = 75.0
+
+# 搜索的文本相关性算法：linear（每次命中 +score_text_hit）或 bm25（稀有词权重更高、长文本不会因重复命中占优，
+# 文本得分为 BM25 得分乘以 score_text_hit）；命令行可用 search --ranking 临时切换
+ranking = "linear"
+# bm25_k1 = 1.2
+# bm25_b = 0.75
+
+# 标签蕴含关系：添加记忆时自动补全隐含标签
+# 格式：标签名 = ["隐含标签", ...]，例如 "rust-async" = ["rust"]
+[tag_implications]
+
 # 记忆分类映射（标签到分类的映射）
 # 格式：标签名 = 分类名
 # 当添加记忆时，会根据标签自动选择对应的分类文件
@@ -206,6 +514,10 @@ fn resolve_config_path(config_path: Option<&str>) -> PathBuf {
         return std::env::current_dir().unwrap().join(path);
     }
 
+    if let Some(path) = CONFIG_PATH_OVERRIDE.read().ok().and_then(|guard| guard.clone()) {
+        return path;
+    }
+
     let exe_path = std::env::current_exe().unwrap_or_else(|_| std::env::current_dir().unwrap());
     let exe_dir = exe_path.parent().unwrap_or_else(|| Path::new("."));
     
@@ -248,6 +560,41 @@ pub fn get_category_for_tags(config: &Config, tags: &[String]) -> String {
     "default".to_string()
 }
 
+/// 查找与标签拼写相近的分类映射键（编辑距离不超过 2，且键本身与标签不同）
+///
+/// # 参数
+/// * `config` - 配置结构体
+/// * `tag` - 标签
+///
+/// # 返回
+/// 最相近的映射键，没有相近的键时返回 None
+pub fn similar_mapping_key(config: &Config, tag: &str) -> Option<String> {
+    let mapping = config.category_mapping.as_ref()?;
+    let mut keys: Vec<&String> = mapping.keys().collect();
+    keys.sort();
+    keys.into_iter()
+        .filter(|key| key.as_str() != tag)
+        .map(|key| (edit_distance(key, tag), key))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, key)| key.clone())
+}
+
+/// 计算两个字符串的编辑距离（Levenshtein，按字符计）
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
 /// 获取记忆存储路径
 ///
 /// # 参数
@@ -360,3 +707,20 @@ pub fn get_config_path(config_value: &Option<String>, default_value: &str, base_
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_buffer_is_off_unless_size_is_set() {
+        let mut config = Config::default();
+        assert_eq!(get_write_buffer(&config), None);
+
+        config.write_buffer_size = Some(50);
+        assert_eq!(get_write_buffer(&config), Some((50, Some(Duration::from_secs(DEFAULT_WRITE_BUFFER_FLUSH_SECS)))));
+
+        config.write_buffer_flush_secs = Some(0);
+        assert_eq!(get_write_buffer(&config), Some((50, None)));
+    }
+}
@@ -1,9 +1,72 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde_json;
 use glob;
 use crate::record::MemoryRecord;
-use crate::config::{load_config, get_memory_path};
+use crate::config::{load_config, get_memory_path, Config};
+use crate::store::{atomic_write, check_memory_dir, ensure_writable_dir};
+use crate::lock::{acquire_lock_with_cleanup, category_lock_path, LockGuard};
+use crate::backup::{backup_file, prune_backups};
+
+/// 分类文件名后缀（`{分类}-global-gmem-recorder.json`）
+pub const CATEGORY_FILE_SUFFIX: &str = "-global-gmem-recorder.json";
+
+/// 旧版本拼写错误的分类文件名后缀（recoder），仍会被读取，可用 `migrate-filenames` 重命名
+pub const LEGACY_CATEGORY_FILE_SUFFIX: &str = "-global-gmem-recoder.json";
+
+/// 列出记忆目录中的分类文件（两种拼写）
+/// 正确拼写的文件排在前面，同一分类两种拼写都存在时优先使用正确拼写中的记录
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+///
+/// # 返回
+/// (分类名, 文件路径) 列表
+pub(crate) fn list_category_files(output_dir: &str) -> Vec<(String, PathBuf)> {
+    let mut files = Vec::new();
+    for suffix in [CATEGORY_FILE_SUFFIX, LEGACY_CATEGORY_FILE_SUFFIX] {
+        let pattern = PathBuf::from(output_dir).join(format!("*{}", suffix));
+        for path in glob::glob(&pattern.to_string_lossy()).into_iter().flatten().flatten() {
+            let category = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(suffix));
+            if let Some(category) = category {
+                if path.is_file() {
+                    files.push((category.to_string(), path.clone()));
+                }
+            }
+        }
+    }
+    files
+}
+
+/// 获取分类文件按旧拼写（recoder）命名时的路径
+///
+/// # 参数
+/// * `path` - 正确拼写的分类文件路径
+///
+/// # 返回
+/// 旧拼写的路径；文件名不是分类文件时返回 None
+fn legacy_counterpart(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let category = name.strip_suffix(CATEGORY_FILE_SUFFIX)?;
+    Some(path.with_file_name(format!("{}{}", category, LEGACY_CATEGORY_FILE_SUFFIX)))
+}
+
+/// 分类文件只有旧拼写版本时，先把它重命名为正确拼写，之后按新文件名读写
+///
+/// # 参数
+/// * `path` - 正确拼写的分类文件路径
+///
+/// # 返回
+/// 操作结果
+pub fn adopt_legacy_category_file(path: &Path) -> std::io::Result<()> {
+    if path.exists() {
+        return Ok(());
+    }
+    match legacy_counterpart(path) {
+        Some(legacy) if legacy.is_file() => fs::rename(&legacy, path),
+        _ => Ok(()),
+    }
+}
 
 /// 从所有分类文件中加载记忆
 ///
@@ -11,26 +74,31 @@ use crate::config::{load_config, get_memory_path};
 /// 所有记忆记录
 fn load_all_records() -> std::io::Result<Vec<MemoryRecord>> {
     let config = load_config(None);
-    let output_dir = get_memory_path(&config);
+    load_all_records_in(&get_memory_path(&config))
+}
+
+/// 从指定目录的所有分类文件（两种拼写）和原始记忆文件中加载记忆
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+///
+/// # 返回
+/// 所有记忆记录（ID 重复时保留先读到的）
+fn load_all_records_in(output_dir: &str) -> std::io::Result<Vec<MemoryRecord>> {
+    check_memory_dir(std::path::Path::new(&output_dir))?;
+    ensure_writable_dir(std::path::Path::new(&output_dir))?;
     let mut all_records: Vec<MemoryRecord> = Vec::new();
     let mut record_ids: std::collections::HashSet<String> = std::collections::HashSet::new();
     
-    // 读取所有分类文件
-    let pattern = format!("{}\\*-global-gmem-recoder.json", output_dir);
-    if let Ok(entries) = glob::glob(&pattern) {
-        for entry in entries {
-            if let Ok(path) = entry {
-                if path.is_file() {
-                    if let Ok(raw) = fs::read_to_string(&path) {
-                        if !raw.trim().is_empty() {
-                            if let Ok(records) = serde_json::from_str::<Vec<MemoryRecord>>(&raw) {
-                                for record in records {
-                                    if !record_ids.contains(&record.id) {
-                                        record_ids.insert(record.id.clone());
-                                        all_records.push(record);
-                                    }
-                                }
-                            }
+    // 读取所有分类文件（包括旧拼写的文件）
+    for (_, path) in list_category_files(output_dir) {
+        if let Ok(raw) = fs::read_to_string(&path) {
+            if !raw.trim().is_empty() {
+                if let Ok(records) = serde_json::from_str::<Vec<MemoryRecord>>(&raw) {
+                    for record in records {
+                        if !record_ids.contains(&record.id) {
+                            record_ids.insert(record.id.clone());
+                            all_records.push(record);
                         }
                     }
                 }
@@ -112,14 +180,46 @@ fn add_correct_tags(records: Vec<MemoryRecord>) -> Vec<MemoryRecord> {
     corrected_records
 }
 
+/// 增量整理使用的时间戳文件名（由整理定时器维护，内容为 Unix 秒）
+const ORGANIZE_TIMESTAMP_FILE: &str = ".organize_timestamp";
+
+/// 整理时等待每个分类文件锁的超时时间（毫秒）
+const ORGANIZE_LOCK_TIMEOUT_MS: u64 = 15000;
+
 /// 直接整理记忆，按分类保存
 ///
 /// # 返回
 /// 操作结果
 pub fn direct_organize() -> std::io::Result<()> {
+    direct_organize_with_options(false)
+}
+
+/// 直接整理记忆，可选增量模式
+///
+/// # 参数
+/// * `incremental` - 增量模式：只重新处理 `updated_at` 晚于 `.organize_timestamp` 的记忆，
+///   并只重写受影响的分类文件；时间戳文件不存在时退回完整整理
+///
+/// # 返回
+/// 操作结果
+pub fn direct_organize_with_options(incremental: bool) -> std::io::Result<()> {
+    if incremental {
+        let config = load_config(None);
+        let output_dir = get_memory_path(&config);
+        match read_last_organize_time(std::path::Path::new(&output_dir)) {
+            Some(since) => return incremental_organize(&output_dir, since),
+            None => println!("未找到 {}，执行完整整理", ORGANIZE_TIMESTAMP_FILE),
+        }
+    }
+
     println!("开始直接整理全局记忆...");
     
-    // 1. 读取所有分类文件中的记忆
+    // 1. 锁定分类文件后读取所有分类文件中的记忆，锁持有到写入完成
+    let config = load_config(None);
+    let output_dir = get_memory_path(&config);
+    check_memory_dir(std::path::Path::new(&output_dir))?;
+    ensure_writable_dir(std::path::Path::new(&output_dir))?;
+    let _locks = lock_category_dir(&output_dir, &config)?;
     let records = load_all_records()?;
     
     // 2. 为放错的记忆添加正确的标签
@@ -137,7 +237,6 @@ pub fn direct_organize() -> std::io::Result<()> {
         }
         
         // 确定分类
-        let config = load_config(None);
         let category = crate::config::get_category_for_tags(&config, &record.tags);
         
         // 添加到对应分类
@@ -145,17 +244,11 @@ pub fn direct_organize() -> std::io::Result<()> {
     }
     
     // 4. 保存到各个分类文件
-    let config = load_config(None);
-    let output_dir = get_memory_path(&config);
-    
-    for (category, records) in &category_records {
-        let file_name = format!("{}-global-gmem-recoder.json", category);
-        let file_path = PathBuf::from(&output_dir).join(file_name);
-        
-        // 保存文件
-        let json = serde_json::to_string_pretty(records)?;
-        fs::write(&file_path, json)?;
-        
+    let files: Vec<(PathBuf, &Vec<MemoryRecord>)> = category_records.iter()
+        .map(|(category, records)| (category_file_path(&output_dir, category), records))
+        .collect();
+    commit_category_files(&files, None)?;
+    for (file_path, records) in &files {
         println!("已保存 {} 条记忆到 {}", records.len(), file_path.display());
     }
     
@@ -168,8 +261,617 @@ pub fn direct_organize() -> std::io::Result<()> {
     
     println!("\n生成的分类文件：");
     for category in category_records.keys() {
-        println!("{}\\{}-global-gmem-recoder.json", output_dir, category);
+        println!("{}", category_file_path(&output_dir, category).display());
     }
     
     Ok(())
 }
+
+/// 读取上次整理时间
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+///
+/// # 返回
+/// 上次整理的 Unix 时间戳（秒），文件不存在或无法解析时返回 None
+fn read_last_organize_time(output_dir: &std::path::Path) -> Option<i64> {
+    let raw = fs::read_to_string(output_dir.join(ORGANIZE_TIMESTAMP_FILE)).ok()?;
+    raw.trim().parse().ok()
+}
+
+/// 判断记录是否在指定时间之后被修改（无法解析时间的记录视为已修改）
+///
+/// # 参数
+/// * `record` - 记忆记录
+/// * `since` - Unix 时间戳（秒）
+///
+/// # 返回
+/// 是否需要重新整理
+fn is_updated_since(record: &MemoryRecord, since: i64) -> bool {
+    match chrono::DateTime::parse_from_rfc3339(&record.updated_at) {
+        Ok(updated) => updated.timestamp() > since,
+        Err(_) => true,
+    }
+}
+
+/// 增量整理：只把上次整理后修改过的记忆合并到分类文件中
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+/// * `since` - 上次整理的 Unix 时间戳（秒）
+///
+/// # 返回
+/// 操作结果
+fn incremental_organize(output_dir: &str, since: i64) -> std::io::Result<()> {
+    println!("开始增量整理全局记忆（上次整理时间戳: {}）...", since);
+    check_memory_dir(std::path::Path::new(output_dir))?;
+    ensure_writable_dir(std::path::Path::new(output_dir))?;
+    let config = load_config(None);
+
+    // 1. 锁定分类文件后读取现有分类文件（分类名 -> 记录），锁持有到写入完成
+    let _locks = lock_category_dir(output_dir, &config)?;
+    let mut categories = load_category_files(output_dir)?;
+
+    // 2. 收集修改过的记录（分类文件 + 原始的global-memory-recorder.json）
+    let mut changed: Vec<MemoryRecord> = Vec::new();
+    let mut changed_ids: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let input_path = PathBuf::from(output_dir).join("global-memory-recorder.json");
+    let mut sources: Vec<MemoryRecord> = categories.values().flatten().cloned().collect();
+    if input_path.exists() {
+        let raw = fs::read_to_string(&input_path)?;
+        if !raw.trim().is_empty() {
+            if let Ok(records) = serde_json::from_str::<Vec<MemoryRecord>>(&raw) {
+                sources.extend(records);
+            }
+        }
+    }
+    for record in sources {
+        if is_updated_since(&record, since) && changed_ids.insert(record.id.clone()) {
+            changed.push(record);
+        }
+    }
+
+    if changed.is_empty() {
+        println!("没有需要整理的记忆");
+        return Ok(());
+    }
+
+    // 3. 从原分类中移除修改过的记录，再按新分类合并
+    let mut dirty: std::collections::BTreeSet<String> = std::collections::BTreeSet::new();
+    for (category, records) in categories.iter_mut() {
+        let before = records.len();
+        records.retain(|r| !changed_ids.contains(&r.id));
+        if records.len() != before {
+            dirty.insert(category.clone());
+        }
+    }
+    for record in add_correct_tags(changed.clone()) {
+        if record.deleted_at.is_some() {
+            continue;
+        }
+        let category = crate::config::get_category_for_tags(&config, &record.tags);
+        categories.entry(category.clone()).or_default().push(record);
+        dirty.insert(category);
+    }
+
+    // 4. 只重写受影响的分类文件
+    let files: Vec<(PathBuf, &Vec<MemoryRecord>)> = dirty.iter()
+        .map(|category| (category_file_path(output_dir, category), &categories[category]))
+        .collect();
+    commit_category_files(&files, None)?;
+    for (file_path, records) in &files {
+        println!("已更新 {} 条记忆到 {}", records.len(), file_path.display());
+    }
+
+    println!("\n增量整理完成！处理了 {} 条修改过的记忆，重写了 {} 个分类文件", changed.len(), dirty.len());
+    Ok(())
+}
+
+/// 单条记忆重新分类的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecategorizeResult {
+    /// 记录已从 `from` 分类文件移动到 `to` 分类文件
+    Moved { from: String, to: String },
+    /// 记录已在正确的分类文件中，未做修改
+    Unchanged(String),
+    /// 没有任何分类文件包含该 ID
+    NotFound,
+}
+
+/// 按当前标签重新计算单条记忆的分类，并把它移动到正确的分类文件
+/// 源文件和目标文件通过 `commit_category_files` 一起提交，不会出现只写了一半的情况
+///
+/// # 参数
+/// * `id` - 记忆 ID
+///
+/// # 返回
+/// 重新分类的结果
+pub fn recategorize(id: &str) -> std::io::Result<RecategorizeResult> {
+    let config = load_config(None);
+    recategorize_in(&get_memory_path(&config), &config, id)
+}
+
+/// 在指定目录中重新分类单条记忆
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+/// * `config` - 配置（提供分类映射）
+/// * `id` - 记忆 ID
+///
+/// # 返回
+/// 重新分类的结果
+fn recategorize_in(output_dir: &str, config: &Config, id: &str) -> std::io::Result<RecategorizeResult> {
+    check_memory_dir(Path::new(output_dir))?;
+    ensure_writable_dir(Path::new(output_dir))?;
+
+    let _locks = lock_category_dir(output_dir, config)?;
+    let mut categories = load_category_files(output_dir)?;
+    let from = match categories.iter().find(|(_, records)| records.iter().any(|r| r.id == id)) {
+        Some((category, _)) => category.clone(),
+        None => return Ok(RecategorizeResult::NotFound),
+    };
+
+    let records = categories.get_mut(&from).expect("category found above");
+    let index = records.iter().position(|r| r.id == id).expect("record found above");
+    let to = crate::config::get_category_for_tags(config, &records[index].tags);
+    if to == from {
+        return Ok(RecategorizeResult::Unchanged(from));
+    }
+
+    let record = records.remove(index);
+    categories.entry(to.clone()).or_default().push(record);
+
+    let files: Vec<(PathBuf, &Vec<MemoryRecord>)> = [&from, &to].iter()
+        .map(|category| (category_file_path(output_dir, category), &categories[*category]))
+        .collect();
+    commit_category_files(&files, None)?;
+
+    Ok(RecategorizeResult::Moved { from, to })
+}
+
+/// 读取一个分类文件中的全部记录（空文件视为没有记录）
+///
+/// # 参数
+/// * `path` - 分类文件路径
+///
+/// # 返回
+/// 文件中的记录；无法解析时返回带文件路径的错误
+pub(crate) fn read_category_file(path: &Path) -> std::io::Result<Vec<MemoryRecord>> {
+    let raw = fs::read_to_string(path)?;
+    if raw.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str::<Vec<MemoryRecord>>(&raw)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+/// 读取记忆目录中的所有分类文件
+/// 同一分类两种拼写的文件都存在时合并，ID 重复的记录以正确拼写的文件为准
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+///
+/// # 返回
+/// 分类名到记录的映射；任一分类文件无法解析时返回错误
+pub(crate) fn load_category_files(output_dir: &str) -> std::io::Result<std::collections::BTreeMap<String, Vec<MemoryRecord>>> {
+    let mut categories: std::collections::BTreeMap<String, Vec<MemoryRecord>> = std::collections::BTreeMap::new();
+    for (category, path) in list_category_files(output_dir) {
+        let records = read_category_file(&path)?;
+        let existing = categories.entry(category).or_default();
+        for record in records {
+            if !existing.iter().any(|r| r.id == record.id) {
+                existing.push(record);
+            }
+        }
+    }
+    Ok(categories)
+}
+
+/// 把旧拼写（recoder）的分类文件重命名为正确拼写（recorder）
+/// 正确拼写的文件已存在时合并两者的记录（ID 重复时保留正确拼写文件中的记录）后删除旧文件
+///
+/// # 返回
+/// (旧路径, 新路径) 列表
+pub fn migrate_filenames() -> std::io::Result<Vec<(PathBuf, PathBuf)>> {
+    let config = load_config(None);
+    migrate_filenames_in(&get_memory_path(&config), &config)
+}
+
+/// 重命名指定目录中旧拼写的分类文件
+/// 先锁定所有分类文件再读取，合并写入时使用的是锁内读到的最新记录
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+/// * `config` - 配置（提供分类映射）
+///
+/// # 返回
+/// (旧路径, 新路径) 列表
+fn migrate_filenames_in(output_dir: &str, config: &Config) -> std::io::Result<Vec<(PathBuf, PathBuf)>> {
+    check_memory_dir(Path::new(output_dir))?;
+
+    let _locks = lock_category_dir(output_dir, config)?;
+    let categories = load_category_files(output_dir)?;
+    let mut migrated = Vec::new();
+    for (category, legacy_path) in list_category_files(output_dir) {
+        if !legacy_path.to_string_lossy().ends_with(LEGACY_CATEGORY_FILE_SUFFIX) {
+            continue;
+        }
+        let new_path = category_file_path(output_dir, &category);
+        if new_path.exists() {
+            // 合并后写入新文件，commit_category_files 会删除旧拼写的文件
+            commit_category_files(&[(new_path.clone(), &categories[&category])], None)?;
+        } else {
+            fs::rename(&legacy_path, &new_path)?;
+        }
+        migrated.push((legacy_path, new_path));
+    }
+    Ok(migrated)
+}
+
+/// 合并分类文件的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeStoresReport {
+    /// 读取的分类文件数
+    pub files: usize,
+    /// 写入输出文件的记录数
+    pub records: usize,
+    /// 多个分类文件中内容相同的重复记录数（只保留一份）
+    pub duplicates: usize,
+    /// 同一 ID 内容不同的记录 ID（保留 `updated_at` 较新的一条）
+    pub conflicts: Vec<String>,
+}
+
+/// 把目录中的分类文件（两种拼写）合并为一个单文件存储，与 `split` 相反
+/// 记录按 ID 去重，按创建时间和 ID 排序后原子写入输出文件
+///
+/// # 参数
+/// * `dir` - 分类文件所在目录
+/// * `output` - 输出文件路径（已存在时覆盖）
+///
+/// # 返回
+/// 合并结果；任一分类文件无法解析时返回错误，不写入输出文件
+pub fn merge_stores(dir: &Path, output: &Path) -> std::io::Result<MergeStoresReport> {
+    let mut report = MergeStoresReport::default();
+    let mut merged: std::collections::BTreeMap<String, MemoryRecord> = std::collections::BTreeMap::new();
+
+    for (_, path) in list_category_files(&dir.to_string_lossy()) {
+        let raw = fs::read_to_string(&path)?;
+        report.files += 1;
+        if raw.trim().is_empty() {
+            continue;
+        }
+        let records = serde_json::from_str::<Vec<MemoryRecord>>(&raw)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+        for record in records {
+            match merged.get_mut(&record.id) {
+                None => {
+                    merged.insert(record.id.clone(), record);
+                }
+                Some(existing) => {
+                    let same = serde_json::to_value(&*existing).ok() == serde_json::to_value(&record).ok();
+                    if same {
+                        report.duplicates += 1;
+                        continue;
+                    }
+                    if !report.conflicts.contains(&record.id) {
+                        report.conflicts.push(record.id.clone());
+                    }
+                    if record.updated_at > existing.updated_at {
+                        *existing = record;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut records: Vec<MemoryRecord> = merged.into_values().collect();
+    records.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+    report.records = records.len();
+    atomic_write(output, &records)?;
+
+    Ok(report)
+}
+
+/// 获取分类文件路径
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+/// * `category` - 分类名
+///
+/// # 返回
+/// 分类文件路径
+pub(crate) fn category_file_path(output_dir: &str, category: &str) -> PathBuf {
+    PathBuf::from(output_dir).join(format!("{}{}", category, CATEGORY_FILE_SUFFIX))
+}
+
+/// 获取整理时可能读写的所有分类文件的锁
+/// 包括目录中已有的分类文件（两种拼写）、配置映射到的分类和 default 分类。
+/// 调用方在读取分类文件之前获取并持有到提交完成，读改写期间其他写入方的修改不会被覆盖
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+/// * `config` - 配置（提供分类映射）
+///
+/// # 返回
+/// 锁守卫列表，离开作用域时释放
+pub(crate) fn lock_category_dir(output_dir: &str, config: &Config) -> std::io::Result<Vec<LockGuard>> {
+    let mut categories = vec!["default".to_string()];
+    if let Some(mapping) = &config.category_mapping {
+        categories.extend(mapping.values().cloned());
+    }
+    let files: Vec<PathBuf> = list_category_files(output_dir).into_iter()
+        .map(|(_, path)| path)
+        .chain(categories.iter().map(|category| category_file_path(output_dir, category)))
+        .collect();
+    lock_category_files(&files)
+}
+
+/// 获取分类文件及其旧拼写文件的专属锁（按路径顺序获取，避免死锁）
+///
+/// # 参数
+/// * `files` - 分类文件路径
+///
+/// # 返回
+/// 锁守卫列表，离开作用域时释放
+fn lock_category_files(files: &[PathBuf]) -> std::io::Result<Vec<LockGuard>> {
+    let mut lock_paths: Vec<PathBuf> = files.iter()
+        .cloned()
+        .chain(files.iter().filter_map(|path| legacy_counterpart(path)))
+        .map(|path| category_lock_path(&path))
+        .collect();
+    lock_paths.sort();
+    lock_paths.dedup();
+    lock_paths.iter()
+        .map(|lock_path| acquire_lock_with_cleanup(lock_path, Some(ORGANIZE_LOCK_TIMEOUT_MS), Some(300)))
+        .collect()
+}
+
+/// 先把所有分类文件写入临时文件，全部成功后再逐个重命名替换
+/// 写入阶段失败时清理已写的临时文件，不会留下部分写入的分类文件
+/// 调用方必须在读取分类文件之前通过 `lock_category_dir` 获取锁，并持有到本函数返回
+/// 写入成功后删除同一分类旧拼写（recoder）的文件，其记录已由调用方合并
+///
+/// # 参数
+/// * `files` - (分类文件路径, 记录) 列表
+/// * `backup_keep` - 替换前把已有的分类文件备份为 `.bak` 并只保留最近的这么多个（None 表示不备份）
+///
+/// # 返回
+/// 操作结果；备份失败时不替换任何分类文件
+pub(crate) fn commit_category_files(files: &[(PathBuf, &Vec<MemoryRecord>)], backup_keep: Option<usize>) -> std::io::Result<()> {
+    let legacy_paths: Vec<PathBuf> = files.iter().filter_map(|(path, _)| legacy_counterpart(path)).collect();
+
+    let mut staged: Vec<(PathBuf, &PathBuf)> = Vec::new();
+
+    for (file_path, records) in files {
+        let tmp_path = PathBuf::from(format!("{}.tmp.{}.tmp", file_path.display(), std::process::id()));
+        let written = serde_json::to_string_pretty(records)
+            .map_err(std::io::Error::other)
+            .and_then(|json| fs::write(&tmp_path, json));
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp_path);
+            for (tmp, _) in &staged {
+                let _ = fs::remove_file(tmp);
+            }
+            return Err(e);
+        }
+        staged.push((tmp_path, file_path));
+    }
+
+    if let Some(keep) = backup_keep {
+        let backed_up = staged.iter()
+            .filter(|(_, file_path)| file_path.is_file())
+            .try_for_each(|(_, file_path)| backup_file(file_path, None).and_then(|_| prune_backups(file_path, keep)).map(|_| ()));
+        if let Err(e) = backed_up {
+            for (tmp, _) in &staged {
+                let _ = fs::remove_file(tmp);
+            }
+            return Err(e);
+        }
+    }
+
+    for (tmp_path, file_path) in &staged {
+        fs::rename(tmp_path, file_path)?;
+    }
+
+    for legacy_path in legacy_paths.iter().filter(|p| p.is_file()) {
+        fs::remove_file(legacy_path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造测试记录
+    fn record(id: &str, text: &str, tags: &[&str], updated_at: &str) -> MemoryRecord {
+        MemoryRecord {
+            id: id.to_string(),
+            text: text.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            keywords: Vec::new(),
+            created_at: updated_at.to_string(),
+            updated_at: updated_at.to_string(),
+            deleted_at: None,
+            idempotency_key: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn incremental_organize_rewrites_only_touched_categories() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().to_str().unwrap();
+        let old = "2000-01-01T00:00:00.000Z";
+        let untouched = category_file_path(output_dir, "untouched");
+        let default_file = category_file_path(output_dir, "default");
+        atomic_write(&untouched, &[record("m_old_a", "old note", &[], old)]).unwrap();
+        atomic_write(&default_file, &[record("m_old_b", "old default note", &[], old)]).unwrap();
+        let before = fs::read(&untouched).unwrap();
+        let before_modified = fs::metadata(&untouched).unwrap().modified().unwrap();
+
+        let now = chrono::Utc::now();
+        let fresh = record("m_new", "fresh note", &[], &now.to_rfc3339());
+        atomic_write(&dir.path().join("global-memory-recorder.json"), &[fresh]).unwrap();
+        incremental_organize(output_dir, now.timestamp() - 60).unwrap();
+
+        assert_eq!(fs::read(&untouched).unwrap(), before);
+        assert_eq!(fs::metadata(&untouched).unwrap().modified().unwrap(), before_modified);
+        let ids: Vec<String> = load_category_files(output_dir).unwrap()["default"].iter().map(|r| r.id.clone()).collect();
+        assert_eq!(ids, vec!["m_old_b", "m_new"]);
+    }
+
+    #[test]
+    fn failed_category_write_leaves_no_partial_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().to_str().unwrap();
+        let old = "2000-01-01T00:00:00.000Z";
+        let first = category_file_path(output_dir, "a");
+        let second = category_file_path(output_dir, "b");
+        atomic_write(&first, &[record("m_old", "old note", &[], old)]).unwrap();
+        let before = fs::read(&first).unwrap();
+
+        // 第二个分类的临时文件路径被目录占用，写入阶段在第一个分类之后失败
+        let blocked = PathBuf::from(format!("{}.tmp.{}.tmp", second.display(), std::process::id()));
+        fs::create_dir(&blocked).unwrap();
+        let a = vec![record("m_a", "new a", &[], old)];
+        let b = vec![record("m_b", "new b", &[], old)];
+        assert!(commit_category_files(&[(first.clone(), &a), (second.clone(), &b)], None).is_err());
+
+        assert_eq!(fs::read(&first).unwrap(), before);
+        assert!(!second.exists());
+        let leftovers: Vec<PathBuf> = fs::read_dir(dir.path()).unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.is_file() && p.to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+    }
+
+    #[test]
+    fn parallel_category_writes_survive_concurrent_organize() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().to_str().unwrap().to_string();
+
+        // 两个写入方分别向 rust、git 分类追加记录，同时反复执行增量整理（读改写所有分类文件）
+        let writers: Vec<_> = ["rust", "git"].into_iter()
+            .map(|category| {
+                let output_dir = output_dir.clone();
+                std::thread::spawn(move || {
+                    let path = category_file_path(&output_dir, category);
+                    for i in 0..10 {
+                        let _locks = lock_category_files(std::slice::from_ref(&path)).unwrap();
+                        let mut records = load_category_files(&output_dir).unwrap().remove(category).unwrap_or_default();
+                        let id = format!("m_{}_{}", category, i);
+                        records.push(record(&id, &id, &[category], &chrono::Utc::now().to_rfc3339()));
+                        commit_category_files(&[(path.clone(), &records)], None).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let organizer = {
+            let output_dir = output_dir.clone();
+            std::thread::spawn(move || {
+                for _ in 0..10 {
+                    incremental_organize(&output_dir, 0).unwrap();
+                }
+            })
+        };
+        for handle in writers {
+            handle.join().unwrap();
+        }
+        organizer.join().unwrap();
+
+        let mut ids: Vec<String> = load_category_files(&output_dir).unwrap()
+            .into_values()
+            .flatten()
+            .map(|r| r.id)
+            .collect();
+        ids.sort();
+        let mut expected: Vec<String> = ["git", "rust"].iter()
+            .flat_map(|category| (0..10).map(move |i| format!("m_{}_{}", category, i)))
+            .collect();
+        expected.sort();
+        assert_eq!(ids, expected);
+        let leftover_locks: Vec<PathBuf> = fs::read_dir(dir.path()).unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.to_string_lossy().ends_with(crate::lock::CATEGORY_LOCK_SUFFIX))
+            .collect();
+        assert!(leftover_locks.is_empty(), "{:?}", leftover_locks);
+    }
+
+    #[test]
+    fn both_spellings_load_and_migration_renames_the_legacy_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().to_str().unwrap();
+        let old = "2000-01-01T00:00:00.000Z";
+        let current = category_file_path(output_dir, "rust");
+        let legacy = dir.path().join(format!("git{}", LEGACY_CATEGORY_FILE_SUFFIX));
+        atomic_write(&current, &[record("m_rust", "rust note", &["rust"], old)]).unwrap();
+        atomic_write(&legacy, &[record("m_git", "git note", &["git"], old)]).unwrap();
+
+        let mut ids: Vec<String> = load_all_records_in(output_dir).unwrap().into_iter().map(|r| r.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["m_git", "m_rust"]);
+
+        let migrated = migrate_filenames_in(output_dir, &Config::default()).unwrap();
+        let renamed = category_file_path(output_dir, "git");
+        assert_eq!(migrated, vec![(legacy.clone(), renamed.clone())]);
+        assert!(!legacy.exists());
+        let records = load_category_files(output_dir).unwrap();
+        assert_eq!(records["git"].iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), vec!["m_git"]);
+        assert_eq!(records["rust"].len(), 1);
+        assert!(migrate_filenames_in(output_dir, &Config::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn recategorize_moves_a_record_between_category_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().to_str().unwrap();
+        let old = "2000-01-01T00:00:00.000Z";
+        let rust_file = category_file_path(output_dir, "rust");
+        let git_file = category_file_path(output_dir, "git");
+        // m_misfiled 带 git 标签却在 rust 分类文件中
+        atomic_write(&rust_file, &[
+            record("m_rust", "rust note", &["rust"], old),
+            record("m_misfiled", "git note", &["git"], old),
+        ]).unwrap();
+        atomic_write(&git_file, &[record("m_git", "other git note", &["git"], old)]).unwrap();
+        let config = Config::default();
+
+        let result = recategorize_in(output_dir, &config, "m_misfiled").unwrap();
+        assert_eq!(result, RecategorizeResult::Moved { from: "rust".to_string(), to: "git".to_string() });
+        let categories = load_category_files(output_dir).unwrap();
+        let ids = |category: &str| categories[category].iter().map(|r| r.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids("rust"), vec!["m_rust"]);
+        assert_eq!(ids("git"), vec!["m_git", "m_misfiled"]);
+
+        assert_eq!(recategorize_in(output_dir, &config, "m_misfiled").unwrap(), RecategorizeResult::Unchanged("git".to_string()));
+        assert_eq!(recategorize_in(output_dir, &config, "m_missing").unwrap(), RecategorizeResult::NotFound);
+    }
+
+    #[test]
+    fn merge_stores_dedupes_a_shared_id_and_reports_the_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().to_str().unwrap();
+        let (old, new) = ("2000-01-01T00:00:00.000Z", "2001-01-01T00:00:00.000Z");
+        atomic_write(&category_file_path(output_dir, "rust"), &[
+            record("m_rust", "rust note", &["rust"], new),
+            record("m_shared", "shared note", &["rust"], old),
+        ]).unwrap();
+        atomic_write(&dir.path().join(format!("git{}", LEGACY_CATEGORY_FILE_SUFFIX)), &[
+            record("m_shared", "shared note, edited", &["git"], new),
+            record("m_git", "git note", &["git"], old),
+        ]).unwrap();
+        let output = dir.path().join("merged").join("global-memory-recorder.json");
+
+        let report = merge_stores(dir.path(), &output).unwrap();
+        assert_eq!(report, MergeStoresReport { files: 2, records: 3, duplicates: 0, conflicts: vec!["m_shared".to_string()] });
+
+        let merged: Vec<MemoryRecord> = serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        let ids: Vec<&str> = merged.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["m_git", "m_rust", "m_shared"]);
+        // 冲突时保留 updated_at 较新的一条
+        assert_eq!(merged[2].text, "shared note, edited");
+    }
+}
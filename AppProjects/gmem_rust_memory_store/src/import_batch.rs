@@ -0,0 +1,71 @@
+use serde_json::Value;
+
+/// 批量导入中失败的一项
+#[derive(Debug, Clone)]
+pub struct ImportFailure {
+    /// 条目序号（从 1 开始，与导入进度输出一致）
+    pub index: usize,
+    /// 条目标题（章节标题或记录 ID），便于定位
+    pub title: String,
+    /// 失败原因
+    pub error: String,
+}
+
+/// 检查 MCP 服务器对 `add_memory` 请求的输出
+/// 只根据 JSON-RPC 响应中的 `error` 字段判断失败，记忆文本中出现 "error" 字样不会被误判
+///
+/// # 参数
+/// * `stdout` - MCP 服务器的标准输出（每行一个 JSON-RPC 响应）
+///
+/// # 返回
+/// 成功时返回 Ok；响应包含错误或没有任何响应时返回错误信息
+pub fn check_add_response(stdout: &str) -> Result<(), String> {
+    let mut responded = false;
+    for line in stdout.lines().filter(|l| !l.trim().is_empty()) {
+        let response: Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        responded = true;
+        if let Some(error) = response.get("error").filter(|e| !e.is_null()) {
+            let message = error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error");
+            return Err(format!("添加记忆失败: {}", message));
+        }
+    }
+    if responded {
+        Ok(())
+    } else {
+        Err("MCP 服务器没有返回响应".to_string())
+    }
+}
+
+/// 打印批量导入的失败清单（没有失败时不输出）
+///
+/// # 参数
+/// * `failures` - 失败的条目
+pub fn print_failure_report(failures: &[ImportFailure]) {
+    if failures.is_empty() {
+        return;
+    }
+    println!("=====================================");
+    println!("以下 {} 项导入失败:", failures.len());
+    for failure in failures {
+        println!("  #{} {}: {}", failure.index, failure.title, failure.error.trim());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_json_rpc_errors_fail_an_item() {
+        let ok = r#"{"jsonrpc":"2.0","id":1,"result":{"text":"fix the error handling"}}"#;
+        assert!(check_add_response(ok).is_ok());
+
+        let failed = format!("{}\n{}", ok, r#"{"jsonrpc":"2.0","id":2,"error":{"code":-32602,"message":"text is empty"}}"#);
+        assert_eq!(check_add_response(&failed).unwrap_err(), "添加记忆失败: text is empty");
+
+        assert!(check_add_response("startup noise\n").is_err());
+    }
+}
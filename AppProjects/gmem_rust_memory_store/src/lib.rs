@@ -12,19 +12,24 @@ pub mod direct_organize;
 pub mod read_memory;
 pub mod md_processor;
 pub mod mcp_serialization;
+pub mod backup;
+pub mod import_batch;
+pub mod audit;
 
-pub use record::{MemoryRecord, StoreStats, SearchHit, CompressResult};
-pub use timestamp::{now_iso, make_id};
+pub use record::{MemoryRecord, StoreStats, StatsSnapshot, SearchHit, CompressResult, ScoreBreakdown, ScoringWeights, StoreMetrics, ImportReport, SearchProfile, CompressProfile, UnmappedTag, file_source, source_matches, SOURCE_CLI, SOURCE_MCP, SOURCE_IMPORT_JSON};
+pub use timestamp::{now_iso, make_id, make_id_with_prefix, parse_time_bound};
 pub use keywords::extract_keywords;
-pub use lock::{acquire_lock, release_lock, LockType, acquire_lock_with_cleanup, cleanup_expired_locks};
-pub use store::{MemoryStore, score_record};
-pub use compress::compress_deterministic;
+pub use lock::{acquire_lock, release_lock, LockGuard, LockType, acquire_lock_with_cleanup, cleanup_expired_locks, category_lock_path, LockInfo, set_lock_history, acquire_store_lock, release_own_lock, STORE_LOCK_FILE};
+pub use store::{MemoryStore, ListOptions, ListSort, SearchOptions, TagMatch, TextLimitMode, RankingMode, Bm25Params, Bm25Corpus, score_record, score_breakdown, migrate_legacy_file, namespace_tag, parse_records, expand_implied_tags, ensure_writable_dir, is_temp, TEMP_TAG, STATS_HISTORY_FILE, highlight_matches, DEFAULT_HIGHLIGHT_MARKERS, DEFAULT_AUTO_CUTOFF_RATIO, discover_memory_path, set_memory_discovery};
+pub use backup::{backup_file, prune_backups, prune_purge_journals, DEFAULT_BACKUP_KEEP, DEFAULT_PURGE_JOURNAL_KEEP, PURGE_JOURNAL_PREFIX};
+pub use audit::{AuditEntry, AuditOp, text_hash};
+pub use compress::{compress_deterministic, compress_hits, compress_with_options, compress_with_profile, recent_hits, BudgetUnit, CompressOptions};
 #[cfg(feature = "llm")]
-pub use compress::compress_with_llm;
-pub use cli::{parse, run_repl};
-pub use config::{Config, load_config, get_config_file_path, get_config_string, get_config_path, get_memory_path};
+pub use compress::{compress_with_llm, LlmError};
+pub use cli::{parse, parse_tokens, run_repl, confirm, is_affirmative};
+pub use config::{Config, load_config, get_default_tags, get_scoring_weights, get_write_buffer, get_config_file_path, set_config_path, get_config_string, get_config_path, get_memory_path, get_prompt};
 pub use logs::{init_global_logger, LogConfig, LogLevel, debug, info, warn, error};
 pub use organize_memory::organize_memory;
-pub use direct_organize::direct_organize;
-pub use read_memory::read_memory;
+pub use direct_organize::{direct_organize, direct_organize_with_options, recategorize, RecategorizeResult, migrate_filenames, merge_stores, MergeStoresReport, CATEGORY_FILE_SUFFIX, LEGACY_CATEGORY_FILE_SUFFIX};
+pub use read_memory::{read_memory, read_memory_with_format, format_table, ReadFormat};
 pub use md_processor::{MdProcessor, MdProcessorOptions, process_single_md_file};
@@ -1,11 +1,14 @@
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use serde::Serialize;
 use crate::timestamp::now_iso;
 
-/// 锁文件类型枚举
+/// 锁持有者类型枚举
+/// 同一个记忆文件的所有模式共用一个锁文件（互相排斥），类型只写入锁文件内容用于诊断
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LockType {
     /// 交互模式锁
@@ -17,19 +20,125 @@ pub enum LockType {
 }
 
 impl LockType {
-    /// 获取锁文件后缀
+    /// 获取写入锁文件的类型名
     ///
     /// # 返回
-    /// 锁文件后缀
-    pub fn suffix(&self) -> &'static str {
+    /// 类型名（interactive、cli、mcp）
+    pub fn name(&self) -> &'static str {
         match self {
-            LockType::Interactive => ".interactive.lock",
-            LockType::Cli => ".cli.lock",
-            LockType::Mcp => ".mcp.lock",
+            LockType::Interactive => "interactive",
+            LockType::Cli => "cli",
+            LockType::Mcp => "mcp",
         }
     }
 }
 
+/// 记忆文件的存储锁文件名（位于记忆文件所在目录，所有锁类型共用）
+pub const STORE_LOCK_FILE: &str = ".copilot-memory.lock";
+
+/// 旧版本按锁类型区分的锁文件后缀，只在清理遗留锁时识别
+const LEGACY_LOCK_SUFFIXES: [&str; 3] = [".interactive.lock", ".cli.lock", ".mcp.lock"];
+
+/// 分类文件锁的后缀
+pub const CATEGORY_LOCK_SUFFIX: &str = ".category.lock";
+
+/// 获取分类文件专属的锁文件路径
+/// 每个分类文件使用独立的锁，向不同分类并发写入时互不阻塞
+///
+/// # 参数
+/// * `category_file` - 分类文件路径（如 `rust-global-gmem-recorder.json`）
+///
+/// # 返回
+/// 同目录下的 `.{文件名去扩展名}.category.lock`
+pub fn category_lock_path(category_file: &Path) -> PathBuf {
+    let stem = category_file.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let dir = category_file.parent().unwrap_or_else(|| Path::new("."));
+    dir.join(format!(".{}{}", stem, CATEGORY_LOCK_SUFFIX))
+}
+
+/// 锁守卫
+/// 持有锁文件句柄，离开作用域时自动删除锁文件
+#[derive(Debug)]
+pub struct LockGuard {
+    path: PathBuf,
+    file: Option<File>,
+    /// 获取锁的 ISO 时间戳（写入锁历史）
+    acquired_at: String,
+    /// 获取锁的时刻（计算持有时长）
+    acquired: Instant,
+}
+
+/// 锁历史文件名（位于锁文件所在目录）
+pub const LOCK_HISTORY_FILE: &str = ".lock-history";
+
+/// 锁历史文件的大小上限，超过后轮换为 `.lock-history.old`
+const LOCK_HISTORY_MAX_BYTES: u64 = 1024 * 1024;
+
+/// 是否在释放锁时记录锁历史（debug 模式下开启）
+static LOCK_HISTORY_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 开启或关闭锁历史记录
+/// 开启后每次 `LockGuard` 释放都会向 `.lock-history` 追加一行（pid、获取时间、释放时间、持有时长）
+///
+/// # 参数
+/// * `enabled` - 是否开启
+pub fn set_lock_history(enabled: bool) {
+    LOCK_HISTORY_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+impl LockGuard {
+    /// 获取锁文件路径
+    ///
+    /// # 返回
+    /// 锁文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        // 先关闭句柄再删除锁文件；锁文件已被删除时忽略错误
+        self.file.take();
+        release_lock(&self.path);
+        if LOCK_HISTORY_ENABLED.load(Ordering::Relaxed) {
+            // 锁历史只用于排查，写入失败不影响释放
+            let _ = append_lock_history(&self.path, &self.acquired_at, self.acquired.elapsed());
+        }
+    }
+}
+
+/// 向锁文件所在目录的 `.lock-history` 追加一行释放记录
+/// 文件超过大小上限时先轮换为 `.lock-history.old`
+///
+/// # 参数
+/// * `lock_path` - 被释放的锁文件路径
+/// * `acquired_at` - 获取锁的 ISO 时间戳
+/// * `held` - 持有时长
+///
+/// # 返回
+/// 写入结果
+fn append_lock_history(lock_path: &Path, acquired_at: &str, held: Duration) -> io::Result<()> {
+    let dir = lock_path.parent().unwrap_or_else(|| Path::new("."));
+    let history_path = dir.join(LOCK_HISTORY_FILE);
+    if fs::metadata(&history_path).map(|m| m.len() >= LOCK_HISTORY_MAX_BYTES).unwrap_or(false) {
+        fs::rename(&history_path, dir.join(format!("{}.old", LOCK_HISTORY_FILE)))?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&history_path)?;
+    writeln!(
+        file,
+        "{} pid={} acquired_at={} released_at={} duration_ms={}",
+        lock_path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default(),
+        std::process::id(),
+        acquired_at,
+        now_iso(),
+        held.as_millis()
+    )
+}
+
 /// 获取文件锁以实现并发访问安全
 /// 使用原子文件创建（wx 标志）作为锁定机制
 ///
@@ -38,11 +147,24 @@ impl LockType {
 /// * `timeout_ms` - 等待锁的最大时间（默认 2500ms）
 ///
 /// # 返回
-/// 锁文件句柄
+/// 锁守卫，离开作用域时释放锁
 ///
 /// # 错误
 /// 如果在超时时间内无法获取锁则返回错误
-pub fn acquire_lock(lock_path: &Path, timeout_ms: Option<u64>) -> io::Result<File> {
+pub fn acquire_lock(lock_path: &Path, timeout_ms: Option<u64>) -> io::Result<LockGuard> {
+    acquire_lock_tagged(lock_path, timeout_ms, None)
+}
+
+/// 获取锁并把持有者类型写入锁文件（`<pid> <iso 时间戳> <类型>`）
+///
+/// # 参数
+/// * `lock_path` - 锁文件路径
+/// * `timeout_ms` - 等待锁的最大时间（默认 2500ms）
+/// * `owner` - 持有者类型（None 时不写类型）
+///
+/// # 返回
+/// 锁守卫，离开作用域时释放锁
+fn acquire_lock_tagged(lock_path: &Path, timeout_ms: Option<u64>, owner: Option<LockType>) -> io::Result<LockGuard> {
     let timeout = timeout_ms.unwrap_or(2500);
     let start = std::time::Instant::now();
 
@@ -53,8 +175,17 @@ pub fn acquire_lock(lock_path: &Path, timeout_ms: Option<u64>) -> io::Result<Fil
             .open(lock_path)
         {
             Ok(mut file) => {
-                writeln!(file, "{} {}", std::process::id(), now_iso())?;
-                return Ok(file);
+                let acquired_at = now_iso();
+                match owner {
+                    Some(lock_type) => writeln!(file, "{} {} {}", std::process::id(), acquired_at, lock_type.name())?,
+                    None => writeln!(file, "{} {}", std::process::id(), acquired_at)?,
+                }
+                return Ok(LockGuard {
+                    path: lock_path.to_path_buf(),
+                    file: Some(file),
+                    acquired_at,
+                    acquired: Instant::now(),
+                });
             }
             Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
                 if start.elapsed().as_millis() > timeout as u128 {
@@ -80,24 +211,46 @@ pub fn acquire_lock(lock_path: &Path, timeout_ms: Option<u64>) -> io::Result<Fil
 /// * `max_age_seconds` - 锁文件最大年龄（秒），超过此年龄的锁文件会被自动删除（默认 300秒=5分钟）
 ///
 /// # 返回
-/// 锁文件句柄
+/// 锁守卫，离开作用域时释放锁
 ///
 /// # 错误
 /// 如果在超时时间内无法获取锁则返回错误
-pub fn acquire_lock_with_cleanup(lock_path: &Path, timeout_ms: Option<u64>, max_age_seconds: Option<u64>) -> io::Result<File> {
+pub fn acquire_lock_with_cleanup(lock_path: &Path, timeout_ms: Option<u64>, max_age_seconds: Option<u64>) -> io::Result<LockGuard> {
+    remove_if_expired(lock_path, max_age_seconds);
+    acquire_lock(lock_path, timeout_ms)
+}
+
+/// 获取记忆文件的存储锁（清理过期锁后获取，并记录持有者类型）
+/// CLI、交互模式和 MCP 服务器使用同一个锁文件，跨模式写入互相排斥
+///
+/// # 参数
+/// * `lock_path` - 存储锁文件路径
+/// * `lock_type` - 持有者类型（仅用于诊断）
+/// * `timeout_ms` - 等待锁的最大时间（默认 2500ms）
+/// * `max_age_seconds` - 锁文件最大年龄（秒，默认 300）
+///
+/// # 返回
+/// 锁守卫，离开作用域时释放锁
+pub fn acquire_store_lock(lock_path: &Path, lock_type: LockType, timeout_ms: Option<u64>, max_age_seconds: Option<u64>) -> io::Result<LockGuard> {
+    remove_if_expired(lock_path, max_age_seconds);
+    acquire_lock_tagged(lock_path, timeout_ms, Some(lock_type))
+}
+
+/// 锁文件超过最大年龄时删除
+///
+/// # 参数
+/// * `lock_path` - 锁文件路径
+/// * `max_age_seconds` - 锁文件最大年龄（秒，默认 300）
+fn remove_if_expired(lock_path: &Path, max_age_seconds: Option<u64>) {
     let max_age = max_age_seconds.unwrap_or(300);
-    
-    // 检查并清理过期的锁文件
     if lock_path.exists() {
         if let Ok(age) = get_lock_file_age(lock_path) {
             if age > max_age {
-                println!("发现过期锁文件 ({}秒)，自动删除: {}", age, lock_path.display());
+                eprintln!("发现过期锁文件 ({}秒)，自动删除: {}", age, lock_path.display());
                 let _ = fs::remove_file(lock_path);
             }
         }
     }
-    
-    acquire_lock(lock_path, timeout_ms)
 }
 
 /// 获取锁文件年龄（秒）
@@ -132,7 +285,6 @@ pub fn cleanup_expired_locks(lock_dir: &Path, max_age_seconds: Option<u64>) -> u
         return cleaned;
     }
     
-    let lock_suffixes = [".interactive.lock", ".cli.lock", ".mcp.lock"];
     
     if let Ok(entries) = fs::read_dir(lock_dir) {
         for entry in entries.flatten() {
@@ -143,10 +295,13 @@ pub fn cleanup_expired_locks(lock_dir: &Path, max_age_seconds: Option<u64>) -> u
                     .unwrap_or("");
                 
                 // 检查是否是锁文件
-                if lock_suffixes.iter().any(|suffix| file_name.ends_with(suffix)) {
+                let is_lock = file_name == STORE_LOCK_FILE
+                    || file_name.ends_with(CATEGORY_LOCK_SUFFIX)
+                    || LEGACY_LOCK_SUFFIXES.iter().any(|suffix| file_name.ends_with(suffix));
+                if is_lock {
                     if let Ok(age) = get_lock_file_age(&path) {
                         if age > max_age {
-                            println!("清理过期锁文件 ({}秒): {}", age, path.display());
+                            eprintln!("清理过期锁文件 ({}秒): {}", age, path.display());
                             if fs::remove_file(&path).is_ok() {
                                 cleaned += 1;
                             }
@@ -160,6 +315,109 @@ pub fn cleanup_expired_locks(lock_dir: &Path, max_age_seconds: Option<u64>) -> u
     cleaned
 }
 
+/// 锁文件描述信息
+#[derive(Debug, Clone, Serialize)]
+pub struct LockInfo {
+    /// 锁文件路径
+    pub path: PathBuf,
+    /// 持有锁的进程 ID（无法解析时为 None）
+    pub pid: Option<u32>,
+    /// 锁创建时间（ISO 8601，无法解析时为 None）
+    pub created_at: Option<String>,
+    /// 持有者类型（interactive、cli、mcp；旧格式锁文件中没有时为 None）
+    pub lock_type: Option<String>,
+    /// 锁年龄（秒），优先使用锁文件中的时间戳，其次使用文件修改时间
+    pub age_seconds: Option<u64>,
+    /// 持有进程是否仍在运行（无法判断时为 None）
+    pub alive: Option<bool>,
+}
+
+/// 解析锁文件内容（格式为 `<pid> <iso 时间戳>`）
+///
+/// # 参数
+/// * `content` - 锁文件内容
+///
+/// # 返回
+/// (进程 ID, 时间戳)
+pub fn parse_lock_content(content: &str) -> (Option<u32>, Option<String>) {
+    let mut parts = content.split_whitespace();
+    let pid = parts.next().and_then(|p| p.parse().ok());
+    let created_at = parts.next()
+        .filter(|ts| chrono::DateTime::parse_from_rfc3339(ts).is_ok())
+        .map(|ts| ts.to_string());
+    (pid, created_at)
+}
+
+/// 读取并描述锁文件
+///
+/// # 参数
+/// * `lock_path` - 锁文件路径
+///
+/// # 返回
+/// 锁文件信息，锁文件不存在或无法读取时返回 None
+pub fn describe(lock_path: &Path) -> Option<LockInfo> {
+    let content = fs::read_to_string(lock_path).ok()?;
+    let (pid, created_at) = parse_lock_content(&content);
+    let lock_type = content.split_whitespace().nth(2).map(|t| t.to_string());
+
+    let age_seconds = created_at.as_deref()
+        .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+        .map(|ts| (chrono::Utc::now() - ts.with_timezone(&chrono::Utc)).num_seconds().max(0) as u64)
+        .or_else(|| get_lock_file_age(lock_path).ok());
+
+    Some(LockInfo {
+        path: lock_path.to_path_buf(),
+        pid,
+        created_at,
+        lock_type,
+        age_seconds,
+        alive: pid.and_then(is_process_alive),
+    })
+}
+
+/// 判断进程是否仍在运行
+///
+/// # 参数
+/// * `pid` - 进程 ID
+///
+/// # 返回
+/// 是否在运行，无法判断时返回 None
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> Option<bool> {
+    if Path::new("/proc").is_dir() {
+        return Some(Path::new("/proc").join(pid.to_string()).exists());
+    }
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .ok()
+        .map(|status| status.success())
+}
+
+/// 判断进程是否仍在运行
+///
+/// # 参数
+/// * `pid` - 进程 ID
+///
+/// # 返回
+/// 是否在运行，无法判断时返回 None
+#[cfg(windows)]
+fn is_process_alive(pid: u32) -> Option<bool> {
+    let output = std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.split_whitespace().any(|field| field == pid.to_string()))
+}
+
+/// 判断进程是否仍在运行
+#[cfg(not(any(unix, windows)))]
+fn is_process_alive(_pid: u32) -> Option<bool> {
+    None
+}
+
 /// 通过删除锁文件来释放文件锁
 ///
 /// # 参数
@@ -167,3 +425,151 @@ pub fn cleanup_expired_locks(lock_dir: &Path, max_age_seconds: Option<u64>) -> u
 pub fn release_lock(lock_path: &Path) {
     let _ = fs::remove_file(lock_path);
 }
+
+/// 只在锁由当前进程持有时删除锁文件
+/// 用于中断处理：存储锁为各模式共用，不能删除其他进程正持有的锁
+///
+/// # 参数
+/// * `lock_path` - 锁文件路径
+///
+/// # 返回
+/// 是否删除了锁文件
+pub fn release_own_lock(lock_path: &Path) -> bool {
+    let owned = fs::read_to_string(lock_path)
+        .map(|content| parse_lock_content(&content).0 == Some(std::process::id()))
+        .unwrap_or(false);
+    if owned {
+        release_lock(lock_path);
+    }
+    owned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    #[test]
+    fn signal_cleanup_targets_the_store_lock_for_every_lock_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let memory = dir.path().join("memory.json");
+
+        for lock_type in [LockType::Interactive, LockType::Cli, LockType::Mcp] {
+            let store = MemoryStore::new(memory.to_str(), Some(lock_type));
+            let lock_path = store.get_lock_path().to_path_buf();
+            assert_eq!(lock_path, dir.path().join(STORE_LOCK_FILE));
+
+            let guard = acquire_store_lock(&lock_path, lock_type, None, None).unwrap();
+            assert!(release_own_lock(&lock_path));
+            assert!(!lock_path.exists());
+            // 中断处理已经删除了锁文件，守卫再次释放也不会出错
+            drop(guard);
+            assert!(!release_own_lock(&lock_path));
+        }
+    }
+
+    #[test]
+    fn signal_cleanup_leaves_other_process_locks() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock_path = dir.path().join(STORE_LOCK_FILE);
+        fs::write(&lock_path, format!("{} {} mcp\n", std::process::id() + 1, now_iso())).unwrap();
+
+        assert!(!release_own_lock(&lock_path));
+        assert!(lock_path.exists());
+    }
+
+    #[test]
+    fn describe_parses_a_fabricated_lock_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock_path = dir.path().join(STORE_LOCK_FILE);
+        fs::write(&lock_path, "4242 2000-01-01T00:00:00.000Z cli\n").unwrap();
+
+        let info = describe(&lock_path).unwrap();
+        assert_eq!(info.path, lock_path);
+        assert_eq!(info.pid, Some(4242));
+        assert_eq!(info.created_at.as_deref(), Some("2000-01-01T00:00:00.000Z"));
+        assert_eq!(info.lock_type.as_deref(), Some("cli"));
+        assert!(info.age_seconds.unwrap() > 365 * 24 * 3600);
+
+        fs::write(&lock_path, format!("{} {}\n", std::process::id(), now_iso())).unwrap();
+        let info = describe(&lock_path).unwrap();
+        assert_eq!(info.lock_type, None);
+        if cfg!(unix) {
+            assert_eq!(info.alive, Some(true));
+        }
+        assert!(info.age_seconds.unwrap() < 60);
+
+        fs::write(&lock_path, "garbage").unwrap();
+        let info = describe(&lock_path).unwrap();
+        assert_eq!((info.pid, info.created_at), (None, None));
+        assert!(describe(&dir.path().join("missing.lock")).is_none());
+    }
+
+    #[test]
+    fn store_locks_of_different_types_exclude_each_other() {
+        let dir = tempfile::tempdir().unwrap();
+        let memory = dir.path().join("memory.json");
+        let lock_path = dir.path().join(STORE_LOCK_FILE);
+
+        let held = acquire_store_lock(&lock_path, LockType::Cli, None, None).unwrap();
+        let err = acquire_store_lock(&lock_path, LockType::Mcp, Some(100), None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(describe(&lock_path).unwrap().lock_type.as_deref(), Some("cli"));
+        drop(held);
+        assert!(!lock_path.exists());
+
+        // CLI（organize 也使用该类型）和 MCP 同时向同一个记忆文件写入，不会丢失记录
+        let writers: Vec<_> = [LockType::Cli, LockType::Mcp].into_iter()
+            .map(|lock_type| {
+                let memory = memory.clone();
+                thread::spawn(move || {
+                    let store = MemoryStore::new(memory.to_str(), Some(lock_type));
+                    for i in 0..15 {
+                        store.add_memory(&format!("{} note {}", lock_type.name(), i), None).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in writers {
+            handle.join().unwrap();
+        }
+
+        let records = MemoryStore::new(memory.to_str(), Some(LockType::Cli)).load().unwrap();
+        assert_eq!(records.len(), 30);
+        for lock_type in [LockType::Cli, LockType::Mcp] {
+            let prefix = format!("{} note ", lock_type.name());
+            assert_eq!(records.iter().filter(|r| r.text.starts_with(&prefix)).count(), 15);
+        }
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn guarded_release_appends_a_history_line_only_in_debug_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock_path = dir.path().join(".cli.lock");
+        let history_path = dir.path().join(LOCK_HISTORY_FILE);
+
+        drop(acquire_lock(&lock_path, Some(1000)).unwrap());
+        assert!(!history_path.exists());
+
+        // 开关是进程级的，其他测试此时释放的锁只会写到各自的临时目录
+        set_lock_history(true);
+        for _ in 0..2 {
+            drop(acquire_lock(&lock_path, Some(1000)).unwrap());
+        }
+        set_lock_history(false);
+        drop(acquire_lock(&lock_path, Some(1000)).unwrap());
+
+        assert!(!lock_path.exists());
+        let history = fs::read_to_string(&history_path).unwrap();
+        let lines: Vec<&str> = history.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", history);
+        for line in lines {
+            let fields: Vec<&str> = line.split(' ').collect();
+            assert_eq!(fields[0], ".cli.lock");
+            assert_eq!(fields[1], format!("pid={}", std::process::id()));
+            assert!(fields[2].starts_with("acquired_at=") && fields[3].starts_with("released_at="), "{}", line);
+            assert!(fields[4].strip_prefix("duration_ms=").unwrap().parse::<u128>().is_ok(), "{}", line);
+        }
+    }
+}
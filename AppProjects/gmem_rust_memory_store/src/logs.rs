@@ -92,9 +92,9 @@ impl Logger {
         let timestamp = self.get_timestamp();
         let log_message = format!("[{}] [{}] {}", timestamp, level.as_str(), message);
 
-        // 在debug模式下或error级别时，输出到控制台
+        // 在debug模式下或error级别时，输出到控制台（stderr，避免污染 MCP 的 JSON-RPC stdout）
         if self.config.debug_mode || level == LogLevel::Error {
-            println!("{}", log_message);
+            eprintln!("{}", log_message);
         }
 
         // 如果启用了日志文件，则写入文件
@@ -1,21 +1,52 @@
-use gmem_rust_memory_store::{MemoryStore, run_repl, load_config, organize_memory, direct_organize, read_memory, process_single_md_file, LockType};
+use gmem_rust_memory_store::{MemoryStore, run_repl, load_config, organize_memory, direct_organize_with_options, read_memory_with_format, ReadFormat, process_single_md_file, release_own_lock, set_lock_history, set_memory_discovery, LockType, DEFAULT_BACKUP_KEEP};
 use gmem_rust_memory_store::logs::{init_global_logger, LogConfig, LogLevel};
 use gmem_rust_memory_store::config;
 use std::env;
-use std::path::{Path, PathBuf};
+use std::ffi::OsString;
+use std::path::Path;
+use std::sync::Arc;
+
+/// 将操作系统原始参数转换为字符串
+/// 使用 `args_os` 避免非 UTF-8 参数（如某些代码页下的中文路径）导致 panic；
+/// 无法无损转换的参数会被有损转换并打印警告
+///
+/// # 参数
+/// * `args` - 原始参数列表
+///
+/// # 返回
+/// 字符串参数列表
+fn os_args_to_strings<I: IntoIterator<Item = OsString>>(args: I) -> Vec<String> {
+    args.into_iter()
+        .map(|arg| match arg.into_string() {
+            Ok(s) => s,
+            Err(raw) => {
+                let lossy = raw.to_string_lossy().into_owned();
+                eprintln!("Warning: argument is not valid UTF-8, using lossy conversion: {}", lossy);
+                lossy
+            }
+        })
+        .collect()
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = os_args_to_strings(env::args_os());
     
     let mut debug_mode = false;
     let mut memory_path: Option<&str> = None;
+    let mut config_path: Option<&str> = None;
     let mut organize_mode = false;
     let mut direct_organize_mode = false;
+    let mut incremental = false;
     let mut read_mode = false;
     let mut md_mode = false;
     let mut md_file_path: Option<&str> = None;
     let mut md_temporary = false;
+    let mut md_dry_run = false;
+    let mut md_force = false;
     let mut md_category = "default";
+    let mut backup_before_write = false;
+    // 未被全局标志消费的参数，交给命令解析（包括命令自身的 --选项）
+    let mut command_args: Vec<String> = Vec::new();
     
     let mut i = 1;
     while i < args.len() {
@@ -32,6 +63,10 @@ fn main() {
                 direct_organize_mode = true;
                 i += 1;
             }
+            "--incremental" => {
+                incremental = true;
+                i += 1;
+            }
             "--read" => {
                 read_mode = true;
                 i += 1;
@@ -48,6 +83,14 @@ fn main() {
                 md_temporary = true;
                 i += 1;
             }
+            "--md-dry-run" => {
+                md_dry_run = true;
+                i += 1;
+            }
+            "--md-force" => {
+                md_force = true;
+                i += 1;
+            }
             "--md-category" => {
                 if i + 1 < args.len() {
                     md_category = args[i + 1].as_str();
@@ -56,6 +99,21 @@ fn main() {
                     i += 1;
                 }
             }
+            "--config" => {
+                i += 1;
+                if i < args.len() {
+                    config_path = Some(args[i].as_str());
+                    i += 1;
+                }
+            }
+            "--backup-before-write" => {
+                backup_before_write = true;
+                i += 1;
+            }
+            "--no-discover" => {
+                set_memory_discovery(false);
+                i += 1;
+            }
             "--memory-path" => {
                 i += 1;
                 if i < args.len() {
@@ -64,18 +122,25 @@ fn main() {
                 }
             }
             _ => {
-                // 非标志参数，不处理，留给后续的命令解析
+                // 非全局标志参数，留给后续的命令解析
+                command_args.push(args[i].clone());
                 i += 1;
             }
         }
     }
     
-    let config = load_config(None);
+    // --config 对所有模式生效（整理、读取、MD 处理、交互界面）
+    if let Some(path) = config_path {
+        config::set_config_path(path);
+    }
+    let config = load_config(config_path);
     
     // 从配置文件中读取debug_enabled参数，如果命令行没有指定
     if !debug_mode {
         debug_mode = config.debug_enabled.unwrap_or(false);
     }
+    // debug 模式下记录每次锁释放，便于排查锁竞争
+    set_lock_history(debug_mode);
     
     // 初始化日志系统
     let exe_path = std::env::current_exe().unwrap_or_else(|_| std::env::current_dir().unwrap());
@@ -106,16 +171,26 @@ fn main() {
     
     // 处理直接整理模式
     if direct_organize_mode {
-        if let Err(e) = direct_organize() {
+        if let Err(e) = direct_organize_with_options(incremental) {
             eprintln!("Error direct organizing memory: {}", e);
             std::process::exit(1);
         }
         return;
     }
     
-    // 处理记忆读取模式
+    // 处理记忆读取模式（--format 只在读取模式下生效，其他命令如 export 有自己的 --format）
     if read_mode {
-        if let Err(e) = read_memory() {
+        let format = match command_args.iter().position(|a| a == "--format") {
+            Some(pos) => match command_args.get(pos + 1).and_then(|f| ReadFormat::parse(f)) {
+                Some(format) => format,
+                None => {
+                    eprintln!("Error: --format expects one of: full, table, json, markdown");
+                    std::process::exit(1);
+                }
+            },
+            None => ReadFormat::Full,
+        };
+        if let Err(e) = read_memory_with_format(format) {
             eprintln!("Error reading memory: {}", e);
             std::process::exit(1);
         }
@@ -130,7 +205,7 @@ fn main() {
             } else {
                 config.memory_path.as_deref()
             };
-            if let Err(e) = process_single_md_file(file_path, final_memory_path, md_temporary, md_category) {
+            if let Err(e) = process_single_md_file(file_path, final_memory_path, md_temporary, md_category, md_dry_run, md_force) {
                 eprintln!("Error processing MD file: {}", e);
                 std::process::exit(1);
             }
@@ -147,8 +222,8 @@ fn main() {
         config.memory_path.as_deref()
     };
 
-    // 检查是否有非标志命令行参数
-    let has_command_args = args.iter().skip(1).any(|arg| !arg.starts_with("--"));
+    // 检查是否有命令参数
+    let has_command_args = !command_args.is_empty();
 
     // 确定锁类型：交互模式使用Interactive，命令行模式使用Cli
     let lock_type = if has_command_args {
@@ -157,65 +232,74 @@ fn main() {
         LockType::Interactive
     };
 
-    // 计算锁文件路径
-    let lock_path = if let Some(path) = final_memory_path {
-        PathBuf::from(path).join(format!("lock{}", lock_type.suffix()))
-    } else {
-        PathBuf::from("./memory").join(format!("lock{}", lock_type.suffix()))
-    };
-
-    let store = MemoryStore::new(final_memory_path, Some(lock_type));
+    let mut store = MemoryStore::new(final_memory_path, Some(lock_type))
+        .apply_config(&config);
+    // --backup-before-write 只对本次运行生效，配置文件中的 backup_before_write 对所有运行生效
+    if backup_before_write {
+        store = store.with_backup_before_write(Some(config.backup_keep.unwrap_or(DEFAULT_BACKUP_KEEP)));
+    }
+    if let Err(e) = store.ensure_storage() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    store.recover_locks(config.lock_max_age_seconds);
     let version = env!("APP_VERSION");
-    
-    // 对于交互模式，添加信号处理，在程序退出时删除锁文件
-    if lock_type == LockType::Interactive {
-        // 设置 Ctrl+C 处理
-        ctrlc::set_handler(move || {
+
+    // 所有模式都添加信号处理，Ctrl+C 中断时删除存储实际使用的锁文件
+    // 存储锁为各模式共用，只删除本进程持有的锁，不影响其他进程（如 MCP 服务器）正在进行的写入
+    // 信号处理同时持有存储，退出前写入缓冲区中的记忆（write_buffer_size）
+    let store = Arc::new(store);
+    let signal_store = Arc::clone(&store);
+    let lock_path = store.get_lock_path().to_path_buf();
+    ctrlc::set_handler(move || {
+        if let Err(e) = signal_store.flush() {
+            eprintln!("\n写入缓冲的记忆失败: {}", e);
+        }
+        if lock_type == LockType::Interactive {
             println!("\n正在清理锁文件...");
-            if lock_path.exists() {
-                if let Err(e) = std::fs::remove_file(&lock_path) {
-                    eprintln!("删除锁文件失败: {}", e);
-                } else {
-                    println!("锁文件已删除");
-                }
-            }
-            std::process::exit(0);
-        }).expect("设置信号处理失败");
-        
+        } else {
+            eprintln!("\n操作被中断，正在清理锁文件...");
+        }
+        release_own_lock(&lock_path);
+        std::process::exit(if lock_type == LockType::Interactive { 0 } else { 130 });
+    }).expect("设置信号处理失败");
+
+    if lock_type == LockType::Interactive {
         println!("提示: 按 Ctrl+C 退出程序（会自动清理锁文件）");
     }
 
-    if has_command_args {
-        // 构建命令字符串
-        let command_str = args.iter().skip(1)
-            .filter(|arg| !arg.starts_with("--"))
-            .map(|s| s.as_str())
-            .collect::<Vec<&str>>()
-            .join(" ");
-
-        if !command_str.is_empty() {
-            // 解析并执行命令
-            if let Some(parsed) = gmem_rust_memory_store::cli::parse(&command_str) {
-                if let Err(e) = gmem_rust_memory_store::cli::execute_command(&store, &parsed) {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            } else {
-                eprintln!("Invalid command");
-                std::process::exit(1);
-            }
-        } else {
-            // 进入交互界面
-            if let Err(e) = run_repl(store, debug_mode, version) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
-        }
-    } else {
+    // 命令参数直接使用进程参数，不再拼接后重新分词
+    let result = match gmem_rust_memory_store::cli::parse_tokens(&command_args) {
+        Some(parsed) => gmem_rust_memory_store::cli::execute_command(&store, &parsed),
         // 没有命令参数，进入交互界面
-        if let Err(e) = run_repl(store, debug_mode, version) {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
-        }
+        None => run_repl(&store, debug_mode, version),
+    };
+    // 信号处理持有存储的引用，存储不会被销毁，需要显式写入缓冲区（命令失败时也要写入）
+    let flushed = store.flush().map(|_| ());
+    if let Err(e) = result.and(flushed) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn os_string_path_reaches_the_parser() {
+        let path = OsString::from("D:\\记忆\\store.json");
+        let args = os_args_to_strings(vec![OsString::from("get"), OsString::from("m_1"), OsString::from("--to"), path]);
+        let parsed = gmem_rust_memory_store::cli::parse_tokens(&args).unwrap();
+        assert_eq!(parsed.args, vec!["m_1"]);
+        assert_eq!(parsed.opts.get("to").map(String::as_str), Some("D:\\记忆\\store.json"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_arg_is_converted_lossily() {
+        use std::os::unix::ffi::OsStringExt;
+        let raw = OsString::from_vec(vec![b'a', 0xff, b'b']);
+        assert_eq!(os_args_to_strings(vec![raw]), vec!["a\u{fffd}b"]);
     }
 }
@@ -8,6 +8,8 @@ use serde_json::{json, Value};
 #[derive(Debug, Deserialize, Serialize)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
+    /// 请求 ID（通知没有 ID，此时为 null）
+    #[serde(default)]
     pub id: Value,
     pub method: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -141,6 +143,33 @@ pub fn create_error_response(id: Value, code: i32, message: String) -> JsonRpcRe
     }
 }
 
+/// 从无法解析为请求的输入中尽量找回请求 ID，便于客户端关联错误响应
+///
+/// 先按完整 JSON 解析并读取顶层 `id`；JSON 本身损坏时，扫描第一个 `"id":` 之后的值。
+/// 只接受字符串或数字 ID（JSON-RPC 规范允许的类型）。
+///
+/// # 参数
+/// * `raw` - 原始请求文本
+///
+/// # 返回
+/// 找回的请求 ID，找不到时为 `Value::Null`
+pub fn recover_request_id(raw: &str) -> Value {
+    let id = match serde_json::from_str::<Value>(raw) {
+        Ok(value) => value.get("id").cloned(),
+        Err(_) => raw.find("\"id\"").and_then(|pos| {
+            let rest = raw[pos + 4..].trim_start().strip_prefix(':')?;
+            serde_json::Deserializer::from_str(rest)
+                .into_iter::<Value>()
+                .next()?
+                .ok()
+        }),
+    };
+    match id {
+        Some(id @ (Value::String(_) | Value::Number(_))) => id,
+        _ => Value::Null,
+    }
+}
+
 /// 创建工具列表响应
 /// 
 /// # 参数
@@ -178,3 +207,21 @@ pub fn parse_tool_call_params(params: Option<Value>) -> Result<ToolCallParams, S
         None => Err("Missing params".to_string()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_id_is_recovered_from_partially_valid_input() {
+        // 合法 JSON 但不是合法请求（缺少 method）
+        assert_eq!(recover_request_id(r#"{"jsonrpc": "2.0", "id": 7}"#), json!(7));
+        // JSON 本身损坏
+        assert_eq!(recover_request_id(r#"{"jsonrpc": "2.0", "id": "req-1", "method": "tools/list""#), json!("req-1"));
+        assert_eq!(recover_request_id(r#"{"id" : 42, "method": }"#), json!(42));
+        // 找不到或类型不允许时为 null
+        assert_eq!(recover_request_id(r#"{"jsonrpc": "2.0", "method": "#), Value::Null);
+        assert_eq!(recover_request_id(r#"{"id": {"nested": 1}, "#), Value::Null);
+        assert_eq!(recover_request_id("not json"), Value::Null);
+    }
+}
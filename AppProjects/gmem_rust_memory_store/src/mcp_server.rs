@@ -1,46 +1,87 @@
-use gmem_rust_memory_store::{MemoryStore, LockType, load_config, mcp_serialization::{JsonRpcRequest, JsonRpcResponse, JsonRpcError, Tool, create_error_response, create_success_response, create_tools_list_response, parse_tool_call_params}};
+use gmem_rust_memory_store::{MemoryStore, DEFAULT_AUTO_CUTOFF_RATIO, ListOptions, LockType, SearchOptions, TagMatch, compress_hits, compress_with_options, CompressOptions, is_temp, load_config, set_lock_history, release_own_lock, SOURCE_MCP, Config, config, config::get_mcp_max_search_limit, mcp_serialization::{JsonRpcRequest, JsonRpcResponse, JsonRpcError, Tool, create_error_response, create_success_response, recover_request_id, create_tools_list_response, parse_tool_call_params}};
 use serde_json::{json, Value};
 use std::env;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as TokioBufReader};
 
+/// list_memories 单页最大数量
+const MAX_LIST_LIMIT: usize = 100;
+/// list_memories 紧凑模式下的文本最大长度（字符）
+const COMPACT_TEXT_LEN: usize = 200;
+
+/// 解析命令行参数
+/// 用法: gmemory_mcp_server [--config <file>] [<memory_path>]
+///
+/// # 参数
+/// * `args` - 完整的命令行参数（第一个是程序名）
+///
+/// # 返回
+/// (--config 指定的配置文件, 记忆文件路径参数)
+fn parse_server_args(args: &[String]) -> (Option<&str>, Option<&str>) {
+    let mut config_path: Option<&str> = None;
+    let mut path_arg: Option<&str> = None;
+    let mut i = 1;
+    while i < args.len() {
+        if args[i] == "--config" {
+            config_path = args.get(i + 1).map(String::as_str);
+            i += 2;
+        } else {
+            path_arg.get_or_insert(args[i].as_str());
+            i += 1;
+        }
+    }
+    (config_path, path_arg)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
+    let (config_path, path_arg) = parse_server_args(&args);
+
+    // 加载配置文件，--config 与主程序一样对所有读取配置的地方生效
+    if let Some(path) = config_path {
+        config::set_config_path(path);
+    }
+    let config = load_config(config_path);
+    set_lock_history(config.debug_enabled.unwrap_or(false));
     
-    // 加载配置文件
-    let config = load_config(None);
-    
-    // 优先使用命令行参数，否则使用配置文件中的记忆文件路径
-    let memory_path = if args.len() > 1 {
-        Some(args[1].as_str())
-    } else {
-        config.memory_path.as_deref()
-    };
+    // 优先使用命令行参数，否则与主程序一样使用配置文件中的记忆文件路径
+    let memory_path = path_arg.or(config.memory_path.as_deref());
 
-    let store = MemoryStore::new(memory_path, Some(LockType::Mcp));
+    // 信号处理也要持有存储，退出前写入缓冲区中的记忆（write_buffer_size）
+    let store = Arc::new(MemoryStore::new(memory_path, Some(LockType::Mcp))
+        .apply_config(&config));
+    // stdout 只用于 JSON-RPC 响应，存储不可用时在 stderr 说明原因后退出
+    if let Err(e) = store.ensure_storage() {
+        eprintln!("记忆存储不可用: {}", e);
+        std::process::exit(1);
+    }
+    store.recover_locks(config.lock_max_age_seconds);
     let lock_path = store.get_lock_path().to_path_buf();
     
     // 设置信号处理，在程序退出时删除锁文件
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
+    let signal_store = Arc::clone(&store);
     
+    // stdout 只用于 JSON-RPC 响应，所有提示信息都输出到 stderr
     ctrlc::set_handler(move || {
-        println!("\n正在清理锁文件...");
-        if lock_path.exists() {
-            if let Err(e) = std::fs::remove_file(&lock_path) {
-                eprintln!("删除锁文件失败: {}", e);
-            } else {
-                println!("锁文件已删除");
-            }
+        if let Err(e) = signal_store.flush() {
+            eprintln!("\n写入缓冲的记忆失败: {}", e);
+        }
+        eprintln!("\n正在清理锁文件...");
+        // 存储锁与 CLI、交互模式共用，只删除本进程持有的锁
+        if release_own_lock(&lock_path) {
+            eprintln!("锁文件已删除");
         }
         r.store(false, Ordering::SeqCst);
         std::process::exit(0);
     }).expect("设置信号处理失败");
     
-    println!("MCP服务器已启动");
-    println!("提示: 按 Ctrl+C 退出程序（会自动清理锁文件）");
+    eprintln!("MCP服务器已启动");
+    eprintln!("记忆文件: {}", store.get_memory_path().display());
+    eprintln!("提示: 按 Ctrl+C 退出程序（会自动清理锁文件）");
     
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
@@ -62,24 +103,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             continue;
         }
         
+        // 批量请求：整行是请求数组，响应也以数组形式返回
+        if line.starts_with('[') {
+            if let Some(responses) = handle_batch(&store, &config, line).await {
+                let response_json = serde_json::to_string(&responses)?;
+                writer.write_all(response_json.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                writer.flush().await?;
+            }
+            continue;
+        }
+        
         match serde_json::from_str::<JsonRpcRequest>(line) {
             Ok(request) => {
-                let response = handle_request(&store, &request).await;
+                let response = handle_request(&store, &config, &request).await;
                 let response_json = serde_json::to_string(&response)?;
                 writer.write_all(response_json.as_bytes()).await?;
                 writer.write_all(b"\n").await?;
                 writer.flush().await?;
             }
             Err(e) => {
-                let error_response = JsonRpcResponse {
-                    jsonrpc: "2.0".to_string(),
-                    id: Value::Null,
-                    result: None,
-                    error: Some(JsonRpcError {
-                        code: -32700,
-                        message: format!("Parse error: {}", e),
-                    }),
-                };
+                let error_response = create_error_response(recover_request_id(line), -32700, format!("Parse error: {}", e));
                 let response_json = serde_json::to_string(&error_response)?;
                 writer.write_all(response_json.as_bytes()).await?;
                 writer.write_all(b"\n").await?;
@@ -88,14 +132,62 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
     
+    // 信号处理持有存储的引用，存储不会被销毁，需要显式写入缓冲区
+    store.flush()?;
     Ok(())
 }
 
-async fn handle_request(store: &MemoryStore, request: &JsonRpcRequest) -> JsonRpcResponse {
+/// 处理 JSON-RPC 批量请求
+///
+/// # 参数
+/// * `store` - 记忆存储实例
+/// * `config` - 配置
+/// * `line` - 包含请求数组的输入行
+///
+/// # 返回
+/// 响应数组；批量中全部为通知（没有 id）时返回 None，不输出任何内容
+async fn handle_batch(store: &MemoryStore, config: &Config, line: &str) -> Option<Value> {
+    let items = match serde_json::from_str::<Vec<Value>>(line) {
+        Ok(items) => items,
+        Err(e) => {
+            let response = create_error_response(Value::Null, -32700, format!("Parse error: {}", e));
+            return Some(json!(response));
+        }
+    };
+    if items.is_empty() {
+        let response = create_error_response(Value::Null, -32600, "Invalid Request: empty batch".to_string());
+        return Some(json!(response));
+    }
+
+    let mut responses = Vec::new();
+    for item in items {
+        // 通知有 method 但没有 id，按规范只执行不响应；无效请求仍需返回错误
+        let is_notification = item.get("method").is_some() && item.get("id").is_none();
+        let id = match item.get("id") {
+            Some(id @ (Value::String(_) | Value::Number(_))) => id.clone(),
+            _ => Value::Null,
+        };
+        let response = match serde_json::from_value::<JsonRpcRequest>(item) {
+            Ok(request) => handle_request(store, config, &request).await,
+            Err(e) => create_error_response(id, -32600, format!("Invalid Request: {}", e)),
+        };
+        if !is_notification {
+            responses.push(json!(response));
+        }
+    }
+
+    if responses.is_empty() {
+        None
+    } else {
+        Some(Value::Array(responses))
+    }
+}
+
+async fn handle_request(store: &MemoryStore, config: &Config, request: &JsonRpcRequest) -> JsonRpcResponse {
     match request.method.as_str() {
         "initialize" => handle_initialize(request.id.clone()),
         "tools/list" => handle_tools_list(store, request.id.clone()),
-        "tools/call" => handle_tools_call(store, request.params.clone(), request.id.clone()).await,
+        "tools/call" => handle_tools_call(store, config, request.params.clone(), request.id.clone()).await,
         "shutdown" => JsonRpcResponse {
             jsonrpc: "2.0".to_string(),
             id: request.id.clone(),
@@ -147,6 +239,14 @@ fn handle_tools_list(_store: &MemoryStore, id: Value) -> JsonRpcResponse {
                     "tags": {
                         "type": "string",
                         "description": "Comma-separated tags (optional)"
+                    },
+                    "idempotency_key": {
+                        "type": "string",
+                        "description": "Client-supplied key; retries with the same key return the existing memory instead of adding a duplicate (optional)"
+                    },
+                    "source": {
+                        "type": "string",
+                        "description": "Provenance recorded on the memory, e.g. md:<file> (optional, default mcp)"
                     }
                 },
                 "required": ["text"]
@@ -164,7 +264,48 @@ fn handle_tools_list(_store: &MemoryStore, id: Value) -> JsonRpcResponse {
                     },
                     "limit": {
                         "type": "number",
-                        "description": "Maximum number of results (optional)"
+                        "description": "Maximum number of results (optional, default 10, capped by mcp_max_search_limit)"
+                    },
+                    "tags": {
+                        "type": "string",
+                        "description": "Comma-separated tags to filter by (optional)"
+                    },
+                    "tag_match": {
+                        "type": "string",
+                        "enum": ["any", "all"],
+                        "description": "Whether results must have any (default) or all of the tags"
+                    },
+                    "expand_implications": {
+                        "type": "boolean",
+                        "description": "Let tag filters match tags that imply them (per tag_implications config)"
+                    },
+                    "source": {
+                        "type": "string",
+                        "description": "Only match memories from this source; a kind like md matches every md:<file> (optional)"
+                    },
+                    "exclude_temp": {
+                        "type": "boolean",
+                        "description": "Skip memories tagged temp; false includes them even when exclude_temp_by_default is set (default: exclude_temp_by_default)"
+                    },
+                    "within": {
+                        "type": "string",
+                        "description": "Only search memories with this tag and rank them by text, without the tag bonus (optional)"
+                    },
+                    "auto_cutoff": {
+                        "type": "boolean",
+                        "description": "Drop hits scoring below 30% of the top hit (still capped by limit)"
+                    },
+                    "include_deleted": {
+                        "type": "boolean",
+                        "description": "Also search soft-deleted memories; their hits carry deleted_at"
+                    },
+                    "max_bytes": {
+                        "type": "number",
+                        "description": "Cap on total text bytes; when exceeded, texts share the budget and longer ones are truncated and flagged (optional)"
+                    },
+                    "ids_only": {
+                        "type": "boolean",
+                        "description": "Return only an array of matching ids"
                     }
                 },
                 "required": ["query"]
@@ -186,12 +327,42 @@ fn handle_tools_list(_store: &MemoryStore, id: Value) -> JsonRpcResponse {
                     },
                     "limit": {
                         "type": "number",
-                        "description": "Maximum number of memories to compress"
+                        "description": "Maximum number of memories to compress (optional, default 10, capped by mcp_max_search_limit)"
+                    },
+                    "include_hits": {
+                        "type": "boolean",
+                        "description": "Also return the included memories as structured hits"
+                    },
+                    "fallback_recent": {
+                        "type": "boolean",
+                        "description": "Fall back to the most recent memories when the query matches nothing"
                     }
                 },
                 "required": ["query", "budget"]
             }),
         },
+        Tool {
+            name: "recall".to_string(),
+            description: "Search memories and compress the results in one call".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Query to find related memories"
+                    },
+                    "budget": {
+                        "type": "number",
+                        "description": "Maximum character budget for the markdown (default 1000)"
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "Maximum number of memories to return and compress (default 10)"
+                    }
+                },
+                "required": ["query"]
+            }),
+        },
         Tool {
             name: "delete_memory".to_string(),
             description: "Soft delete a memory by ID".to_string(),
@@ -214,22 +385,76 @@ fn handle_tools_list(_store: &MemoryStore, id: Value) -> JsonRpcResponse {
                 "properties": {}
             }),
         },
+        Tool {
+            name: "list_memories".to_string(),
+            description: format!("List memories in stored order with paging (at most {} per page)", MAX_LIST_LIMIT),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "number",
+                        "description": "Maximum number of memories to return (default 20)"
+                    },
+                    "offset": {
+                        "type": "number",
+                        "description": "Number of memories to skip (default 0)"
+                    },
+                    "include_deleted": {
+                        "type": "boolean",
+                        "description": "Include soft-deleted memories (default false)"
+                    },
+                    "compact": {
+                        "type": "boolean",
+                        "description": "Omit keywords and truncate long texts to keep the payload small"
+                    },
+                    "max_bytes": {
+                        "type": "number",
+                        "description": "Cap on total text bytes; when exceeded, texts share the budget and longer ones are truncated and flagged (optional)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "get_metrics".to_string(),
+            description: "Get operation counters (adds, searches, deletes, lock waits and timeouts) since the server started".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "tag_stats".to_string(),
+            description: "Get the most frequent co-occurring tag pairs".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "number",
+                        "description": "Maximum number of tag pairs (optional, default 10)"
+                    }
+                }
+            }),
+        },
     ];
     
     create_tools_list_response(id, tools)
 }
 
-async fn handle_tools_call(store: &MemoryStore, params: Option<Value>, id: Value) -> JsonRpcResponse {
+async fn handle_tools_call(store: &MemoryStore, config: &Config, params: Option<Value>, id: Value) -> JsonRpcResponse {
     match parse_tool_call_params(params) {
         Ok(tool_call) => {
             let arguments = tool_call.arguments.unwrap_or(json!({}));
             
             match tool_call.name.as_str() {
                 "add_memory" => handle_add_memory(store, arguments, id),
-                "search_memory" => handle_search_memory(store, arguments, id),
-                "compress_memory" => handle_compress_memory(store, arguments, id),
+                "search_memory" => handle_search_memory(store, config, arguments, id),
+                "compress_memory" => handle_compress_memory(store, config, arguments, id),
+                "recall" => handle_recall(store, config, arguments, id),
                 "delete_memory" => handle_delete_memory(store, arguments, id),
                 "get_stats" => handle_get_stats(store, id),
+                "get_metrics" => handle_get_metrics(store, id),
+                "list_memories" => handle_list_memories(store, arguments, id),
+                "tag_stats" => handle_tag_stats(store, arguments, id),
                 _ => create_error_response(id, -32601, format!("Tool not found: {}", tool_call.name)),
             }
         },
@@ -251,8 +476,11 @@ fn handle_add_memory(store: &MemoryStore, arguments: Value, id: Value) -> JsonRp
         Some(Value::String(t)) => t.split(',').map(|s| s.trim().to_string()).collect(),
         _ => vec![],
     };
+
+    let idempotency_key = arguments.get("idempotency_key").and_then(|k| k.as_str());
+    let source = arguments.get("source").and_then(|s| s.as_str()).unwrap_or(SOURCE_MCP);
     
-    match store.add_memory(&text, Some(tags)) {
+    match store.add_memory_with_source(&text, Some(tags), idempotency_key, Some(source)) {
         Ok(record) => create_success_response(id, json!({
             "success": true,
             "id": record.id,
@@ -262,7 +490,7 @@ fn handle_add_memory(store: &MemoryStore, arguments: Value, id: Value) -> JsonRp
     }
 }
 
-fn handle_search_memory(store: &MemoryStore, arguments: Value, id: Value) -> JsonRpcResponse {
+fn handle_search_memory(store: &MemoryStore, config: &Config, arguments: Value, id: Value) -> JsonRpcResponse {
     let query = match arguments.get("query") {
         Some(Value::String(q)) => q.clone(),
         _ => {
@@ -271,32 +499,79 @@ fn handle_search_memory(store: &MemoryStore, arguments: Value, id: Value) -> Jso
     };
     
     let limit: usize = match arguments.get("limit") {
-        Some(Value::Number(n)) => n.as_u64().unwrap_or(10) as usize,
-        _ => 10,
+        Some(Value::Number(n)) => n.as_u64().map_or(store.default_limit(), |n| n as usize),
+        _ => store.default_limit(),
+    };
+    let limit = limit.clamp(1, get_mcp_max_search_limit(config));
+
+    let tags: Vec<String> = match arguments.get("tags") {
+        Some(Value::String(t)) => t.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+        _ => vec![],
+    };
+
+    let tag_match = match arguments.get("tag_match").and_then(|m| m.as_str()) {
+        Some("all") => TagMatch::All,
+        Some("any") | None => TagMatch::Any,
+        Some(other) => {
+            return create_error_response(id, -32602, format!("Invalid tag_match: {} (expected any or all)", other));
+        }
+    };
+
+    let options = SearchOptions {
+        limit: Some(limit),
+        tags,
+        tag_match,
+        expand_implications: matches!(arguments.get("expand_implications"), Some(Value::Bool(true))),
+        source: arguments.get("source").and_then(|s| s.as_str()).map(str::to_string),
+        exclude_temp: arguments.get("exclude_temp").and_then(Value::as_bool),
+        within: arguments.get("within").and_then(|t| t.as_str()).map(str::to_string),
+        auto_cutoff: matches!(arguments.get("auto_cutoff"), Some(Value::Bool(true))).then_some(DEFAULT_AUTO_CUTOFF_RATIO),
+        include_deleted: matches!(arguments.get("include_deleted"), Some(Value::Bool(true))),
+        ..SearchOptions::default()
     };
     
-    match store.search(&query, Some(limit)) {
+    let max_bytes = arguments.get("max_bytes").and_then(|n| n.as_u64()).map(|n| n as usize);
+    let ids_only = matches!(arguments.get("ids_only"), Some(Value::Bool(true)));
+
+    match store.search_with_options(&query, &options) {
+        Ok(results) if ids_only => {
+            let ids: Vec<&str> = results.iter().map(|hit| hit.id.as_str()).collect();
+            create_success_response(id, json!({
+                "ids": ids,
+                "count": ids.len(),
+                "limit": limit
+            }))
+        },
         Ok(results) => {
-            let memories: Vec<Value> = results.iter().map(|hit| {
-                json!({
+            let mut memories: Vec<Value> = results.iter().map(|hit| {
+                let mut memory = json!({
                     "id": hit.id,
                     "text": hit.text,
                     "tags": hit.tags,
                     "score": hit.score,
                     "created_at": hit.created_at
-                })
+                });
+                if let Some(deleted_at) = &hit.deleted_at {
+                    memory["deleted_at"] = json!(deleted_at);
+                }
+                memory
             }).collect();
+
+            if let Some(max_bytes) = max_bytes {
+                cap_text_bytes(&mut memories, max_bytes);
+            }
             
             create_success_response(id, json!({
                 "memories": memories,
-                "count": memories.len()
+                "count": memories.len(),
+                "limit": limit
             }))
         },
         Err(e) => create_error_response(id, -32603, format!("Failed to search memory: {}", e)),
     }
 }
 
-fn handle_compress_memory(store: &MemoryStore, arguments: Value, id: Value) -> JsonRpcResponse {
+fn handle_compress_memory(store: &MemoryStore, config: &Config, arguments: Value, id: Value) -> JsonRpcResponse {
     let query = match arguments.get("query") {
         Some(Value::String(q)) => q.clone(),
         _ => {
@@ -310,29 +585,79 @@ fn handle_compress_memory(store: &MemoryStore, arguments: Value, id: Value) -> J
     };
     
     let limit: usize = match arguments.get("limit") {
-        Some(Value::Number(n)) => n.as_u64().unwrap_or(10) as usize,
-        _ => 10,
+        Some(Value::Number(n)) => n.as_u64().map_or(store.default_limit(), |n| n as usize),
+        _ => store.default_limit(),
     };
-    
+    let limit = limit.clamp(1, get_mcp_max_search_limit(config));
+
+    let include_hits = matches!(arguments.get("include_hits"), Some(Value::Bool(true)));
+    let fallback_recent = matches!(arguments.get("fallback_recent"), Some(Value::Bool(true)));
+
+    let records = match store.load_active() {
+        Ok(mut records) => {
+            if store.exclude_temp_by_default() {
+                records.retain(|r| !is_temp(r));
+            }
+            records
+        }
+        Err(e) => return create_error_response(id, -32603, format!("Failed to compress memory: {}", e)),
+    };
+
+    // 与 recall 和 CLI compress 使用同一压缩逻辑，included 与 markdown 中实际放入预算的记忆一致
+    let options = CompressOptions {
+        budget,
+        limit: Some(limit),
+        fallback_recent,
+        weights: *store.scoring_weights(),
+        ..CompressOptions::default()
+    };
+    let compressed = compress_with_options(&records, &query, &options);
+
+    let mut result = json!({
+        "compressed": compressed.markdown,
+        "length": compressed.used,
+        "budget": compressed.budget
+    });
+    if include_hits {
+        result["included"] = json!(compressed.included);
+    }
+    create_success_response(id, result)
+}
+
+fn handle_recall(store: &MemoryStore, config: &Config, arguments: Value, id: Value) -> JsonRpcResponse {
+    let query = match arguments.get("query") {
+        Some(Value::String(q)) => q.clone(),
+        _ => {
+            return create_error_response(id, -32602, "Missing or invalid query parameter".to_string());
+        }
+    };
+
+    let budget: usize = match arguments.get("budget") {
+        Some(Value::Number(n)) => n.as_u64().unwrap_or(1000) as usize,
+        _ => 1000,
+    };
+
+    let limit: usize = match arguments.get("limit") {
+        Some(Value::Number(n)) => n.as_u64().map_or(store.default_limit(), |n| n as usize),
+        _ => store.default_limit(),
+    };
+    let limit = limit.clamp(1, get_mcp_max_search_limit(config));
+
     match store.search(&query, Some(limit)) {
-        Ok(results) => {
-            let compressed = results.iter()
-                .map(|hit| format!("- ({}) [{}] {}", hit.id, hit.tags.join(", "), hit.text))
-                .collect::<Vec<_>>()
-                .join("\n");
-            
-            let markdown = format!(
-                "# Copilot Context (auto)\n\n## Relevant memory\n{}\n--- End ---",
-                compressed
-            );
-            
+        Ok(hits) => {
+            // 压缩块直接由返回的命中生成，两者的记录和顺序始终一致
+            let options = CompressOptions { budget, ..CompressOptions::default() };
+            let compressed = compress_hits(hits.clone(), &options);
             create_success_response(id, json!({
-                "compressed": markdown,
-                "length": markdown.len(),
-                "budget": budget
+                "hits": hits,
+                "count": hits.len(),
+                "limit": limit,
+                "compressed": compressed.markdown,
+                "length": compressed.used,
+                "budget": compressed.budget
             }))
         },
-        Err(e) => create_error_response(id, -32603, format!("Failed to compress memory: {}", e)),
+        Err(e) => create_error_response(id, -32603, format!("Failed to search memory: {}", e)),
     }
 }
 
@@ -364,3 +689,415 @@ fn handle_get_stats(store: &MemoryStore, id: Value) -> JsonRpcResponse {
         Err(e) => create_error_response(id, -32603, format!("Failed to get stats: {}", e)),
     }
 }
+
+fn handle_list_memories(store: &MemoryStore, arguments: Value, id: Value) -> JsonRpcResponse {
+    let limit: usize = match arguments.get("limit") {
+        Some(Value::Number(n)) => n.as_u64().unwrap_or(20) as usize,
+        _ => 20,
+    };
+
+    let offset: usize = match arguments.get("offset") {
+        Some(Value::Number(n)) => n.as_u64().unwrap_or(0) as usize,
+        _ => 0,
+    };
+
+    let include_deleted = matches!(arguments.get("include_deleted"), Some(Value::Bool(true)));
+    let compact = matches!(arguments.get("compact"), Some(Value::Bool(true)));
+    let max_bytes = arguments.get("max_bytes").and_then(|n| n.as_u64()).map(|n| n as usize);
+
+    let opts = ListOptions {
+        limit: Some(limit.clamp(1, MAX_LIST_LIMIT)),
+        offset,
+        include_deleted,
+        ..ListOptions::default()
+    };
+
+    match store.list(&opts) {
+        Ok((records, total)) => {
+            let mut memories: Vec<Value> = records.iter().map(|r| {
+                if compact {
+                    let text: String = r.text.chars().take(COMPACT_TEXT_LEN).collect();
+                    json!({
+                        "id": r.id,
+                        "text": text,
+                        "tags": r.tags,
+                        "created_at": r.created_at,
+                        "updated_at": r.updated_at,
+                        "deleted_at": r.deleted_at
+                    })
+                } else {
+                    json!(r)
+                }
+            }).collect();
+
+            if let Some(max_bytes) = max_bytes {
+                cap_text_bytes(&mut memories, max_bytes);
+            }
+
+            create_success_response(id, json!({
+                "memories": memories,
+                "count": memories.len(),
+                "total": total,
+                "offset": offset
+            }))
+        },
+        Err(e) => create_error_response(id, -32603, format!("Failed to list memories: {}", e)),
+    }
+}
+
+/// 限制结果中 `text` 字段的总字节数
+///
+/// 总字节数已在上限内时不做修改；否则按顺序给每条文本分配剩余预算的平均份额
+/// （`剩余字节数 / 剩余条数`），放得下的保留原文，放不下的截断到份额内（含省略号）
+/// 并标记 `truncated: true`，短文本省下的预算留给后面的条目，总字节数不超过 `max_bytes`。
+fn cap_text_bytes(memories: &mut [Value], max_bytes: usize) {
+    let text_len = |memory: &Value| memory.get("text").and_then(|t| t.as_str()).map_or(0, str::len);
+    if memories.iter().map(text_len).sum::<usize>() <= max_bytes {
+        return;
+    }
+
+    let mut remaining = max_bytes;
+    let mut items_left = memories.iter().filter(|m| m.get("text").and_then(|t| t.as_str()).is_some()).count();
+    for memory in memories.iter_mut() {
+        let Some(text) = memory.get("text").and_then(|t| t.as_str()) else {
+            continue;
+        };
+        let share = remaining / items_left;
+        items_left -= 1;
+        if text.len() <= share {
+            remaining -= text.len();
+            continue;
+        }
+        let truncated = if share < '…'.len_utf8() {
+            String::new()
+        } else {
+            let mut end = share - '…'.len_utf8();
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            format!("{}…", &text[..end])
+        };
+        remaining -= truncated.len();
+        memory["text"] = Value::String(truncated);
+        memory["truncated"] = Value::Bool(true);
+    }
+}
+
+fn handle_get_metrics(store: &MemoryStore, id: Value) -> JsonRpcResponse {
+    create_success_response(id, json!(store.metrics()))
+}
+
+fn handle_tag_stats(store: &MemoryStore, arguments: Value, id: Value) -> JsonRpcResponse {
+    let limit: usize = match arguments.get("limit") {
+        Some(Value::Number(n)) => n.as_u64().unwrap_or(10) as usize,
+        _ => 10,
+    };
+    
+    match store.tag_cooccurrence() {
+        Ok(pairs) => {
+            let mut pairs: Vec<((String, String), usize)> = pairs.into_iter().collect();
+            pairs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            let pairs: Vec<Value> = pairs.into_iter().take(limit).map(|((a, b), count)| {
+                json!({
+                    "tags": [a, b],
+                    "count": count
+                })
+            }).collect();
+            
+            create_success_response(id, json!({
+                "pairs": pairs,
+                "count": pairs.len()
+            }))
+        },
+        Err(e) => create_error_response(id, -32603, format!("Failed to get tag stats: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gmem_rust_memory_store::SearchHit;
+
+    /// 在临时目录中创建记忆存储并添加记忆
+    fn store_with(dir: &std::path::Path, memories: &[(&str, &str)]) -> MemoryStore {
+        let store = MemoryStore::new(dir.join("memory.json").to_str(), Some(LockType::Mcp));
+        for (text, tags) in memories {
+            let tags = tags.split(',').filter(|t| !t.is_empty()).map(str::to_string).collect();
+            store.add_memory(text, Some(tags)).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn compress_memory_returns_the_hits_that_fit_the_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let texts: Vec<String> = (0..3).map(|i| format!("rust note {} {}", i, "padding ".repeat(12))).collect();
+        let store = store_with(dir.path(), &[
+            (texts[0].as_str(), "rust"),
+            (texts[1].as_str(), "rust"),
+            (texts[2].as_str(), "rust"),
+            ("unrelated", ""),
+        ]);
+
+        let response = handle_compress_memory(&store, &Config::default(), json!({"query": "rust", "budget": 300, "include_hits": true}), json!(1));
+        let result = response.result.unwrap();
+        let included: Vec<SearchHit> = serde_json::from_value(result["included"].clone()).unwrap();
+
+        let compressed = result["compressed"].as_str().unwrap();
+        assert!(!included.is_empty());
+        assert!(included.len() < 3);
+        assert!(included.iter().all(|hit| compressed.contains(hit.text.trim())));
+        assert_eq!(result["length"], json!(compressed.chars().count()));
+        assert!(compressed.chars().count() <= 300);
+
+        let response = handle_compress_memory(&store, &Config::default(), json!({"query": "rust", "budget": 300}), json!(2));
+        assert!(response.result.unwrap().get("included").is_none());
+    }
+
+    #[tokio::test]
+    async fn batch_returns_an_array_of_responses_without_notifications() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[]);
+        let batch = json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "initialize"},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "id": 3},
+            {"jsonrpc": "2.0", "id": "list", "method": "tools/list"}
+        ]);
+
+        // 无效请求返回错误，但不影响批量中后面的请求
+        let responses = handle_batch(&store, &Config::default(), &batch.to_string()).await.unwrap();
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["id"], json!(1));
+        assert!(responses[0]["result"]["serverInfo"].is_object(), "{}", responses[0]);
+        assert_eq!(responses[1]["id"], json!(3));
+        assert_eq!(responses[1]["error"]["code"], json!(-32600));
+        assert_eq!(responses[2]["id"], json!("list"));
+        assert!(responses[2]["result"]["tools"].is_array(), "{}", responses[2]);
+
+        let only_notifications = json!([{"jsonrpc": "2.0", "method": "notifications/initialized"}]);
+        assert!(handle_batch(&store, &Config::default(), &only_notifications.to_string()).await.is_none());
+        let empty = handle_batch(&store, &Config::default(), "[]").await.unwrap();
+        assert_eq!(empty["error"]["code"], json!(-32600));
+    }
+
+    #[test]
+    fn add_memory_records_mcp_source_unless_given() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[]);
+        handle_add_memory(&store, json!({"text": "plain add"}), json!(1)).result.unwrap();
+        handle_add_memory(&store, json!({"text": "from md", "source": "md:notes.md"}), json!(2)).result.unwrap();
+
+        let records = store.load().unwrap();
+        let source_of = |text: &str| records.iter().find(|r| r.text == text).unwrap().source.clone();
+        assert_eq!(source_of("plain add").as_deref(), Some(SOURCE_MCP));
+        assert_eq!(source_of("from md").as_deref(), Some("md:notes.md"));
+    }
+
+    #[test]
+    fn add_memory_with_same_idempotency_key_creates_one_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[]);
+        let args = json!({"text": "retried add", "idempotency_key": "req-1"});
+
+        let first = handle_add_memory(&store, args.clone(), json!(1)).result.unwrap();
+        let retry = handle_add_memory(&store, args, json!(2)).result.unwrap();
+        assert_eq!(first["id"], retry["id"]);
+        assert_eq!(store.load().unwrap().len(), 1);
+
+        handle_add_memory(&store, json!({"text": "retried add", "idempotency_key": "req-2"}), json!(3));
+        assert_eq!(store.load().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn list_memories_pages_through_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let memories: Vec<(String, &str)> = (0..5).map(|i| (format!("memory {}", i), "")).collect();
+        let memories: Vec<(&str, &str)> = memories.iter().map(|(t, tags)| (t.as_str(), *tags)).collect();
+        let store = store_with(dir.path(), &memories);
+        let deleted = store.load().unwrap()[4].id.clone();
+        store.soft_delete(&deleted).unwrap();
+
+        let mut seen = Vec::new();
+        for offset in [0, 2, 4] {
+            let result = handle_list_memories(&store, json!({"limit": 2, "offset": offset}), json!(1)).result.unwrap();
+            assert_eq!(result["total"], json!(4));
+            assert_eq!(result["offset"], json!(offset));
+            for memory in result["memories"].as_array().unwrap() {
+                seen.push(memory["id"].as_str().unwrap().to_string());
+            }
+        }
+        assert_eq!(seen.len(), 4);
+        assert!(!seen.contains(&deleted));
+
+        let result = handle_list_memories(&store, json!({"limit": 10, "include_deleted": true}), json!(2)).result.unwrap();
+        assert_eq!(result["count"], json!(5));
+
+        let more: Vec<(String, Vec<String>)> = (0..MAX_LIST_LIMIT).map(|i| (format!("bulk {}", i), Vec::new())).collect();
+        store.add_many(&more).unwrap();
+        let result = handle_list_memories(&store, json!({"limit": 100000}), json!(3)).result.unwrap();
+        assert_eq!(result["count"], json!(MAX_LIST_LIMIT));
+        assert_eq!(result["total"], json!(MAX_LIST_LIMIT + 4));
+    }
+
+    #[test]
+    fn max_bytes_truncates_oversized_texts_and_flags_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let big = format!("rust {}", "x".repeat(2000));
+        let store = store_with(dir.path(), &[(big.as_str(), ""), (big.as_str(), ""), ("rust short", "")]);
+        let config = Config::default();
+
+        let result = handle_search_memory(&store, &config, json!({"query": "rust", "max_bytes": 600}), json!(1)).result.unwrap();
+        let memories = result["memories"].as_array().unwrap();
+        let truncated: Vec<&Value> = memories.iter().filter(|m| m["truncated"] == json!(true)).collect();
+        assert!(!truncated.is_empty());
+        for memory in &truncated {
+            let text = memory["text"].as_str().unwrap();
+            assert!(text.ends_with('…'));
+        }
+        assert!(memories.iter().map(|m| m["text"].as_str().unwrap().len()).sum::<usize>() <= 600);
+        assert!(memories.iter().any(|m| m["text"] == json!("rust short") && m.get("truncated").is_none()));
+
+        let result = handle_list_memories(&store, json!({"max_bytes": 600}), json!(2)).result.unwrap();
+        assert!(result["memories"].as_array().unwrap().iter().any(|m| m["truncated"] == json!(true)));
+
+        let result = handle_search_memory(&store, &config, json!({"query": "rust"}), json!(3)).result.unwrap();
+        assert!(result["memories"].as_array().unwrap().iter().all(|m| m.get("truncated").is_none()));
+    }
+
+    #[test]
+    fn cap_text_bytes_keeps_the_total_within_max_bytes() {
+        let mut memories = vec![
+            json!({"text": "a".repeat(500)}),
+            json!({"text": "b".repeat(2000)}),
+            json!({"id": "no text"}),
+            json!({"text": "c".repeat(2000)}),
+        ];
+        cap_text_bytes(&mut memories, 600);
+        let lens: Vec<usize> = memories.iter().map(|m| m.get("text").and_then(|t| t.as_str()).map_or(0, str::len)).collect();
+        assert_eq!(lens, [200, 200, 0, 200]);
+        assert!(memories.iter().filter(|m| m.get("text").is_some()).all(|m| m["truncated"] == json!(true)));
+
+        // 前面的短文本省下的预算留给后面的条目
+        let mut memories = vec![json!({"text": "short"}), json!({"text": "d".repeat(2000)})];
+        cap_text_bytes(&mut memories, 600);
+        assert_eq!(memories[0]["text"], json!("short"));
+        assert_eq!(memories[1]["text"].as_str().unwrap().len(), 600 - "short".len());
+
+        // 多字节文本在字符边界截断
+        let mut memories = vec![json!({"text": "记忆".repeat(100)})];
+        cap_text_bytes(&mut memories, 100);
+        let text = memories[0]["text"].as_str().unwrap();
+        assert!(text.len() <= 100 && text.ends_with('…'));
+    }
+
+    #[test]
+    fn recall_returns_hits_and_compressed_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[
+            ("rust lock files keep writers apart", "rust"),
+            ("rust search ranks memories", "rust"),
+            ("unrelated note", ""),
+        ]);
+
+        let result = handle_recall(&store, &Config::default(), json!({"query": "rust", "budget": 500}), json!(1)).result.unwrap();
+        let hits: Vec<SearchHit> = serde_json::from_value(result["hits"].clone()).unwrap();
+        assert_eq!(result["count"], json!(hits.len()));
+        assert!(hits[..2].iter().all(|hit| hit.text.starts_with("rust")));
+        let compressed = result["compressed"].as_str().unwrap();
+        // 压缩块按命中的顺序列出同一批记忆
+        let positions: Vec<usize> = hits.iter().map(|hit| compressed.find(&hit.text).unwrap()).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(!compressed.contains("unrelated note") || hits.iter().any(|hit| hit.text == "unrelated note"));
+        assert!(result["length"].as_u64().unwrap() > 0);
+
+        let result = handle_recall(&store, &Config::default(), json!({"query": "rust", "budget": 500, "limit": 1}), json!(3)).result.unwrap();
+        let hits: Vec<SearchHit> = serde_json::from_value(result["hits"].clone()).unwrap();
+        assert_eq!(hits.len(), 1);
+        let compressed = result["compressed"].as_str().unwrap();
+        assert!(compressed.contains(&hits[0].text));
+        let other = if hits[0].text.contains("lock") { "rust search ranks memories" } else { "rust lock files keep writers apart" };
+        assert!(!compressed.contains(other));
+
+        let response = handle_recall(&store, &Config::default(), json!({"budget": 500}), json!(2));
+        assert_eq!(response.error.unwrap().code, -32602);
+    }
+
+    #[test]
+    fn absurd_limits_are_capped_by_mcp_max_search_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let texts: Vec<String> = (0..5).map(|i| format!("rust note {}", i)).collect();
+        let memories: Vec<(&str, &str)> = texts.iter().map(|t| (t.as_str(), "rust")).collect();
+        let store = store_with(dir.path(), &memories);
+        let config = Config { mcp_max_search_limit: Some(2), ..Config::default() };
+        let args = json!({"query": "rust", "limit": 1_000_000, "budget": 5000, "include_hits": true});
+
+        let result = handle_search_memory(&store, &config, args.clone(), json!(1)).result.unwrap();
+        assert_eq!(result["count"], json!(2));
+        assert_eq!(result["limit"], json!(2));
+
+        let result = handle_recall(&store, &config, args.clone(), json!(2)).result.unwrap();
+        assert_eq!(result["count"], json!(2));
+
+        let result = handle_compress_memory(&store, &config, args, json!(3)).result.unwrap();
+        assert_eq!(result["included"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn search_memory_ids_only_returns_just_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[("rust borrow checker", "rust"), ("rust lock files", "rust")]);
+
+        let result = handle_search_memory(&store, &Config::default(), json!({"query": "rust", "ids_only": true}), json!(1)).result.unwrap();
+        let mut ids: Vec<String> = serde_json::from_value(result["ids"].clone()).unwrap();
+        ids.sort();
+        let mut expected: Vec<String> = store.load().unwrap().into_iter().map(|r| r.id).collect();
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert_eq!(result["count"], json!(2));
+        assert!(result.get("memories").is_none());
+    }
+
+    #[test]
+    fn search_default_limit_applies_when_no_limit_is_given() {
+        let dir = tempfile::tempdir().unwrap();
+        let texts: Vec<String> = (0..8).map(|i| format!("rust note {}", i)).collect();
+        let memories: Vec<(&str, &str)> = texts.iter().map(|t| (t.as_str(), "rust")).collect();
+        let config = Config { search_default_limit: Some(5), ..Config::default() };
+        let store = store_with(dir.path(), &memories).apply_config(&config);
+
+        let result = handle_search_memory(&store, &config, json!({"query": "rust", "ids_only": true}), json!(1)).result.unwrap();
+        assert_eq!(result["count"], json!(5));
+        let result = handle_recall(&store, &config, json!({"query": "rust", "budget": 5000}), json!(2)).result.unwrap();
+        assert_eq!(result["count"], json!(5));
+
+        // 显式的 limit 仍然优先
+        let result = handle_search_memory(&store, &config, json!({"query": "rust", "limit": 7, "ids_only": true}), json!(3)).result.unwrap();
+        assert_eq!(result["count"], json!(7));
+    }
+
+    #[test]
+    fn memory_path_comes_from_the_config_when_argv_omits_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let memory_path = dir.path().join("configured").join("memory.json");
+        let config_file = dir.path().join("config.toml");
+        std::fs::write(&config_file, format!("memory_path = {:?}\n", memory_path.to_str().unwrap())).unwrap();
+        let args = |rest: &[&str]| -> Vec<String> {
+            std::iter::once("gmemory_mcp_server").chain(rest.iter().copied()).map(str::to_string).collect()
+        };
+
+        let without_path = args(&["--config", config_file.to_str().unwrap()]);
+        let (config_path, path_arg) = parse_server_args(&without_path);
+        assert_eq!((config_path, path_arg), (config_file.to_str(), None));
+        let config = load_config(config_path);
+        let store = MemoryStore::new(path_arg.or(config.memory_path.as_deref()), Some(LockType::Mcp));
+        assert_eq!(store.get_memory_path(), memory_path.as_path());
+
+        // 命令行给出的路径优先于配置文件
+        let explicit = dir.path().join("explicit.json");
+        let with_path = args(&[explicit.to_str().unwrap(), "--config", config_file.to_str().unwrap()]);
+        let (config_path, path_arg) = parse_server_args(&with_path);
+        assert_eq!((config_path, path_arg), (config_file.to_str(), explicit.to_str()));
+    }
+}
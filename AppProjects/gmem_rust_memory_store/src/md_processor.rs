@@ -1,8 +1,16 @@
-use crate::store::MemoryStore;
-use crate::config::{load_config, get_memory_path};
-use crate::lock::LockType;
+use crate::store::{MemoryStore, atomic_write, ensure_writable_dir, merge_tags};
+use crate::config::{load_config, get_memory_path, get_default_tags};
+use crate::lock::{acquire_lock_with_cleanup, category_lock_path, LockType};
+use crate::record::file_source;
+use crate::direct_organize::{adopt_legacy_category_file, CATEGORY_FILE_SUFFIX};
 use std::path::{Path, PathBuf};
 
+/// MD 文件导入的记忆来源类型（完整来源为 `md:<文件名>`）
+const SOURCE_MD: &str = "md";
+
+/// 直接处理模式下等待锁的超时时间（毫秒），比普通写入更长
+const DIRECT_LOCK_TIMEOUT_MS: u64 = 15000;
+
 /// MD文件处理选项
 #[derive(Clone)]
 pub struct MdProcessorOptions {
@@ -12,6 +20,10 @@ pub struct MdProcessorOptions {
     pub category: String,
     /// 额外标签
     pub additional_tags: Vec<String>,
+    /// 预览模式：只打印将要生成的记录，不写入记忆库
+    pub dry_run: bool,
+    /// 分类文件损坏时是否强制覆盖（损坏文件总会先备份为 .corrupt）
+    pub force_overwrite: bool,
 }
 
 impl Default for MdProcessorOptions {
@@ -20,6 +32,8 @@ impl Default for MdProcessorOptions {
             is_temporary: false,
             category: "default".to_string(),
             additional_tags: Vec::new(),
+            dry_run: false,
+            force_overwrite: false,
         }
     }
 }
@@ -39,7 +53,8 @@ impl MdProcessor {
     /// MD文件处理器实例
     pub fn new(memory_path: Option<&str>) -> Self {
         Self {
-            store: MemoryStore::new(memory_path, Some(LockType::Cli)),
+            store: MemoryStore::new(memory_path, Some(LockType::Cli))
+                .apply_config(&load_config(None)),
         }
     }
 
@@ -54,29 +69,23 @@ impl MdProcessor {
     pub fn add_md_to_memory(&self, file_path: &Path, options: MdProcessorOptions) -> std::io::Result<()> {
         println!("开始处理MD文件: {}", file_path.display());
         
-        // 读取MD文件内容
-        let content = std::fs::read_to_string(file_path)?;
-        
-        // 生成记忆文本
+        let (memory_text, tags) = build_md_memory(file_path, &options)?;
         let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
-        let memory_text = format!("# {} 内容\n\n{}", file_name, content);
-        
-        // 生成标签
-        let mut tags = vec!["markdown".to_string(), "file".to_string()];
         
-        // 如果是临时文件，添加temp标签
-        if options.is_temporary {
-            tags.push("temp".to_string());
+        // 预览模式：只显示计算结果，不写入
+        if options.dry_run {
+            println!("[预览模式] 不会写入记忆库");
+            println!("文件: {}", file_path.display());
+            println!("文本预览: {}", preview_text(&memory_text, 200));
+            println!("标签: {}", tags.join(", "));
+            println!("分类: {}", options.category);
+            println!("目标文件: {}", self.store.target_file_for_tags(&tags).display());
+            return Ok(());
         }
         
-        // 添加额外标签
-        tags.extend(options.additional_tags);
-        
         // 检查记忆库中是否已经存在相同的记忆
-        let existing_records = self.store.load()?;
-        let memory_exists = existing_records.iter().any(|record| {
-            record.text == memory_text && record.deleted_at.is_none()
-        });
+        let existing_records = self.store.load_active()?;
+        let memory_exists = existing_records.iter().any(|record| record.text == memory_text);
         
         if memory_exists {
             println!("记忆已存在，跳过添加");
@@ -86,7 +95,8 @@ impl MdProcessor {
         }
         
         // 添加到记忆库
-        self.store.add_memory(&memory_text, Some(tags))?;
+        let source = file_source(SOURCE_MD, &file_name);
+        self.store.add_memory_with_source(&memory_text, Some(tags), None, Some(&source))?;
         
         println!("成功将MD文件添加到记忆库！");
         println!("文件: {}", file_path.display());
@@ -135,6 +145,8 @@ impl MdProcessor {
 /// * `memory_path` - 记忆存储路径
 /// * `is_temporary` - 是否为临时文件
 /// * `category` - 记忆分类
+/// * `dry_run` - 是否为预览模式（不写入）
+/// * `force_overwrite` - 分类文件损坏时是否强制覆盖
 ///
 /// # 返回
 /// 操作结果
@@ -143,6 +155,8 @@ pub fn process_single_md_file(
     memory_path: Option<&str>,
     is_temporary: bool,
     category: &str,
+    dry_run: bool,
+    force_overwrite: bool,
 ) -> std::io::Result<()> {
     // 尝试使用普通方式处理
     let processor = MdProcessor::new(memory_path);
@@ -150,82 +164,191 @@ pub fn process_single_md_file(
         is_temporary,
         category: category.to_string(),
         additional_tags: Vec::new(),
+        dry_run,
+        force_overwrite,
     };
     
-    match processor.add_md_to_memory(&PathBuf::from(file_path), options) {
+    match processor.add_md_to_memory(&PathBuf::from(file_path), options.clone()) {
         Ok(_) => Ok(()),
         Err(e) if e.to_string().contains("Timed out acquiring lock") => {
             // 如果是锁超时错误，使用直接处理方式
             println!("锁超时，尝试使用直接处理方式...");
-            direct_process_single_md_file(file_path, memory_path, is_temporary, category)
+            direct_process_single_md_file(file_path, memory_path, &options)
         }
         Err(e) => Err(e),
     }
 }
 
-/// 直接处理单个MD文件，绕过锁文件机制
+/// 读取MD文件并生成记忆文本和标签
 ///
 /// # 参数
 /// * `file_path` - MD文件路径
-/// * `memory_path` - 记忆存储路径
-/// * `is_temporary` - 是否为临时文件
-/// * `category` - 记忆分类
+/// * `options` - 处理选项
 ///
 /// # 返回
-/// 操作结果
-fn direct_process_single_md_file(
-    file_path: &str,
-    _memory_path: Option<&str>,
-    is_temporary: bool,
-    category: &str,
-) -> std::io::Result<()> {
-    println!("开始直接处理MD文件: {}", file_path);
-    
+/// (记忆文本, 标签)
+fn build_md_memory(file_path: &Path, options: &MdProcessorOptions) -> std::io::Result<(String, Vec<String>)> {
     // 读取MD文件内容
     let content = std::fs::read_to_string(file_path)?;
     
     // 生成记忆文本
-    let file_name = std::path::Path::new(file_path).file_name().unwrap_or_default().to_string_lossy();
+    let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
     let memory_text = format!("# {} 内容\n\n{}", file_name, content);
     
     // 生成标签
     let mut tags = vec!["markdown".to_string(), "file".to_string()];
     
     // 如果是临时文件，添加temp标签
-    if is_temporary {
+    if options.is_temporary {
         tags.push("temp".to_string());
     }
     
+    // 添加额外标签
+    tags.extend(options.additional_tags.iter().cloned());
+    
+    Ok((memory_text, tags))
+}
+
+/// 截取文本预览（按字符截断，避免切断多字节字符）
+///
+/// # 参数
+/// * `text` - 原始文本
+/// * `max_chars` - 最大字符数
+///
+/// # 返回
+/// 预览文本，超长时以省略号结尾
+fn preview_text(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(max_chars).collect();
+    format!("{}...", head)
+}
+
+/// 获取损坏分类文件的备份路径（原文件名加 .corrupt 后缀）
+///
+/// # 参数
+/// * `path` - 分类文件路径
+///
+/// # 返回
+/// 备份文件路径
+fn corrupt_sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".corrupt");
+    path.with_file_name(name)
+}
+
+/// 直接处理单个MD文件（普通方式锁超时后的备用路径）
+///
+/// 先以更长的超时获取分类文件目录的锁，仍然超时时才在无锁状态下写入并打印醒目警告。
+/// 写入统一使用 `atomic_write`（临时文件 + 重命名）。
+///
+/// 如果现有分类文件无法解析，会先备份为 `.corrupt` 文件；
+/// 除非设置了 `force_overwrite`，否则返回错误而不覆盖原文件。
+///
+/// # 参数
+/// * `file_path` - MD文件路径
+/// * `memory_path` - 记忆存储路径
+/// * `options` - 处理选项
+///
+/// # 返回
+/// 操作结果
+fn direct_process_single_md_file(
+    file_path: &str,
+    _memory_path: Option<&str>,
+    options: &MdProcessorOptions,
+) -> std::io::Result<()> {
+    let is_temporary = options.is_temporary;
+    let category = options.category.as_str();
+    println!("开始直接处理MD文件: {}", file_path);
+    
+    // 生成记忆文本和标签（与普通方式相同，包含额外标签）
+    let (memory_text, tags) = build_md_memory(Path::new(file_path), options)?;
+    
+    // 合并配置的默认标签
+    let config = load_config(None);
+    let tags = merge_tags(tags, &get_default_tags(&config));
+    
     // 提取关键词
     let keywords = crate::keywords::extract_keywords(&memory_text);
     
     // 创建新记录
     let new_record = crate::record::MemoryRecord {
-        id: crate::timestamp::make_id(),
+        id: crate::timestamp::make_id_with_prefix(config.id_prefix.as_deref().unwrap_or(crate::timestamp::DEFAULT_ID_PREFIX)),
         text: memory_text,
         tags,
         keywords,
         created_at: crate::timestamp::now_iso(),
         updated_at: crate::timestamp::now_iso(),
         deleted_at: None,
+        idempotency_key: None,
+        source: Some(file_source(SOURCE_MD, file_path)),
     };
     
     // 确定存储路径
-    let config = load_config(None);
     let base_path = get_memory_path(&config);
-    let output_dir = std::path::Path::new(&base_path);
+    let output_file_path = match append_to_category_file(Path::new(&base_path), category, new_record, options.force_overwrite)? {
+        Some(path) => path,
+        None => {
+            println!("记忆已存在，跳过添加");
+            return Ok(());
+        }
+    };
     
-    // 确保输出目录存在
-    std::fs::create_dir_all(output_dir)?;
+    println!("成功将MD文件添加到记忆库！");
+    println!("文件: {}", file_path);
+    println!("分类: {}", category);
+    println!("是否临时: {}", is_temporary);
+    println!("保存到: {}", output_file_path.display());
     
-    // 确定分类文件路径
-    let file_name = format!("{}-global-gmem-recoder.json", category);
+    Ok(())
+}
+
+/// 把一条记录追加到分类文件（direct 模式的写入部分）
+///
+/// 先以更长的超时获取分类文件专属的锁，仍然超时时才在无锁状态下写入并打印醒目警告。
+///
+/// # 参数
+/// * `output_dir` - 记忆目录
+/// * `category` - 分类名称
+/// * `new_record` - 新记录
+/// * `force_overwrite` - 分类文件损坏时是否强制覆盖
+///
+/// # 返回
+/// 写入的分类文件路径；文本相同的记录已存在时返回 None
+fn append_to_category_file(
+    output_dir: &Path,
+    category: &str,
+    new_record: crate::record::MemoryRecord,
+    force_overwrite: bool,
+) -> std::io::Result<Option<PathBuf>> {
+    // 确保输出目录存在且可写
+    ensure_writable_dir(output_dir)?;
+    
+    // 确定分类文件路径（只有旧拼写的文件时先重命名，继续在原有记录上追加）
+    let file_name = format!("{}{}", category, CATEGORY_FILE_SUFFIX);
     let output_file_path = output_dir.join(file_name);
+    adopt_legacy_category_file(&output_file_path)?;
+    
+    // 获取该分类文件专属的锁（使用更长的超时），超时后才退回无锁写入
+    let lock_path = category_lock_path(&output_file_path);
+    let _lock = match acquire_lock_with_cleanup(&lock_path, Some(DIRECT_LOCK_TIMEOUT_MS), Some(300)) {
+        Ok(guard) => Some(guard),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+            eprintln!("========================================");
+            eprintln!("警告: 等待锁超时 ({} ms): {}", DIRECT_LOCK_TIMEOUT_MS, lock_path.display());
+            eprintln!("警告: 将在无锁状态下写入 {}", output_file_path.display());
+            eprintln!("警告: 如有其他进程同时写入，其修改可能被覆盖！");
+            eprintln!("========================================");
+            None
+        }
+        Err(e) => return Err(e),
+    };
     
     // 读取现有记录（使用明确的文件打开和关闭）
     let mut records: Vec<crate::record::MemoryRecord> = Vec::new();
     if output_file_path.exists() {
-        // 尝试读取现有文件，如果失败则忽略
+        // 尝试读取现有文件，读取或解析失败时不能静默丢弃已有记忆
         match std::fs::read_to_string(&output_file_path) {
             Ok(raw) if !raw.trim().is_empty() => {
                 match serde_json::from_str(&raw) {
@@ -233,12 +356,28 @@ fn direct_process_single_md_file(
                         records = parsed_records;
                     }
                     Err(e) => {
-                        println!("警告: 解析现有记录失败: {}, 将创建新文件", e);
+                        let backup_path = corrupt_sidecar_path(&output_file_path);
+                        std::fs::copy(&output_file_path, &backup_path)?;
+                        if !force_overwrite {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                format!(
+                                    "分类文件已损坏: {} ({})，已备份到 {}，如需覆盖请使用 --md-force",
+                                    output_file_path.display(),
+                                    e,
+                                    backup_path.display()
+                                ),
+                            ));
+                        }
+                        println!("警告: 解析现有记录失败: {}, 已备份到 {}, 强制覆盖", e, backup_path.display());
                     }
                 }
             }
             Err(e) => {
-                println!("警告: 读取现有记录失败: {}, 将创建新文件", e);
+                return Err(std::io::Error::new(
+                    e.kind(),
+                    format!("读取现有记录失败: {} ({})", output_file_path.display(), e),
+                ));
             }
             _ => {
                 // 文件为空，使用空向量
@@ -247,72 +386,145 @@ fn direct_process_single_md_file(
     }
     
     // 检查是否已经存在相同的记录
-    let mut record_exists = false;
-    for record in &records {
-        if record.text == new_record.text {
-            record_exists = true;
-            println!("记忆已存在，跳过添加");
-            break;
-        }
+    if records.iter().any(|record| record.text == new_record.text) {
+        return Ok(None);
     }
     
-    // 如果记录不存在，添加新记录
-    if !record_exists {
-        records.push(new_record);
-        
-        // 保存文件（使用临时文件）
-        let json = serde_json::to_string_pretty(&records)?;
-        
-        // 创建临时文件
-        let temp_file_path = output_dir.join(format!("temp_{}.json", std::process::id()));
-        
-        // 写入临时文件
-        std::fs::write(&temp_file_path, &json)?;
-        
-        // 尝试删除目标文件（如果存在）
-        if output_file_path.exists() {
-            match std::fs::remove_file(&output_file_path) {
-                Ok(_) => {
-                    println!("已删除现有文件: {}", output_file_path.display());
-                }
-                Err(e) => {
-                    println!("警告: 删除现有文件失败: {}, 将尝试直接重命名", e);
-                }
-            }
+    // 添加新记录并原子写入（临时文件 + 重命名）
+    records.push(new_record);
+    atomic_write(&output_file_path, &records)?;
+    
+    Ok(Some(output_file_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::MemoryRecord;
+
+    fn record(text: &str) -> MemoryRecord {
+        MemoryRecord {
+            id: crate::timestamp::make_id(),
+            text: text.to_string(),
+            tags: vec!["markdown".to_string()],
+            keywords: Vec::new(),
+            created_at: crate::timestamp::now_iso(),
+            updated_at: crate::timestamp::now_iso(),
+            deleted_at: None,
+            idempotency_key: None,
+            source: None,
         }
-        
-        // 尝试重命名临时文件到目标文件
-        match std::fs::rename(&temp_file_path, &output_file_path) {
-            Ok(_) => {
-                println!("成功将MD文件添加到记忆库！");
-                println!("文件: {}", file_path);
-                println!("分类: {}", category);
-                println!("是否临时: {}", is_temporary);
-                println!("保存到: {}", output_file_path.display());
-            }
-            Err(e) => {
-                println!("警告: 重命名临时文件失败: {}", e);
-                // 尝试直接写入目标文件
-                match std::fs::write(&output_file_path, &json) {
-                    Ok(_) => {
-                        println!("成功将MD文件添加到记忆库！");
-                        println!("文件: {}", file_path);
-                        println!("分类: {}", category);
-                        println!("是否临时: {}", is_temporary);
-                        println!("保存到: {}", output_file_path.display());
+    }
+
+    #[test]
+    fn dry_run_reports_tags_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let md = dir.path().join("notes.md");
+        std::fs::write(&md, "hello").unwrap();
+        let memory = dir.path().join("memory.json");
+        let options = MdProcessorOptions {
+            is_temporary: true,
+            additional_tags: vec!["docs".to_string()],
+            dry_run: true,
+            ..MdProcessorOptions::default()
+        };
+
+        let (text, tags) = build_md_memory(&md, &options).unwrap();
+        assert!(text.contains("notes.md") && text.ends_with("hello"));
+        assert_eq!(tags, vec!["markdown", "file", "temp", "docs"]);
+
+        let processor = MdProcessor::new(memory.to_str());
+        processor.add_md_to_memory(&md, options).unwrap();
+        assert!(!memory.exists());
+    }
+
+    #[test]
+    fn added_md_file_records_its_file_name_as_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let md = dir.path().join("notes.md");
+        std::fs::write(&md, "hello").unwrap();
+        let memory = dir.path().join("memory.json");
+
+        let processor = MdProcessor::new(memory.to_str());
+        processor.add_md_to_memory(&md, MdProcessorOptions::default()).unwrap();
+        let records = MemoryStore::new(memory.to_str(), None).load().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].source.as_deref(), Some("md:notes.md"));
+    }
+
+    #[test]
+    fn corrupt_category_file_is_kept_in_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let category_file = dir.path().join(format!("notes{}", CATEGORY_FILE_SUFFIX));
+        let corrupt = "[{\"id\": \"m_old\", \"text\": \"old memory\"";
+        std::fs::write(&category_file, corrupt).unwrap();
+
+        let err = append_to_category_file(dir.path(), "notes", record("new"), false).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::read_to_string(&category_file).unwrap(), corrupt);
+        assert_eq!(std::fs::read_to_string(corrupt_sidecar_path(&category_file)).unwrap(), corrupt);
+
+        append_to_category_file(dir.path(), "notes", record("new"), true).unwrap();
+        let records: Vec<MemoryRecord> = serde_json::from_str(&std::fs::read_to_string(&category_file).unwrap()).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(std::fs::read_to_string(corrupt_sidecar_path(&category_file)).unwrap(), corrupt);
+    }
+
+    #[test]
+    fn concurrent_writers_keep_every_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let writers: Vec<_> = (0..2)
+            .map(|writer| {
+                let dir = dir.path().to_path_buf();
+                std::thread::spawn(move || {
+                    for i in 0..10 {
+                        let text = format!("writer {} record {}", writer, i);
+                        append_to_category_file(&dir, "notes", record(&text), false).unwrap();
                     }
-                    Err(e) => {
-                        println!("错误: 直接写入目标文件失败: {}", e);
-                        // 清理临时文件
-                        let _ = std::fs::remove_file(&temp_file_path);
-                        return Err(e);
+                })
+            })
+            .collect();
+        for handle in writers {
+            handle.join().unwrap();
+        }
+
+        let category_file = dir.path().join(format!("notes{}", CATEGORY_FILE_SUFFIX));
+        let records: Vec<MemoryRecord> = serde_json::from_str(&std::fs::read_to_string(&category_file).unwrap()).unwrap();
+        assert_eq!(records.len(), 20);
+        assert!(!category_lock_path(&category_file).exists());
+    }
+
+    #[test]
+    fn different_categories_do_not_share_a_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let rust_file = dir.path().join(format!("rust{}", CATEGORY_FILE_SUFFIX));
+        let git_file = dir.path().join(format!("git{}", CATEGORY_FILE_SUFFIX));
+        assert_ne!(category_lock_path(&rust_file), category_lock_path(&git_file));
+
+        // rust 分类的锁被占用时，git 分类的写入不需要等待
+        let held = crate::lock::acquire_lock(&category_lock_path(&rust_file), None).unwrap();
+        let started = std::time::Instant::now();
+        append_to_category_file(dir.path(), "git", record("git while rust is locked"), false).unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        drop(held);
+
+        let writers: Vec<_> = ["rust", "git"].into_iter()
+            .map(|category| {
+                let dir = dir.path().to_path_buf();
+                std::thread::spawn(move || {
+                    for i in 0..10 {
+                        let text = format!("{} record {}", category, i);
+                        append_to_category_file(&dir, category, record(&text), false).unwrap();
                     }
-                }
-            }
+                })
+            })
+            .collect();
+        for handle in writers {
+            handle.join().unwrap();
         }
-    } else {
-        println!("记忆已存在，跳过添加");
+
+        let count = |path: &Path| serde_json::from_str::<Vec<MemoryRecord>>(&std::fs::read_to_string(path).unwrap()).unwrap().len();
+        assert_eq!(count(&rust_file), 10);
+        assert_eq!(count(&git_file), 11);
     }
-    
-    Ok(())
 }
@@ -1,6 +1,7 @@
-use crate::store::MemoryStore;
+use crate::store::{MemoryStore, check_memory_dir, ensure_writable_dir};
 use crate::config::{load_config, get_memory_path};
 use crate::lock::LockType;
+use crate::direct_organize::CATEGORY_FILE_SUFFIX;
 
 /// 整理记忆，按分类保存
 pub fn organize_memory() -> std::io::Result<()> {
@@ -9,31 +10,29 @@ pub fn organize_memory() -> std::io::Result<()> {
     // 从配置文件读取记忆路径
     let config = load_config(None);
     let memory_path = get_memory_path(&config);
+    check_memory_dir(std::path::Path::new(&memory_path))?;
+    ensure_writable_dir(std::path::Path::new(&memory_path))?;
     
     // 1. 首先加载当前的global-memory-recorder.json文件
     let single_file_path = format!("{}\\global-memory-recorder.json", memory_path);
     let single_file_store = MemoryStore::new(Some(&single_file_path), Some(LockType::Cli));
-    let records = single_file_store.load()?;
+    let records = single_file_store.load_active()?;
     
     println!("加载了 {} 条记忆记录", records.len());
     
-    // 2. 创建目录存储的store实例
-    let directory_store = MemoryStore::new(Some(&memory_path), Some(LockType::Cli));
+    // 2. 创建目录存储的store实例（记录按分类写入分类文件）
+    let directory_store = MemoryStore::new(Some(&memory_path), Some(LockType::Cli))
+        .with_directory_mode(&config);
     
     // 3. 按分类重新保存
     let mut category_counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
     
     for record in records {
-        // 跳过已删除的记录
-        if record.deleted_at.is_some() {
-            continue;
-        }
-        
         // 确定分类
         let category = crate::config::get_category_for_tags(&config, &record.tags);
         
         // 保存到对应分类
-        directory_store.add_memory(&record.text, Some(record.tags.clone()))?;
+        directory_store.add_memory_with_source(&record.text, Some(record.tags.clone()), None, record.source.as_deref())?;
         
         // 更新分类计数
         *category_counts.entry(category).or_insert(0) += 1;
@@ -47,13 +46,13 @@ pub fn organize_memory() -> std::io::Result<()> {
     }
     
     println!("\n记忆已按分类保存到以下文件：");
-    println!("{}\\[category]-global-gmem-recoder.json", memory_path);
+    println!("{}\\[category]{}", memory_path, CATEGORY_FILE_SUFFIX);
     println!("其中 [category] 为对应的分类名称");
     
     // 显示生成的分类文件列表
     println!("\n生成的分类文件：");
     for category in category_counts.keys() {
-        println!("- {}\\{}-global-gmem-recoder.json", memory_path, category);
+        println!("- {}\\{}{}", memory_path, category, CATEGORY_FILE_SUFFIX);
     }
     
     Ok(())
@@ -1,31 +1,91 @@
 use crate::store::MemoryStore;
 use crate::config::{load_config, get_memory_path};
 use crate::lock::LockType;
+use crate::record::MemoryRecord;
+
+/// 读取模式的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadFormat {
+    /// 每条记忆一个详细信息块（默认）
+    #[default]
+    Full,
+    /// 对齐的表格：ID、标签、文本首行
+    Table,
+    /// 原始 JSON 数组，便于管道处理
+    Json,
+    /// 复用 markdown 导出器，按分类输出
+    Markdown,
+}
+
+impl ReadFormat {
+    /// 从命令行参数解析输出格式
+    ///
+    /// # 参数
+    /// * `s` - 格式名（table、json、markdown/md、full）
+    ///
+    /// # 返回
+    /// 识别的格式，未知格式返回 None
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "full" => Some(ReadFormat::Full),
+            "table" => Some(ReadFormat::Table),
+            "json" => Some(ReadFormat::Json),
+            "markdown" | "md" => Some(ReadFormat::Markdown),
+            _ => None,
+        }
+    }
+}
 
 /// 读取并显示所有记忆
 pub fn read_memory() -> std::io::Result<()> {
-    println!("开始读取全局记忆...");
-    
+    read_memory_with_format(ReadFormat::Full)
+}
+
+/// 按指定格式读取并显示所有记忆
+///
+/// # 参数
+/// * `format` - 输出格式
+///
+/// # 返回
+/// 读取失败时返回错误
+pub fn read_memory_with_format(format: ReadFormat) -> std::io::Result<()> {
     // 从配置文件读取记忆路径
     let config = load_config(None);
     let memory_path = get_memory_path(&config);
-    
+
     // 创建记忆存储实例，使用配置文件中的路径
     let store = MemoryStore::new(Some(&memory_path), Some(LockType::Cli));
-    
-    // 加载所有记忆
-    let records = store.load()?;
-    
+
+    match format {
+        ReadFormat::Full => {}
+        ReadFormat::Table => {
+            print!("{}", format_table(&store.load_active()?));
+            return Ok(());
+        }
+        ReadFormat::Json => {
+            let records = store.load_active()?;
+            let json = serde_json::to_string_pretty(&records).map_err(std::io::Error::other)?;
+            println!("{}", json);
+            return Ok(());
+        }
+        ReadFormat::Markdown => {
+            for md in store.markdown_by_category(&config)?.values() {
+                print!("{}", md);
+            }
+            return Ok(());
+        }
+    }
+
+    println!("开始读取全局记忆...");
+
+    // 加载所有未删除的记忆
+    let records = store.load_active()?;
+
     println!("成功加载了 {} 条记忆记录", records.len());
     println!("========================================");
-    
+
     // 显示每条记忆
     for (index, record) in records.iter().enumerate() {
-        // 跳过已删除的记录
-        if record.deleted_at.is_some() {
-            continue;
-        }
-        
         println!("记忆 #{}:", index + 1);
         println!("ID: {}", record.id);
         println!("内容: {}", record.text);
@@ -35,7 +95,76 @@ pub fn read_memory() -> std::io::Result<()> {
         println!("更新时间: {}", record.updated_at);
         println!("========================================");
     }
-    
+
     println!("记忆读取完成！");
     Ok(())
 }
+
+/// 把记忆渲染为对齐的表格（ID、标签、文本首行）
+///
+/// # 参数
+/// * `records` - 记忆记录
+///
+/// # 返回
+/// 含表头的表格文本，每行以换行结尾
+pub fn format_table(records: &[MemoryRecord]) -> String {
+    let rows: Vec<(String, String, String)> = records
+        .iter()
+        .map(|r| {
+            let first_line = r.text.lines().next().unwrap_or("").trim().to_string();
+            (r.id.clone(), r.tags.join(","), first_line)
+        })
+        .collect();
+
+    let id_width = rows.iter().map(|(id, _, _)| id.chars().count()).max().unwrap_or(0).max(2);
+    let tags_width = rows.iter().map(|(_, tags, _)| tags.chars().count()).max().unwrap_or(0).max(4);
+
+    let mut out = format!("{:<id_width$}  {:<tags_width$}  TEXT\n", "ID", "TAGS");
+    for (id, tags, text) in rows {
+        out.push_str(&format!("{:<id_width$}  {:<tags_width$}  {}\n", id, tags, text));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, text: &str, tags: &[&str]) -> MemoryRecord {
+        MemoryRecord {
+            id: id.to_string(),
+            text: text.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            keywords: Vec::new(),
+            created_at: "2000-01-01T00:00:00.000Z".to_string(),
+            updated_at: "2000-01-01T00:00:00.000Z".to_string(),
+            deleted_at: None,
+            idempotency_key: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn table_aligns_columns_and_keeps_only_the_first_line() {
+        let table = format_table(&[
+            record("m_1", "first line\nsecond line", &["rust", "lock"]),
+            record("m_long_id", "短文本", &[]),
+        ]);
+        assert_eq!(table, concat!(
+            "ID         TAGS       TEXT\n",
+            "m_1        rust,lock  first line\n",
+            "m_long_id             短文本\n",
+        ));
+        assert_eq!(format_table(&[]), "ID  TAGS  TEXT\n");
+    }
+
+    #[test]
+    fn format_names_parse_case_insensitively() {
+        assert_eq!(ReadFormat::parse("TABLE"), Some(ReadFormat::Table));
+        assert_eq!(ReadFormat::parse("json"), Some(ReadFormat::Json));
+        assert_eq!(ReadFormat::parse("md"), Some(ReadFormat::Markdown));
+        assert_eq!(ReadFormat::parse("markdown"), Some(ReadFormat::Markdown));
+        assert_eq!(ReadFormat::parse("full"), Some(ReadFormat::Full));
+        assert_eq!(ReadFormat::parse("csv"), None);
+    }
+}
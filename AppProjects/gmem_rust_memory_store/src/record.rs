@@ -21,6 +21,52 @@ pub struct MemoryRecord {
     /// 如果软删除则为 ISO 时间戳，否则为 null
     #[serde(alias = "deletedAt")]
     pub deleted_at: Option<String>,
+    /// 客户端提供的幂等键（用于安全重试，可选）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// 记忆来源（如 `cli`、`mcp`、`md:<文件名>`、`import-json`），旧记录为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// 通过 CLI `add` 命令添加的记忆来源
+pub const SOURCE_CLI: &str = "cli";
+/// 通过 MCP `add_memory` 工具添加的记忆来源
+pub const SOURCE_MCP: &str = "mcp";
+/// 通过 JSON 导入添加的记忆来源
+pub const SOURCE_IMPORT_JSON: &str = "import-json";
+/// 来源为空的旧记录在统计中使用的名称
+pub const SOURCE_UNKNOWN: &str = "unknown";
+
+/// 构造基于文件的来源标识（`<kind>:<文件名>`，如 `md:notes.md`）
+///
+/// # 参数
+/// * `kind` - 来源类型（如 `md`、`txt`）
+/// * `path` - 文件路径（只保留文件名部分）
+///
+/// # 返回
+/// 来源标识
+pub fn file_source(kind: &str, path: &str) -> String {
+    let file_name = std::path::Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+    format!("{}:{}", kind, file_name)
+}
+
+/// 判断记录来源是否匹配过滤条件
+/// 过滤条件可以是完整来源（`md:notes.md`），也可以只是类型前缀（`md`）
+///
+/// # 参数
+/// * `source` - 记录的来源（可选）
+/// * `filter` - 过滤条件（不区分大小写）
+///
+/// # 返回
+/// 是否匹配
+pub fn source_matches(source: Option<&str>, filter: &str) -> bool {
+    let filter = filter.trim().to_lowercase();
+    let source = source.unwrap_or(SOURCE_UNKNOWN).to_lowercase();
+    source == filter || source.strip_prefix(&filter).is_some_and(|rest| rest.starts_with(':'))
 }
 
 /// 记忆存储统计信息
@@ -34,6 +80,128 @@ pub struct StoreStats {
     pub deleted: usize,
     /// 标签频率映射
     pub tags: HashMap<String, usize>,
+    /// 来源频率映射（来源为空的旧记录计入 `unknown`）
+    #[serde(default)]
+    pub sources: HashMap<String, usize>,
+}
+
+/// 一次统计快照（`stats-history.csv` 中的一行）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    /// 快照时间（ISO 8601）
+    pub timestamp: String,
+    /// 总记录数（包括已删除的）
+    pub total: usize,
+    /// 活跃记录数
+    pub active: usize,
+    /// 软删除记录数
+    pub deleted: usize,
+    /// 不同标签的数量
+    pub distinct_tags: usize,
+}
+
+impl StatsSnapshot {
+    /// CSV 表头
+    pub const CSV_HEADER: &'static str = "timestamp,total,active,deleted,distinct_tags";
+
+    /// 转换为一行 CSV（不含换行）
+    pub fn to_csv_line(&self) -> String {
+        format!("{},{},{},{},{}", self.timestamp, self.total, self.active, self.deleted, self.distinct_tags)
+    }
+
+    /// 解析一行 CSV
+    ///
+    /// # 参数
+    /// * `line` - CSV 行
+    ///
+    /// # 返回
+    /// 解析出的快照，表头或格式不正确时返回 None
+    pub fn parse_csv_line(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if fields.len() != 5 {
+            return None;
+        }
+        Some(StatsSnapshot {
+            timestamp: fields[0].to_string(),
+            total: fields[1].parse().ok()?,
+            active: fields[2].parse().ok()?,
+            deleted: fields[3].parse().ok()?,
+            distinct_tags: fields[4].parse().ok()?,
+        })
+    }
+}
+
+/// JSON 导入结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// 成功（或将会成功）导入的记录数
+    pub success: usize,
+    /// 因 ID 已存在而跳过的记录数
+    pub skipped: usize,
+    /// 失败的记录数
+    pub failed: usize,
+    /// 被跳过的记录 ID
+    pub skipped_ids: Vec<String>,
+    /// 格式错误的记录（`#序号: 错误信息`，序号从 1 开始），仅非严格模式下收集
+    #[serde(default)]
+    pub malformed: Vec<String>,
+    /// 是否为模拟导入（未写入磁盘）
+    pub simulated: bool,
+}
+
+/// `category_mapping` 中没有映射的标签（`check-categories` 的检查结果）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnmappedTag {
+    /// 标签
+    pub tag: String,
+    /// 带该标签的活跃记忆数
+    pub count: usize,
+    /// 其中因为没有任何已映射标签而落入 `default` 分类的记忆数
+    pub default_count: usize,
+    /// 与该标签拼写相近的映射键（可能是映射中的拼写错误）
+    pub similar_key: Option<String>,
+}
+
+/// 搜索各阶段耗时（毫秒），用于 `--profile` 诊断；同时记录分页前的命中总数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchProfile {
+    /// 加载记忆文件
+    pub load_ms: f64,
+    /// 过滤与评分
+    pub score_ms: f64,
+    /// 排序与截取
+    pub sort_ms: f64,
+    /// 按 offset、limit 截取前的命中总数（auto_cutoff 之后）
+    #[serde(default)]
+    pub total_hits: usize,
+}
+
+/// 压缩各阶段耗时（毫秒），用于 `--profile` 诊断
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompressProfile {
+    /// 加载记忆文件
+    pub load_ms: f64,
+    /// 搜索与评分
+    pub score_ms: f64,
+    /// 生成 markdown 并按预算截断
+    pub truncate_ms: f64,
+}
+
+/// 记忆存储的运行计数（自存储实例创建起累计）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoreMetrics {
+    /// 添加的记忆数
+    pub adds: u64,
+    /// 搜索次数
+    pub searches: u64,
+    /// 删除的记忆数（软删除与永久删除）
+    pub deletes: u64,
+    /// 需要等待其他进程释放的加锁次数
+    pub lock_waits: u64,
+    /// 加锁超时次数
+    pub lock_timeouts: u64,
+    /// 累计等待锁的毫秒数
+    pub lock_wait_ms: u64,
 }
 
 /// 带相关性分数的搜索结果
@@ -45,6 +213,12 @@ pub struct SearchHit {
     pub keywords: Vec<String>,
     pub created_at: String,
     pub updated_at: String,
+    /// 记忆来源（可选）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// 软删除时间（只有搜索包含已删除记录时才可能有值）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
     /// 相关性分数（越高越相关）
     pub score: f64,
 }
@@ -56,8 +230,58 @@ pub struct CompressResult {
     pub markdown: String,
     /// 包含的搜索命中
     pub included: Vec<SearchHit>,
-    /// 请求的字符预算
+    /// 请求的预算（单位见 `CompressOptions::budget_unit`，默认字符数）
     pub budget: usize,
-    /// 实际使用的字符数
+    /// 实际使用的长度（与预算同一单位）
     pub used: usize,
 }
+
+/// 相关性评分的权重
+/// 时效性加分在 `recency_halflife_days` 天时减半，并线性衰减到两倍该天数时为 0
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScoringWeights {
+    /// 文本中查询词每次命中的得分
+    pub text_hit: f64,
+    /// 每个匹配标签的得分
+    pub tag_hit: f64,
+    /// 每个匹配的提取关键词的得分
+    pub keyword_hit: f64,
+    /// 时效性加分的最大值（刚更新的记忆）
+    pub recency_max: f64,
+    /// 时效性加分减半所需的天数
+    pub recency_halflife_days: f64,
+}
+
+impl Default for ScoringWeights {
+    fn default() -> Self {
+        Self {
+            text_hit: 5.0,
+            tag_hit: 8.0,
+            keyword_hit: 6.0,
+            recency_max: 5.0,
+            recency_halflife_days: 75.0,
+        }
+    }
+}
+
+/// 相关性分数的组成明细
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    /// 文本中查询词的命中次数
+    pub text_hits: usize,
+    /// 文本命中得分
+    pub text_score: f64,
+    /// 标签匹配得分
+    pub tag_score: f64,
+    /// 提取关键词匹配得分
+    pub keyword_score: f64,
+    /// 时效性得分
+    pub recency: f64,
+}
+
+impl ScoreBreakdown {
+    /// 各项得分之和，与 `score_record` 返回值一致
+    pub fn total(&self) -> f64 {
+        self.text_score + self.tag_score + self.keyword_score + self.recency
+    }
+}
@@ -1,27 +1,442 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use serde_json;
-use crate::record::{MemoryRecord, StoreStats, SearchHit};
-use crate::timestamp::{now_iso, make_id};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use crate::record::{MemoryRecord, StoreStats, StatsSnapshot, SearchHit, ScoreBreakdown, ScoringWeights, StoreMetrics, ImportReport, SearchProfile, UnmappedTag, source_matches, SOURCE_IMPORT_JSON, SOURCE_UNKNOWN};
+use crate::audit::{append_audit_entries, AuditOp};
+use crate::backup::{backup_file, prune_backups, prune_purge_journals, purge_journal_path, DEFAULT_BACKUP_KEEP, DEFAULT_PURGE_JOURNAL_KEEP};
+use crate::timestamp::{now_iso, canonicalize_iso, make_id_with_prefix, parse_time_bound, DEFAULT_ID_PREFIX};
 use crate::keywords::extract_keywords;
-use crate::lock::{acquire_lock_with_cleanup, LockType};
+use crate::lock::{acquire_lock_with_cleanup, acquire_store_lock, category_lock_path, cleanup_expired_locks, LockGuard, LockType, STORE_LOCK_FILE};
+use crate::direct_organize::{adopt_legacy_category_file, category_file_path, commit_category_files, list_category_files, lock_category_dir, read_category_file};
+use crate::config::{Config, get_bm25_params, get_category_for_tags, get_default_tags, get_scoring_weights, get_search_default_limit, get_write_buffer, similar_mapping_key, DEFAULT_SEARCH_LIMIT};
 
 const DEFAULT_MEMORY_PATH: &str = ".copilot-memory.json";
 
+/// 标签过滤的匹配方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagMatch {
+    /// 记录包含任意一个过滤标签即可
+    #[default]
+    Any,
+    /// 记录必须包含所有过滤标签
+    All,
+}
+
+impl TagMatch {
+    /// 判断记录标签是否满足过滤条件（过滤标签为空时总是满足）
+    ///
+    /// # 参数
+    /// * `record_tags` - 记录的标签
+    /// * `filter_tags` - 过滤标签
+    ///
+    /// # 返回
+    /// 是否满足
+    pub fn matches(&self, record_tags: &[String], filter_tags: &[String]) -> bool {
+        if filter_tags.is_empty() {
+            return true;
+        }
+        match self {
+            TagMatch::Any => filter_tags.iter().any(|t| record_tags.contains(t)),
+            TagMatch::All => filter_tags.iter().all(|t| record_tags.contains(t)),
+        }
+    }
+}
+
+/// 文本超过 `max_text_len` 时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextLimitMode {
+    /// 截断到上限并追加省略号，记录带上 `truncated` 标签
+    #[default]
+    Truncate,
+    /// 拒绝添加并返回错误
+    Reject,
+}
+
+impl TextLimitMode {
+    /// 从配置字符串解析（`reject` 为拒绝，其他值为截断）
+    ///
+    /// # 参数
+    /// * `mode` - 配置值
+    ///
+    /// # 返回
+    /// 处理方式
+    pub fn from_config(mode: Option<&str>) -> Self {
+        match mode.map(|m| m.trim().to_lowercase()).as_deref() {
+            Some("reject") => TextLimitMode::Reject,
+            _ => TextLimitMode::Truncate,
+        }
+    }
+}
+
+/// 搜索的文本相关性算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankingMode {
+    /// 每次命中固定加分（`text_hit`）
+    #[default]
+    Linear,
+    /// BM25：按词在语料中的稀有程度加权，并按文本长度归一化
+    Bm25,
+}
+
+impl RankingMode {
+    /// 按名称解析（`linear` 或 `bm25`，不区分大小写）
+    ///
+    /// # 参数
+    /// * `name` - 算法名称
+    ///
+    /// # 返回
+    /// 排序算法，无法识别时返回 None
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "linear" => Some(RankingMode::Linear),
+            "bm25" => Some(RankingMode::Bm25),
+            _ => None,
+        }
+    }
+
+    /// 从配置字符串解析（无法识别或未设置时为 linear）
+    ///
+    /// # 参数
+    /// * `mode` - 配置值
+    ///
+    /// # 返回
+    /// 排序算法
+    pub fn from_config(mode: Option<&str>) -> Self {
+        mode.and_then(Self::from_name).unwrap_or_default()
+    }
+}
+
+/// BM25 参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bm25Params {
+    /// 词频饱和度（越大重复命中的加分越多）
+    pub k1: f64,
+    /// 长度归一化强度（0 表示不按长度归一化，1 表示完全归一化）
+    pub b: f64,
+}
+
+impl Default for Bm25Params {
+    fn default() -> Self {
+        Self { k1: 1.2, b: 0.75 }
+    }
+}
+
+/// 一次搜索的 BM25 语料统计（查询词的文档频率和平均文本长度）
+/// 每次搜索按候选记录重新计算，不做缓存
+#[derive(Debug, Clone, Default)]
+pub struct Bm25Corpus {
+    /// 语料中的记录数
+    doc_count: usize,
+    /// 平均文本长度（字符）
+    avg_len: f64,
+    /// 查询词 -> 包含它的记录数
+    doc_freq: HashMap<String, usize>,
+}
+
+impl Bm25Corpus {
+    /// 统计查询词在记录中的文档频率
+    ///
+    /// # 参数
+    /// * `records` - 语料记录
+    /// * `query` - 搜索查询
+    ///
+    /// # 返回
+    /// 语料统计
+    pub fn build(records: &[MemoryRecord], query: &str) -> Self {
+        let terms = query_terms(query);
+        let mut doc_freq: HashMap<String, usize> = terms.iter().map(|t| (t.clone(), 0)).collect();
+        let mut total_len = 0usize;
+        for r in records {
+            let text = r.text.to_lowercase();
+            total_len += text.chars().count();
+            for term in &terms {
+                if text.contains(term.as_str()) {
+                    *doc_freq.entry(term.clone()).or_default() += 1;
+                }
+            }
+        }
+        let avg_len = if records.is_empty() { 0.0 } else { total_len as f64 / records.len() as f64 };
+        Self { doc_count: records.len(), avg_len, doc_freq }
+    }
+
+    /// 计算记录文本相对于查询的 BM25 得分
+    /// 词频按子串出现次数统计（与 linear 的命中计数一致，中文不需要分词），文本长度按字符计
+    ///
+    /// # 参数
+    /// * `r` - 记忆记录
+    /// * `query` - 搜索查询
+    /// * `params` - BM25 参数
+    ///
+    /// # 返回
+    /// BM25 得分（没有命中时为 0）
+    pub fn score(&self, r: &MemoryRecord, query: &str, params: &Bm25Params) -> f64 {
+        let text = r.text.to_lowercase();
+        let len = text.chars().count() as f64;
+        let norm = if self.avg_len > 0.0 { len / self.avg_len } else { 1.0 };
+        let n = self.doc_count as f64;
+        query_terms(query).iter()
+            .map(|term| {
+                let tf = text.matches(term.as_str()).count() as f64;
+                if tf == 0.0 {
+                    return 0.0;
+                }
+                let df = self.doc_freq.get(term).copied().unwrap_or(0) as f64;
+                let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                idf * tf * (params.k1 + 1.0) / (tf + params.k1 * (1.0 - params.b + params.b * norm))
+            })
+            .sum()
+    }
+}
+
+/// 把查询拆成去重的小写词
+///
+/// # 参数
+/// * `query` - 搜索查询
+///
+/// # 返回
+/// 查询词列表
+fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for term in query.to_lowercase().split_whitespace() {
+        if !terms.iter().any(|t| t == term) {
+            terms.push(term.to_string());
+        }
+    }
+    terms
+}
+
+/// 被截断的记忆自动附加的标签
+pub const TRUNCATED_TAG: &str = "truncated";
+
+/// 临时记忆的标签（`--no-temp` 或 `exclude_temp_by_default` 时从搜索中排除）
+pub const TEMP_TAG: &str = "temp";
+
+/// `--auto-cutoff` 默认的截断比例（最高分的 30%）
+pub const DEFAULT_AUTO_CUTOFF_RATIO: f64 = 0.3;
+
+/// 统计历史文件名（位于记忆文件所在目录）
+pub const STATS_HISTORY_FILE: &str = "stats-history.csv";
+
+/// 搜索选项
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// 返回的最大结果数（默认为 `search_default_limit`）
+    pub limit: Option<usize>,
+    /// 命名空间，仅匹配带有 `proj:<namespace>` 标签的记忆
+    pub namespace: Option<String>,
+    /// 过滤标签（为空表示不过滤）
+    /// 查询为空且指定了过滤标签时，按创建时间从新到旧列出所有匹配的记忆
+    pub tags: Vec<String>,
+    /// 过滤标签的匹配方式
+    pub tag_match: TagMatch,
+    /// 过滤时是否考虑标签蕴含（过滤 `rust` 也匹配只带 `rust-async` 的记录）
+    pub expand_implications: bool,
+    /// 来源过滤（如 `md` 匹配所有 `md:<文件名>`，None 表示不过滤）
+    pub source: Option<String>,
+    /// 是否排除带 `temp` 标签的临时记忆（None 表示使用存储配置的 `exclude_temp_by_default`）
+    pub exclude_temp: Option<bool>,
+    /// 限定范围的标签：只在带该标签的记忆中搜索，并且不计标签加分（候选记录都带该标签）
+    pub within: Option<String>,
+    /// 相关度断崖截断比例：分数低于最高分乘以该比例的结果被丢弃（None 表示不截断）
+    /// 设置后如果没有指定 `limit`，不再限制默认的结果数
+    pub auto_cutoff: Option<f64>,
+    /// 是否同时搜索已软删除的记录（命中带 `deleted_at`，用于排查删除了什么）
+    pub include_deleted: bool,
+    /// 文本相关性算法（None 表示使用存储配置的 `ranking`）
+    pub ranking: Option<RankingMode>,
+    /// 分页：跳过排序后的前若干条命中再取 `limit` 条（None 表示从第一条开始）
+    pub offset: Option<usize>,
+    /// 只匹配在该时间及之后创建的记忆（RFC 3339 或 `YYYY-MM-DD`，无法解析时搜索报错）
+    pub after: Option<String>,
+    /// 只匹配在该时间之前创建的记忆（RFC 3339 或 `YYYY-MM-DD`，日期包含当天）
+    pub before: Option<String>,
+}
+
+/// 列出记忆时的排序字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListSort {
+    /// 按创建时间
+    Created,
+    /// 按最后修改时间
+    Updated,
+}
+
+impl ListSort {
+    /// 按名称解析（`created` 或 `updated`，不区分大小写）
+    ///
+    /// # 参数
+    /// * `name` - 排序字段名称
+    ///
+    /// # 返回
+    /// 排序字段，无法识别时返回 None
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "created" => Some(ListSort::Created),
+            "updated" => Some(ListSort::Updated),
+            _ => None,
+        }
+    }
+}
+
+/// 列出记忆的选项
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    /// 返回的最大数量（None 表示不限制）
+    pub limit: Option<usize>,
+    /// 跳过的记录数
+    pub offset: usize,
+    /// 是否包含已软删除的记录
+    pub include_deleted: bool,
+    /// 只列出带该标签的记录（按 `normalize_tags` 规则去空白、转小写后比较）
+    pub tag: Option<String>,
+    /// 排序字段（None 表示按存储顺序）
+    pub sort_by: Option<ListSort>,
+    /// 是否按排序字段从新到旧排列
+    pub descending: bool,
+}
+
 /// 记忆存储结构
 pub struct MemoryStore {
     memory_path: PathBuf,
     lock_path: PathBuf,
     lock_type: LockType,
+    /// 缓冲写入状态（None 表示每次添加立即写入）
+    write_buffer: Option<WriteBuffer>,
+    /// 运行计数
+    metrics: Metrics,
+    /// 每次添加时合并的默认标签
+    default_tags: Vec<String>,
+    /// 新记忆的 ID 前缀
+    id_prefix: String,
+    /// 写入时是否按 created_at、id 排序
+    sort_on_write: bool,
+    /// 记忆文本的最大长度（字符，None 表示不限制）
+    max_text_len: Option<usize>,
+    /// 文本超长时的处理方式
+    text_limit_mode: TextLimitMode,
+    /// 标签蕴含关系（标签 -> 它隐含的标签）
+    tag_implications: HashMap<String, Vec<String>>,
+    /// 搜索时是否默认排除带 `temp` 标签的记忆
+    exclude_temp_by_default: bool,
+    /// 未指定 limit 时搜索返回的结果数
+    default_limit: usize,
+    /// 相关性评分权重
+    scoring_weights: ScoringWeights,
+    /// 默认的文本相关性算法
+    ranking: RankingMode,
+    /// BM25 参数
+    bm25_params: Bm25Params,
+    /// 审计日志路径（None 表示不记录）
+    audit_log: Option<PathBuf>,
+    /// 目录模式下提供分类映射的配置（None 表示单文件存储）
+    directory_config: Option<Config>,
+    /// 保留的永久删除日志数量
+    purge_journal_keep: usize,
+    /// 写入前备份保留的数量（None 表示写入前不备份）
+    backup_keep: Option<usize>,
+}
+
+/// `MemoryStore::lock` 持有的锁：存储锁，目录模式下还有所有分类文件的锁
+struct StoreLock {
+    _store: LockGuard,
+    _categories: Vec<LockGuard>,
+}
+
+/// 运行计数（原子操作，开销可忽略）
+#[derive(Default)]
+struct Metrics {
+    adds: AtomicU64,
+    searches: AtomicU64,
+    deletes: AtomicU64,
+    lock_waits: AtomicU64,
+    lock_timeouts: AtomicU64,
+    lock_wait_ms: AtomicU64,
+}
+
+/// 缓冲写入状态
+///
+/// 添加的记忆先保存在内存中，达到待写入数量上限、刷新间隔到期（由后台线程检查）、
+/// 调用 `flush()` 或存储被销毁时，一次性加锁并原子写入。
+/// 注意：进程在刷新前崩溃（或调用 `std::process::exit`）会丢失缓冲区中的记忆。
+struct WriteBuffer {
+    /// 待写入数量上限，达到后立即刷新
+    max_pending: usize,
+    /// 刷新间隔（可选），缓冲区非空且距上次刷新超过该间隔时由后台线程刷新
+    flush_interval: Option<Duration>,
+    /// 待写入的记录（与后台刷新线程共享）
+    pending: Arc<Mutex<Vec<MemoryRecord>>>,
+    /// 上次刷新时间（与后台刷新线程共享）
+    last_flush: Arc<Mutex<Instant>>,
+    /// 后台刷新线程（第一次缓冲添加时启动）
+    flusher: Mutex<Option<BackgroundFlusher>>,
+}
+
+/// 后台刷新线程及其停止信号
+struct BackgroundFlusher {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    handle: JoinHandle<()>,
+}
+
+impl BackgroundFlusher {
+    /// 启动后台刷新线程：每隔 `interval` 检查一次，缓冲区非空且距上次刷新超过间隔时写入
+    ///
+    /// # 参数
+    /// * `writer` - 不带缓冲的存储副本（与原存储写入同一位置）
+    /// * `interval` - 刷新间隔
+    /// * `pending` - 待写入的记录
+    /// * `last_flush` - 上次刷新时间
+    fn spawn(writer: MemoryStore, interval: Duration, pending: Arc<Mutex<Vec<MemoryRecord>>>, last_flush: Arc<Mutex<Instant>>) -> Self {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let signal = Arc::clone(&stop);
+        let handle = std::thread::spawn(move || {
+            let (stopped, wake) = &*signal;
+            loop {
+                let guard = stopped.lock().unwrap();
+                let (guard, _) = wake.wait_timeout(guard, interval).unwrap();
+                if *guard {
+                    break;
+                }
+                drop(guard);
+
+                let mut pending = pending.lock().unwrap();
+                if pending.is_empty() || last_flush.lock().unwrap().elapsed() < interval {
+                    continue;
+                }
+                match writer.append_records(&pending) {
+                    Ok(()) => {
+                        pending.clear();
+                        *last_flush.lock().unwrap() = Instant::now();
+                    }
+                    Err(e) => eprintln!("Failed to flush buffered memories: {}", e),
+                }
+            }
+        });
+        Self { stop, handle }
+    }
+
+    /// 通知后台线程退出并等待其结束
+    fn shutdown(self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap() = true;
+        wake.notify_all();
+        let _ = self.handle.join();
+    }
 }
 
 impl MemoryStore {
     /// 创建新的记忆存储实例
     ///
     /// # 参数
-    /// * `memory_path` - 记忆文件路径（可选，默认为 .copilot-memory.json）
+    /// * `memory_path` - 记忆文件路径（可选，默认向上查找已有的 .copilot-memory.json，找不到时使用当前目录）
     /// * `lock_type` - 锁文件类型（可选，默认为 Cli）
     ///
     /// # 返回
@@ -29,261 +444,1897 @@ impl MemoryStore {
     pub fn new(memory_path: Option<&str>, lock_type: Option<LockType>) -> Self {
         let mp = resolve_memory_path(memory_path);
         let lt = lock_type.unwrap_or(LockType::Cli);
-        let lock = resolve_lock_path(&mp, lt);
+        let lock = resolve_lock_path(&mp);
         Self {
             memory_path: mp,
             lock_path: lock,
             lock_type: lt,
+            write_buffer: None,
+            metrics: Metrics::default(),
+            default_tags: Vec::new(),
+            id_prefix: DEFAULT_ID_PREFIX.to_string(),
+            sort_on_write: false,
+            max_text_len: None,
+            text_limit_mode: TextLimitMode::default(),
+            tag_implications: HashMap::new(),
+            exclude_temp_by_default: false,
+            default_limit: DEFAULT_SEARCH_LIMIT,
+            scoring_weights: ScoringWeights::default(),
+            ranking: RankingMode::default(),
+            bm25_params: Bm25Params::default(),
+            audit_log: None,
+            directory_config: None,
+            purge_journal_keep: DEFAULT_PURGE_JOURNAL_KEEP,
+            backup_keep: None,
         }
     }
 
-    /// 从磁盘加载记忆存储
+    /// 确保记忆文件所在目录存在且可写
+    /// 在创建存储后调用，尽早给出明确的错误，而不是在第一次写入时才失败
     ///
     /// # 返回
-    /// 包含所有记录的向量
-    pub fn load(&self) -> io::Result<Vec<MemoryRecord>> {
-        if !self.memory_path.exists() {
-            return Ok(Vec::new());
+    /// 目录可用时返回 Ok；无法创建或不可写时返回包含路径的错误
+    pub fn ensure_storage(&self) -> io::Result<()> {
+        if self.directory_config.is_some() {
+            check_memory_dir(&self.memory_path)?;
+            return ensure_writable_dir(&self.memory_path);
         }
-
-        let raw = fs::read_to_string(&self.memory_path)?;
-        if raw.trim().is_empty() {
-            return Ok(Vec::new());
+        match self.memory_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => ensure_writable_dir(dir),
+            _ => Ok(()),
         }
-
-        let data: Vec<MemoryRecord> = serde_json::from_str(&raw)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-
-        Ok(data)
     }
 
-    /// 添加新记忆到存储
-    /// 自动从文本中提取关键词以改进搜索
+    /// 启用目录模式：记忆路径视为目录，记录按分类分散保存到目录中的
+    /// `{分类}-global-gmem-recorder.json`（与 organize、split 生成的文件相同）
+    /// 加载、搜索、添加、删除等接口与单文件存储一致，调用方无需区分两种布局；
+    /// 每次写入时按当前标签重新分配分类，旧拼写（recoder）的分类文件读取后会被替换
     ///
     /// # 参数
-    /// * `text` - 记忆内容（必需）
-    /// * `tags` - 用于分类的可选标签
+    /// * `config` - 配置（提供分类映射）
     ///
     /// # 返回
-    /// 创建的记忆记录
-    ///
-    /// # 错误
-    /// 如果文本为空则返回错误
-    pub fn add_memory(&self, text: &str, tags: Option<Vec<String>>) -> io::Result<MemoryRecord> {
-        let _lock = acquire_lock_with_cleanup(&self.lock_path, None, Some(300))?;
-        let records = self.load()?;
-
-        let t = text.trim();
-        if t.is_empty() {
-            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot add an empty memory."));
-        }
-
-        let keywords = extract_keywords(t);
-        let rec = MemoryRecord {
-            id: make_id(),
-            text: t.to_string(),
-            tags: normalize_tags(tags),
-            keywords,
-            created_at: now_iso(),
-            updated_at: now_iso(),
-            deleted_at: None,
-        };
+    /// 目录模式的记忆存储实例
+    pub fn with_directory_mode(mut self, config: &Config) -> Self {
+        self.lock_path = self.memory_path.join(STORE_LOCK_FILE);
+        self.directory_config = Some(config.clone());
+        self
+    }
 
-        let mut new_records = records;
-        new_records.push(rec.clone());
-        atomic_write(&self.memory_path, &new_records)?;
+    /// 是否为目录模式（记录分散在分类文件中）
+    pub fn is_directory_mode(&self) -> bool {
+        self.directory_config.is_some()
+    }
 
-        Ok(rec)
+    /// 存放记忆数据的目录：目录模式下是记忆路径本身，否则是记忆文件所在目录
+    fn store_dir(&self) -> PathBuf {
+        if self.directory_config.is_some() {
+            return self.memory_path.clone();
+        }
+        match self.memory_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
     }
 
-    /// 搜索记忆并按相关性排序
+    /// 清理锁文件目录中崩溃残留的过期锁
+    /// 在启动时、第一次操作之前调用，避免第一次写入白白等到超时
     ///
     /// # 参数
-    /// * `query` - 搜索查询（空格分隔的关键词）
-    /// * `limit` - 返回的最大结果数（默认 10）
+    /// * `max_age_seconds` - 锁文件最大年龄（秒），None 时使用默认的 300 秒
     ///
     /// # 返回
-    /// 按分数降序排列的搜索命中数组
-    pub fn search(&self, query: &str, limit: Option<usize>) -> io::Result<Vec<SearchHit>> {
-        let records = self.load()?;
-        let limit = limit.unwrap_or(10);
-
-        let mut hits: Vec<SearchHit> = Vec::new();
-        for r in &records {
-            if r.deleted_at.is_some() {
-                continue;
-            }
-            let score = score_record(r, query);
-            if score <= 0.0 {
-                continue;
-            }
-            hits.push(SearchHit {
-                id: r.id.clone(),
-                text: r.text.clone(),
-                tags: r.tags.clone(),
-                keywords: r.keywords.clone(),
-                created_at: r.created_at.clone(),
-                updated_at: r.updated_at.clone(),
-                score,
-            });
+    /// 清理的锁文件数量
+    pub fn recover_locks(&self, max_age_seconds: Option<u64>) -> usize {
+        match self.lock_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => cleanup_expired_locks(dir, max_age_seconds),
+            _ => cleanup_expired_locks(Path::new("."), max_age_seconds),
         }
-
-        hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
-        Ok(hits.into_iter().take(std::cmp::max(1, limit)).collect())
     }
 
-    /// 计算记忆存储的统计信息
+    /// 应用配置文件中与存储行为相关的设置（默认标签、ID 前缀、写入排序、文本长度上限、标签蕴含）
+    ///
+    /// # 参数
+    /// * `config` - 配置结构体
     ///
     /// # 返回
-    /// 包括计数和标签频率的统计信息
-    pub fn compute_stats(&self) -> io::Result<StoreStats> {
-        let records = self.load()?;
-
-        let mut tags: HashMap<String, usize> = HashMap::new();
-        let mut deleted = 0;
-
-        for r in &records {
-            if r.deleted_at.is_some() {
-                deleted += 1;
-            }
-            for t in &r.tags {
-                *tags.entry(t.clone()).or_insert(0) += 1;
-            }
-        }
-
-        Ok(StoreStats {
-            total: records.len(),
-            active: records.len() - deleted,
-            deleted,
-            tags,
-        })
+    /// 应用配置后的记忆存储实例
+    pub fn apply_config(self, config: &Config) -> Self {
+        let id_prefix = config.id_prefix.clone().unwrap_or_else(|| DEFAULT_ID_PREFIX.to_string());
+        // 目录模式会改变锁文件和审计日志的基准目录，需要最先应用
+        let store = if config.directory_store.unwrap_or(false) {
+            self.with_directory_mode(config)
+        } else {
+            self
+        };
+        let store = match get_write_buffer(config) {
+            Some((max_pending, flush_interval)) => store.with_write_buffer(max_pending, flush_interval),
+            None => store,
+        };
+        store.with_default_tags(get_default_tags(config))
+            .with_id_prefix(&id_prefix)
+            .with_sort_on_write(config.sort_on_write.unwrap_or(false))
+            .with_max_text_len(
+                config.max_text_len.filter(|len| *len > 0),
+                TextLimitMode::from_config(config.max_text_mode.as_deref()),
+            )
+            .with_tag_implications(config.tag_implications.clone().unwrap_or_default())
+            .with_exclude_temp_by_default(config.exclude_temp_by_default.unwrap_or(false))
+            .with_default_limit(get_search_default_limit(config))
+            .with_scoring_weights(get_scoring_weights(config))
+            .with_ranking(RankingMode::from_config(config.ranking.as_deref()), get_bm25_params(config))
+            .with_backup_before_write(config.backup_before_write.unwrap_or(false)
+                .then(|| config.backup_keep.unwrap_or(DEFAULT_BACKUP_KEEP)))
+            .with_purge_journal_keep(config.purge_journal_keep.unwrap_or(DEFAULT_PURGE_JOURNAL_KEEP))
+            .with_audit_log(config.audit_log.as_deref().map(str::trim).filter(|p| !p.is_empty()).map(PathBuf::from))
     }
 
-    /// 软删除记忆（标记为已删除）
+    /// 设置审计日志路径，之后每次添加、修改、删除、永久删除都会追加 JSONL 记录
+    /// 相对路径相对于记忆文件所在目录
     ///
     /// # 参数
-    /// * `id` - 记忆 ID
+    /// * `path` - 审计日志路径（None 表示不记录）
     ///
     /// # 返回
-    /// 是否找到并删除了记忆
-    pub fn soft_delete(&self, id: &str) -> io::Result<bool> {
-        let _lock = acquire_lock_with_cleanup(&self.lock_path, None, Some(300))?;
-        let mut records = self.load()?;
-        
-        let found = records.iter_mut().any(|r| {
-            if r.id == id && r.deleted_at.is_none() {
-                r.deleted_at = Some(now_iso());
-                r.updated_at = now_iso();
-                true
+    /// 记忆存储实例
+    pub fn with_audit_log(mut self, path: Option<PathBuf>) -> Self {
+        self.audit_log = path.map(|p| {
+            if p.is_absolute() {
+                p
             } else {
-                false
+                self.store_dir().join(p)
             }
         });
-        
-        if found {
-            atomic_write(&self.memory_path, &records)?;
-        }
-        
-        Ok(found)
+        self
     }
 
-    /// 硬删除记忆（永久删除）
+    /// 向审计日志追加变更记录（未配置审计日志时不做任何操作）
+    /// 变更已经写入存储，审计日志写入失败只打印警告，不让操作失败
     ///
     /// # 参数
-    /// * `id` - 记忆 ID（可选）
-    /// * `tag` - 标签匹配（可选）
-    /// * `match_text` - 文本匹配（可选）
-    ///
-    /// # 返回
-    /// 删除的记忆数量
-    pub fn purge(&self, id: Option<&str>, tag: Option<&str>, match_text: Option<&str>) -> io::Result<usize> {
-        let _lock = acquire_lock_with_cleanup(&self.lock_path, None, Some(300))?;
-        let mut records = self.load()?;
-        
-        let initial_len = records.len();
-        records.retain(|r| {
-            if let Some(id_val) = id {
-                if r.id == id_val {
-                    return false;
-                }
-            }
-            if let Some(tag_val) = tag {
-                if r.tags.iter().any(|t| t == tag_val) {
-                    return false;
-                }
-            }
-            if let Some(text_val) = match_text {
-                if r.text.contains(text_val) {
-                    return false;
-                }
+    /// * `op` - 变更类型
+    /// * `records` - 被变更的记录
+    fn audit<'a>(&self, op: AuditOp, records: impl IntoIterator<Item = &'a MemoryRecord>) {
+        if let Some(path) = &self.audit_log {
+            if let Err(e) = append_audit_entries(path, op, records) {
+                eprintln!("警告: 写入审计日志失败 ({}): {}", path.display(), e);
             }
-            true
-        });
-        
-        let purged = initial_len - records.len();
-        if purged > 0 {
-            atomic_write(&self.memory_path, &records)?;
         }
-        
-        Ok(purged)
     }
 
-    /// 导出所有记忆为 JSON 字符串
+    /// 设置搜索时是否默认排除带 `temp` 标签的临时记忆
+    ///
+    /// # 参数
+    /// * `enabled` - 是否默认排除
     ///
     /// # 返回
-    /// JSON 格式的记忆数据
-    pub fn export_json(&self) -> io::Result<String> {
-        let records = self.load()?;
-        serde_json::to_string_pretty(&records)
-            .map_err(io::Error::other)
+    /// 记忆存储实例
+    pub fn with_exclude_temp_by_default(mut self, enabled: bool) -> Self {
+        self.exclude_temp_by_default = enabled;
+        self
     }
 
-    /// 从 JSON 导入记忆
+    /// 搜索时是否默认排除带 `temp` 标签的临时记忆
+    pub fn exclude_temp_by_default(&self) -> bool {
+        self.exclude_temp_by_default
+    }
+
+    /// 设置未指定 limit 时搜索返回的结果数
     ///
     /// # 参数
-    /// * `json_data` - JSON 格式的记忆数据
+    /// * `limit` - 默认结果数（最小 1）
     ///
     /// # 返回
-    /// (成功数量, 跳过数量, 失败数量)
-    pub fn import_json(&self, json_data: &str) -> io::Result<(usize, usize, usize)> {
-        let _lock = acquire_lock_with_cleanup(&self.lock_path, None, Some(300))?;
-        let mut records = self.load()?;
-
-        let imported: Vec<MemoryRecord> = serde_json::from_str(json_data)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-
-        let existing_ids: std::collections::HashSet<String> = 
-            records.iter().map(|r| r.id.clone()).collect();
-
-        let mut success = 0;
-        let mut skipped = 0;
-
-        for mut rec in imported {
-            if existing_ids.contains(&rec.id) {
-                skipped += 1;
-                continue;
-            }
-
-            rec.created_at = now_iso();
-            rec.updated_at = now_iso();
-            records.push(rec);
-            success += 1;
-        }
-
-        atomic_write(&self.memory_path, &records)?;
+    /// 记忆存储实例
+    pub fn with_default_limit(mut self, limit: usize) -> Self {
+        self.default_limit = limit.max(1);
+        self
+    }
 
-        Ok((success, skipped, 0))
+    /// 未指定 limit 时搜索返回的结果数
+    pub fn default_limit(&self) -> usize {
+        self.default_limit
     }
 
-    /// 获取锁文件路径
+    /// 设置保留的永久删除日志数量（`purge --journal` 每次生成一个，超过后删除最旧的）
+    ///
+    /// # 参数
+    /// * `keep` - 保留数量（最小 1）
     ///
     /// # 返回
-    /// 锁文件路径
-    pub fn get_lock_path(&self) -> &std::path::Path {
-        &self.lock_path
+    /// 记忆存储实例
+    pub fn with_purge_journal_keep(mut self, keep: usize) -> Self {
+        self.purge_journal_keep = keep.max(1);
+        self
     }
 
-    /// 获取锁类型
+    /// 设置相关性评分权重（搜索、压缩和 --explain 共用）
+    ///
+    /// # 参数
+    /// * `weights` - 评分权重
+    ///
+    /// # 返回
+    /// 记忆存储实例
+    pub fn with_scoring_weights(mut self, weights: ScoringWeights) -> Self {
+        self.scoring_weights = weights;
+        self
+    }
+
+    /// 相关性评分权重
+    pub fn scoring_weights(&self) -> &ScoringWeights {
+        &self.scoring_weights
+    }
+
+    /// 设置搜索默认的文本相关性算法
+    ///
+    /// # 参数
+    /// * `ranking` - 排序算法（`SearchOptions::ranking` 可按次覆盖）
+    /// * `params` - BM25 参数
+    ///
+    /// # 返回
+    /// 记忆存储实例
+    pub fn with_ranking(mut self, ranking: RankingMode, params: Bm25Params) -> Self {
+        self.ranking = ranking;
+        self.bm25_params = params;
+        self
+    }
+
+    /// 搜索默认的文本相关性算法
+    pub fn ranking(&self) -> RankingMode {
+        self.ranking
+    }
+
+    /// 计算记录的分数明细，BM25 模式下文本得分为 BM25 得分乘以 `text_hit`
+    ///
+    /// # 参数
+    /// * `r` - 记忆记录
+    /// * `query` - 搜索查询
+    /// * `corpus` - BM25 语料统计（None 表示 linear）
+    ///
+    /// # 返回
+    /// 分数明细
+    pub fn score_with(&self, r: &MemoryRecord, query: &str, corpus: Option<&Bm25Corpus>) -> ScoreBreakdown {
+        let mut breakdown = score_breakdown(r, query, &self.scoring_weights);
+        if let Some(corpus) = corpus {
+            if breakdown.text_hits > 0 {
+                breakdown.text_score = corpus.score(r, query, &self.bm25_params) * self.scoring_weights.text_hit;
+            }
+        }
+        breakdown
+    }
+
+    /// 设置写入前备份：每次重写记忆文件前，先把当前文件复制为 `<文件名>.<时间>.bak`，
+    /// 只保留最近的 `keep` 个（目录模式下每个被重写的分类文件各自备份）。
+    /// 写坏的文件可以从备份恢复，但每次写入多一次完整复制
+    ///
+    /// # 参数
+    /// * `keep` - 保留的备份数量（最小 1，None 表示不备份）
+    ///
+    /// # 返回
+    /// 记忆存储实例
+    pub fn with_backup_before_write(mut self, keep: Option<usize>) -> Self {
+        self.backup_keep = keep.map(|k| k.max(1));
+        self
+    }
+
+    /// 设置标签蕴含关系，添加记忆时自动补全隐含标签
+    ///
+    /// # 参数
+    /// * `implications` - 标签到其隐含标签的映射（如 `rust-async` -> [`rust`]）
+    ///
+    /// # 返回
+    /// 记忆存储实例
+    pub fn with_tag_implications(mut self, implications: HashMap<String, Vec<String>>) -> Self {
+        self.tag_implications = implications;
+        self
+    }
+
+    /// 设置记忆文本的最大长度
+    ///
+    /// # 参数
+    /// * `max_text_len` - 最大字符数（None 表示不限制）
+    /// * `mode` - 超长时截断还是拒绝
+    ///
+    /// # 返回
+    /// 记忆存储实例
+    pub fn with_max_text_len(mut self, max_text_len: Option<usize>, mode: TextLimitMode) -> Self {
+        self.max_text_len = max_text_len;
+        self.text_limit_mode = mode;
+        self
+    }
+
+    /// 设置写入时是否按 `created_at`、`id` 排序记录
+    /// 启用后相同的记录集合总是写出相同的字节，便于在 git 中审阅差异
+    ///
+    /// # 参数
+    /// * `enabled` - 是否启用
+    ///
+    /// # 返回
+    /// 记忆存储实例
+    pub fn with_sort_on_write(mut self, enabled: bool) -> Self {
+        self.sort_on_write = enabled;
+        self
+    }
+
+    /// 将记录写入记忆文件（启用 sort_on_write 时先排序）
+    /// 目录模式下按分类写入各分类文件
+    ///
+    /// # 参数
+    /// * `records` - 要写入的全部记录
+    fn save(&self, records: &[MemoryRecord]) -> io::Result<()> {
+        if let Some(config) = &self.directory_config {
+            return self.save_categories(config, records);
+        }
+        if self.sort_on_write {
+            let mut sorted = records.to_vec();
+            sorted.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+            return atomic_write_with_backup(&self.memory_path, &sorted, self.backup_keep);
+        }
+        atomic_write_with_backup(&self.memory_path, records, self.backup_keep)
+    }
+
+    /// 目录模式的写入：按分类分组后通过 `commit_category_files` 一起提交
+    /// 已有但不再包含任何记录的分类文件写为空数组，使删除和重新分类生效
+    /// 调用方已通过 `lock()` 持有所有分类文件的锁
+    ///
+    /// # 参数
+    /// * `config` - 配置（提供分类映射）
+    /// * `records` - 要写入的全部记录
+    fn save_categories(&self, config: &Config, records: &[MemoryRecord]) -> io::Result<()> {
+        let dir = self.memory_path.to_string_lossy().into_owned();
+        fs::create_dir_all(&self.memory_path)?;
+
+        let mut categories: BTreeMap<String, Vec<MemoryRecord>> = list_category_files(&dir)
+            .into_iter()
+            .map(|(category, _)| (category, Vec::new()))
+            .collect();
+        for r in records {
+            categories.entry(get_category_for_tags(config, &r.tags)).or_default().push(r.clone());
+        }
+        if self.sort_on_write {
+            for recs in categories.values_mut() {
+                recs.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+            }
+        }
+
+        let files: Vec<(PathBuf, &Vec<MemoryRecord>)> = categories.iter()
+            .map(|(category, recs)| (category_file_path(&dir, category), recs))
+            .collect();
+        commit_category_files(&files, self.backup_keep)
+    }
+
+    /// 设置新记忆的 ID 前缀
+    ///
+    /// # 参数
+    /// * `prefix` - ID 前缀（例如 `proj`，生成 `proj_...` 形式的 ID）
+    ///
+    /// # 返回
+    /// 使用该前缀的记忆存储实例
+    pub fn with_id_prefix(mut self, prefix: &str) -> Self {
+        self.id_prefix = prefix.to_string();
+        self
+    }
+
+    /// 设置每次添加记忆时合并的默认标签
+    ///
+    /// # 参数
+    /// * `default_tags` - 默认标签（规范化后与用户标签去重合并）
+    ///
+    /// # 返回
+    /// 带默认标签的记忆存储实例
+    pub fn with_default_tags(mut self, default_tags: Vec<String>) -> Self {
+        self.default_tags = default_tags;
+        self
+    }
+
+    /// 获取运行计数快照
+    ///
+    /// # 返回
+    /// 自存储实例创建起累计的操作计数
+    pub fn metrics(&self) -> StoreMetrics {
+        let m = &self.metrics;
+        StoreMetrics {
+            adds: m.adds.load(Ordering::Relaxed),
+            searches: m.searches.load(Ordering::Relaxed),
+            deletes: m.deletes.load(Ordering::Relaxed),
+            lock_waits: m.lock_waits.load(Ordering::Relaxed),
+            lock_timeouts: m.lock_timeouts.load(Ordering::Relaxed),
+            lock_wait_ms: m.lock_wait_ms.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 获取存储锁并记录等待时间与超时次数
+    /// 目录模式下随后获取所有分类文件的锁，与 organize、md 导入等直接写分类文件的路径互相排斥
+    ///
+    /// # 返回
+    /// 锁守卫
+    fn lock(&self) -> io::Result<StoreLock> {
+        let start = Instant::now();
+        let result = acquire_store_lock(&self.lock_path, self.lock_type, None, Some(300)).and_then(|store| {
+            let categories = match &self.directory_config {
+                Some(config) => {
+                    fs::create_dir_all(&self.memory_path)?;
+                    lock_category_dir(&self.memory_path.to_string_lossy(), config)?
+                }
+                None => Vec::new(),
+            };
+            Ok(StoreLock { _store: store, _categories: categories })
+        });
+        let waited = start.elapsed().as_millis() as u64;
+
+        // 获取锁失败时会至少休眠 50ms 再重试，以此区分是否发生了等待
+        if waited >= 50 {
+            self.metrics.lock_waits.fetch_add(1, Ordering::Relaxed);
+            self.metrics.lock_wait_ms.fetch_add(waited, Ordering::Relaxed);
+        }
+        if let Err(e) = &result {
+            if e.kind() == io::ErrorKind::WouldBlock {
+                self.metrics.lock_timeouts.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+
+    /// 启用缓冲写入模式
+    /// 减少批量添加（如 MCP 导入循环）时每次都完整重写文件的开销。
+    /// 设置了刷新间隔时，第一次缓冲添加会启动后台线程，空闲时也会按间隔写入。
+    /// 刷新前进程崩溃会丢失缓冲区中尚未写入的记忆。
+    ///
+    /// # 参数
+    /// * `max_pending` - 待写入数量上限（最小 1），达到后立即刷新
+    /// * `flush_interval` - 刷新间隔（可选）
+    ///
+    /// # 返回
+    /// 启用缓冲写入的记忆存储实例
+    pub fn with_write_buffer(mut self, max_pending: usize, flush_interval: Option<Duration>) -> Self {
+        self.write_buffer = Some(WriteBuffer {
+            max_pending: max_pending.max(1),
+            flush_interval: flush_interval.filter(|interval| !interval.is_zero()),
+            pending: Arc::new(Mutex::new(Vec::new())),
+            last_flush: Arc::new(Mutex::new(Instant::now())),
+            flusher: Mutex::new(None),
+        });
+        self
+    }
+
+    /// 是否启用了缓冲写入
+    pub fn is_write_buffered(&self) -> bool {
+        self.write_buffer.is_some()
+    }
+
+    /// 设置了刷新间隔且后台刷新线程尚未启动时启动它
+    /// 在第一次缓冲添加时调用，此时存储的其余设置已经全部应用
+    fn start_flusher(&self, buffer: &WriteBuffer) {
+        let interval = match buffer.flush_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        let mut flusher = buffer.flusher.lock().unwrap();
+        if flusher.is_none() {
+            *flusher = Some(BackgroundFlusher::spawn(
+                self.unbuffered_copy(),
+                interval,
+                Arc::clone(&buffer.pending),
+                Arc::clone(&buffer.last_flush),
+            ));
+        }
+    }
+
+    /// 复制一个不带缓冲的存储实例（写入同一位置，供后台刷新线程使用）
+    fn unbuffered_copy(&self) -> MemoryStore {
+        MemoryStore {
+            memory_path: self.memory_path.clone(),
+            lock_path: self.lock_path.clone(),
+            lock_type: self.lock_type,
+            write_buffer: None,
+            metrics: Metrics::default(),
+            default_tags: self.default_tags.clone(),
+            id_prefix: self.id_prefix.clone(),
+            sort_on_write: self.sort_on_write,
+            max_text_len: self.max_text_len,
+            text_limit_mode: self.text_limit_mode,
+            tag_implications: self.tag_implications.clone(),
+            exclude_temp_by_default: self.exclude_temp_by_default,
+            default_limit: self.default_limit,
+            scoring_weights: self.scoring_weights,
+            ranking: self.ranking,
+            bm25_params: self.bm25_params,
+            audit_log: self.audit_log.clone(),
+            directory_config: self.directory_config.clone(),
+            purge_journal_keep: self.purge_journal_keep,
+            backup_keep: self.backup_keep,
+        }
+    }
+
+    /// 加锁后把记录追加到磁盘上的记忆文件（不经过缓冲区）
+    ///
+    /// # 参数
+    /// * `new_records` - 要追加的记录
+    fn append_records(&self, new_records: &[MemoryRecord]) -> io::Result<()> {
+        let _lock = self.lock()?;
+        let mut records = self.read_file()?;
+        records.extend(new_records.iter().cloned());
+        self.save(&records)?;
+        self.audit(AuditOp::Add, new_records);
+        Ok(())
+    }
+
+    /// 将缓冲区中的记忆一次性写入磁盘
+    /// 未启用缓冲写入或缓冲区为空时不做任何操作
+    ///
+    /// # 返回
+    /// 写入的记忆数量
+    pub fn flush(&self) -> io::Result<usize> {
+        let buffer = match &self.write_buffer {
+            Some(buffer) => buffer,
+            None => return Ok(0),
+        };
+
+        let mut pending = buffer.pending.lock().unwrap();
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        self.append_records(&pending)?;
+
+        let flushed = pending.len();
+        pending.clear();
+        *buffer.last_flush.lock().unwrap() = Instant::now();
+        Ok(flushed)
+    }
+
+    /// 获取缓冲区中尚未写入的记忆数量
+    ///
+    /// # 返回
+    /// 待写入数量（未启用缓冲写入时为 0）
+    pub fn pending_count(&self) -> usize {
+        self.write_buffer
+            .as_ref()
+            .map(|b| b.pending.lock().unwrap().len())
+            .unwrap_or(0)
+    }
+
+    /// 校验记忆文件路径
+    /// 记忆存储需要一个 JSON 文件路径；路径指向目录、或上级路径是文件时返回明确的错误
+    ///
+    /// # 返回
+    /// 路径可用时返回 Ok
+    pub fn check(&self) -> io::Result<()> {
+        if self.directory_config.is_some() {
+            return check_memory_dir(&self.memory_path);
+        }
+        if self.memory_path.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Memory path is a directory, expected a JSON file: {} (try {})",
+                    self.memory_path.display(),
+                    self.memory_path.join(DEFAULT_MEMORY_PATH).display()
+                ),
+            ));
+        }
+        if let Some(parent) = self.memory_path.parent() {
+            if parent.is_file() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Parent of memory path is a file, expected a directory: {}",
+                        parent.display()
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 从磁盘加载记忆存储
+    /// 启用缓冲写入时，结果包含尚未写入磁盘的记忆
+    ///
+    /// # 返回
+    /// 包含所有记录的向量
+    pub fn load(&self) -> io::Result<Vec<MemoryRecord>> {
+        let mut records = self.read_file()?;
+        if let Some(buffer) = &self.write_buffer {
+            records.extend(buffer.pending.lock().unwrap().iter().cloned());
+        }
+        Ok(records)
+    }
+
+    /// 按 ID 获取单条记忆，包括已软删除的记录（便于查看被删除的内容）
+    ///
+    /// # 参数
+    /// * `id` - 记忆 ID
+    ///
+    /// # 返回
+    /// 匹配的记录，不存在时返回 None
+    pub fn get_by_id(&self, id: &str) -> io::Result<Option<MemoryRecord>> {
+        Ok(self.load()?.into_iter().find(|r| r.id == id))
+    }
+
+    /// 在存储目录的某个分类文件中按 ID 获取单条记忆，包括已软删除的记录
+    /// 两种拼写（recorder / recoder）的分类文件都会查找，正确拼写优先；只读取，不重命名旧文件
+    ///
+    /// # 参数
+    /// * `id` - 记忆 ID
+    /// * `category` - 分类名
+    ///
+    /// # 返回
+    /// 匹配的记录，不存在时返回 None
+    pub fn get_by_id_in_category(&self, id: &str, category: &str) -> io::Result<Option<MemoryRecord>> {
+        let dir = self.store_dir();
+        for (name, path) in list_category_files(&dir.to_string_lossy()) {
+            if name != category {
+                continue;
+            }
+            let raw = fs::read_to_string(&path)?;
+            if let Some(record) = parse_records(&raw)?.into_iter().find(|r| r.id == id) {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 加载未被软删除的记忆
+    /// 需要忽略已删除记录的调用方应使用此方法，避免各自重复过滤
+    ///
+    /// # 返回
+    /// 所有活跃记录
+    pub fn load_active(&self) -> io::Result<Vec<MemoryRecord>> {
+        let mut records = self.load()?;
+        records.retain(|r| r.deleted_at.is_none());
+        Ok(records)
+    }
+
+    /// 读取磁盘上的记忆文件（不含缓冲区）
+    ///
+    /// # 返回
+    /// 文件中的所有记录
+    fn read_file(&self) -> io::Result<Vec<MemoryRecord>> {
+        self.check()?;
+        if !self.memory_path.exists() {
+            return Ok(Vec::new());
+        }
+        if self.directory_config.is_some() {
+            return read_category_dir(&self.memory_path);
+        }
+
+        let raw = read_store_text(&self.memory_path)?;
+        if raw.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        parse_records(&raw)
+    }
+
+    /// 添加新记忆到存储
+    /// 自动从文本中提取关键词以改进搜索
+    ///
+    /// # 参数
+    /// * `text` - 记忆内容（必需）
+    /// * `tags` - 用于分类的可选标签
+    ///
+    /// # 返回
+    /// 创建的记忆记录
+    ///
+    /// # 错误
+    /// 如果文本为空则返回错误
+    pub fn add_memory(&self, text: &str, tags: Option<Vec<String>>) -> io::Result<MemoryRecord> {
+        self.add_memory_idempotent(text, tags, None)
+    }
+
+    /// 添加新记忆到存储（支持幂等键，便于客户端安全重试）
+    /// 如果已存在相同幂等键的记录，直接返回该记录而不重复插入
+    ///
+    /// # 参数
+    /// * `text` - 记忆内容（必需）
+    /// * `tags` - 用于分类的可选标签
+    /// * `idempotency_key` - 客户端提供的幂等键（可选）
+    ///
+    /// # 返回
+    /// 创建的（或已存在的）记忆记录
+    ///
+    /// # 错误
+    /// 如果文本为空则返回错误
+    pub fn add_memory_idempotent(&self, text: &str, tags: Option<Vec<String>>, idempotency_key: Option<&str>) -> io::Result<MemoryRecord> {
+        self.add_memory_with_source(text, tags, idempotency_key, None)
+    }
+
+    /// 添加新记忆到存储，并记录其来源
+    ///
+    /// # 参数
+    /// * `text` - 记忆内容（必需）
+    /// * `tags` - 用于分类的可选标签
+    /// * `idempotency_key` - 客户端提供的幂等键（可选）
+    /// * `source` - 记忆来源（如 `cli`、`mcp`、`md:<文件名>`，可选）
+    ///
+    /// # 返回
+    /// 创建的（或已存在的）记忆记录
+    ///
+    /// # 错误
+    /// 如果文本为空则返回错误
+    pub fn add_memory_with_source(
+        &self,
+        text: &str,
+        tags: Option<Vec<String>>,
+        idempotency_key: Option<&str>,
+        source: Option<&str>,
+    ) -> io::Result<MemoryRecord> {
+        let mut rec = self.build_record(text, tags)?;
+        let key = idempotency_key.map(str::trim).filter(|k| !k.is_empty());
+        rec.idempotency_key = key.map(str::to_string);
+        rec.source = source.map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+
+        if let Some(buffer) = &self.write_buffer {
+            if let Some(k) = key {
+                if let Some(existing) = find_by_idempotency_key(&self.load()?, k) {
+                    return Ok(existing);
+                }
+            }
+            let should_flush = {
+                let mut pending = buffer.pending.lock().unwrap();
+                pending.push(rec.clone());
+                pending.len() >= buffer.max_pending
+            };
+            if should_flush {
+                self.flush()?;
+            } else {
+                self.start_flusher(buffer);
+            }
+            self.metrics.adds.fetch_add(1, Ordering::Relaxed);
+            return Ok(rec);
+        }
+
+        let _lock = self.lock()?;
+        let records = self.load()?;
+
+        if let Some(k) = key {
+            if let Some(existing) = find_by_idempotency_key(&records, k) {
+                return Ok(existing);
+            }
+        }
+
+        let mut new_records = records;
+        new_records.push(rec.clone());
+        self.save(&new_records)?;
+        self.audit(AuditOp::Add, [&rec]);
+        self.metrics.adds.fetch_add(1, Ordering::Relaxed);
+
+        Ok(rec)
+    }
+
+    /// 构建新的记忆记录（校验文本、规范化标签、提取关键词）
+    ///
+    /// # 参数
+    /// * `text` - 记忆内容
+    /// * `tags` - 可选标签
+    ///
+    /// # 返回
+    /// 尚未写入的记忆记录
+    ///
+    /// # 错误
+    /// 如果文本为空，或超过 `max_text_len` 且处理方式为拒绝，则返回错误
+    fn build_record(&self, text: &str, tags: Option<Vec<String>>) -> io::Result<MemoryRecord> {
+        let t = text.trim();
+        if t.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot add an empty memory."));
+        }
+
+        let mut tags = tags.unwrap_or_default();
+        let text = match self.max_text_len {
+            Some(max) if t.chars().count() > max => match self.text_limit_mode {
+                TextLimitMode::Reject => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Memory text is {} characters, exceeding max_text_len {}", t.chars().count(), max),
+                    ));
+                }
+                TextLimitMode::Truncate => {
+                    tags.push(TRUNCATED_TAG.to_string());
+                    format!("{}…", t.chars().take(max).collect::<String>())
+                }
+            },
+            _ => t.to_string(),
+        };
+
+        let keywords = extract_keywords(&text);
+        Ok(MemoryRecord {
+            id: make_id_with_prefix(&self.id_prefix),
+            text,
+            tags: expand_implied_tags(merge_tags(tags, &self.default_tags), &self.tag_implications),
+            keywords,
+            created_at: now_iso(),
+            updated_at: now_iso(),
+            deleted_at: None,
+            idempotency_key: None,
+            source: None,
+        })
+    }
+
+    /// 搜索记忆并按相关性排序
+    ///
+    /// # 参数
+    /// * `query` - 搜索查询（空格分隔的关键词）
+    /// * `limit` - 返回的最大结果数（默认为 `search_default_limit`）
+    ///
+    /// # 返回
+    /// 按分数降序排列的搜索命中数组
+    pub fn search(&self, query: &str, limit: Option<usize>) -> io::Result<Vec<SearchHit>> {
+        self.search_in_namespace(query, None, limit)
+    }
+
+    /// 在指定命名空间内搜索记忆并按相关性排序
+    ///
+    /// # 参数
+    /// * `query` - 搜索查询（空格分隔的关键词）
+    /// * `namespace` - 命名空间（可选），仅匹配带有 `proj:<namespace>` 标签的记忆
+    /// * `limit` - 返回的最大结果数（默认为 `search_default_limit`）
+    ///
+    /// # 返回
+    /// 按分数降序排列的搜索命中数组
+    pub fn search_in_namespace(&self, query: &str, namespace: Option<&str>, limit: Option<usize>) -> io::Result<Vec<SearchHit>> {
+        let options = SearchOptions {
+            limit,
+            namespace: namespace.map(str::to_string),
+            ..SearchOptions::default()
+        };
+        self.search_with_options(query, &options)
+    }
+
+    /// 按搜索选项搜索记忆并按相关性排序
+    ///
+    /// # 参数
+    /// * `query` - 搜索查询（空格分隔的关键词）
+    /// * `options` - 搜索选项（数量上限、命名空间、标签过滤）
+    ///
+    /// # 返回
+    /// 按分数降序排列的搜索命中数组
+    pub fn search_with_options(&self, query: &str, options: &SearchOptions) -> io::Result<Vec<SearchHit>> {
+        self.search_with_profile(query, options).map(|(hits, _)| hits)
+    }
+
+    /// 按搜索选项搜索记忆，并记录加载、评分、排序各阶段耗时
+    ///
+    /// # 参数
+    /// * `query` - 搜索查询（空格分隔的关键词）
+    /// * `options` - 搜索选项
+    ///
+    /// # 返回
+    /// (按分数降序排列的搜索命中数组, 各阶段耗时)
+    pub fn search_with_profile(&self, query: &str, options: &SearchOptions) -> io::Result<(Vec<SearchHit>, SearchProfile)> {
+        let parse_bound = |bound: &Option<String>, end_of_day: bool| {
+            bound.as_deref()
+                .map(|ts| parse_time_bound(ts, end_of_day).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)))
+                .transpose()
+        };
+        let after = parse_bound(&options.after, false)?;
+        let before = parse_bound(&options.before, true)?;
+
+        let mut profile = SearchProfile::default();
+        let started = Instant::now();
+        let records = if options.include_deleted {
+            self.load()?
+        } else {
+            self.load_active()?
+        };
+        profile.load_ms = elapsed_ms(started);
+
+        let started = Instant::now();
+        let limit = match (options.limit, options.auto_cutoff) {
+            (Some(limit), _) => limit,
+            (None, Some(_)) => usize::MAX,
+            (None, None) => self.default_limit,
+        };
+        let ns_tag = options.namespace.as_deref().map(namespace_tag);
+        let filter_tags = normalize_tags(Some(options.tags.clone()));
+        let exclude_temp = options.exclude_temp.unwrap_or(self.exclude_temp_by_default);
+        let within = options.within.as_deref().map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty());
+        let corpus = (options.ranking.unwrap_or(self.ranking) == RankingMode::Bm25)
+            .then(|| Bm25Corpus::build(&records, query));
+        let list_by_tags = query.trim().is_empty() && !filter_tags.is_empty();
+        self.metrics.searches.fetch_add(1, Ordering::Relaxed);
+
+        let mut hits: Vec<SearchHit> = Vec::new();
+        for r in &records {
+            if let Some(tag) = &ns_tag {
+                if !r.tags.contains(tag) {
+                    continue;
+                }
+            }
+            let matched = if options.expand_implications {
+                options.tag_match.matches(&expand_implied_tags(r.tags.clone(), &self.tag_implications), &filter_tags)
+            } else {
+                options.tag_match.matches(&r.tags, &filter_tags)
+            };
+            if !matched {
+                continue;
+            }
+            if exclude_temp && is_temp(r) {
+                continue;
+            }
+            if let Some(tag) = &within {
+                if !r.tags.contains(tag) {
+                    continue;
+                }
+            }
+            if let Some(filter) = &options.source {
+                if !source_matches(r.source.as_deref(), filter) {
+                    continue;
+                }
+            }
+            if after.is_some() || before.is_some() {
+                // 创建时间无法解析的记录无法判断是否在范围内，直接排除
+                let created = match chrono::DateTime::parse_from_rfc3339(&r.created_at) {
+                    Ok(created) => created,
+                    Err(_) => continue,
+                };
+                if after.is_some_and(|after| created < after) || before.is_some_and(|before| created >= before) {
+                    continue;
+                }
+            }
+            let mut breakdown = self.score_with(r, query, corpus.as_ref());
+            if within.is_some() {
+                breakdown.tag_score = 0.0;
+            }
+            let score = breakdown.total();
+            if score <= 0.0 && !list_by_tags {
+                continue;
+            }
+            hits.push(SearchHit {
+                id: r.id.clone(),
+                text: r.text.clone(),
+                tags: r.tags.clone(),
+                keywords: r.keywords.clone(),
+                created_at: r.created_at.clone(),
+                updated_at: r.updated_at.clone(),
+                source: r.source.clone(),
+                deleted_at: r.deleted_at.clone(),
+                score,
+            });
+        }
+
+        profile.score_ms = elapsed_ms(started);
+
+        let started = Instant::now();
+        if list_by_tags {
+            hits.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+        } else {
+            hits.sort_by(compare_hits);
+        }
+        if let (Some(ratio), Some(top)) = (options.auto_cutoff, hits.first().map(|h| h.score)) {
+            let floor = top * ratio;
+            hits.retain(|h| h.score >= floor);
+        }
+        profile.total_hits = hits.len();
+        let hits: Vec<SearchHit> = hits.into_iter()
+            .skip(options.offset.unwrap_or(0))
+            .take(std::cmp::max(1, limit))
+            .collect();
+        profile.sort_ms = elapsed_ms(started);
+
+        Ok((hits, profile))
+    }
+
+    /// 仅按关键词字段快速查找记忆（不做全文评分）
+    ///
+    /// # 参数
+    /// * `keyword` - 关键词（不区分大小写，需与提取出的关键词完全一致）
+    /// * `limit` - 返回的最大结果数（可选，默认为 `search_default_limit`）
+    ///
+    /// # 返回
+    /// 按存储顺序排列的匹配记录
+    pub fn search_by_keyword(&self, keyword: &str, limit: Option<usize>) -> io::Result<Vec<MemoryRecord>> {
+        let keyword = keyword.trim().to_lowercase();
+        if keyword.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.metrics.searches.fetch_add(1, Ordering::Relaxed);
+
+        Ok(self.load_active()?
+            .into_iter()
+            .filter(|r| r.keywords.iter().any(|k| k.to_lowercase() == keyword))
+            .take(limit.unwrap_or(self.default_limit))
+            .collect())
+    }
+
+    /// 分页列出记忆（默认按存储顺序）
+    ///
+    /// # 参数
+    /// * `opts` - 列出选项（分页、标签过滤、排序、是否包含已删除记录）
+    ///
+    /// # 返回
+    /// (当前页的记录, 过滤后的记录总数)
+    pub fn list(&self, opts: &ListOptions) -> io::Result<(Vec<MemoryRecord>, usize)> {
+        let mut records = if opts.include_deleted {
+            self.load()?
+        } else {
+            self.load_active()?
+        };
+        if let Some(tag) = normalize_tags(opts.tag.clone().map(|t| vec![t])).first() {
+            records.retain(|r| r.tags.contains(tag));
+        }
+        if let Some(sort_by) = opts.sort_by {
+            let key = |r: &MemoryRecord| match sort_by {
+                ListSort::Created => r.created_at.clone(),
+                ListSort::Updated => r.updated_at.clone(),
+            };
+            records.sort_by(|a, b| key(a).cmp(&key(b)).then_with(|| a.id.cmp(&b.id)));
+        }
+        if opts.descending {
+            records.reverse();
+        }
+        let total = records.len();
+        let page = records.into_iter()
+            .skip(opts.offset)
+            .take(opts.limit.unwrap_or(usize::MAX))
+            .collect();
+        Ok((page, total))
+    }
+
+    /// 计算记忆存储的统计信息
+    ///
+    /// # 返回
+    /// 包括计数和标签频率的统计信息
+    pub fn compute_stats(&self) -> io::Result<StoreStats> {
+        self.compute_stats_in_namespace(None)
+    }
+
+    /// 计算指定命名空间内的统计信息
+    ///
+    /// # 参数
+    /// * `namespace` - 命名空间（可选），仅统计带有 `proj:<namespace>` 标签的记忆
+    ///
+    /// # 返回
+    /// 包括计数和标签频率的统计信息
+    pub fn compute_stats_in_namespace(&self, namespace: Option<&str>) -> io::Result<StoreStats> {
+        let mut records = self.load()?;
+        if let Some(tag) = namespace.map(namespace_tag) {
+            records.retain(|r| r.tags.contains(&tag));
+        }
+
+        let mut tags: HashMap<String, usize> = HashMap::new();
+        let mut sources: HashMap<String, usize> = HashMap::new();
+        let mut deleted = 0;
+
+        for r in &records {
+            if r.deleted_at.is_some() {
+                deleted += 1;
+            }
+            for t in &r.tags {
+                *tags.entry(t.clone()).or_insert(0) += 1;
+            }
+            let source = r.source.as_deref().unwrap_or(SOURCE_UNKNOWN);
+            *sources.entry(source.to_string()).or_insert(0) += 1;
+        }
+
+        Ok(StoreStats {
+            total: records.len(),
+            active: records.len() - deleted,
+            deleted,
+            tags,
+            sources,
+        })
+    }
+
+    /// 统计历史文件路径（记忆文件所在目录下的 `stats-history.csv`）
+    pub fn stats_history_path(&self) -> PathBuf {
+        self.store_dir().join(STATS_HISTORY_FILE)
+    }
+
+    /// 计算当前统计并追加一行到统计历史文件，用于绘制记忆库增长曲线
+    /// 文件不存在时先写入表头
+    ///
+    /// # 返回
+    /// 本次记录的快照
+    pub fn record_stats_snapshot(&self) -> io::Result<StatsSnapshot> {
+        let stats = self.compute_stats()?;
+        let snapshot = StatsSnapshot {
+            timestamp: now_iso(),
+            total: stats.total,
+            active: stats.active,
+            deleted: stats.deleted,
+            distinct_tags: stats.tags.len(),
+        };
+
+        let path = self.stats_history_path();
+        let is_new = fs::metadata(&path).map(|m| m.len() == 0).unwrap_or(true);
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        if is_new {
+            writeln!(file, "{}", StatsSnapshot::CSV_HEADER)?;
+        }
+        writeln!(file, "{}", snapshot.to_csv_line())?;
+
+        Ok(snapshot)
+    }
+
+    /// 读取统计历史
+    ///
+    /// # 返回
+    /// 按记录顺序排列的快照（文件不存在时为空，无法解析的行被忽略）
+    pub fn read_stats_history(&self) -> io::Result<Vec<StatsSnapshot>> {
+        let content = match fs::read_to_string(self.stats_history_path()) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(content.lines().filter_map(StatsSnapshot::parse_csv_line).collect())
+    }
+
+    /// 统计标签共现次数
+    /// 对每条活跃记录中的每一对不同标签计数（单标签记录不产生标签对）
+    ///
+    /// # 返回
+    /// 标签对（按字典序排列）到共现次数的映射
+    pub fn tag_cooccurrence(&self) -> io::Result<HashMap<(String, String), usize>> {
+        let records = self.load_active()?;
+        let mut pairs: HashMap<(String, String), usize> = HashMap::new();
+
+        for r in &records {
+            let mut tags: Vec<&String> = r.tags.iter().collect();
+            tags.sort();
+            tags.dedup();
+            for (i, a) in tags.iter().enumerate() {
+                for b in &tags[i + 1..] {
+                    *pairs.entry(((*a).clone(), (*b).clone())).or_insert(0) += 1;
+                }
+            }
+        }
+
+        Ok(pairs)
+    }
+
+    /// 找出活跃记忆中在 `category_mapping` 里没有映射的标签
+    /// 命名空间标签（`proj:`）不参与分类，不会被报告
+    ///
+    /// # 参数
+    /// * `config` - 配置（提供分类映射）
+    ///
+    /// # 返回
+    /// 未映射的标签，按落入 `default` 的记忆数、记忆数降序，再按标签名排序
+    pub fn unmapped_tags(&self, config: &Config) -> io::Result<Vec<UnmappedTag>> {
+        let records = self.load_active()?;
+        let mapping = config.category_mapping.clone().unwrap_or_default();
+
+        let mut unmapped: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for r in &records {
+            let in_default = !r.tags.iter().any(|t| mapping.contains_key(t));
+            for tag in &r.tags {
+                if mapping.contains_key(tag) || tag.starts_with(NAMESPACE_TAG_PREFIX) {
+                    continue;
+                }
+                let entry = unmapped.entry(tag.clone()).or_default();
+                entry.0 += 1;
+                if in_default {
+                    entry.1 += 1;
+                }
+            }
+        }
+
+        let mut tags: Vec<UnmappedTag> = unmapped.into_iter()
+            .map(|(tag, (count, default_count))| UnmappedTag {
+                similar_key: similar_mapping_key(config, &tag),
+                tag,
+                count,
+                default_count,
+            })
+            .collect();
+        tags.sort_by(|a, b| b.default_count.cmp(&a.default_count)
+            .then_with(|| b.count.cmp(&a.count))
+            .then_with(|| a.tag.cmp(&b.tag)));
+        Ok(tags)
+    }
+
+    /// 批量添加记忆：只获取一次锁、读取一次、写入一次（批量导入时避免每条记忆都加锁）
+    ///
+    /// # 参数
+    /// * `items` - (记忆内容, 标签) 列表
+    ///
+    /// # 返回
+    /// 与 `items` 一一对应的结果：成功为新记录，失败（如文本为空）为错误；加锁、读取或写入失败时整体返回错误
+    pub fn add_many(&self, items: &[(String, Vec<String>)]) -> io::Result<Vec<io::Result<MemoryRecord>>> {
+        self.add_many_with_source(items, None)
+    }
+
+    /// 批量添加记忆，并记录其来源
+    ///
+    /// # 参数
+    /// * `items` - (记忆内容, 标签) 列表
+    /// * `source` - 记忆来源（如 `import-json`，可选）
+    ///
+    /// # 返回
+    /// 与 `items` 一一对应的结果；加锁、读取或写入失败时整体返回错误，此时没有记录被写入
+    pub fn add_many_with_source(&self, items: &[(String, Vec<String>)], source: Option<&str>) -> io::Result<Vec<io::Result<MemoryRecord>>> {
+        let source = source.map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+        let candidates: Vec<io::Result<MemoryRecord>> = items.iter()
+            .map(|(text, tags)| {
+                let mut rec = self.build_record(text, Some(tags.clone()))?;
+                rec.source = source.clone();
+                Ok(rec)
+            })
+            .collect();
+        self.append_many(candidates, false)
+    }
+
+    /// 在一次加锁、一次读取、一次写入内追加一批已构造好的记录（`add_many` 和 JSON 导入共用）
+    /// ID 已存在（包括本批中前面的记录）的候选记录不写入，对应结果为 AlreadyExists 错误
+    ///
+    /// # 参数
+    /// * `candidates` - 候选记录；本身为错误的项原样返回
+    /// * `simulate` - 模拟模式：只计算结果，不写入磁盘
+    ///
+    /// # 返回
+    /// 与 `candidates` 一一对应的结果；加锁、读取或写入失败时整体返回错误，此时没有记录被写入
+    fn append_many(&self, candidates: Vec<io::Result<MemoryRecord>>, simulate: bool) -> io::Result<Vec<io::Result<MemoryRecord>>> {
+        if !candidates.iter().any(|c| c.is_ok()) {
+            return Ok(candidates);
+        }
+
+        self.flush()?;
+        let _lock = self.lock()?;
+        let mut records = self.load()?;
+        let mut existing_ids: std::collections::HashSet<String> = records.iter().map(|r| r.id.clone()).collect();
+
+        let mut added: Vec<MemoryRecord> = Vec::new();
+        let results: Vec<io::Result<MemoryRecord>> = candidates.into_iter()
+            .map(|candidate| {
+                let rec = candidate?;
+                if !existing_ids.insert(rec.id.clone()) {
+                    return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Memory {} already exists", rec.id)));
+                }
+                added.push(rec.clone());
+                Ok(rec)
+            })
+            .collect();
+
+        if !simulate && !added.is_empty() {
+            records.extend(added.iter().cloned());
+            self.save(&records)?;
+            self.audit(AuditOp::Add, &added);
+            self.metrics.adds.fetch_add(added.len() as u64, Ordering::Relaxed);
+        }
+        Ok(results)
+    }
+
+    /// 把一条完整记录加入存储，保留它的时间戳、标签、关键词和来源（跨存储复制、移动时使用）
+    ///
+    /// # 参数
+    /// * `record` - 要加入的记录
+    /// * `keep_id` - 是否保留原 ID；为 false 时按本存储的 ID 前缀生成新 ID
+    ///
+    /// # 返回
+    /// 加入后的记录；保留的 ID 在本存储中已存在时返回 AlreadyExists 错误
+    pub fn insert_record(&self, mut record: MemoryRecord, keep_id: bool) -> io::Result<MemoryRecord> {
+        self.flush()?;
+        let _lock = self.lock()?;
+        let mut records = self.load()?;
+
+        if !keep_id {
+            record.id = make_id_with_prefix(&self.id_prefix);
+        } else if records.iter().any(|r| r.id == record.id) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Memory {} already exists in {}", record.id, self.memory_path.display()),
+            ));
+        }
+
+        records.push(record.clone());
+        self.save(&records)?;
+        self.audit(AuditOp::Add, [&record]);
+        self.metrics.adds.fetch_add(1, Ordering::Relaxed);
+        Ok(record)
+    }
+
+    /// 把一条活跃记忆复制到另一个存储
+    ///
+    /// # 参数
+    /// * `id` - 记忆 ID
+    /// * `target` - 目标存储（不能是当前存储）
+    /// * `keep_id` - 是否在目标存储中保留原 ID
+    ///
+    /// # 返回
+    /// 目标存储中的新记录；当前存储中没有该活跃记忆时返回 None
+    pub fn copy_to(&self, id: &str, target: &MemoryStore, keep_id: bool) -> io::Result<Option<MemoryRecord>> {
+        if target.memory_path == self.memory_path {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Target store is the current store: {}", self.memory_path.display()),
+            ));
+        }
+        let record = match self.load_active()?.into_iter().find(|r| r.id == id) {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        target.insert_record(record, keep_id).map(Some)
+    }
+
+    /// 把一条活跃记忆移动到另一个存储：先写入目标存储，成功后再软删除当前存储中的记录
+    ///
+    /// # 参数
+    /// * `id` - 记忆 ID
+    /// * `target` - 目标存储（不能是当前存储）
+    /// * `keep_id` - 是否在目标存储中保留原 ID
+    ///
+    /// # 返回
+    /// 目标存储中的新记录；当前存储中没有该活跃记忆时返回 None
+    pub fn move_to(&self, id: &str, target: &MemoryStore, keep_id: bool) -> io::Result<Option<MemoryRecord>> {
+        let moved = self.copy_to(id, target, keep_id)?;
+        if moved.is_some() {
+            self.soft_delete(id)?;
+        }
+        Ok(moved)
+    }
+
+    /// 软删除记忆（标记为已删除）
+    ///
+    /// # 参数
+    /// * `id` - 记忆 ID
+    ///
+    /// # 返回
+    /// 是否找到并删除了记忆
+    pub fn soft_delete(&self, id: &str) -> io::Result<bool> {
+        Ok(self.soft_delete_many(&[id])?[0])
+    }
+
+    /// 在一次加锁内软删除多条记忆
+    ///
+    /// # 参数
+    /// * `ids` - 记忆 ID 列表
+    ///
+    /// # 返回
+    /// 与 `ids` 一一对应的结果：是否找到并删除了该记忆（不存在或已删除为 false）
+    pub fn soft_delete_many(&self, ids: &[&str]) -> io::Result<Vec<bool>> {
+        self.flush()?;
+        let _lock = self.lock()?;
+        let mut records = self.load()?;
+        let now = now_iso();
+
+        let mut deleted_ids: Vec<&str> = Vec::new();
+        let results: Vec<bool> = ids.iter().map(|id| {
+            match records.iter_mut().find(|r| r.id == *id && r.deleted_at.is_none()) {
+                Some(r) => {
+                    r.deleted_at = Some(now.clone());
+                    r.updated_at = now.clone();
+                    deleted_ids.push(id);
+                    true
+                }
+                None => false,
+            }
+        }).collect();
+
+        let deleted = deleted_ids.len();
+        if deleted > 0 {
+            self.save(&records)?;
+            self.audit(AuditOp::Delete, records.iter().filter(|r| deleted_ids.contains(&r.id.as_str())));
+            self.metrics.deletes.fetch_add(deleted as u64, Ordering::Relaxed);
+        }
+
+        Ok(results)
+    }
+
+    /// 硬删除记忆（永久删除）
+    ///
+    /// # 参数
+    /// * `id` - 记忆 ID（可选）
+    /// * `tag` - 标签匹配（可选）
+    /// * `match_text` - 文本匹配（可选）
+    ///
+    /// # 返回
+    /// 删除的记忆数量
+    pub fn purge(&self, id: Option<&str>, tag: Option<&str>, match_text: Option<&str>) -> io::Result<usize> {
+        self.purge_journaled(id, tag, match_text, false).map(|(purged, _)| purged)
+    }
+
+    /// 硬删除记忆，可选在写入前把删除的记录保存到永久删除日志（可用 `restore_purge` 恢复）
+    ///
+    /// # 参数
+    /// * `id` - 记忆 ID（可选）
+    /// * `tag` - 标签匹配（可选）
+    /// * `match_text` - 文本匹配（可选）
+    /// * `journal` - 是否写永久删除日志
+    ///
+    /// # 返回
+    /// (删除的记忆数量, 日志路径；未写日志或没有删除时为 None)
+    pub fn purge_journaled(&self, id: Option<&str>, tag: Option<&str>, match_text: Option<&str>, journal: bool) -> io::Result<(usize, Option<PathBuf>)> {
+        self.purge_matching(journal, |r| {
+            id.is_some_and(|id_val| r.id == id_val)
+                || tag.is_some_and(|tag_val| r.tags.iter().any(|t| t == tag_val))
+                || match_text.is_some_and(|text_val| r.text.contains(text_val))
+        })
+    }
+
+    /// 永久删除所有已软删除的记忆
+    /// 与 `purge` 按 ID/标签/文本匹配删除不同，只移除 `deleted_at` 已设置的记录
+    ///
+    /// # 返回
+    /// 删除的记忆数量
+    pub fn purge_deleted(&self) -> io::Result<usize> {
+        self.purge_deleted_journaled(false).map(|(purged, _)| purged)
+    }
+
+    /// 永久删除所有已软删除的记忆，可选先写永久删除日志
+    ///
+    /// # 参数
+    /// * `journal` - 是否写永久删除日志
+    ///
+    /// # 返回
+    /// (删除的记忆数量, 日志路径；未写日志或没有删除时为 None)
+    pub fn purge_deleted_journaled(&self, journal: bool) -> io::Result<(usize, Option<PathBuf>)> {
+        self.purge_matching(journal, |r| r.deleted_at.is_some())
+    }
+
+    /// 永久删除满足条件的记录；写日志时先写日志再保存，日志写失败则不删除
+    /// 日志保存在记忆目录中，超过 `purge_journal_keep` 个时删除最旧的
+    ///
+    /// # 参数
+    /// * `journal` - 是否写永久删除日志
+    /// * `matches` - 要删除的记录
+    ///
+    /// # 返回
+    /// (删除的记忆数量, 日志路径)
+    fn purge_matching(&self, journal: bool, matches: impl Fn(&MemoryRecord) -> bool) -> io::Result<(usize, Option<PathBuf>)> {
+        self.flush()?;
+        let _lock = self.lock()?;
+        let records = self.load()?;
+
+        let (removed, records): (Vec<MemoryRecord>, Vec<MemoryRecord>) = records.into_iter().partition(|r| matches(r));
+
+        let purged = removed.len();
+        let mut journal_path = None;
+        if purged > 0 {
+            if journal {
+                let dir = self.store_dir();
+                let path = purge_journal_path(&dir);
+                atomic_write(&path, &removed)?;
+                prune_purge_journals(&dir, self.purge_journal_keep)?;
+                journal_path = Some(path);
+            }
+            self.save(&records)?;
+            self.audit(AuditOp::Purge, &removed);
+            self.metrics.deletes.fetch_add(purged as u64, Ordering::Relaxed);
+        }
+
+        Ok((purged, journal_path))
+    }
+
+    /// 从永久删除日志恢复记录，存储中已存在的 ID 会被跳过
+    ///
+    /// # 参数
+    /// * `journal_path` - 日志文件路径（`purge_journaled` 返回的路径）
+    ///
+    /// # 返回
+    /// 恢复的记录数量
+    pub fn restore_purge(&self, journal_path: &str) -> io::Result<usize> {
+        let raw = fs::read_to_string(journal_path)?;
+        let journal = parse_records(&raw)?;
+
+        self.flush()?;
+        let _lock = self.lock()?;
+        let mut records = self.load()?;
+
+        let mut restored = Vec::new();
+        for r in journal {
+            if records.iter().any(|existing| existing.id == r.id) || restored.iter().any(|done: &MemoryRecord| done.id == r.id) {
+                continue;
+            }
+            restored.push(r);
+        }
+
+        if !restored.is_empty() {
+            records.extend(restored.iter().cloned());
+            self.save(&records)?;
+            self.audit(AuditOp::Add, &restored);
+            self.metrics.adds.fetch_add(restored.len() as u64, Ordering::Relaxed);
+        }
+        Ok(restored.len())
+    }
+
+    /// 永久删除文本为空（或只有空白）的记录
+    /// `add_memory` 不会创建这种记录，它们只可能来自有问题的导入或手动编辑
+    ///
+    /// # 返回
+    /// 删除的记录数量
+    pub fn purge_empty_text(&self) -> io::Result<usize> {
+        self.flush()?;
+        let _lock = self.lock()?;
+        let records = self.load()?;
+
+        let (removed, records): (Vec<MemoryRecord>, Vec<MemoryRecord>) = records.into_iter().partition(|r| r.text.trim().is_empty());
+
+        let purged = removed.len();
+        if purged > 0 {
+            self.save(&records)?;
+            self.audit(AuditOp::Purge, &removed);
+            self.metrics.deletes.fetch_add(purged as u64, Ordering::Relaxed);
+        }
+
+        Ok(purged)
+    }
+
+    /// 查找文本为空（或只有空白）的记录，这些记录搜索不到却仍计入统计
+    ///
+    /// # 返回
+    /// 这些记录的 ID（按存储顺序）
+    pub fn find_empty_text(&self) -> io::Result<Vec<String>> {
+        Ok(self.load()?
+            .into_iter()
+            .filter(|r| r.text.trim().is_empty())
+            .map(|r| r.id)
+            .collect())
+    }
+
+    /// 查找重复的记忆 ID（手动编辑或导入异常可能导致多条记录共用一个 ID）
+    ///
+    /// # 返回
+    /// 出现多次的 ID（按字典序排列）
+    pub fn find_duplicate_ids(&self) -> io::Result<Vec<String>> {
+        let records = self.load()?;
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for r in &records {
+            *counts.entry(r.id.as_str()).or_insert(0) += 1;
+        }
+        Ok(counts.into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(id, _)| id.to_string())
+            .collect())
+    }
+
+    /// 为重复 ID 的记录重新生成 ID（每组保留第一条记录的原 ID）
+    ///
+    /// # 返回
+    /// 重新生成 ID 的记录数量
+    pub fn regenerate_duplicate_ids(&self) -> io::Result<usize> {
+        self.flush()?;
+        let _lock = self.lock()?;
+        let mut records = self.load()?;
+
+        let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+        let mut changed_ids: Vec<String> = Vec::new();
+        for r in records.iter_mut() {
+            if !seen.insert(r.id.clone()) {
+                r.id = make_id_with_prefix(&self.id_prefix);
+                r.updated_at = now_iso();
+                seen.insert(r.id.clone());
+                changed_ids.push(r.id.clone());
+            }
+        }
+        let regenerated = changed_ids.len();
+
+        if regenerated > 0 {
+            self.save(&records)?;
+            self.audit(AuditOp::Update, records.iter().filter(|r| changed_ids.contains(&r.id)));
+        }
+
+        Ok(regenerated)
+    }
+
+    /// 在所有记忆中重命名标签（用于合并同义标签，如把 `workflow` 并入 `ci`）
+    /// 标签按 `normalize_tags` 的规则去除首尾空白并转为小写；已带有新标签的记录不会重复出现该标签
+    ///
+    /// # 参数
+    /// * `from` - 原标签
+    /// * `to` - 新标签
+    ///
+    /// # 返回
+    /// 被修改的记录数量；标签为空时返回 `InvalidInput` 错误
+    pub fn rename_tag(&self, from: &str, to: &str) -> io::Result<usize> {
+        let from = from.trim().to_lowercase();
+        let to = to.trim().to_lowercase();
+        if from.is_empty() || to.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Tag names must not be empty."));
+        }
+        if from == to {
+            return Ok(0);
+        }
+
+        self.flush()?;
+        let _lock = self.lock()?;
+        let mut records = self.load()?;
+
+        let mut changed_ids: Vec<String> = Vec::new();
+        for r in records.iter_mut() {
+            if !r.tags.iter().any(|t| t.trim().to_lowercase() == from) {
+                continue;
+            }
+            let mut tags: Vec<String> = Vec::with_capacity(r.tags.len());
+            for t in &r.tags {
+                let cleaned = t.trim().to_lowercase();
+                let (tag, key) = if cleaned == from { (to.clone(), to.clone()) } else { (t.clone(), cleaned) };
+                // 两边都按规范化后的形式比较，大小写不同的同一标签只保留第一个
+                if !tags.iter().any(|existing| existing.trim().to_lowercase() == key) {
+                    tags.push(tag);
+                }
+            }
+            r.tags = tags;
+            r.updated_at = now_iso();
+            changed_ids.push(r.id.clone());
+        }
+        let renamed = changed_ids.len();
+
+        if renamed > 0 {
+            self.save(&records)?;
+            self.audit(AuditOp::Update, records.iter().filter(|r| changed_ids.contains(&r.id)));
+        }
+
+        Ok(renamed)
+    }
+
+    /// 导出所有记忆为 JSON 字符串
+    ///
+    /// # 返回
+    /// JSON 格式的记忆数据
+    pub fn export_json(&self) -> io::Result<String> {
+        let records = self.load()?;
+        serde_json::to_string_pretty(&records)
+            .map_err(io::Error::other)
+    }
+
+    /// 按分类导出活跃记忆为 markdown 文件
+    /// 每个分类（由 `get_category_for_tags` 决定）写入一个 `{category}.md`，每条记忆一个小节
+    ///
+    /// # 参数
+    /// * `config` - 配置（提供分类映射）
+    /// * `out_dir` - 输出目录（不存在时自动创建）
+    ///
+    /// # 返回
+    /// 写入的文件路径列表（按分类名排序）
+    pub fn export_markdown_by_category(&self, config: &Config, out_dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let documents = self.markdown_by_category(config)?;
+
+        fs::create_dir_all(out_dir)?;
+
+        let mut written = Vec::new();
+        for (category, md) in documents {
+            let file_path = out_dir.join(format!("{}.md", category));
+            fs::write(&file_path, md)?;
+            written.push(file_path);
+        }
+
+        Ok(written)
+    }
+
+    /// 按分类把活跃记忆渲染为 markdown 文档（不写入磁盘）
+    /// 每条记忆一个小节，分类内按创建时间排序
+    ///
+    /// # 参数
+    /// * `config` - 配置（提供分类映射）
+    ///
+    /// # 返回
+    /// 分类名到 markdown 文档的映射（按分类名排序）
+    pub fn markdown_by_category(&self, config: &Config) -> io::Result<BTreeMap<String, String>> {
+        let records = self.load_active()?;
+
+        let mut categories: BTreeMap<String, Vec<&MemoryRecord>> = BTreeMap::new();
+        for r in &records {
+            let category = get_category_for_tags(config, &r.tags);
+            categories.entry(category).or_default().push(r);
+        }
+
+        let mut documents = BTreeMap::new();
+        for (category, mut recs) in categories {
+            recs.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+
+            let mut md = format!("# {}\n\n", category);
+            for r in recs {
+                md.push_str(&format!("## {}\n\n", r.id));
+                if !r.tags.is_empty() {
+                    md.push_str(&format!("- Tags: {}\n", r.tags.join(", ")));
+                }
+                md.push_str(&format!("- Created: {}\n", r.created_at));
+                md.push_str(&format!("- Updated: {}\n\n", r.updated_at));
+                md.push_str(r.text.trim_end());
+                md.push_str("\n\n");
+            }
+
+            documents.insert(category, md);
+        }
+
+        Ok(documents)
+    }
+
+    /// 把单文件存储按分类拆分为分类文件（`{分类}-global-gmem-recorder.json`），写入存储所在目录
+    /// 与 organize 不同，记录原样保留（ID、时间戳、关键词、删除标记都不变）；
+    /// 分类文件已存在时合并，ID 相同的记录以存储中的为准。存储本身不会被修改
+    ///
+    /// # 参数
+    /// * `config` - 配置（提供分类映射）
+    ///
+    /// # 返回
+    /// (分类名, 分类文件路径, 从存储拆分出的记录数) 列表（按分类名排序）
+    pub fn split_by_category(&self, config: &Config) -> io::Result<Vec<(String, PathBuf, usize)>> {
+        self.flush()?;
+        let _lock = self.lock()?;
+        let records = self.load()?;
+
+        let mut categories: BTreeMap<String, Vec<MemoryRecord>> = BTreeMap::new();
+        for r in records {
+            categories.entry(get_category_for_tags(config, &r.tags)).or_default().push(r);
+        }
+
+        let dir = self.store_dir();
+        let mut written = Vec::new();
+        for (category, split) in categories {
+            let file_path = category_file_path(&dir.to_string_lossy(), &category);
+            // 目录模式下 lock() 已持有所有分类文件的锁
+            let _category_lock = match self.directory_config {
+                Some(_) => None,
+                None => Some(acquire_lock_with_cleanup(&category_lock_path(&file_path), None, Some(300))?),
+            };
+            adopt_legacy_category_file(&file_path)?;
+
+            let mut merged: Vec<MemoryRecord> = if file_path.exists() {
+                let raw = fs::read_to_string(&file_path)?;
+                if raw.trim().is_empty() {
+                    Vec::new()
+                } else {
+                    serde_json::from_str(&raw)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", file_path.display(), e)))?
+                }
+            } else {
+                Vec::new()
+            };
+            merged.retain(|existing| !split.iter().any(|r| r.id == existing.id));
+            let count = split.len();
+            merged.extend(split);
+
+            atomic_write(&file_path, &merged)?;
+            written.push((category, file_path, count));
+        }
+
+        Ok(written)
+    }
+
+    /// 从 JSON 导入记忆
+    ///
+    /// # 参数
+    /// * `json_data` - JSON 格式的记忆数据
+    ///
+    /// # 返回
+    /// (成功数量, 跳过数量, 失败数量)
+    pub fn import_json(&self, json_data: &str) -> io::Result<(usize, usize, usize)> {
+        let report = self.import_json_with_report(json_data, false, false)?;
+        Ok((report.success, report.skipped, report.failed))
+    }
+
+    /// 从 JSON 导入记忆并返回详细结果
+    ///
+    /// # 参数
+    /// * `json_data` - JSON 格式的记忆数据
+    /// * `simulate` - 模拟模式：只统计将会产生的结果，不写入磁盘
+    /// * `strict` - 严格模式：任意一条记录格式错误时整个导入失败；
+    ///   否则逐条解析，格式错误的记录计入失败数量，其余记录照常导入
+    ///
+    /// # 返回
+    /// 导入结果（包括被跳过的重复 ID 和格式错误的记录）
+    pub fn import_json_with_report(&self, json_data: &str, simulate: bool, strict: bool) -> io::Result<ImportReport> {
+        let mut report = ImportReport {
+            simulated: simulate,
+            ..ImportReport::default()
+        };
+
+        let imported = if strict {
+            parse_records(json_data)?
+        } else {
+            let mut parsed = Vec::new();
+            for (index, value) in parse_record_values(json_data)?.into_iter().enumerate() {
+                match serde_json::from_value::<MemoryRecord>(value) {
+                    Ok(record) => parsed.push(record),
+                    Err(e) => {
+                        report.failed += 1;
+                        report.malformed.push(format!("#{}: {}", index + 1, e));
+                    }
+                }
+            }
+            parsed
+        };
+
+        let candidates: Vec<io::Result<MemoryRecord>> = imported.into_iter()
+            .map(|mut rec| {
+                rec.created_at = now_iso();
+                rec.updated_at = now_iso();
+                // 保留导出文件中已有的来源，只为没有来源的记录标记为 JSON 导入
+                if rec.source.is_none() {
+                    rec.source = Some(SOURCE_IMPORT_JSON.to_string());
+                }
+                Ok(rec)
+            })
+            .collect();
+        let ids: Vec<String> = candidates.iter().flatten().map(|r| r.id.clone()).collect();
+
+        // 与 add_many 走同一条批量写入路径；导入文件中重复出现的 ID 也按已存在处理，只导入第一条
+        for (id, result) in ids.into_iter().zip(self.append_many(candidates, simulate)?) {
+            match result {
+                Ok(_) => report.success += 1,
+                Err(_) => {
+                    report.skipped += 1;
+                    report.skipped_ids.push(id);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// 一次性规范化整个存储
+    /// 在锁内迁移 camelCase 字段、规范化标签、重新提取关键词、统一时间戳格式，
+    /// 有修改时只原子重写一次
+    ///
+    /// # 返回
+    /// (记录总数, 被修改的记录数)
+    pub fn tidy(&self) -> io::Result<(usize, usize)> {
+        self.flush()?;
+        self.check()?;
+        let _lock = self.lock()?;
+        if !self.memory_path.exists() {
+            return Ok((0, 0));
+        }
+
+        let values = if self.directory_config.is_some() {
+            // 分类文件由本工具写入，不含旧版字段，直接按规范记录读取
+            self.read_file()?
+                .into_iter()
+                .map(|r| serde_json::to_value(r).map_err(io::Error::other))
+                .collect::<io::Result<Vec<_>>>()?
+        } else {
+            let raw = read_store_text(&self.memory_path)?;
+            if raw.trim().is_empty() {
+                return Ok((0, 0));
+            }
+            parse_record_values(&raw)?
+        };
+        let total = values.len();
+        let mut changed_ids: Vec<String> = Vec::new();
+        let mut records = Vec::with_capacity(total);
+        for value in values {
+            let (mut record, migrated) = migrate_legacy_value(value)?;
+            if tidy_record(&mut record, &self.tag_implications) || migrated {
+                changed_ids.push(record.id.clone());
+            }
+            records.push(record);
+        }
+        let changed = changed_ids.len();
+
+        if changed > 0 {
+            self.save(&records)?;
+            self.audit(AuditOp::Update, records.iter().filter(|r| changed_ids.contains(&r.id)));
+        }
+
+        Ok((total, changed))
+    }
+
+    /// 获取记忆文件路径
+    ///
+    /// # 返回
+    /// 记忆文件路径
+    pub fn get_memory_path(&self) -> &std::path::Path {
+        &self.memory_path
+    }
+
+    /// 获取带这些标签的新记忆会写入的文件
+    /// 目录模式下是按标签（合并默认标签和隐含标签后）分配的分类文件，否则是记忆文件本身
+    ///
+    /// # 参数
+    /// * `tags` - 新记忆的标签
+    ///
+    /// # 返回
+    /// 目标文件路径
+    pub fn target_file_for_tags(&self, tags: &[String]) -> PathBuf {
+        match &self.directory_config {
+            Some(config) => {
+                let tags = expand_implied_tags(merge_tags(tags.to_vec(), &self.default_tags), &self.tag_implications);
+                category_file_path(&self.memory_path.to_string_lossy(), &get_category_for_tags(config, &tags))
+            }
+            None => self.memory_path.clone(),
+        }
+    }
+
+    /// 获取锁文件路径
+    ///
+    /// # 返回
+    /// 锁文件路径
+    pub fn get_lock_path(&self) -> &std::path::Path {
+        &self.lock_path
+    }
+
+    /// 获取锁类型
     ///
     /// # 返回
     /// 锁类型
@@ -292,6 +2343,109 @@ impl MemoryStore {
     }
 }
 
+impl Drop for MemoryStore {
+    fn drop(&mut self) {
+        // 先停止后台刷新线程，再把剩余的缓冲写入磁盘
+        if let Some(buffer) = &self.write_buffer {
+            if let Some(flusher) = buffer.flusher.lock().unwrap().take() {
+                flusher.shutdown();
+            }
+        }
+        if let Err(e) = self.flush() {
+            eprintln!("Failed to flush buffered memories: {}", e);
+        }
+    }
+}
+
+/// 命名空间标签前缀
+pub const NAMESPACE_TAG_PREFIX: &str = "proj:";
+
+/// 生成命名空间对应的标签（`proj:<namespace>`）
+/// 命名空间只是普通标签上的约定，不改变存储格式
+///
+/// # 参数
+/// * `namespace` - 命名空间名称
+///
+/// # 返回
+/// 规范化后的命名空间标签
+pub fn namespace_tag(namespace: &str) -> String {
+    format!("{}{}", NAMESPACE_TAG_PREFIX, namespace.trim().to_lowercase())
+}
+
+/// 校验记忆目录路径（整理、分类文件等按目录使用记忆路径的场景）
+///
+/// # 参数
+/// * `path` - 记忆目录路径
+///
+/// # 返回
+/// 路径不存在或是目录时返回 Ok；指向文件时返回明确的错误
+pub fn check_memory_dir(path: &Path) -> io::Result<()> {
+    if path.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Memory directory path points to a file, expected a directory of *-global-gmem-recorder.json files: {}",
+                path.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// 搜索结果的排序规则：分数降序；分数相同时按 `updated_at` 降序，再按 `id` 升序
+/// 保证相同数据在每次写入后都得到相同的顺序（压缩的预算截断也依赖此顺序）
+///
+/// # 参数
+/// * `a` - 搜索命中
+/// * `b` - 搜索命中
+///
+/// # 返回
+/// 排序关系
+pub(crate) fn compare_hits(a: &SearchHit, b: &SearchHit) -> std::cmp::Ordering {
+    b.score.partial_cmp(&a.score)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| b.updated_at.cmp(&a.updated_at))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+/// 判断记录是否为临时记忆（带 `temp` 标签）
+///
+/// # 参数
+/// * `r` - 记忆记录
+///
+/// # 返回
+/// 是否为临时记忆
+pub fn is_temp(r: &MemoryRecord) -> bool {
+    r.tags.iter().any(|t| t == TEMP_TAG)
+}
+
+/// 创建目录（如不存在）并通过写入临时文件确认其可写
+///
+/// # 参数
+/// * `dir` - 目录路径
+///
+/// # 返回
+/// 目录可用时返回 Ok；无法创建或不可写时返回包含路径和原因的错误
+pub fn ensure_writable_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir).map_err(|e| io::Error::new(
+        e.kind(),
+        format!("Cannot create memory directory {}: {}", dir.display(), e),
+    ))?;
+
+    let probe = dir.join(format!(".gmem-write-test-{}", std::process::id()));
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&probe)
+        .map_err(|e| io::Error::new(
+            e.kind(),
+            format!("Memory directory is not writable {}: {}", dir.display(), e),
+        ))?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
 /// 规范化标签为小写、修剪、唯一值
 fn normalize_tags(tags: Option<Vec<String>>) -> Vec<String> {
     match tags {
@@ -309,8 +2463,125 @@ fn normalize_tags(tags: Option<Vec<String>>) -> Vec<String> {
     }
 }
 
+/// 计算从指定时刻起经过的毫秒数
+pub(crate) fn elapsed_ms(started: Instant) -> f64 {
+    started.elapsed().as_secs_f64() * 1000.0
+}
+
+/// 按幂等键查找记录
+///
+/// # 参数
+/// * `records` - 记忆记录数组
+/// * `key` - 幂等键
+///
+/// # 返回
+/// 带有该幂等键的记录（可选）
+fn find_by_idempotency_key(records: &[MemoryRecord], key: &str) -> Option<MemoryRecord> {
+    records.iter().find(|r| r.idempotency_key.as_deref() == Some(key)).cloned()
+}
+
+/// 合并标签与默认标签（规范化并去重）
+///
+/// # 参数
+/// * `tags` - 用户提供的标签
+/// * `default_tags` - 默认标签
+///
+/// # 返回
+/// 合并后的标签
+pub(crate) fn merge_tags(mut tags: Vec<String>, default_tags: &[String]) -> Vec<String> {
+    tags.extend(default_tags.iter().cloned());
+    normalize_tags(Some(tags))
+}
+
+/// 按蕴含关系补全隐含标签（传递展开，结果规范化并去重）
+///
+/// # 参数
+/// * `tags` - 原始标签
+/// * `implications` - 标签到其隐含标签的映射
+///
+/// # 返回
+/// 包含所有隐含标签的标签列表
+pub fn expand_implied_tags(tags: Vec<String>, implications: &HashMap<String, Vec<String>>) -> Vec<String> {
+    if implications.is_empty() {
+        return tags;
+    }
+
+    let mut expanded = normalize_tags(Some(tags));
+    let mut i = 0;
+    while i < expanded.len() {
+        if let Some(implied) = implications.get(&expanded[i]) {
+            for tag in normalize_tags(Some(implied.clone())) {
+                if !expanded.contains(&tag) {
+                    expanded.push(tag);
+                }
+            }
+        }
+        i += 1;
+    }
+    expanded
+}
+
+/// 未指定记忆路径时是否向上查找已有的存储（`--no-discover` 关闭）
+static MEMORY_DISCOVERY_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// 设置未指定记忆路径时是否向上查找已有的存储
+///
+/// # 参数
+/// * `enabled` - 是否启用向上查找（默认启用）
+pub fn set_memory_discovery(enabled: bool) {
+    MEMORY_DISCOVERY_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// 从指定目录开始逐级向上查找已有的 `.copilot-memory.json`（类似 git 查找 `.git`）
+///
+/// # 参数
+/// * `start` - 开始查找的目录
+///
+/// # 返回
+/// 找到的记忆文件路径，一直到根目录都没有时返回 None
+pub fn discover_memory_path(start: &Path) -> Option<PathBuf> {
+    start.ancestors()
+        .map(|dir| dir.join(DEFAULT_MEMORY_PATH))
+        .find(|candidate| candidate.is_file())
+}
+
+/// 读取目录模式下所有分类文件（两种拼写）中的记录
+/// 同一 ID 出现在多个分类文件中时全部保留，由 doctor 报告并修复重复 ID；
+/// 只去掉内容完全相同的副本（如旧拼写文件中残留的同一条记录），不会丢失数据
+///
+/// # 参数
+/// * `dir` - 分类文件所在目录
+///
+/// # 返回
+/// 所有分类文件中的记录；任一文件无法解析时返回错误
+fn read_category_dir(dir: &Path) -> io::Result<Vec<MemoryRecord>> {
+    let mut records: Vec<MemoryRecord> = Vec::new();
+    let mut seen_ids: std::collections::HashSet<String> = std::collections::HashSet::new();
+    for (_, path) in list_category_files(&dir.to_string_lossy()) {
+        for record in read_category_file(&path)? {
+            if !seen_ids.insert(record.id.clone()) {
+                let value = serde_json::to_value(&record).map_err(io::Error::other)?;
+                let identical = records.iter()
+                    .filter(|r| r.id == record.id)
+                    .any(|r| serde_json::to_value(r).ok().as_ref() == Some(&value));
+                if identical {
+                    continue;
+                }
+            }
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
 /// 解析记忆文件路径
+/// 未指定路径时先向上查找已有的存储，找不到再使用当前目录下的 `.copilot-memory.json`
 fn resolve_memory_path(p: Option<&str>) -> PathBuf {
+    if p.is_none() && MEMORY_DISCOVERY_ENABLED.load(Ordering::Relaxed) {
+        if let Some(found) = std::env::current_dir().ok().and_then(|cwd| discover_memory_path(&cwd)) {
+            return found;
+        }
+    }
     let raw = p.unwrap_or(DEFAULT_MEMORY_PATH).trim();
     let path = Path::new(raw);
     if path.is_absolute() {
@@ -320,72 +2591,358 @@ fn resolve_memory_path(p: Option<&str>) -> PathBuf {
     }
 }
 
+/// 旧版 camelCase 字段名到规范 snake_case 字段名的映射
+const LEGACY_FIELDS: [(&str, &str); 3] = [
+    ("createdAt", "created_at"),
+    ("updatedAt", "updated_at"),
+    ("deletedAt", "deleted_at"),
+];
+
+/// 将旧版 camelCase 记忆文件迁移为规范的 snake_case 格式
+/// 一次性重写整个文件，使混合格式的存储保持一致；
+/// 旧版记录缺少的 keywords 会根据文本重新提取，缺少的 tags 补为空数组
+///
+/// # 参数
+/// * `path` - 记忆文件路径
+///
+/// # 返回
+/// (记录总数, 被迁移的记录数)
+pub fn migrate_legacy_file(path: &Path) -> io::Result<(usize, usize)> {
+    let lock_path = resolve_lock_path(path);
+    let _lock = acquire_store_lock(&lock_path, LockType::Cli, None, Some(300))?;
+
+    let raw = read_store_text(path)?;
+    let values: Vec<serde_json::Value> = serde_json::from_str(&raw)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let total = values.len();
+    let mut migrated = 0;
+    let mut records = Vec::with_capacity(total);
+    for value in values {
+        let (record, changed) = migrate_legacy_value(value)?;
+        if changed {
+            migrated += 1;
+        }
+        records.push(record);
+    }
+
+    if migrated > 0 {
+        atomic_write(path, &records)?;
+    }
+
+    Ok((total, migrated))
+}
+
+/// 将单条旧版记录（JSON 值）转换为规范记录
+///
+/// # 参数
+/// * `value` - 记录的 JSON 值
+///
+/// # 返回
+/// (规范记录, 是否做了迁移)
+fn migrate_legacy_value(mut value: serde_json::Value) -> io::Result<(MemoryRecord, bool)> {
+    let obj = value.as_object_mut().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Memory record is not a JSON object")
+    })?;
+
+    let mut changed = false;
+    for (legacy, canonical) in LEGACY_FIELDS {
+        if let Some(v) = obj.remove(legacy) {
+            obj.entry(canonical).or_insert(v);
+            changed = true;
+        }
+    }
+    if !obj.contains_key("tags") {
+        obj.insert("tags".to_string(), serde_json::json!([]));
+        changed = true;
+    }
+    if !obj.contains_key("keywords") {
+        let text = obj.get("text").and_then(|t| t.as_str()).unwrap_or("");
+        obj.insert("keywords".to_string(), serde_json::json!(extract_keywords(text)));
+        changed = true;
+    }
+
+    let record: MemoryRecord = serde_json::from_value(value)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok((record, changed))
+}
+
+/// 规范化单条记录：标签小写去重排序并补全隐含标签、按文本重新提取关键词、时间戳统一为上海时区格式
+///
+/// # 参数
+/// * `record` - 记忆记录
+/// * `implications` - 标签蕴含关系
+///
+/// # 返回
+/// 记录是否被修改
+fn tidy_record(record: &mut MemoryRecord, implications: &HashMap<String, Vec<String>>) -> bool {
+    let mut changed = false;
+
+    let mut tags = expand_implied_tags(normalize_tags(Some(record.tags.clone())), implications);
+    tags.sort();
+    if tags != record.tags {
+        record.tags = tags;
+        changed = true;
+    }
+
+    // 关键词提取结果的顺序不固定，按集合比较
+    let mut keywords = extract_keywords(&record.text);
+    keywords.sort();
+    let mut existing = record.keywords.clone();
+    existing.sort();
+    if keywords != existing {
+        record.keywords = keywords;
+        changed = true;
+    }
+
+    for ts in [&mut record.created_at, &mut record.updated_at] {
+        if let Some(canonical) = canonicalize_iso(ts) {
+            if canonical != *ts {
+                *ts = canonical;
+                changed = true;
+            }
+        }
+    }
+    if let Some(canonical) = record.deleted_at.as_deref().and_then(canonicalize_iso) {
+        if record.deleted_at.as_deref() != Some(canonical.as_str()) {
+            record.deleted_at = Some(canonical);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// 包装对象中可存放记录数组的字段名
+const WRAPPED_RECORD_FIELDS: [&str; 2] = ["records", "memories"];
+
+/// 把记忆 JSON 文本解析为原始 JSON 值列表（接受与 `parse_records` 相同的两种形式）
+///
+/// # 参数
+/// * `raw` - JSON 文本
+///
+/// # 返回
+/// 每条记录的 JSON 值
+fn parse_record_values(raw: &str) -> io::Result<Vec<serde_json::Value>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    match serde_json::from_str::<serde_json::Value>(raw).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))? {
+        serde_json::Value::Array(values) => Ok(values),
+        serde_json::Value::Object(mut object) => {
+            for field in WRAPPED_RECORD_FIELDS {
+                if let Some(serde_json::Value::Array(values)) = object.remove(field) {
+                    return Ok(values);
+                }
+            }
+            Err(invalid("Expected an array of records or an object with a records/memories array"))
+        }
+        _ => Err(invalid("Expected an array of records or an object with a records/memories array")),
+    }
+}
+
+/// 解析记忆 JSON 文本
+///
+/// 接受两种形式：
+/// * 裸数组：`[{...}, {...}]`
+/// * 包装对象：`{"records": [...]}` 或 `{"memories": [...]}`（其他工具的导出格式）
+///
+/// # 参数
+/// * `raw` - JSON 文本
+///
+/// # 返回
+/// 记忆记录列表，两种形式都无法解析时返回裸数组的解析错误
+pub fn parse_records(raw: &str) -> io::Result<Vec<MemoryRecord>> {
+    let array_err = match serde_json::from_str::<Vec<MemoryRecord>>(raw) {
+        Ok(records) => return Ok(records),
+        Err(e) => e,
+    };
+
+    if let Ok(serde_json::Value::Object(mut object)) = serde_json::from_str::<serde_json::Value>(raw) {
+        for field in WRAPPED_RECORD_FIELDS {
+            if let Some(value) = object.remove(field) {
+                return serde_json::from_value(value)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, array_err))
+}
+
 /// 解析锁文件路径
-/// 解析锁文件路径
+/// 所有锁类型共用同一个锁文件，保证 CLI、交互模式和 MCP 服务器的写入互相排斥
 ///
 /// # 参数
 /// * `memory_path` - 记忆文件路径
-/// * `lock_type` - 锁文件类型
 ///
 /// # 返回
 /// 锁文件路径
-fn resolve_lock_path(memory_path: &Path, lock_type: LockType) -> PathBuf {
-    let lock_name = format!(".copilot-memory{}", lock_type.suffix());
-    memory_path.parent().unwrap().join(lock_name)
+pub(crate) fn resolve_lock_path(memory_path: &Path) -> PathBuf {
+    memory_path.parent().unwrap().join(STORE_LOCK_FILE)
+}
+
+/// gzip 文件头魔数
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// 判断记忆文件路径是否使用 gzip 压缩（`.gz` 扩展名）
+fn is_gzip_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+/// 读取记忆文件内容，`.gz` 扩展名或 gzip 魔数的文件会透明解压
+///
+/// # 参数
+/// * `path` - 记忆文件路径
+///
+/// # 返回
+/// 文件的 JSON 文本
+fn read_store_text(path: &Path) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    if is_gzip_path(path) || bytes.starts_with(&GZIP_MAGIC) {
+        let mut text = String::new();
+        GzDecoder::new(bytes.as_slice()).read_to_string(&mut text)?;
+        return Ok(text);
+    }
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
 /// 使用临时文件 + 重命名模式原子性写入
-fn atomic_write(path: &Path, data: &Vec<MemoryRecord>) -> io::Result<()> {
+/// 路径以 `.gz` 结尾时写入 gzip 压缩内容
+pub(crate) fn atomic_write(path: &Path, data: &[MemoryRecord]) -> io::Result<()> {
+    atomic_write_with_backup(path, data, None)
+}
+
+/// 原子性写入，可选在替换前备份原文件
+///
+/// # 参数
+/// * `path` - 目标文件
+/// * `data` - 要写入的记录
+/// * `backup_keep` - 替换前把已有文件备份为 `.bak` 并只保留最近的这么多个（None 表示不备份）
+///
+/// # 返回
+/// 写入结果；备份失败时不替换原文件
+pub(crate) fn atomic_write_with_backup(path: &Path, data: &[MemoryRecord], backup_keep: Option<usize>) -> io::Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
 
-    let tmp_path = format!("{}.tmp.{}.tmp", path.display(), std::process::id());
+    let tmp_path = format!("{}.tmp.{}.{:04x}.tmp", path.display(), std::process::id(), fastrand::u16(..));
     let tmp = Path::new(&tmp_path);
 
     let json = serde_json::to_string_pretty(data)
         .map_err(io::Error::other)?;
 
-    fs::write(tmp, json)?;
+    if is_gzip_path(path) {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(json.as_bytes())?;
+        fs::write(tmp, encoder.finish()?)?;
+    } else {
+        fs::write(tmp, json)?;
+    }
+    if let Some(keep) = backup_keep.filter(|_| path.is_file()) {
+        let backed_up = backup_file(path, None).and_then(|_| prune_backups(path, keep));
+        if let Err(e) = backed_up {
+            let _ = fs::remove_file(tmp);
+            return Err(e);
+        }
+    }
     fs::rename(tmp, path)?;
 
     Ok(())
 }
 
+/// 默认的高亮标记（Markdown 粗体）
+pub const DEFAULT_HIGHLIGHT_MARKERS: (&str, &str) = ("**", "**");
+
+/// 用标记包裹文本中与查询词匹配的片段
+/// 匹配方式与 `score_record` 相同（不区分大小写的子串）；重叠或相邻的匹配合并为一段，不会产生嵌套或断开的标记
+///
+/// # 参数
+/// * `text` - 要高亮的文本
+/// * `query` - 搜索查询（空格分隔的关键词）
+/// * `open` - 匹配片段前插入的标记
+/// * `close` - 匹配片段后插入的标记
+///
+/// # 返回
+/// 带高亮标记的文本（查询为空时原样返回）
+pub fn highlight_matches(text: &str, query: &str, open: &str, close: &str) -> String {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for token in query.split_whitespace() {
+        let re = regex::Regex::new(&format!(r"(?i){}", regex::escape(token))).unwrap();
+        spans.extend(re.find_iter(text).map(|m| (m.start(), m.end())));
+    }
+    spans.sort();
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let mut out = String::with_capacity(text.len() + merged.len() * (open.len() + close.len()));
+    let mut pos = 0;
+    for (start, end) in merged {
+        out.push_str(&text[pos..start]);
+        out.push_str(open);
+        out.push_str(&text[start..end]);
+        out.push_str(close);
+        pos = end;
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
 /// 计算记录相对于查询的相关性分数
 ///
-/// 评分公式：
-/// - 文本中每个关键词匹配 +5 分
-/// - 每个标签匹配 +8 分
-/// - 每个提取的关键词匹配 +6 分
-/// - 时效性 +0-5 分（更新 = 更高）
+/// 评分公式（括号内为默认权重）：
+/// - 文本中每个关键词匹配 +text_hit 分（5）
+/// - 每个标签匹配 +tag_hit 分（8）
+/// - 每个提取的关键词匹配 +keyword_hit 分（6）
+/// - 时效性 +0-recency_max 分（5，更新 = 更高）
 ///
 /// # 参数
 /// * `r` - 要评分的记忆记录
 /// * `query` - 搜索查询
+/// * `weights` - 评分权重
 ///
 /// # 返回
 /// 数值相关性分数（0 = 无匹配）
-pub fn score_record(r: &MemoryRecord, query: &str) -> f64 {
+pub fn score_record(r: &MemoryRecord, query: &str, weights: &ScoringWeights) -> f64 {
+    score_breakdown(r, query, weights).total()
+}
+
+/// 计算记录相对于查询的相关性分数明细
+/// 各项之和等于 `score_record` 的返回值
+///
+/// # 参数
+/// * `r` - 要评分的记忆记录
+/// * `query` - 搜索查询
+/// * `weights` - 评分权重
+///
+/// # 返回
+/// 分数明细（查询为空时各项均为 0）
+pub fn score_breakdown(r: &MemoryRecord, query: &str, weights: &ScoringWeights) -> ScoreBreakdown {
     let q = query.trim().to_lowercase();
     if q.is_empty() {
-        return 0.0;
+        return ScoreBreakdown::default();
     }
 
     let text = r.text.to_lowercase();
-    let mut score = 0.0;
+    let mut breakdown = ScoreBreakdown::default();
 
     for token in q.split_whitespace() {
         let re = regex::Regex::new(&format!(r"(?i){}", regex::escape(token))).unwrap();
         let hits = re.find_iter(&text).count();
-        score += hits as f64 * 5.0;
+        breakdown.text_hits += hits;
+        breakdown.text_score += hits as f64 * weights.text_hit;
 
         if r.tags.iter().any(|t| t.to_lowercase() == token) {
-            score += 8.0;
+            breakdown.tag_score += weights.tag_hit;
         }
 
         if r.keywords.iter().any(|k| k == token) {
-            score += 6.0;
+            breakdown.keyword_score += weights.keyword_hit;
         }
     }
 
@@ -399,8 +2956,1169 @@ pub fn score_record(r: &MemoryRecord, query: &str) -> f64 {
         .abs();
 
     let days = age_ms as f64 / (1000.0 * 60.0 * 60.0 * 24.0);
-    let recency = (5.0 - (days / 30.0).min(5.0)).max(0.0);
-    score += recency;
+    let span = weights.recency_halflife_days * 2.0;
+    let remaining = if span > 0.0 { 1.0 - (days / span).min(1.0) } else { 0.0 };
+    breakdown.recency = (weights.recency_max * remaining).max(0.0);
+
+    breakdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造测试记录（时间戳固定在很久以前，时效性得分为 0）
+    fn record(text: &str, tags: &[&str]) -> MemoryRecord {
+        MemoryRecord {
+            id: make_id_with_prefix(DEFAULT_ID_PREFIX),
+            text: text.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            keywords: extract_keywords(text),
+            created_at: "2000-01-01T00:00:00.000Z".to_string(),
+            updated_at: "2000-01-01T00:00:00.000Z".to_string(),
+            deleted_at: None,
+            idempotency_key: None,
+            source: None,
+        }
+    }
+
+    /// 在临时目录中创建写入了指定记录的存储
+    fn store_with(dir: &Path, records: &[MemoryRecord]) -> MemoryStore {
+        let path = dir.join("memory.json");
+        atomic_write(&path, records).unwrap();
+        MemoryStore::new(path.to_str(), Some(LockType::Cli))
+    }
+
+    #[test]
+    fn score_breakdown_sums_to_score() {
+        let weights = ScoringWeights::default();
+        let r = record("rust lock and more rust", &["rust"]);
+        let breakdown = score_breakdown(&r, "rust lock", &weights);
+
+        assert_eq!(breakdown.text_hits, 3);
+        assert_eq!(breakdown.text_score, 3.0 * weights.text_hit);
+        assert_eq!(breakdown.tag_score, weights.tag_hit);
+        assert_eq!(breakdown.keyword_score, 2.0 * weights.keyword_hit);
+        assert_eq!(breakdown.recency, 0.0);
+        assert_eq!(
+            breakdown.text_score + breakdown.tag_score + breakdown.keyword_score + breakdown.recency,
+            score_record(&r, "rust lock", &weights)
+        );
+    }
+
+    #[test]
+    fn tag_cooccurrence_counts_pairs() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[
+            record("one", &["rust", "cli", "lock"]),
+            record("two", &["rust", "cli"]),
+            record("three", &["rust"]),
+            record("four", &[]),
+        ]);
+
+        let pairs = store.tag_cooccurrence().unwrap();
+        let count = |a: &str, b: &str| pairs.get(&(a.to_string(), b.to_string())).copied();
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(count("cli", "rust"), Some(2));
+        assert_eq!(count("cli", "lock"), Some(1));
+        assert_eq!(count("lock", "rust"), Some(1));
+    }
+
+    #[test]
+    fn check_rejects_directory_and_file_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let as_dir = MemoryStore::new(dir.path().to_str(), Some(LockType::Cli));
+        let err = as_dir.check().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains(DEFAULT_MEMORY_PATH));
+        assert!(as_dir.load().is_err());
+
+        let file = dir.path().join("memory.json");
+        fs::write(&file, "[]").unwrap();
+        let under_file = MemoryStore::new(file.join("nested.json").to_str(), Some(LockType::Cli));
+        assert_eq!(under_file.check().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        let ok = MemoryStore::new(file.to_str(), Some(LockType::Cli));
+        assert!(ok.check().is_ok());
+        assert!(ok.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn export_markdown_writes_one_file_per_category() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut deleted = record("gone", &["rust"]);
+        deleted.deleted_at = Some(now_iso());
+        let store = store_with(dir.path(), &[
+            record("borrow checker", &["rust"]),
+            record("cargo features", &["rust"]),
+            record("rebase", &["git"]),
+            record("untagged", &[]),
+            deleted,
+        ]);
+
+        let out = dir.path().join("md");
+        let written = store.export_markdown_by_category(&Config::default(), &out).unwrap();
+        let names: Vec<String> = written.iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec!["default.md", "git.md", "rust.md"]);
+
+        let headings = |name: &str| fs::read_to_string(out.join(name)).unwrap().matches("\n## ").count();
+        assert_eq!(headings("default.md"), 1);
+        assert_eq!(headings("git.md"), 1);
+        assert_eq!(headings("rust.md"), 2);
+    }
+
+    #[test]
+    fn buffered_adds_are_written_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.json");
+        let store = MemoryStore::new(path.to_str(), Some(LockType::Cli)).with_write_buffer(3, None);
+
+        store.add_memory("first", None).unwrap();
+        store.add_memory("second", None).unwrap();
+        assert!(!path.exists());
+        assert_eq!(store.pending_count(), 2);
+        assert_eq!(store.load().unwrap().len(), 2);
+
+        store.add_memory("third", None).unwrap();
+        assert_eq!(store.pending_count(), 0);
+        assert_eq!(parse_records(&fs::read_to_string(&path).unwrap()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn add_many_reports_each_item_and_writes_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[record("existing note", &[])]).with_backup_before_write(Some(5));
+        let items = vec![
+            ("rust note".to_string(), vec!["rust".to_string()]),
+            ("   ".to_string(), vec!["rust".to_string()]),
+            ("git note".to_string(), Vec::new()),
+        ];
+
+        let results = store.add_many_with_source(&items, Some("import-json")).unwrap();
+        assert_eq!(results.len(), 3);
+        let added: Vec<&MemoryRecord> = results.iter().filter_map(|r| r.as_ref().ok()).collect();
+        assert_eq!(added.iter().map(|r| r.text.as_str()).collect::<Vec<_>>(), ["rust note", "git note"]);
+        assert!(added.iter().all(|r| r.source.as_deref() == Some("import-json") && !r.keywords.is_empty()));
+        assert_eq!(results[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        // 无效项不影响其余记录，整批只写入一次（只产生一个写入前备份）
+        let texts: Vec<String> = store.load().unwrap().into_iter().map(|r| r.text).collect();
+        assert_eq!(texts, ["existing note", "rust note", "git note"]);
+        let backups = fs::read_dir(dir.path()).unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().ends_with(".bak"))
+            .count();
+        assert_eq!(backups, 1);
+        assert_eq!(store.metrics().adds, 2);
+
+        // 全部无效时不写入
+        let results = store.add_many(&[(String::new(), Vec::new())]).unwrap();
+        assert!(results[0].is_err());
+        assert_eq!(store.load().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn flush_and_drop_keep_every_buffered_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.json");
+        let store = MemoryStore::new(path.to_str(), Some(LockType::Cli)).with_write_buffer(100, None);
+        for i in 0..5 {
+            store.add_memory(&format!("memory {}", i), None).unwrap();
+        }
+        assert_eq!(store.flush().unwrap(), 5);
+        assert_eq!(store.flush().unwrap(), 0);
+
+        store.add_memory("written on drop", None).unwrap();
+        drop(store);
+        let texts: Vec<String> = MemoryStore::new(path.to_str(), Some(LockType::Cli))
+            .load()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.text)
+            .collect();
+        assert_eq!(texts.len(), 6);
+        assert_eq!(texts.last().map(String::as_str), Some("written on drop"));
+    }
+
+    #[test]
+    fn background_flush_writes_idle_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.json");
+        let store = MemoryStore::new(path.to_str(), Some(LockType::Cli))
+            .with_write_buffer(100, Some(Duration::from_millis(50)));
+        store.add_memory("idle", None).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while store.pending_count() > 0 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(store.pending_count(), 0);
+        assert_eq!(parse_records(&fs::read_to_string(&path).unwrap()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn migrate_rewrites_camel_case_as_snake_case() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.json");
+        fs::write(&path, r#"[
+            {"id": "m_legacy", "text": "legacy rust note", "createdAt": "2024-01-01T00:00:00.000Z", "updatedAt": "2024-01-02T00:00:00.000Z"},
+            {"id": "m_current", "text": "current", "tags": [], "keywords": [], "created_at": "2024-01-01T00:00:00.000Z", "updated_at": "2024-01-01T00:00:00.000Z"}
+        ]"#).unwrap();
+
+        assert_eq!(migrate_legacy_file(&path).unwrap(), (2, 1));
+
+        let raw = fs::read_to_string(&path).unwrap();
+        for legacy in ["createdAt", "updatedAt", "deletedAt"] {
+            assert!(!raw.contains(legacy));
+        }
+        let values: Vec<serde_json::Value> = serde_json::from_str(&raw).unwrap();
+        assert_eq!(values[0]["created_at"], "2024-01-01T00:00:00.000Z");
+        assert_eq!(values[0]["updated_at"], "2024-01-02T00:00:00.000Z");
+        assert_eq!(values[0]["tags"], serde_json::json!([]));
+        assert!(values[0]["keywords"].as_array().unwrap().iter().any(|k| k == "rust"));
+
+        assert_eq!(migrate_legacy_file(&path).unwrap(), (2, 0));
+    }
+
+    #[test]
+    fn purge_deleted_removes_only_soft_deleted_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut gone = record("gone", &["rust"]);
+        gone.deleted_at = Some(now_iso());
+        let mut also_gone = record("also gone", &[]);
+        also_gone.deleted_at = Some(now_iso());
+        let store = store_with(dir.path(), &[
+            record("kept", &["rust"]),
+            gone,
+            record("gone too but active", &[]),
+            also_gone,
+        ]);
+
+        assert_eq!(store.purge_deleted().unwrap(), 2);
+        let texts: Vec<String> = store.load().unwrap().into_iter().map(|r| r.text).collect();
+        assert_eq!(texts, vec!["kept", "gone too but active"]);
+        assert_eq!(store.purge_deleted().unwrap(), 0);
+    }
+
+    #[test]
+    fn metrics_count_operations_and_lock_waits() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[]);
+        let first = store.add_memory("rust metrics", None).unwrap();
+        store.add_memory("more rust", None).unwrap();
+        store.search("rust", None).unwrap();
+        store.soft_delete(&first.id).unwrap();
+
+        let held = store.lock().unwrap();
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(held);
+        });
+        store.add_memory("after wait", None).unwrap();
+        release.join().unwrap();
+
+        let metrics = store.metrics();
+        assert_eq!(metrics.adds, 3);
+        assert_eq!(metrics.searches, 1);
+        assert_eq!(metrics.deletes, 1);
+        assert_eq!(metrics.lock_waits, 1);
+        assert!(metrics.lock_wait_ms >= 50);
+        assert_eq!(metrics.lock_timeouts, 0);
+    }
+
+    #[test]
+    fn default_tags_are_merged_without_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            default_tags: Some(vec!["source:cli".to_string(), "Team".to_string()]),
+            ..Config::default()
+        };
+        let store = store_with(dir.path(), &[]).apply_config(&config);
+
+        let mut plain = store.add_memory("no tags", None).unwrap().tags;
+        plain.sort();
+        assert_eq!(plain, vec!["source:cli", "team"]);
+
+        let mut overlapping = store.add_memory("overlap", Some(vec!["team".to_string(), "rust".to_string()])).unwrap().tags;
+        overlapping.sort();
+        assert_eq!(overlapping, vec!["rust", "source:cli", "team"]);
+
+        assert!(store_with(dir.path(), &[]).add_memory("off by default", None).unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn idempotency_key_returns_the_existing_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[]);
+        let first = store.add_memory_idempotent("retry me", None, Some("key-1")).unwrap();
+        let retry = store.add_memory_idempotent("retry me", None, Some(" key-1 ")).unwrap();
+        assert_eq!(first.id, retry.id);
+        assert_eq!(store.load().unwrap().len(), 1);
+
+        let buffered = MemoryStore::new(dir.path().join("memory.json").to_str(), Some(LockType::Cli))
+            .with_write_buffer(10, None);
+        let again = buffered.add_memory_idempotent("retry me", None, Some("key-1")).unwrap();
+        assert_eq!(again.id, first.id);
+        assert_eq!(buffered.pending_count(), 0);
+    }
+
+    #[test]
+    fn tags_any_and_all_differ_on_partial_overlap() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[
+            record("rust cli note", &["rust", "cli"]),
+            record("rust only note", &["rust"]),
+            record("cli only note", &["cli"]),
+            record("untagged note", &[]),
+        ]);
+        let texts = |tag_match: TagMatch| -> Vec<String> {
+            let options = SearchOptions {
+                tags: vec!["rust".to_string(), "cli".to_string()],
+                tag_match,
+                ..SearchOptions::default()
+            };
+            let mut texts: Vec<String> = store.search_with_options("note", &options).unwrap()
+                .into_iter()
+                .map(|h| h.text)
+                .collect();
+            texts.sort();
+            texts
+        };
+
+        assert_eq!(texts(TagMatch::Any), vec!["cli only note", "rust cli note", "rust only note"]);
+        assert_eq!(texts(TagMatch::All), vec!["rust cli note"]);
+        assert_eq!(TagMatch::default(), TagMatch::Any);
+    }
+
+    #[test]
+    fn simulated_import_matches_the_real_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = record("already here", &[]);
+        let store = store_with(dir.path(), &[existing.clone(), record("also here", &[])]);
+        let new_one = record("new one", &[]);
+        let mut repeated = record("new one again", &[]);
+        repeated.id = new_one.id.clone();
+        let incoming = serde_json::to_value(vec![existing.clone(), new_one.clone(), record("new two", &[]), repeated]).unwrap();
+        let mut values = incoming.as_array().unwrap().clone();
+        values.push(serde_json::json!({"id": "m_broken"}));
+        let json = serde_json::to_string(&values).unwrap();
+        let before = fs::read(dir.path().join("memory.json")).unwrap();
+
+        let simulated = store.import_json_with_report(&json, true, false).unwrap();
+        assert!(simulated.simulated);
+        assert_eq!((simulated.success, simulated.skipped, simulated.failed), (2, 2, 1));
+        assert_eq!(simulated.skipped_ids, vec![existing.id.clone(), new_one.id.clone()]);
+        assert_eq!(fs::read(dir.path().join("memory.json")).unwrap(), before);
+
+        let real = store.import_json_with_report(&json, false, false).unwrap();
+        assert!(!real.simulated);
+        assert_eq!((real.success, real.skipped, real.failed), (simulated.success, simulated.skipped, simulated.failed));
+        assert_eq!(real.skipped_ids, simulated.skipped_ids);
+        let records = store.load().unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records.iter().filter(|r| r.id == new_one.id).count(), 1);
+    }
+
+    #[test]
+    fn gzip_store_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.json.gz");
+        let store = MemoryStore::new(path.to_str(), Some(LockType::Cli));
+        let added = store.add_memory("compressed rust note", Some(vec!["rust".to_string()])).unwrap();
+
+        let raw = fs::read(&path).unwrap();
+        assert_eq!(raw[..2], GZIP_MAGIC);
+        let loaded = MemoryStore::new(path.to_str(), Some(LockType::Cli)).load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].id, added.id);
+        assert_eq!(loaded[0].text, "compressed rust note");
+
+        // 扩展名不是 .gz 但内容是 gzip 时按魔数识别
+        let renamed = dir.path().join("memory.json");
+        fs::write(&renamed, &raw).unwrap();
+        assert_eq!(MemoryStore::new(renamed.to_str(), Some(LockType::Cli)).load().unwrap()[0].id, added.id);
+    }
+
+    #[test]
+    fn generated_ids_use_the_configured_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = record("from the m_ store", &[]);
+        let config = Config { id_prefix: Some("proj".to_string()), ..Config::default() };
+        let store = store_with(dir.path(), std::slice::from_ref(&legacy)).apply_config(&config);
+
+        let added = store.add_memory("prefixed", None).unwrap();
+        assert!(added.id.starts_with("proj_"), "{}", added.id);
+        assert!(legacy.id.starts_with("m_"));
+
+        let export = serde_json::to_string(&store.load().unwrap()).unwrap();
+        let report = store.import_json_with_report(&export, false, true).unwrap();
+        assert_eq!((report.success, report.skipped), (0, 2));
+    }
+
+    #[test]
+    fn duplicate_ids_are_detected_and_regenerated() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = record("first copy", &[]);
+        let mut second = record("second copy", &[]);
+        second.id = first.id.clone();
+        let store = store_with(dir.path(), &[first.clone(), second, record("unique", &[])]);
+
+        assert_eq!(store.find_duplicate_ids().unwrap(), vec![first.id.clone()]);
+        assert_eq!(store.regenerate_duplicate_ids().unwrap(), 1);
+        assert!(store.find_duplicate_ids().unwrap().is_empty());
+
+        let records = store.load().unwrap();
+        assert_eq!(records[0].id, first.id);
+        assert_ne!(records[1].id, first.id);
+        assert_eq!(records[1].text, "second copy");
+    }
+
+    #[test]
+    fn sort_on_write_gives_byte_identical_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut a = record("a", &[]);
+        a.created_at = "2024-01-02T00:00:00.000Z".to_string();
+        let b = record("b", &[]);
+        let c = record("c", &[]);
+        let path = dir.path().join("memory.json");
+        let store = MemoryStore::new(path.to_str(), Some(LockType::Cli)).with_sort_on_write(true);
+
+        store.save(&[a.clone(), b.clone(), c.clone()]).unwrap();
+        let first = fs::read(&path).unwrap();
+        store.save(&[c.clone(), a.clone(), b.clone()]).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), first);
+
+        let ids: Vec<String> = store.load().unwrap().into_iter().map(|r| r.id).collect();
+        let mut expected = vec![b.id.clone(), c.id.clone()];
+        expected.sort();
+        expected.push(a.id);
+        assert_eq!(ids, expected);
+
+        let unsorted = MemoryStore::new(path.to_str(), Some(LockType::Cli));
+        unsorted.save(&[c.clone(), b.clone()]).unwrap();
+        assert_eq!(unsorted.load().unwrap()[0].id, c.id);
+    }
+
+    #[test]
+    fn profiles_report_non_negative_phase_timings() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[record("rust profile", &["rust"]), record("other", &[])]);
+
+        let (hits, profile) = store.search_with_profile("rust", &SearchOptions::default()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(profile.total_hits, 1);
+        for ms in [profile.load_ms, profile.score_ms, profile.sort_ms] {
+            assert!(ms >= 0.0);
+        }
+
+        let records = store.load().unwrap();
+        let (result, profile) = crate::compress::compress_with_profile(&records, "rust", &crate::compress::CompressOptions::default());
+        assert_eq!(result.included.len(), 1);
+        for ms in [profile.load_ms, profile.score_ms, profile.truncate_ms] {
+            assert!(ms >= 0.0);
+        }
+    }
+
+    #[test]
+    fn keyword_search_matches_only_extracted_keywords() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut substring_only = record("rustacean handbook", &[]);
+        substring_only.keywords = vec!["rustacean".to_string(), "handbook".to_string()];
+        let mut stale = record("text mentions rust", &[]);
+        stale.keywords = vec!["text".to_string()];
+        let store = store_with(dir.path(), &[record("Rust borrow checker", &[]), substring_only, stale]);
+
+        let texts: Vec<String> = store.search_by_keyword("RUST", None).unwrap().into_iter().map(|r| r.text).collect();
+        assert_eq!(texts, vec!["Rust borrow checker"]);
+        assert!(store.search_by_keyword("borr", None).unwrap().is_empty());
+        assert!(store.search_by_keyword("  ", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn load_accepts_bare_arrays_and_wrapped_objects() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.json");
+        let records = vec![record("first", &[]), record("second", &[])];
+        let array = serde_json::to_value(&records).unwrap();
+        let store = MemoryStore::new(path.to_str(), Some(LockType::Cli));
+
+        for raw in [
+            array.clone(),
+            serde_json::json!({"records": array.clone()}),
+            serde_json::json!({"memories": array.clone(), "version": 2}),
+        ] {
+            fs::write(&path, raw.to_string()).unwrap();
+            let texts: Vec<String> = store.load().unwrap().into_iter().map(|r| r.text).collect();
+            assert_eq!(texts, vec!["first", "second"]);
+        }
+
+        fs::write(&path, r#"{"items": []}"#).unwrap();
+        assert_eq!(store.load().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let wrapped = serde_json::json!({"memories": [record("imported", &[])]}).to_string();
+        assert_eq!(store_with(dir.path(), &[]).import_json(&wrapped).unwrap(), (1, 0, 0));
+    }
+
+    #[test]
+    fn tidy_normalizes_tags_keywords_and_field_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.json");
+        fs::write(&path, r#"[
+            {"id": "m_1", "text": "Rust borrow checker", "tags": ["Rust", "rust", " CLI "], "keywords": [], "created_at": "2024-01-01T00:00:00.000Z", "updated_at": "2024-01-01T00:00:00.000Z"},
+            {"id": "m_2", "text": "legacy lock note", "tags": ["Lock"], "createdAt": "2024-01-01T00:00:00.000Z", "updatedAt": "2024-01-01T00:00:00.000Z"}
+        ]"#).unwrap();
+        let store = MemoryStore::new(path.to_str(), Some(LockType::Cli));
+
+        assert_eq!(store.tidy().unwrap(), (2, 2));
+        let sorted = |mut words: Vec<String>| {
+            words.sort();
+            words
+        };
+        let records = store.load().unwrap();
+        assert_eq!(records[0].tags, vec!["cli", "rust"]);
+        assert_eq!(sorted(records[0].keywords.clone()), sorted(extract_keywords("Rust borrow checker")));
+        assert_eq!(records[1].tags, vec!["lock"]);
+        assert_eq!(sorted(records[1].keywords.clone()), sorted(extract_keywords("legacy lock note")));
+        assert!(!fs::read_to_string(&path).unwrap().contains("createdAt"));
+
+        assert_eq!(store.tidy().unwrap(), (2, 0));
+    }
+
+    #[test]
+    fn max_text_len_truncates_or_rejects_at_the_boundary() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = |mode: &str| Config {
+            max_text_len: Some(5),
+            max_text_mode: Some(mode.to_string()),
+            ..Config::default()
+        };
+
+        let truncating = store_with(dir.path(), &[]).apply_config(&config("truncate"));
+        let exact = truncating.add_memory("12345", None).unwrap();
+        assert_eq!(exact.text, "12345");
+        assert!(exact.tags.is_empty());
+        let long = truncating.add_memory("123456", None).unwrap();
+        assert_eq!(long.text, "12345…");
+        assert_eq!(long.tags, vec![TRUNCATED_TAG]);
+
+        let rejecting = store_with(dir.path(), &[]).apply_config(&config("reject"));
+        assert_eq!(rejecting.add_memory("12345", None).unwrap().text, "12345");
+        let err = rejecting.add_memory("123456", None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(rejecting.load().unwrap().len(), 1);
+
+        let unlimited = store_with(dir.path(), &[]).apply_config(&Config::default());
+        assert_eq!(unlimited.add_memory(&"x".repeat(10_000), None).unwrap().text.len(), 10_000);
+    }
+
+    #[test]
+    fn soft_delete_many_reports_each_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = record("a", &[]);
+        let b = record("b", &[]);
+        let mut already = record("already deleted", &[]);
+        already.deleted_at = Some(now_iso());
+        let store = store_with(dir.path(), &[a.clone(), b.clone(), already.clone()]);
+
+        let results = store.soft_delete_many(&[&a.id, "m_missing", &already.id, &b.id]).unwrap();
+        assert_eq!(results, vec![true, false, false, true]);
+        assert!(store.load_active().unwrap().is_empty());
+        assert_eq!(store.metrics().deletes, 2);
+        assert_eq!(store.soft_delete_many(&[&a.id]).unwrap(), vec![false]);
+    }
+
+    #[test]
+    fn load_active_excludes_deleted_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut deleted = record("deleted", &[]);
+        deleted.deleted_at = Some(now_iso());
+        let store = store_with(dir.path(), &[record("active", &[]), deleted]);
+
+        let active: Vec<String> = store.load_active().unwrap().into_iter().map(|r| r.text).collect();
+        assert_eq!(active, vec!["active"]);
+        assert_eq!(store.load().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn implied_tags_expand_on_add_and_in_search_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let implications: HashMap<String, Vec<String>> = [
+            ("rust-async".to_string(), vec!["Rust".to_string()]),
+            ("rust".to_string(), vec!["lang".to_string()]),
+        ].into_iter().collect();
+        let store = store_with(dir.path(), &[record("tokio runtime note", &["rust-async"])])
+            .with_tag_implications(implications);
+
+        let mut tags = store.add_memory("async trait note", Some(vec!["rust-async".to_string(), "rust".to_string()])).unwrap().tags;
+        tags.sort();
+        assert_eq!(tags, vec!["lang", "rust", "rust-async"]);
+
+        let search = |expand_implications: bool| -> Vec<String> {
+            let options = SearchOptions {
+                tags: vec!["rust".to_string()],
+                expand_implications,
+                ..SearchOptions::default()
+            };
+            store.search_with_options("note", &options).unwrap().into_iter().map(|h| h.text).collect()
+        };
+        assert_eq!(search(false), vec!["async trait note"]);
+        let mut expanded = search(true);
+        expanded.sort();
+        assert_eq!(expanded, vec!["async trait note", "tokio runtime note"]);
+
+        assert_eq!(store_with(dir.path(), &[]).add_memory("plain", Some(vec!["rust-async".to_string()])).unwrap().tags, vec!["rust-async"]);
+    }
+
+    #[test]
+    fn directory_store_spreads_adds_across_category_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            category_mapping: Some([
+                ("rust".to_string(), "rust".to_string()),
+                ("git".to_string(), "git".to_string()),
+            ].into_iter().collect()),
+            ..Config::default()
+        };
+        let store = MemoryStore::new(dir.path().to_str(), Some(LockType::Cli)).with_directory_mode(&config);
+
+        let rust = store.add_memory("rust borrow checker note", Some(vec!["rust".to_string()])).unwrap();
+        let git = store.add_memory("git rebase note", Some(vec!["git".to_string()])).unwrap();
+
+        let dir_str = dir.path().to_string_lossy();
+        for (category, added) in [("rust", &rust), ("git", &git)] {
+            let path = category_file_path(&dir_str, category);
+            let ids: Vec<String> = parse_records(&fs::read_to_string(&path).unwrap()).unwrap().into_iter().map(|r| r.id).collect();
+            assert_eq!(ids, vec![added.id.clone()], "{}", path.display());
+        }
+        assert!(!dir.path().join(DEFAULT_MEMORY_PATH).exists());
+
+        // 直接写分类文件的一方（organize、md 导入）持有分类锁时，目录模式的写入会等待
+        let held = crate::lock::acquire_lock(&category_lock_path(&category_file_path(&dir_str, "git")), None).unwrap();
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(held);
+        });
+        let started = Instant::now();
+        store.add_memory("another rust note", Some(vec!["rust".to_string()])).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        release.join().unwrap();
+
+        let hits: Vec<String> = store.search("note", None).unwrap().into_iter().map(|h| h.id).collect();
+        assert_eq!(hits.len(), 3);
+        assert!(hits.contains(&rust.id) && hits.contains(&git.id));
+
+        // 目录模式的锁释放后不留下分类锁文件
+        let leftover_locks: Vec<PathBuf> = fs::read_dir(dir.path()).unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.to_string_lossy().ends_with(".lock"))
+            .collect();
+        assert!(leftover_locks.is_empty(), "{:?}", leftover_locks);
+    }
+
+    #[test]
+    fn directory_store_keeps_records_sharing_an_id_across_category_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_str = dir.path().to_string_lossy().into_owned();
+        let mut rust = record("rust copy", &["rust"]);
+        rust.id = "m_shared".to_string();
+        let mut git = record("git copy", &["git"]);
+        git.id = "m_shared".to_string();
+        atomic_write(&category_file_path(&dir_str, "rust"), &[rust.clone()]).unwrap();
+        atomic_write(&category_file_path(&dir_str, "git"), &[git.clone()]).unwrap();
+        // 旧拼写文件中残留的完全相同的副本只读出一条
+        atomic_write(&dir.path().join(format!("rust{}", crate::direct_organize::LEGACY_CATEGORY_FILE_SUFFIX)), &[rust.clone()]).unwrap();
+        let store = MemoryStore::new(dir.path().to_str(), Some(LockType::Cli)).with_directory_mode(&Config::default());
+
+        let mut texts: Vec<String> = store.load().unwrap().into_iter().map(|r| r.text).collect();
+        texts.sort();
+        assert_eq!(texts, ["git copy", "rust copy"]);
+        assert_eq!(store.find_duplicate_ids().unwrap(), ["m_shared"]);
+
+        // 之后的写入不会删掉其中任何一条，直到 doctor 修复重复 ID
+        store.add_memory("unrelated note", None).unwrap();
+        assert_eq!(store.load().unwrap().len(), 3);
+        assert_eq!(store.regenerate_duplicate_ids().unwrap(), 1);
+        assert!(store.find_duplicate_ids().unwrap().is_empty());
+        let mut texts: Vec<String> = store.load().unwrap().into_iter().map(|r| r.text).collect();
+        texts.sort();
+        assert_eq!(texts, ["git copy", "rust copy", "unrelated note"]);
+    }
+
+    #[test]
+    fn target_file_for_tags_is_the_file_the_add_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_store = store_with(dir.path(), &[]);
+        assert_eq!(file_store.target_file_for_tags(&["rust".to_string()]), dir.path().join("memory.json"));
+
+        let category_dir = dir.path().join("categories");
+        fs::create_dir(&category_dir).unwrap();
+        let store = MemoryStore::new(category_dir.to_str(), Some(LockType::Cli)).with_directory_mode(&Config::default());
+        let tags = vec!["rust".to_string()];
+        let target = store.target_file_for_tags(&tags);
+        assert!(!target.exists());
+        store.add_memory("rust note", Some(tags)).unwrap();
+        assert_eq!(list_category_files(&category_dir.to_string_lossy()).into_iter().map(|(_, path)| path).collect::<Vec<_>>(), [target]);
+    }
+
+    #[test]
+    fn backup_before_write_keeps_the_pre_write_content() {
+        let backups = |dir: &Path, file_name: &str| -> Vec<PathBuf> {
+            fs::read_dir(dir).unwrap()
+                .map(|e| e.unwrap().path())
+                .filter(|p| {
+                    let name = p.file_name().unwrap().to_string_lossy().into_owned();
+                    name.starts_with(&format!("{}.", file_name)) && name.ends_with(".bak")
+                })
+                .collect()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[record("before", &[])]).with_backup_before_write(Some(2));
+        let before = fs::read(dir.path().join("memory.json")).unwrap();
+        store.add_memory("after", None).unwrap();
+        let written = backups(dir.path(), "memory.json");
+        assert_eq!(written.len(), 1);
+        assert_eq!(fs::read(&written[0]).unwrap(), before);
+
+        // 目录模式下每个被重写的分类文件各自备份
+        let category_dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(category_dir.path().to_str(), Some(LockType::Cli))
+            .with_directory_mode(&Config::default())
+            .with_backup_before_write(Some(2));
+        store.add_memory("first", None).unwrap();
+        let category_file = category_file_path(&category_dir.path().to_string_lossy(), "default");
+        assert!(backups(category_dir.path(), &category_file.file_name().unwrap().to_string_lossy()).is_empty());
+        let before = fs::read(&category_file).unwrap();
+        store.add_memory("second", None).unwrap();
+        let written = backups(category_dir.path(), &category_file.file_name().unwrap().to_string_lossy());
+        assert_eq!(written.len(), 1);
+        assert_eq!(fs::read(&written[0]).unwrap(), before);
+        assert_eq!(store.load().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn lenient_import_skips_malformed_records_and_strict_rejects_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[]);
+        let mut values = serde_json::to_value(vec![record("valid one", &[]), record("valid two", &[])]).unwrap()
+            .as_array()
+            .unwrap()
+            .clone();
+        values.insert(1, serde_json::json!({"id": "m_broken", "text": 42}));
+        let json = serde_json::to_string(&values).unwrap();
+
+        assert!(store.import_json_with_report(&json, false, true).is_err());
+        assert!(store.load().unwrap().is_empty());
+
+        let report = store.import_json_with_report(&json, false, false).unwrap();
+        assert_eq!((report.success, report.skipped, report.failed), (2, 0, 1));
+        assert_eq!(report.malformed.len(), 1);
+        assert!(report.malformed[0].starts_with("#2: "), "{}", report.malformed[0]);
+        let mut texts: Vec<String> = store.load().unwrap().into_iter().map(|r| r.text).collect();
+        texts.sort();
+        assert_eq!(texts, vec!["valid one", "valid two"]);
+    }
+
+    #[test]
+    fn source_is_recorded_per_ingestion_path_and_filters_search_and_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[]);
+        store.add_memory_with_source("rust from cli", None, None, Some(crate::record::SOURCE_CLI)).unwrap();
+        store.add_memory_with_source("rust from mcp", None, None, Some(crate::record::SOURCE_MCP)).unwrap();
+        let md = crate::record::file_source("md", "/docs/notes.md");
+        assert_eq!(md, "md:notes.md");
+        store.add_memory_with_source("rust from md", None, None, Some(&md)).unwrap();
+        store.add_memory("rust without source", None).unwrap();
+
+        let mut kept = record("rust kept md", &[]);
+        kept.source = Some("md:old.md".to_string());
+        let imported = serde_json::to_string(&vec![record("rust imported", &[]), kept]).unwrap();
+        assert_eq!(store.import_json(&imported).unwrap(), (2, 0, 0));
+
+        let source_of = |text: &str| {
+            store.load().unwrap().into_iter().find(|r| r.text == text).unwrap().source
+        };
+        assert_eq!(source_of("rust from cli").as_deref(), Some("cli"));
+        assert_eq!(source_of("rust from mcp").as_deref(), Some("mcp"));
+        assert_eq!(source_of("rust from md").as_deref(), Some("md:notes.md"));
+        assert_eq!(source_of("rust without source"), None);
+        assert_eq!(source_of("rust imported").as_deref(), Some(SOURCE_IMPORT_JSON));
+        assert_eq!(source_of("rust kept md").as_deref(), Some("md:old.md"));
+
+        let search = |filter: &str| {
+            let options = SearchOptions { source: Some(filter.to_string()), ..SearchOptions::default() };
+            let mut texts: Vec<String> = store.search_with_options("rust", &options).unwrap().into_iter().map(|h| h.text).collect();
+            texts.sort();
+            texts
+        };
+        assert_eq!(search("MD"), vec!["rust from md", "rust kept md"]);
+        assert_eq!(search("md:notes.md"), vec!["rust from md"]);
+        assert_eq!(search(SOURCE_UNKNOWN), vec!["rust without source"]);
+
+        let sources = store.compute_stats().unwrap().sources;
+        for (source, count) in [("cli", 1), ("mcp", 1), ("md:notes.md", 1), ("md:old.md", 1), (SOURCE_IMPORT_JSON, 1), (SOURCE_UNKNOWN, 1)] {
+            assert_eq!(sources.get(source), Some(&count), "{}", source);
+        }
+    }
+
+    #[test]
+    fn ensure_storage_creates_the_directory_and_reports_unusable_locations() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a/b/memory.json");
+        MemoryStore::new(nested.to_str(), None).ensure_storage().unwrap();
+        assert!(nested.parent().unwrap().is_dir());
+
+        // 父路径是普通文件，目录无法创建
+        let blocker = dir.path().join("blocker");
+        fs::write(&blocker, "").unwrap();
+        let store = MemoryStore::new(blocker.join("memory.json").to_str(), None);
+        let message = store.ensure_storage().unwrap_err().to_string();
+        assert!(message.starts_with("Cannot create memory directory"), "{}", message);
+        assert!(message.contains(&blocker.display().to_string()), "{}", message);
 
-    score
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let read_only = dir.path().join("read-only");
+            fs::create_dir(&read_only).unwrap();
+            fs::set_permissions(&read_only, fs::Permissions::from_mode(0o555)).unwrap();
+            // 以 root 运行时权限位不起作用，此时跳过只读目录的断言
+            if fs::write(read_only.join("probe"), "").is_err() {
+                let store = MemoryStore::new(read_only.join("memory.json").to_str(), None);
+                let message = store.ensure_storage().unwrap_err().to_string();
+                assert!(message.starts_with("Memory directory is not writable"), "{}", message);
+                assert!(message.contains(&read_only.display().to_string()), "{}", message);
+            }
+            fs::set_permissions(&read_only, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn temp_records_are_excluded_by_flag_or_store_default_unless_overridden() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[record("rust kept note", &["rust"]), record("rust scratch note", &[TEMP_TAG])]);
+        let texts = |store: &MemoryStore, exclude_temp: Option<bool>| -> Vec<String> {
+            let options = SearchOptions { exclude_temp, ..SearchOptions::default() };
+            let mut texts: Vec<String> = store.search_with_options("rust", &options).unwrap().into_iter().map(|h| h.text).collect();
+            texts.sort();
+            texts
+        };
+
+        assert_eq!(texts(&store, None), vec!["rust kept note", "rust scratch note"]);
+        assert_eq!(texts(&store, Some(true)), vec!["rust kept note"]);
+
+        let store = store.with_exclude_temp_by_default(true);
+        assert!(store.exclude_temp_by_default());
+        assert_eq!(texts(&store, None), vec!["rust kept note"]);
+        // 单次搜索可以覆盖配置的默认排除
+        assert_eq!(texts(&store, Some(false)), vec!["rust kept note", "rust scratch note"]);
+    }
+
+    #[test]
+    fn equal_scores_are_ordered_by_updated_at_then_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut records: Vec<MemoryRecord> = [("m_b", "2001"), ("m_a", "2000"), ("m_c", "2001"), ("m_d", "2000"), ("m_e", "2002")]
+            .iter()
+            .map(|(id, year)| {
+                let mut r = record("same rust note", &[]);
+                r.id = id.to_string();
+                r.updated_at = format!("{}-01-01T00:00:00.000Z", year);
+                r
+            })
+            .collect();
+        let expected = vec!["m_e", "m_b", "m_c", "m_a", "m_d"];
+
+        for _ in 0..2 {
+            let store = store_with(dir.path(), &records);
+            let hits = store.search("rust", Some(10)).unwrap();
+            assert!(hits.iter().all(|h| h.score == hits[0].score));
+            assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), expected);
+            // 文件中的顺序不影响结果
+            records.reverse();
+        }
+    }
+
+    #[test]
+    fn within_scopes_by_tag_and_ranks_without_the_tag_bonus() {
+        let dir = tempfile::tempdir().unwrap();
+        let tagged = record("tokio runtime", &["rust", "tokio"]);
+        let repeated = record("tokio tokio runtime", &["rust"]);
+        let store = store_with(dir.path(), &[tagged.clone(), repeated.clone(), record("tokio tokio tokio", &["go"])]);
+        let ids = |within: Option<&str>| -> Vec<(String, f64)> {
+            let options = SearchOptions { within: within.map(str::to_string), ..SearchOptions::default() };
+            store.search_with_options("tokio", &options).unwrap().into_iter().map(|h| (h.id, h.score)).collect()
+        };
+
+        // 不限定范围时，带 tokio 标签的记录靠标签加分排在前面
+        let all = ids(None);
+        assert_eq!(all.len(), 3);
+        assert!(all.iter().position(|(id, _)| *id == tagged.id) < all.iter().position(|(id, _)| *id == repeated.id));
+
+        // 限定在 rust 范围内只比较文本和关键词得分
+        let weights = ScoringWeights::default();
+        let scoped = ids(Some("Rust"));
+        let expected: Vec<(String, f64)> = [&repeated, &tagged].iter().map(|r| {
+            let b = score_breakdown(r, "tokio", &weights);
+            (r.id.clone(), b.text_score + b.keyword_score + b.recency)
+        }).collect();
+        assert_eq!(scoped, expected);
+    }
+
+    #[test]
+    fn auto_cutoff_trims_the_tail_below_the_score_cliff() {
+        let dir = tempfile::tempdir().unwrap();
+        let strong = record("tokio runtime tokio runtime tokio runtime", &["tokio", "runtime"]);
+        let middle = record("tokio runtime", &["runtime"]);
+        let tail = [record("tokio once", &[]), record("mentions tokio", &[])];
+        let store = store_with(dir.path(), &[tail[0].clone(), strong.clone(), tail[1].clone(), middle.clone()]);
+        let search = |auto_cutoff: Option<f64>| -> Vec<(String, f64)> {
+            let options = SearchOptions { auto_cutoff, ..SearchOptions::default() };
+            store.search_with_options("tokio runtime", &options).unwrap().into_iter().map(|h| (h.id, h.score)).collect()
+        };
+
+        let all = search(None);
+        assert_eq!(all.len(), 4);
+
+        // 尾部两条只命中一次文本，低于最高分的 30%，在断崖处被截掉
+        let trimmed = search(Some(DEFAULT_AUTO_CUTOFF_RATIO));
+        let ids: Vec<&str> = trimmed.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, [strong.id.as_str(), middle.id.as_str()]);
+        let floor = trimmed[0].1 * DEFAULT_AUTO_CUTOFF_RATIO;
+        assert!(all.iter().filter(|(id, _)| tail.iter().any(|r| r.id == *id)).all(|(_, score)| *score < floor));
+    }
+
+    #[test]
+    fn discovery_finds_a_store_two_directories_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("a").join("b");
+        fs::create_dir_all(&sub).unwrap();
+        let store_path = dir.path().join(DEFAULT_MEMORY_PATH);
+        atomic_write(&store_path, &[record("discovered note", &[])]).unwrap();
+
+        assert_eq!(discover_memory_path(&sub), Some(store_path.clone()));
+        // 更近的存储优先于上层目录中的存储
+        let nearer = dir.path().join("a").join(DEFAULT_MEMORY_PATH);
+        atomic_write(&nearer, &[]).unwrap();
+        assert_eq!(discover_memory_path(&sub), Some(nearer));
+    }
+
+    #[test]
+    fn every_mutation_appends_one_audit_line_with_its_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let duplicate = record("duplicated id", &[]);
+        let store = store_with(dir.path(), &[duplicate.clone(), duplicate])
+            .with_audit_log(Some(PathBuf::from("audit.jsonl")));
+
+        let kept = store.add_memory("kept note", None).unwrap();
+        let deleted = store.add_memory("deleted note", None).unwrap();
+        let purged = store.add_memory("purged note", None).unwrap();
+        assert!(store.soft_delete(&deleted.id).unwrap());
+        assert_eq!(store.purge(Some(&purged.id), None, None).unwrap(), 1);
+        assert_eq!(store.purge_deleted().unwrap(), 1);
+        assert_eq!(store.regenerate_duplicate_ids().unwrap(), 1);
+
+        let log = fs::read_to_string(dir.path().join("audit.jsonl")).unwrap();
+        let entries: Vec<crate::audit::AuditEntry> = log.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let ops: Vec<(AuditOp, &str)> = entries.iter().map(|e| (e.op, e.id.as_str())).collect();
+        assert_eq!(&ops[..6], [
+            (AuditOp::Add, kept.id.as_str()),
+            (AuditOp::Add, deleted.id.as_str()),
+            (AuditOp::Add, purged.id.as_str()),
+            (AuditOp::Delete, deleted.id.as_str()),
+            (AuditOp::Purge, purged.id.as_str()),
+            (AuditOp::Purge, deleted.id.as_str()),
+        ]);
+        assert_eq!(ops.len(), 7);
+        assert_eq!(ops[6].0, AuditOp::Update);
+        // 只记录文本哈希，不记录原文
+        assert_eq!(entries[0].text_hash, crate::audit::text_hash("kept note"));
+        assert!(!log.contains("kept note"));
+    }
+
+    #[test]
+    fn unmapped_tags_are_reported_with_their_default_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut deleted = record("deleted note", &["forgotten"]);
+        deleted.deleted_at = Some("2000-01-02T00:00:00.000Z".to_string());
+        let store = store_with(dir.path(), &[
+            record("typo note", &["rsut"]),
+            record("async note", &["rust", "tokio"]),
+            record("cluster note", &["kubernetes"]),
+            record("cluster in foo", &["kubernetes", "proj:foo"]),
+            record("git note", &["git"]),
+            deleted,
+        ]);
+
+        let unmapped = store.unmapped_tags(&Config::default()).unwrap();
+        let summary: Vec<(&str, usize, usize, Option<&str>)> = unmapped.iter()
+            .map(|u| (u.tag.as_str(), u.count, u.default_count, u.similar_key.as_deref()))
+            .collect();
+        // tokio 的记忆靠 rust 映射分类，不会落入 default；命名空间标签和已删除记忆不报告
+        assert_eq!(summary, [
+            ("kubernetes", 2, 2, None),
+            ("rsut", 1, 1, Some("rust")),
+            ("tokio", 1, 0, None),
+        ]);
+    }
+
+    #[test]
+    fn empty_text_records_are_reported_and_optionally_purged() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = record("real note", &[]);
+        let empty = record("", &["rust"]);
+        let blank = record(" \n\t", &[]);
+        let store = store_with(dir.path(), &[empty.clone(), kept.clone(), blank.clone()]);
+
+        assert_eq!(store.find_empty_text().unwrap(), [empty.id.clone(), blank.id.clone()]);
+        // 只报告不会修改存储
+        assert_eq!(store.load().unwrap().len(), 3);
+
+        assert_eq!(store.purge_empty_text().unwrap(), 2);
+        let ids: Vec<String> = store.load().unwrap().into_iter().map(|r| r.id).collect();
+        assert_eq!(ids, [kept.id]);
+        assert!(store.find_empty_text().unwrap().is_empty());
+        assert_eq!(store.purge_empty_text().unwrap(), 0);
+    }
+
+    #[test]
+    fn deleted_records_are_searched_only_when_included() {
+        let dir = tempfile::tempdir().unwrap();
+        let active = record("rust active note", &[]);
+        let mut deleted = record("rust deleted note", &[]);
+        deleted.deleted_at = Some("2000-01-02T00:00:00.000Z".to_string());
+        let store = store_with(dir.path(), &[active.clone(), deleted.clone()]);
+        let search = |include_deleted: bool| -> Vec<(String, Option<String>)> {
+            let options = SearchOptions { include_deleted, ..SearchOptions::default() };
+            let mut hits: Vec<(String, Option<String>)> = store.search_with_options("rust", &options).unwrap()
+                .into_iter().map(|h| (h.id, h.deleted_at)).collect();
+            hits.sort();
+            hits
+        };
+
+        assert_eq!(search(false), [(active.id.clone(), None)]);
+        let mut expected = vec![(active.id, None), (deleted.id, deleted.deleted_at)];
+        expected.sort();
+        assert_eq!(search(true), expected);
+    }
+
+    #[test]
+    fn copy_and_move_transfer_records_between_stores() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source_dir, target_dir) = (dir.path().join("source"), dir.path().join("target"));
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::create_dir_all(&target_dir).unwrap();
+        let copied = record("copied note", &["rust"]);
+        let moved = record("moved note", &["temp"]);
+        let source = store_with(&source_dir, &[copied.clone(), moved.clone()]);
+        let target = store_with(&target_dir, &[]);
+
+        // 复制：目标存储中保留 ID 和时间戳，源记录不变
+        let copy = source.copy_to(&copied.id, &target, true).unwrap().unwrap();
+        assert_eq!((&copy.id, &copy.created_at, &copy.tags), (&copied.id, &copied.created_at, &copied.tags));
+        assert_eq!(source.load_active().unwrap().len(), 2);
+        assert_eq!(source.copy_to(&copied.id, &target, true).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        let renamed = source.copy_to(&copied.id, &target, false).unwrap().unwrap();
+        assert_ne!(renamed.id, copied.id);
+        assert_eq!(renamed.text, copied.text);
+
+        // 移动：写入目标存储后软删除源记录
+        let moved_copy = source.move_to(&moved.id, &target, true).unwrap().unwrap();
+        assert_eq!(moved_copy.id, moved.id);
+        let source_ids: Vec<String> = source.load_active().unwrap().into_iter().map(|r| r.id).collect();
+        assert_eq!(source_ids, [copied.id.as_str()]);
+        assert!(source.load().unwrap().iter().any(|r| r.id == moved.id && r.deleted_at.is_some()));
+        let target_ids: Vec<String> = target.load_active().unwrap().into_iter().map(|r| r.id).collect();
+        assert_eq!(target_ids, [copied.id.clone(), renamed.id, moved.id.clone()]);
+
+        // 已删除的记录和当前存储都不能作为来源或目标
+        assert!(source.move_to(&moved.id, &target, true).unwrap().is_none());
+        assert_eq!(source.copy_to(&copied.id, &source, false).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn bm25_ranks_a_rare_term_above_a_common_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let rare = record("zebra note", &[]);
+        let common = record("apple apple", &[]);
+        let mut records = vec![common.clone(), rare.clone()];
+        records.extend((0..6).map(|i| record(&format!("apple filler {}", i), &[])));
+        let store = store_with(dir.path(), &records);
+        let top = |ranking: RankingMode| -> String {
+            let options = SearchOptions { ranking: Some(ranking), ..SearchOptions::default() };
+            store.search_with_options("apple zebra", &options).unwrap().remove(0).id
+        };
+
+        // linear 只数命中次数，重复常见词的记录排在前面
+        assert_eq!(top(RankingMode::Linear), common.id);
+        // BM25 按文档频率加权，只出现在一条记录中的词更重要
+        assert_eq!(top(RankingMode::Bm25), rare.id);
+
+        let corpus = Bm25Corpus::build(&records, "apple zebra");
+        let params = Bm25Params::default();
+        assert!(corpus.score(&rare, "zebra", &params) > corpus.score(&common, "apple", &params));
+        assert_eq!(corpus.score(&rare, "apple", &params), 0.0);
+    }
+
+    #[test]
+    fn rename_tag_renames_merges_and_counts_changed_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let renamed = record("workflow only", &["workflow", "rust"]);
+        let merged = record("both tags", &["ci", " Workflow "]);
+        let mixed_case = record("mixed case", &["workflow", "New", "new"]);
+        let untouched = record("untouched", &["ci"]);
+        let store = store_with(dir.path(), &[renamed.clone(), merged.clone(), mixed_case.clone(), untouched.clone()]);
+
+        assert_eq!(store.rename_tag(" WORKFLOW", "ci").unwrap(), 3);
+        let tags_of = |id: &str| store.load().unwrap().into_iter().find(|r| r.id == id).unwrap();
+        assert_eq!(tags_of(&renamed.id).tags, ["ci", "rust"]);
+        assert_eq!(tags_of(&merged.id).tags, ["ci"]);
+        assert_eq!(tags_of(&mixed_case.id).tags, ["ci", "New"]);
+        assert_ne!(tags_of(&renamed.id).updated_at, renamed.updated_at);
+        assert_eq!(tags_of(&untouched.id).updated_at, untouched.updated_at);
+
+        assert_eq!(store.rename_tag("workflow", "ci").unwrap(), 0);
+        assert_eq!(store.rename_tag("ci", "CI").unwrap(), 0);
+        assert_eq!(store.rename_tag(" ", "ci").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn recover_locks_removes_only_stale_locks() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store_with(dir.path(), &[]);
+        let stale = [dir.path().join(STORE_LOCK_FILE), dir.path().join(".cli.lock")];
+        let fresh = dir.path().join(format!(".rust{}", crate::lock::CATEGORY_LOCK_SUFFIX));
+        for path in &stale {
+            let file = fs::File::create(path).unwrap();
+            file.set_modified(std::time::SystemTime::now() - Duration::from_secs(3600)).unwrap();
+        }
+        fs::File::create(&fresh).unwrap();
+
+        assert_eq!(store.recover_locks(Some(300)), 2);
+        assert!(stale.iter().all(|path| !path.exists()));
+        assert!(fresh.exists());
+        assert_eq!(store.recover_locks(Some(300)), 0);
+    }
+
+    #[test]
+    fn stats_snapshots_append_to_the_history_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = record("rust note", &["rust"]);
+        let store = store_with(dir.path(), std::slice::from_ref(&first));
+        assert!(store.read_stats_history().unwrap().is_empty());
+
+        let before = store.record_stats_snapshot().unwrap();
+        store.add_memory("git note", Some(vec!["git".to_string()])).unwrap();
+        store.soft_delete(&first.id).unwrap();
+        let after = store.record_stats_snapshot().unwrap();
+
+        assert_eq!((before.total, before.active, before.deleted, before.distinct_tags), (1, 1, 0, 1));
+        assert_eq!((after.total, after.active, after.deleted, after.distinct_tags), (2, 1, 1, 2));
+        assert_eq!(store.read_stats_history().unwrap(), vec![before, after]);
+
+        let csv = fs::read_to_string(dir.path().join(STATS_HISTORY_FILE)).unwrap();
+        assert_eq!(csv.lines().next(), Some(StatsSnapshot::CSV_HEADER));
+        assert_eq!(csv.lines().count(), 3);
+    }
 }
@@ -1,4 +1,7 @@
-use chrono::{FixedOffset, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+
+/// 存储使用的 ISO 时间格式（毫秒精度，带时区偏移）
+const ISO_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%:z";
 
 /// 返回当前时间作为上海时区的 ISO 字符串
 ///
@@ -8,14 +11,71 @@ pub fn now_iso() -> String {
     let now = Utc::now();
     let shanghai_offset = FixedOffset::east_opt(8 * 3600).unwrap();
     let local_time = now.with_timezone(&shanghai_offset);
-    local_time.format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string()
+    local_time.format(ISO_FORMAT).to_string()
+}
+
+/// 将任意 RFC 3339 时间字符串统一为存储使用的上海时区格式
+///
+/// # 参数
+/// * `ts` - 时间字符串
+///
+/// # 返回
+/// 规范格式的时间字符串，无法解析时返回 None
+pub fn canonicalize_iso(ts: &str) -> Option<String> {
+    let parsed = chrono::DateTime::parse_from_rfc3339(ts.trim()).ok()?;
+    let shanghai_offset = FixedOffset::east_opt(8 * 3600).unwrap();
+    Some(parsed.with_timezone(&shanghai_offset).format(ISO_FORMAT).to_string())
+}
+
+/// 解析时间范围的边界，接受 RFC 3339 时间或 `YYYY-MM-DD` 日期
+/// 日期按上海时区（与 `now_iso()` 一致）解释：起始边界取当天 00:00，结束边界取次日 00:00（即包含当天）
+///
+/// # 参数
+/// * `ts` - 时间或日期字符串
+/// * `end_of_day` - 日期是否作为结束边界
+///
+/// # 返回
+/// 边界时间，无法解析时返回错误说明
+pub fn parse_time_bound(ts: &str, end_of_day: bool) -> Result<DateTime<FixedOffset>, String> {
+    let ts = ts.trim();
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(ts) {
+        return Ok(parsed);
+    }
+    let date = NaiveDate::parse_from_str(ts, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid time bound: {} (expected RFC 3339 or YYYY-MM-DD)", ts))?;
+    let date = if end_of_day {
+        date.succ_opt().ok_or_else(|| format!("Invalid time bound: {}", ts))?
+    } else {
+        date
+    };
+    let shanghai_offset = FixedOffset::east_opt(8 * 3600).unwrap();
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(shanghai_offset).single())
+        .ok_or_else(|| format!("Invalid time bound: {}", ts))
 }
 
+/// 默认的记忆 ID 前缀
+pub const DEFAULT_ID_PREFIX: &str = "m";
+
 /// 生成带时间戳和随机后缀的唯一记忆 ID（上海时区）
 ///
 /// # 返回
 /// 唯一的记忆 ID，格式：m_YYYYMMDDTHHMMSSZ_randomhex
 pub fn make_id() -> String {
+    make_id_with_prefix(DEFAULT_ID_PREFIX)
+}
+
+/// 生成带指定前缀的唯一记忆 ID（上海时区）
+///
+/// # 参数
+/// * `prefix` - ID 前缀（末尾的 `_` 会被去掉，为空时使用默认前缀 `m`）
+///
+/// # 返回
+/// 唯一的记忆 ID，格式：<prefix>_YYYYMMDDTHHMMSSZ_randomhex
+pub fn make_id_with_prefix(prefix: &str) -> String {
+    let prefix = prefix.trim().trim_end_matches('_');
+    let prefix = if prefix.is_empty() { DEFAULT_ID_PREFIX } else { prefix };
+
     let now = Utc::now();
     let shanghai_offset = FixedOffset::east_opt(8 * 3600).unwrap();
     let local_time = now.with_timezone(&shanghai_offset);
@@ -29,5 +89,5 @@ pub fn make_id() -> String {
         })
         .collect();
     
-    format!("m_{}_{}", ts, rand)
+    format!("{}_{}_{}", prefix, ts, rand)
 }
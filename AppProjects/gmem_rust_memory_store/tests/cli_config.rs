@@ -0,0 +1,29 @@
+//! `--config` 指定的配置文件对命令行模式生效
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn custom_config_selects_its_memory_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory_path = dir.path().join("custom").join("memory.json");
+    fs::create_dir_all(memory_path.parent().unwrap()).unwrap();
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, format!("memory_path = {:?}\n", memory_path.to_str().unwrap())).unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_GmemoryStore"))
+            .arg("--config")
+            .arg(&config_path)
+            .args(args)
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    run(&["add", "stored via a custom config"]);
+    assert!(fs::read_to_string(&memory_path).unwrap().contains("stored via a custom config"));
+    assert!(run(&["search", "custom"]).contains("stored via a custom config"));
+    assert!(!dir.path().join(".copilot-memory.json").exists());
+}
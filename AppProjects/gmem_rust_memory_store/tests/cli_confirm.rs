@@ -0,0 +1,51 @@
+//! `purge` 在确认提示中接受 y/yes/是，其他回答取消操作
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// 使用临时配置运行命令行并把 `answer` 写入 stdin，返回 stdout
+fn run_with_answer(dir: &Path, args: &[&str], answer: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_GmemoryStore"))
+        .arg("--config")
+        .arg(dir.join("config.toml"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(answer.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// 记忆文件中的记录条数
+fn record_count(memory_path: &Path) -> usize {
+    let records: Vec<serde_json::Value> = serde_json::from_str(&fs::read_to_string(memory_path).unwrap()).unwrap();
+    records.len()
+}
+
+#[test]
+fn purge_confirmation_accepts_yes_forms_and_cancels_otherwise() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory_path = dir.path().join("memory.json");
+    fs::write(dir.path().join("config.toml"), format!("memory_path = {:?}\n", memory_path.to_str().unwrap())).unwrap();
+    for tag in ["a", "b", "c"] {
+        run_with_answer(dir.path(), &["add", "--tags", tag, "note", tag], "");
+    }
+
+    for answer in ["n\n", "no\n", ""] {
+        let stdout = run_with_answer(dir.path(), &["purge", "--tag", "a"], answer);
+        assert!(stdout.contains("Purge cancelled"), "{:?}: {}", answer, stdout);
+        assert_eq!(record_count(&memory_path), 3);
+    }
+
+    for (answer, tag, left) in [("YES\n", "a", 2), ("是\n", "b", 1), ("y\n", "c", 0)] {
+        let stdout = run_with_answer(dir.path(), &["purge", "--tag", tag], answer);
+        assert!(stdout.contains("Purged 1 memories"), "{:?}: {}", answer, stdout);
+        assert_eq!(record_count(&memory_path), left);
+    }
+}
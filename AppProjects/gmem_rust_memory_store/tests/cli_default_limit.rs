@@ -0,0 +1,33 @@
+//! 配置 `search_default_limit` 后，命令行 search 未指定 --limit 时按配置返回结果数
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// 使用临时配置运行命令行，返回 stdout
+fn run(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_GmemoryStore"))
+        .arg("--config")
+        .arg(dir.join("config.toml"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn search_returns_the_configured_default_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory_path = dir.path().join("memory.json");
+    fs::write(
+        dir.path().join("config.toml"),
+        format!("memory_path = {:?}\nsearch_default_limit = 5\n", memory_path.to_str().unwrap()),
+    ).unwrap();
+    for i in 0..8 {
+        run(dir.path(), &["add", &format!("rust note {}", i)]);
+    }
+
+    assert_eq!(run(dir.path(), &["search", "--ids-only", "rust"]).lines().count(), 5);
+    assert_eq!(run(dir.path(), &["search", "--ids-only", "--limit", "7", "rust"]).lines().count(), 7);
+}
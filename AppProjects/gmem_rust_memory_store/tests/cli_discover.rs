@@ -0,0 +1,36 @@
+//! 未指定记忆路径时从当前目录向上查找已有的存储，`--no-discover` 关闭查找
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// 在指定目录中使用不含 memory_path 的配置运行命令行，返回 stdout
+fn run(cwd: &Path, config: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_GmemoryStore"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn store_is_discovered_from_a_subdirectory_unless_disabled() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(&config, "").unwrap();
+    let sub = dir.path().join("a").join("b");
+    fs::create_dir_all(&sub).unwrap();
+
+    run(dir.path(), &config, &["add", "discovered upward note"]);
+    assert!(dir.path().join(".copilot-memory.json").is_file());
+
+    let found = run(&sub, &config, &["search", "upward"]);
+    assert!(found.contains("discovered upward note"), "{}", found);
+    assert!(!sub.join(".copilot-memory.json").exists());
+
+    let isolated = run(&sub, &config, &["--no-discover", "search", "upward"]);
+    assert!(!isolated.contains("discovered upward note"), "{}", isolated);
+}
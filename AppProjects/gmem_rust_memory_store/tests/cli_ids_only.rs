@@ -0,0 +1,38 @@
+//! `search --ids-only` 每行只输出一个 ID，便于在管道中使用
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// 使用临时配置运行命令行，返回 stdout
+fn run(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_GmemoryStore"))
+        .arg("--config")
+        .arg(dir.join("config.toml"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn search_ids_only_prints_one_id_per_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory_path = dir.path().join("memory.json");
+    fs::write(dir.path().join("config.toml"), format!("memory_path = {:?}\n", memory_path.to_str().unwrap())).unwrap();
+
+    run(dir.path(), &["add", "rust borrow checker"]);
+    run(dir.path(), &["add", "rust lock files"]);
+    run(dir.path(), &["add", "git rebase"]);
+    let records: Vec<serde_json::Value> = serde_json::from_str(&fs::read_to_string(&memory_path).unwrap()).unwrap();
+    let ids: Vec<&str> = records.iter().map(|r| r["id"].as_str().unwrap()).collect();
+
+    // 只有 ID，没有分数、文本或提示信息；命中最多的两条排在前面
+    let stdout = run(dir.path(), &["search", "--ids-only", "rust", "borrow"]);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(!lines.is_empty());
+    assert!(lines.iter().all(|line| ids.contains(line)), "{}", stdout);
+    assert_eq!(lines[0], ids[0]);
+    assert_eq!(lines[1], ids[1]);
+}
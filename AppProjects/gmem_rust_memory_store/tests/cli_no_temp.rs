@@ -0,0 +1,46 @@
+//! `compress --no-temp` 和 `exclude_temp_by_default` 都会跳过带 temp 标签的记忆，`--include-temp` 可以取消默认排除
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// 使用指定配置运行命令行，返回 stdout
+fn run(dir: &Path, config: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_GmemoryStore"))
+        .arg("--config")
+        .arg(dir.join(config))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn temp_memories_are_skipped_by_flag_or_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory_path = dir.path().join("memory.json");
+    let memory_line = format!("memory_path = {:?}\n", memory_path.to_str().unwrap());
+    fs::write(dir.path().join("config.toml"), &memory_line).unwrap();
+    fs::write(dir.path().join("no-temp.toml"), format!("{}exclude_temp_by_default = true\n", memory_line)).unwrap();
+
+    run(dir.path(), "config.toml", &["add", "--tags", "rust", "rust kept note"]);
+    run(dir.path(), "config.toml", &["add", "--tags", "temp", "rust scratch note"]);
+
+    let all = run(dir.path(), "config.toml", &["compress", "rust"]);
+    assert!(all.contains("rust kept note") && all.contains("rust scratch note"), "{}", all);
+
+    for (config, args) in [("config.toml", &["compress", "--no-temp", "rust"][..]), ("no-temp.toml", &["compress", "rust"])] {
+        let output = run(dir.path(), config, args);
+        assert!(output.contains("rust kept note"), "{}", output);
+        assert!(!output.contains("rust scratch note"), "{}", output);
+    }
+
+    let searched = run(dir.path(), "no-temp.toml", &["search", "rust"]);
+    assert!(searched.contains("rust kept note") && !searched.contains("rust scratch note"), "{}", searched);
+
+    for command in ["search", "compress"] {
+        let output = run(dir.path(), "no-temp.toml", &[command, "--include-temp", "rust"]);
+        assert!(output.contains("rust kept note") && output.contains("rust scratch note"), "{}", output);
+    }
+}
@@ -0,0 +1,39 @@
+//! 命令行 search 默认按 `result_preview_len` 截断结果文本，`--full` 显示全文
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// 使用临时配置运行命令行，返回 stdout
+fn run(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_GmemoryStore"))
+        .arg("--config")
+        .arg(dir.join("config.toml"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn long_search_results_are_truncated_unless_full() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory_path = dir.path().join("memory.json");
+    fs::write(
+        dir.path().join("config.toml"),
+        format!("memory_path = {:?}\nresult_preview_len = 40\n", memory_path.to_str().unwrap()),
+    ).unwrap();
+    let long = format!("rust preview {} tail-marker", "filler words ".repeat(20));
+    run(dir.path(), &["add", &long]);
+    run(dir.path(), &["add", "rust short note"]);
+
+    let shown = run(dir.path(), &["search", "rust"]);
+    assert!(!shown.contains("tail-marker"), "{}", shown);
+    assert!(shown.contains("rust preview filler words"), "{}", shown);
+    assert!(shown.contains("…"), "{}", shown);
+    assert!(shown.contains("rust short note"), "{}", shown);
+
+    let full = run(dir.path(), &["search", "--full", "rust"]);
+    assert!(full.contains(&long), "{}", full);
+}
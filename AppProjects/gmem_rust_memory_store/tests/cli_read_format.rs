@@ -0,0 +1,48 @@
+//! `--read --format table|json|markdown` 输出各自的结构
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// 使用临时配置运行命令行，返回 stdout
+fn run(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_GmemoryStore"))
+        .arg("--config")
+        .arg(dir.join("config.toml"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn read_formats_produce_their_structure() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory_path = dir.path().join("memory.json");
+    fs::write(dir.path().join("config.toml"), format!("memory_path = {:?}\n", memory_path.to_str().unwrap())).unwrap();
+    run(dir.path(), &["add", "--tags", "rust", "rust borrow checker"]);
+    run(dir.path(), &["add", "--tags", "git", "git rebase"]);
+    let records: Vec<serde_json::Value> = serde_json::from_str(&fs::read_to_string(&memory_path).unwrap()).unwrap();
+    let ids: Vec<&str> = records.iter().map(|r| r["id"].as_str().unwrap()).collect();
+
+    // 表格：表头 + 每条记忆一行，各列对齐
+    let table = run(dir.path(), &["--read", "--format", "table"]);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", table);
+    assert!(lines[0].starts_with("ID") && lines[0].ends_with("TEXT"), "{}", table);
+    let text_column = lines[0].find("TEXT").unwrap();
+    assert!(lines[1].starts_with(ids[0]) && lines[1][text_column..].starts_with("rust borrow checker"), "{}", table);
+    assert!(lines[2].starts_with(ids[1]) && lines[2][text_column..].starts_with("git rebase"), "{}", table);
+
+    // JSON：可直接解析的记录数组
+    let json: Vec<serde_json::Value> = serde_json::from_str(&run(dir.path(), &["--read", "--format", "json"])).unwrap();
+    assert_eq!(json.iter().map(|r| r["id"].as_str().unwrap()).collect::<Vec<_>>(), ids);
+
+    // markdown：每个分类一个文档，包含各自的记忆
+    let markdown = run(dir.path(), &["--read", "--format", "markdown"]);
+    let git_at = markdown.find("git rebase").unwrap();
+    let rust_at = markdown.find("rust borrow checker").unwrap();
+    assert!(git_at < rust_at, "{}", markdown);
+    assert!(markdown.lines().filter(|l| l.starts_with("# ")).count() >= 2, "{}", markdown);
+}
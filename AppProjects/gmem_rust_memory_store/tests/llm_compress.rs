@@ -0,0 +1,138 @@
+//! `compress_with_llm` 读取配置中的 DeepSeek 地址和密钥，接口由本地模拟服务器代替
+#![cfg(feature = "llm")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+use gmem_rust_memory_store::config::Config;
+use gmem_rust_memory_store::record::MemoryRecord;
+use gmem_rust_memory_store::{compress_with_llm, LlmError};
+
+/// 启动只应答一次请求的 HTTP 服务器
+///
+/// # 参数
+/// * `status` - 状态行（如 `200 OK`）
+/// * `body` - 响应正文
+///
+/// # 返回
+/// (接口地址, 返回收到的原始请求的线程)
+fn serve_once(status: &'static str, body: String) -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let api_base = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                content_length = value.trim().parse().unwrap();
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut payload = vec![0; content_length];
+        reader.read_exact(&mut payload).unwrap();
+        request.push_str(&String::from_utf8_lossy(&payload));
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        request
+    });
+    (api_base, handle)
+}
+
+/// 构造带 `rust` 标签的测试记录
+fn record(id: &str, text: &str) -> MemoryRecord {
+    MemoryRecord {
+        id: id.to_string(),
+        text: text.to_string(),
+        tags: vec!["rust".to_string()],
+        keywords: vec!["rust".to_string()],
+        created_at: "2024-01-01T00:00:00.000Z".to_string(),
+        updated_at: "2024-01-01T00:00:00.000Z".to_string(),
+        deleted_at: None,
+        idempotency_key: None,
+        source: None,
+    }
+}
+
+/// 构造指向模拟服务器的配置
+fn config(api_base: &str, api_key: &str) -> Config {
+    Config {
+        deepseek_api_key: Some(api_key.to_string()),
+        deepseek_api_base: Some(api_base.to_string()),
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn successful_response_becomes_the_markdown() {
+    let body = serde_json::json!({
+        "choices": [{"message": {"role": "assistant", "content": "  # Context\n- rust locks  "}}]
+    })
+    .to_string();
+    let (api_base, server) = serve_once("200 OK", body);
+    let records = vec![record("m_1", "rust lock files keep writers apart")];
+
+    let result = compress_with_llm(&records, "rust", 500, None, &config(&api_base, "sk-test")).await.unwrap();
+    assert_eq!(result.markdown, "# Context\n- rust locks");
+    assert_eq!(result.used, result.markdown.chars().count());
+    assert_eq!(result.included.len(), 1);
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /chat/completions "));
+    assert!(request.to_lowercase().contains("authorization: bearer sk-test"));
+    assert!(request.contains("rust lock files keep writers apart"));
+}
+
+#[tokio::test]
+async fn cjk_output_is_cut_by_characters() {
+    let content = "记忆".repeat(150);
+    let body = serde_json::json!({"choices": [{"message": {"role": "assistant", "content": content}}]}).to_string();
+    let (api_base, server) = serve_once("200 OK", body);
+    let records = vec![record("m_1", "rust lock files keep writers apart")];
+
+    // 300 个字符超过 200 个字符的预算，截断后连同省略号正好 200 个字符（按字节计只会剩下 66 个字）
+    let result = compress_with_llm(&records, "rust", 200, None, &config(&api_base, "sk-test")).await.unwrap();
+    assert_eq!(result.markdown, format!("{}…", content.chars().take(199).collect::<String>()));
+    assert_eq!(result.used, 200);
+    assert_eq!(result.used, result.markdown.chars().count());
+    server.join().unwrap();
+}
+
+#[tokio::test]
+async fn non_success_status_is_a_typed_error() {
+    let (api_base, server) = serve_once("401 Unauthorized", r#"{"error":"invalid key"}"#.to_string());
+    let records = vec![record("m_1", "rust lock files keep writers apart")];
+
+    let err = compress_with_llm(&records, "rust", 500, None, &config(&api_base, "sk-bad")).await.unwrap_err();
+    match err {
+        LlmError::Status { status, body } => {
+            assert_eq!(status, 401);
+            assert!(body.contains("invalid key"));
+        }
+        other => panic!("expected a status error, got {}", other),
+    }
+    server.join().unwrap();
+}
+
+#[tokio::test]
+async fn empty_key_falls_back_to_deterministic_compression() {
+    // 地址指向不存在的服务，退回时不应发出请求
+    let records = vec![record("m_1", "rust lock files keep writers apart")];
+
+    let result = compress_with_llm(&records, "rust", 500, None, &config("http://127.0.0.1:9", "  ")).await.unwrap();
+    assert!(result.markdown.starts_with("# Copilot Context (auto)"));
+    assert!(result.markdown.contains("- (m_1) [rust] rust lock files keep writers apart"));
+}
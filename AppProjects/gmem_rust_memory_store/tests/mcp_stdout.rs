@@ -0,0 +1,99 @@
+//! MCP 服务器的 stdout 只能包含 JSON-RPC 响应，库函数的诊断输出必须走 stderr
+#![cfg(feature = "full")]
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+use gmem_rust_memory_store::lock::STORE_LOCK_FILE;
+use serde_json::{json, Value};
+
+#[test]
+fn tool_diagnostics_do_not_reach_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory_path = dir.path().join("memory.json");
+    let config_path = dir.path().join("config.toml");
+    fs::write(&config_path, format!("memory_path = {:?}\n", memory_path.to_str().unwrap())).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gmemory_mcp_server"))
+        .arg("--config")
+        .arg(&config_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    let mut request = |id: u64, name: &str, arguments: Value| -> String {
+        let line = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {"name": name, "arguments": arguments}
+        });
+        writeln!(stdin, "{}", line).unwrap();
+        stdin.flush().unwrap();
+        let mut response = String::new();
+        stdout.read_line(&mut response).unwrap();
+        response
+    };
+    let mut responses = vec![request(1, "search_memory", json!({"query": "anything"}))];
+
+    // 服务器运行期间留下一个过期的存储锁，下一次写入会在清理它时输出诊断信息
+    let lock = File::create(dir.path().join(STORE_LOCK_FILE)).unwrap();
+    lock.set_modified(SystemTime::now() - Duration::from_secs(3600)).unwrap();
+    drop(lock);
+    responses.push(request(2, "add_memory", json!({"text": "written after a stale lock"})));
+
+    drop(stdin);
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    let mut stderr = String::new();
+    child.stderr.take().unwrap().read_to_string(&mut stderr).unwrap();
+    assert!(child.wait().unwrap().success());
+
+    assert!(rest.is_empty(), "unexpected stdout: {}", rest);
+    for (id, response) in responses.iter().enumerate() {
+        let value: Value = serde_json::from_str(response).unwrap();
+        assert_eq!(value["id"], json!(id + 1));
+        assert!(value.get("error").is_none(), "{}", response);
+    }
+    assert!(stderr.contains("发现过期锁文件"), "stderr: {}", stderr);
+    assert!(fs::read_to_string(&memory_path).unwrap().contains("written after a stale lock"));
+}
+
+#[test]
+fn parse_errors_echo_the_recoverable_id() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    let memory_path = dir.path().join("memory.json");
+    fs::write(&config_path, format!("memory_path = {:?}\n", memory_path.to_str().unwrap())).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gmemory_mcp_server"))
+        .arg("--config")
+        .arg(&config_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, r#"{{"jsonrpc": "2.0", "id": "req-7", "method": "tools/list""#).unwrap();
+    writeln!(stdin, r#"{{"jsonrpc": "2.0", "id": 8, "method": }}"#).unwrap();
+    writeln!(stdin, "not json").unwrap();
+    drop(stdin);
+
+    let mut stdout = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut stdout).unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let responses: Vec<Value> = stdout.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(responses.len(), 3, "{}", stdout);
+    for (response, id) in responses.iter().zip([json!("req-7"), json!(8), Value::Null]) {
+        assert_eq!(response["id"], id);
+        assert_eq!(response["error"]["code"], json!(-32700));
+    }
+}
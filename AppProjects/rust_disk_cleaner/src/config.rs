@@ -1,6 +1,7 @@
 use std::fs;
 use std::path::Path;
 use serde::{Deserialize, Serialize};
+use crate::scanner::{default_threads, DEFAULT_BUFFER_SIZE};
 
 /// 配置结构体
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +10,9 @@ pub struct Config {
     pub scan_paths: Vec<String>,
     /// 排除路径列表
     pub exclude_paths: Vec<String>,
+    /// 包含模式列表（非空时只清理匹配至少一个模式的文件）
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
     /// 文件最大年龄（天）
     pub max_age_days: u64,
     /// 最小文件大小（字节）
@@ -17,6 +21,23 @@ pub struct Config {
     pub dry_run: bool,
     /// 是否输出详细信息
     pub verbose: bool,
+    /// 并发扫描的线程数
+    #[serde(default = "default_threads")]
+    pub threads: usize,
+    /// 扫描结果缓冲上限（等待写入报告的文件信息数）
+    #[serde(default = "default_buffer_size")]
+    pub buffer_size: usize,
+    /// 是否跳过清理确认，直接执行（仅命令行 --yes）
+    #[serde(skip)]
+    pub assume_yes: bool,
+    /// 是否只生成扫描报告，保证不删除文件、不交互（仅命令行 --report-only）
+    #[serde(skip)]
+    pub report_only: bool,
+}
+
+/// 扫描结果缓冲上限的默认值（供 serde 使用）
+fn default_buffer_size() -> usize {
+    DEFAULT_BUFFER_SIZE
 }
 
 impl Default for Config {
@@ -49,10 +70,15 @@ impl Default for Config {
                 "Users\\*\\Music".to_string(),
                 "Users\\*\\Videos".to_string(),
             ],
+            include_patterns: Vec::new(),
             max_age_days: 30,
             min_file_size: 0,
             dry_run: true,
             verbose: true,
+            threads: default_threads(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            assume_yes: false,
+            report_only: false,
         }
     }
 }
@@ -221,6 +247,9 @@ exclude_paths = [
     "Users\\*\\Videos",
 ]
 
+# 包含模式列表（通配符，非空时只清理同时匹配规则和任一模式的文件）
+# include_patterns = ["*.dmp", "*\\Temp\\*.log"]
+
 # 文件最大年龄（天）
 max_age_days = 30
 
@@ -232,6 +261,12 @@ dry_run = true
 
 # 是否输出详细信息
 verbose = true
+
+# 并发扫描的线程数（默认为CPU核心数）
+# threads = 4
+
+# 扫描结果缓冲上限（等待写入报告的文件信息数，限制内存占用）
+buffer_size = 1024
 "#;
         
         // 写入配置文件
@@ -6,17 +6,37 @@ mod utils;
 mod report;
 
 use std::env;
+use std::io::BufRead;
 use std::path::PathBuf;
 use std::time::Instant;
-use scanner::Scanner;
+use scanner::{Scanner, ScanOptions};
 use cleaner::Cleaner;
 use rules::{get_default_rules, get_exclude_paths};
 use config::Config;
-use report::ReportGenerator;
+use report::{ReportGenerator, ScanReportWriter};
 use utils::{format_file_size, ensure_directory_exists, get_current_timestamp};
 
+/// 退出码：全部成功（或用户取消清理）
+const EXIT_SUCCESS: i32 = 0;
+/// 退出码：报告生成失败等其他错误
+const EXIT_FAILURE: i32 = 1;
+/// 退出码：至少一个扫描路径扫描失败
+const EXIT_SCAN_ERROR: i32 = 2;
+/// 退出码：部分文件删除失败
+const EXIT_DELETE_FAILED: i32 = 3;
+/// 退出码：清理模式下没有找到可清理的文件
+const EXIT_NOTHING_TO_CLEAN: i32 = 4;
+
 /// 主函数
 fn main() {
+    std::process::exit(run());
+}
+
+/// 执行扫描和清理流程
+///
+/// 返回值:
+///   - i32: 进程退出码
+fn run() -> i32 {
     let args: Vec<String> = env::args().collect();
 
     // 解析命令行参数
@@ -29,22 +49,40 @@ fn main() {
     // 创建扫描器
     let rules = get_default_rules();
     let exclude_paths = get_exclude_paths();
-    let scanner = Scanner::new(rules, exclude_paths);
+    let scanner = Scanner::new(rules, exclude_paths)
+        .with_include_patterns(config.include_patterns.clone());
+    let scan_options = ScanOptions {
+        threads: config.threads,
+        buffer_size: config.buffer_size,
+    };
+
+    // 预览模式：扫描结果直接流式写入报告，不在内存中保留完整的文件列表
+    if config.dry_run {
+        return match run_preview(&scanner, &config, &scan_options, "reports") {
+            Ok(true) => EXIT_SUCCESS,
+            Ok(false) => EXIT_SCAN_ERROR,
+            Err(e) => {
+                eprintln!("生成扫描报告失败: {}", e);
+                EXIT_FAILURE
+            }
+        };
+    }
 
     // 扫描文件
     println!("开始扫描文件...");
     let scan_start = Instant::now();
 
     let mut all_files = Vec::new();
+    let mut scan_failed = false;
     for scan_path in &config.scan_paths {
         println!("扫描路径: {}", scan_path);
-        match scanner.scan_directory(scan_path) {
-            Ok(files) => {
-                println!("找到 {} 个无用文件", files.len());
-                all_files.extend(files);
+        match scanner.scan_directory_streaming(scan_path, &scan_options, |file_info| all_files.push(file_info)) {
+            Ok(count) => {
+                println!("找到 {} 个无用文件", count);
             }
             Err(e) => {
                 eprintln!("扫描失败: {}", e);
+                scan_failed = true;
             }
         }
     }
@@ -59,61 +97,85 @@ fn main() {
     println!("总大小: {}", format_file_size(total_size));
     println!();
 
-    // 询问是否清理
-    if config.dry_run {
-        println!("预览模式，不会实际删除文件");
-        println!("使用 --clean 参数执行实际清理");
-        println!();
-
-        // 生成扫描报告
-        if let Err(e) = generate_scan_report(&all_files) {
-            eprintln!("生成扫描报告失败: {}", e);
-        }
-    } else {
-        println!("警告：即将删除 {} 个文件，释放 {} 空间",
-            all_files.len(),
-            format_file_size(total_size)
-        );
-        println!("是否继续? (y/n)");
+    if all_files.is_empty() {
+        println!("没有需要清理的文件");
+        return if scan_failed { EXIT_SCAN_ERROR } else { EXIT_NOTHING_TO_CLEAN };
+    }
 
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
+    // 询问是否清理
+    println!("警告：即将删除 {} 个文件，释放 {} 空间",
+        all_files.len(),
+        format_file_size(total_size)
+    );
+    let stdin = std::io::stdin();
+    if !confirm_clean(config.assume_yes, &mut stdin.lock()) {
+        println!("取消清理");
+        return EXIT_SUCCESS;
+    }
 
-        if input.trim().to_lowercase() != "y" {
-            println!("取消清理");
-            return;
-        }
+    // 清理文件
+    println!("开始清理文件...");
+    let clean_start = Instant::now();
 
-        // 清理文件
-        println!("开始清理文件...");
-        let clean_start = Instant::now();
+    let cleaner = Cleaner::new(config.dry_run, config.verbose);
+    match cleaner.clean_files(&all_files) {
+        Ok(result) => {
+            let clean_duration = clean_start.elapsed();
+            println!("清理完成，耗时: {:?}", clean_duration);
+            println!("清理文件数: {}", result.cleaned_files.len());
+            println!("释放空间: {}", format_file_size(result.total_size));
 
-        let cleaner = Cleaner::new(config.dry_run, config.verbose);
-        match cleaner.clean_files(&all_files) {
-            Ok(result) => {
-                let clean_duration = clean_start.elapsed();
-                println!("清理完成，耗时: {:?}", clean_duration);
-                println!("清理文件数: {}", result.cleaned_files.len());
-                println!("释放空间: {}", format_file_size(result.total_size));
+            if !result.failed_files.is_empty() {
+                println!("失败文件数: {}", result.failed_files.len());
+            }
 
-                if !result.failed_files.is_empty() {
-                    println!("失败文件数: {}", result.failed_files.len());
+            // 生成清理报告
+            let report_ok = match generate_clean_report(&result) {
+                Ok(()) => {
+                    println!("报告已生成");
+                    true
                 }
-
-                // 生成清理报告
-                if let Err(e) = generate_clean_report(&result) {
+                Err(e) => {
                     eprintln!("生成清理报告失败: {}", e);
-                } else {
-                    println!("报告已生成");
+                    false
                 }
-            }
-            Err(e) => {
-                eprintln!("清理失败: {}", e);
-            }
+            };
+
+            exit_code_for(&result, scan_failed, report_ok)
+        }
+        Err(e) => {
+            eprintln!("清理失败: {}", e);
+            EXIT_FAILURE
         }
     }
 }
 
+/// 根据清理结果计算退出码
+///
+/// 删除失败优先于扫描失败，因为前者意味着磁盘上仍残留本应删除的文件；
+/// 报告生成失败只在其他环节全部成功时才体现在退出码上。
+///
+/// 参数:
+///   - result: 清理结果
+///   - scan_failed: 是否有扫描路径扫描失败
+///   - report_ok: 清理报告是否生成成功
+///
+/// 返回值:
+///   - i32: 进程退出码
+fn exit_code_for(result: &cleaner::CleanResult, scan_failed: bool, report_ok: bool) -> i32 {
+    if !result.failed_files.is_empty() {
+        EXIT_DELETE_FAILED
+    } else if scan_failed {
+        EXIT_SCAN_ERROR
+    } else if result.cleaned_files.is_empty() {
+        EXIT_NOTHING_TO_CLEAN
+    } else if !report_ok {
+        EXIT_FAILURE
+    } else {
+        EXIT_SUCCESS
+    }
+}
+
 /// 解析命令行参数
 ///
 /// 参数:
@@ -144,10 +206,13 @@ fn parse_args(args: &[String]) -> Config {
     if let Ok(loaded_config) = Config::load_from_file(&config_file_str) {
         config.scan_paths = loaded_config.scan_paths;
         config.exclude_paths = loaded_config.exclude_paths;
+        config.include_patterns = loaded_config.include_patterns;
         config.max_age_days = loaded_config.max_age_days;
         config.min_file_size = loaded_config.min_file_size;
         config.dry_run = loaded_config.dry_run;
         config.verbose = loaded_config.verbose;
+        config.threads = loaded_config.threads;
+        config.buffer_size = loaded_config.buffer_size;
     }
 
     let mut i = 1;
@@ -171,6 +236,10 @@ fn parse_args(args: &[String]) -> Config {
                     i += 1;
                 }
             }
+            "--include" if i + 1 < args.len() => {
+                config.include_patterns.push(args[i + 1].clone());
+                i += 1;
+            }
             "--max-age" => {
                 if i + 1 < args.len() {
                     if let Ok(days) = args[i + 1].parse::<u64>() {
@@ -179,19 +248,40 @@ fn parse_args(args: &[String]) -> Config {
                     i += 1;
                 }
             }
+            "--threads" if i + 1 < args.len() => {
+                if let Ok(threads) = args[i + 1].parse::<usize>() {
+                    config.threads = threads.max(1);
+                }
+                i += 1;
+            }
+            "--buffer" if i + 1 < args.len() => {
+                if let Ok(size) = args[i + 1].parse::<usize>() {
+                    config.buffer_size = size.max(1);
+                }
+                i += 1;
+            }
             "--config" => {
                 if i + 1 < args.len() {
                     if let Ok(loaded_config) = Config::load_from_file(&args[i + 1]) {
                         config.scan_paths = loaded_config.scan_paths;
                         config.exclude_paths = loaded_config.exclude_paths;
+                        config.include_patterns = loaded_config.include_patterns;
                         config.max_age_days = loaded_config.max_age_days;
                         config.min_file_size = loaded_config.min_file_size;
                         config.dry_run = loaded_config.dry_run;
                         config.verbose = loaded_config.verbose;
+                        config.threads = loaded_config.threads;
+                        config.buffer_size = loaded_config.buffer_size;
                     }
                     i += 1;
                 }
             }
+            "--yes" | "-y" => {
+                config.assume_yes = true;
+            }
+            "--report-only" => {
+                config.report_only = true;
+            }
             "--help" => {
                 print_help();
                 std::process::exit(0);
@@ -201,9 +291,51 @@ fn parse_args(args: &[String]) -> Config {
         i += 1;
     }
 
+    // 仅报告模式优先于 --clean 和配置文件中的 dry_run，确保不会删除任何文件
+    if config.report_only {
+        config.dry_run = true;
+    }
+
     config
 }
 
+/// 确认是否执行清理
+///
+/// 参数:
+///   - assume_yes: 是否自动确认（--yes）
+///   - input: 读取用户回答的输入源
+///
+/// 返回值:
+///   - true: 继续清理
+///   - false: 取消清理（包括输入已关闭或读取失败）
+fn confirm_clean<R: BufRead>(assume_yes: bool, input: &mut R) -> bool {
+    if assume_yes {
+        println!("已指定 --yes，自动确认清理");
+        return true;
+    }
+
+    println!("是否继续? (y/yes/是 确认)");
+
+    let mut answer = String::new();
+    match input.read_line(&mut answer) {
+        Ok(0) | Err(_) => false,
+        Ok(_) => is_affirmative(&answer),
+    }
+}
+
+/// 判断用户回答是否表示确认
+///
+/// 参数:
+///   - answer: 用户输入的回答
+///
+/// 返回值:
+///   - true: 回答为 y、yes 或 是（不区分大小写）
+///   - false: 其他回答
+fn is_affirmative(answer: &str) -> bool {
+    let answer = answer.trim();
+    answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes") || answer == "是"
+}
+
 /// 打印帮助信息
 fn print_help() {
     println!("C盘无用文件清理工具");
@@ -216,34 +348,99 @@ fn print_help() {
     println!("  --quiet          安静模式，不输出详细信息");
     println!("  --scan <路径>    添加扫描路径");
     println!("  --exclude <路径>  添加排除路径");
+    println!("  --include <模式>  只清理匹配通配符模式的文件（可重复，与规则同时生效）");
     println!("  --max-age <天数>  设置文件最大年龄（天）");
     println!("  --config <文件>   指定配置文件（默认：config/default_config.toml）");
+    println!("  --threads <数量>  并发扫描的线程数（默认：CPU核心数）");
+    println!("  --buffer <数量>   扫描结果缓冲上限，限制内存占用（默认：1024）");
+    println!("  --yes, -y        跳过确认，直接执行清理（用于计划任务）");
+    println!("  --report-only    只扫描并生成报告，不删除文件、不询问（优先于 --clean）");
     println!("  --help           显示帮助信息");
     println!();
+    println!("退出码:");
+    println!("  0  全部成功（或取消清理）");
+    println!("  1  报告生成失败等其他错误");
+    println!("  2  有扫描路径扫描失败");
+    println!("  3  部分文件删除失败");
+    println!("  4  清理模式下没有找到可清理的文件");
+    println!();
     println!("示例:");
     println!("  disk_cleaner                    # 预览模式，使用默认配置");
     println!("  disk_cleaner --clean           # 执行实际清理");
     println!("  disk_cleaner --scan \"C:\\Temp\" --clean  # 扫描指定路径并清理");
     println!("  disk_cleaner --config custom.toml --clean  # 使用自定义配置文件");
+    println!("  disk_cleaner --include \"*.log\" --include \"*.dmp\"  # 只预览日志和转储文件");
+    println!("  disk_cleaner --clean --yes     # 计划任务中无人值守清理");
+    println!("  disk_cleaner --report-only     # 计划任务中只生成报告");
 }
 
-/// 生成扫描报告
+/// 预览模式：扫描所有路径并把结果流式写入扫描报告
 ///
 /// 参数:
-///   - files: 扫描到的文件列表
+///   - scanner: 文件扫描器
+///   - config: 配置对象
+///   - scan_options: 并发扫描选项
+///   - report_dir: 扫描报告所在目录
 ///
 /// 返回值:
-///   - Ok(()): 报告生成成功
+///   - Ok(true): 报告生成成功，所有路径扫描成功
+///   - Ok(false): 报告生成成功，但有路径扫描失败
 ///   - Err(String): 错误信息
-fn generate_scan_report(files: &[scanner::FileInfo]) -> Result<(), String> {
-    // 确保reports目录存在
-    ensure_directory_exists("reports")?;
+fn run_preview(
+    scanner: &Scanner,
+    config: &Config,
+    scan_options: &ScanOptions,
+    report_dir: &str,
+) -> Result<bool, String> {
+    // 确保报告目录存在
+    ensure_directory_exists(report_dir)?;
 
-    let report_path = format!("reports/scan_report_{}.txt",
+    let report_path = format!("{}/scan_report_{}.txt",
+        report_dir,
         get_current_timestamp()
     );
-    let report_generator = ReportGenerator::new(report_path);
-    report_generator.generate_scan_report(files)
+    let mut writer = ScanReportWriter::create(report_path)?;
+    let mut write_error: Option<String> = None;
+    let mut scan_failed = false;
+
+    println!("开始扫描文件...");
+    let scan_start = Instant::now();
+
+    for scan_path in &config.scan_paths {
+        println!("扫描路径: {}", scan_path);
+        let scanned = scanner.scan_directory_streaming(scan_path, scan_options, |file_info| {
+            if write_error.is_none() {
+                if let Err(e) = writer.write_file(&file_info) {
+                    write_error = Some(e);
+                }
+            }
+        });
+        match scanned {
+            Ok(count) => println!("找到 {} 个无用文件", count),
+            Err(e) => {
+                eprintln!("扫描失败: {}", e);
+                scan_failed = true;
+            }
+        }
+    }
+
+    let scan_duration = scan_start.elapsed();
+    println!("扫描完成，耗时: {:?}", scan_duration);
+    println!("总共找到 {} 个无用文件", writer.file_count());
+    println!();
+    println!("总大小: {}", format_file_size(writer.total_size()));
+    println!();
+    println!("预览模式，不会实际删除文件");
+    println!("使用 --clean 参数执行实际清理");
+    println!();
+
+    if let Some(e) = write_error {
+        return Err(e);
+    }
+    let report_path = writer.output_path().to_string();
+    writer.finish()?;
+    println!("扫描报告: {}", report_path);
+    Ok(!scan_failed)
 }
 
 /// 生成清理报告
@@ -264,3 +461,104 @@ fn generate_clean_report(result: &cleaner::CleanResult) -> Result<(), String> {
     let report_generator = ReportGenerator::new(report_path);
     report_generator.generate_report(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn file(path: &str) -> scanner::FileInfo {
+        scanner::FileInfo {
+            path: path.to_string(),
+            size: 10,
+            file_type: scanner::FileType::TempFile,
+            last_modified: 0,
+        }
+    }
+
+    fn clean_result(cleaned: usize, failed: usize) -> cleaner::CleanResult {
+        cleaner::CleanResult {
+            cleaned_files: (0..cleaned).map(|i| file(&format!("c{}.tmp", i))).collect(),
+            failed_files: (0..failed).map(|i| (file(&format!("f{}.tmp", i)), "denied".to_string())).collect(),
+            total_size: 10 * cleaned as u64,
+        }
+    }
+
+    #[test]
+    fn exit_code_reflects_the_clean_outcome() {
+        // (已清理, 失败, 扫描失败, 报告成功) -> 退出码
+        let cases = [
+            (2, 0, false, true, EXIT_SUCCESS),
+            (2, 0, false, false, EXIT_FAILURE),
+            (2, 0, true, true, EXIT_SCAN_ERROR),
+            (0, 0, true, true, EXIT_SCAN_ERROR),
+            (1, 1, false, true, EXIT_DELETE_FAILED),
+            (1, 1, true, false, EXIT_DELETE_FAILED),
+            (0, 0, false, true, EXIT_NOTHING_TO_CLEAN),
+            (0, 0, false, false, EXIT_NOTHING_TO_CLEAN),
+        ];
+        for (cleaned, failed, scan_failed, report_ok, expected) in cases {
+            let result = clean_result(cleaned, failed);
+            assert_eq!(
+                exit_code_for(&result, scan_failed, report_ok),
+                expected,
+                "cleaned={} failed={} scan_failed={} report_ok={}",
+                cleaned, failed, scan_failed, report_ok
+            );
+        }
+    }
+
+    #[test]
+    fn confirm_clean_accepts_yes_answers() {
+        for answer in ["y\n", "Y\n", "yes\n", " YES \r\n", "是\n"] {
+            assert!(confirm_clean(false, &mut answer.as_bytes()), "{:?}", answer);
+        }
+        for answer in ["n\n", "no\n", "\n", "yess\n", "否\n"] {
+            assert!(!confirm_clean(false, &mut answer.as_bytes()), "{:?}", answer);
+        }
+    }
+
+    #[test]
+    fn confirm_clean_never_reads_input_with_assume_yes_and_refuses_closed_input() {
+        let mut closed: &[u8] = b"";
+        assert!(confirm_clean(true, &mut closed));
+        assert!(!confirm_clean(false, &mut closed));
+    }
+
+    #[test]
+    fn report_only_overrides_clean_and_deletes_nothing() {
+        let config = parse_args(&args(&["disk_cleaner", "--clean", "--yes", "--report-only"]));
+        assert!(config.report_only);
+        assert!(config.dry_run);
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("old.tmp");
+        fs::write(&target, b"data").unwrap();
+        let report_dir = dir.path().join("reports");
+
+        let config = Config {
+            scan_paths: vec![dir.path().to_str().unwrap().to_string()],
+            ..config
+        };
+        let scanner = Scanner::new(
+            vec![scanner::ScanRule {
+                pattern: "*.tmp".to_string(),
+                file_type: scanner::FileType::TempFile,
+                max_age_days: None,
+            }],
+            Vec::new(),
+        );
+        let scanned = run_preview(&scanner, &config, &ScanOptions::default(), report_dir.to_str().unwrap());
+        assert_eq!(scanned, Ok(true));
+        assert!(target.exists());
+
+        let reports: Vec<_> = fs::read_dir(&report_dir).unwrap().collect();
+        assert_eq!(reports.len(), 1);
+        let report = fs::read_to_string(reports[0].as_ref().unwrap().path()).unwrap();
+        assert!(report.contains(target.to_str().unwrap()));
+    }
+}
@@ -100,6 +100,7 @@ impl ReportGenerator {
     /// 返回值:
     ///   - Ok(()): 报告生成成功
     ///   - Err(String): 错误信息
+    #[allow(dead_code)]
     pub fn generate_scan_report(&self, files: &[FileInfo]) -> Result<(), String> {
         let mut output_file = File::create(&self.output_path)
             .map_err(|e| format!("创建报告文件失败: {}", e))?;
@@ -152,3 +153,166 @@ impl ReportGenerator {
         Ok(())
     }
 }
+
+/// 流式扫描报告写入器
+///
+/// 文件列表在扫描过程中逐条写入，只在内存中保留统计数据，
+/// 扫描结束后调用 `finish` 追加统计信息。
+pub struct ScanReportWriter<W: Write = File> {
+    /// 报告输出
+    output_file: W,
+    /// 报告文件路径
+    output_path: String,
+    /// 已写入的文件数
+    file_count: usize,
+    /// 已写入文件的总大小（字节）
+    total_size: u64,
+    /// 按文件类型统计（总大小, 文件数）
+    type_stats: HashMap<FileType, (u64, usize)>,
+}
+
+impl ScanReportWriter<File> {
+    /// 创建报告文件并写入报告头和文件列表标题
+    ///
+    /// 参数:
+    ///   - output_path: 输出文件路径
+    ///
+    /// 返回值:
+    ///   - Ok(ScanReportWriter): 报告写入器
+    ///   - Err(String): 错误信息
+    pub fn create(output_path: String) -> Result<Self, String> {
+        let output_file = File::create(&output_path)
+            .map_err(|e| format!("创建报告文件失败: {}", e))?;
+        Self::from_writer(output_file, output_path)
+    }
+}
+
+impl<W: Write> ScanReportWriter<W> {
+    /// 在任意输出上写入报告头和文件列表标题
+    ///
+    /// 参数:
+    ///   - output_file: 报告输出
+    ///   - output_path: 报告路径（仅用于显示）
+    ///
+    /// 返回值:
+    ///   - Ok(ScanReportWriter): 报告写入器
+    ///   - Err(String): 错误信息
+    pub fn from_writer(mut output_file: W, output_path: String) -> Result<Self, String> {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+
+        writeln!(output_file, "C盘扫描报告").map_err(|e| format!("写入报告失败: {}", e))?;
+        writeln!(output_file, "生成时间: {}", timestamp).map_err(|e| format!("写入报告失败: {}", e))?;
+        writeln!(output_file).map_err(|e| format!("写入报告失败: {}", e))?;
+        writeln!(output_file, "文件列表").map_err(|e| format!("写入报告失败: {}", e))?;
+
+        Ok(ScanReportWriter {
+            output_file,
+            output_path,
+            file_count: 0,
+            total_size: 0,
+            type_stats: HashMap::new(),
+        })
+    }
+
+    /// 写入一个扫描到的文件
+    ///
+    /// 参数:
+    ///   - file_info: 文件信息
+    ///
+    /// 返回值:
+    ///   - Ok(()): 写入成功
+    ///   - Err(String): 错误信息
+    pub fn write_file(&mut self, file_info: &FileInfo) -> Result<(), String> {
+        writeln!(self.output_file, "{} - {} - {} - {}",
+            file_info.path,
+            format_file_type(&file_info.file_type),
+            format_file_size(file_info.size),
+            format_timestamp(file_info.last_modified)
+        ).map_err(|e| format!("写入报告失败: {}", e))?;
+
+        self.file_count += 1;
+        self.total_size += file_info.size;
+        let entry = self.type_stats.entry(file_info.file_type.clone()).or_insert((0u64, 0usize));
+        entry.0 += file_info.size;
+        entry.1 += 1;
+        Ok(())
+    }
+
+    /// 已写入的文件数
+    pub fn file_count(&self) -> usize {
+        self.file_count
+    }
+
+    /// 已写入文件的总大小（字节）
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    /// 报告文件路径
+    pub fn output_path(&self) -> &str {
+        &self.output_path
+    }
+
+    /// 追加统计信息并结束报告
+    ///
+    /// 返回值:
+    ///   - Ok(W): 写入成功，返回报告输出
+    ///   - Err(String): 错误信息
+    pub fn finish(mut self) -> Result<W, String> {
+        writeln!(self.output_file).map_err(|e| format!("写入报告失败: {}", e))?;
+        writeln!(self.output_file, "统计信息").map_err(|e| format!("写入报告失败: {}", e))?;
+        writeln!(self.output_file, "扫描文件数: {}", self.file_count)
+            .map_err(|e| format!("写入报告失败: {}", e))?;
+        writeln!(self.output_file, "总大小: {}", format_file_size(self.total_size))
+            .map_err(|e| format!("写入报告失败: {}", e))?;
+        writeln!(self.output_file).map_err(|e| format!("写入报告失败: {}", e))?;
+
+        writeln!(self.output_file, "按文件类型统计").map_err(|e| format!("写入报告失败: {}", e))?;
+        for (file_type, (size, count)) in &self.type_stats {
+            writeln!(self.output_file, "{}: {} 个文件, {}",
+                format_file_type(file_type),
+                count,
+                format_file_size(*size)
+            ).map_err(|e| format!("写入报告失败: {}", e))?;
+        }
+
+        Ok(self.output_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64) -> FileInfo {
+        FileInfo {
+            path: path.to_string(),
+            size,
+            file_type: FileType::TempFile,
+            last_modified: 0,
+        }
+    }
+
+    #[test]
+    fn scan_report_streams_every_written_file() {
+        let files = [file("/tmp/a.tmp", 100), file("/tmp/b.tmp", 24)];
+        let mut writer = ScanReportWriter::from_writer(Vec::new(), "memory".to_string()).unwrap();
+        for f in &files {
+            writer.write_file(f).unwrap();
+        }
+        assert_eq!(writer.file_count(), 2);
+        assert_eq!(writer.total_size(), 124);
+
+        let report = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let listed: Vec<&str> = report
+            .lines()
+            .skip_while(|l| *l != "文件列表")
+            .skip(1)
+            .take_while(|l| !l.is_empty())
+            .map(|l| l.split(" - ").next().unwrap())
+            .collect();
+        assert_eq!(listed, ["/tmp/a.tmp", "/tmp/b.tmp"]);
+        assert!(report.contains("扫描文件数: 2"));
+        assert!(report.contains(&format!("总大小: {}", format_file_size(124))));
+    }
+}
@@ -1,5 +1,10 @@
+use std::collections::VecDeque;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex};
+use crate::utils::glob_match;
 
 /// 文件信息结构体
 #[derive(Debug, Clone)]
@@ -38,12 +43,53 @@ pub enum FileType {
     Other,
 }
 
+/// 并发扫描选项
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// 并发扫描的线程数（同时打开的目录句柄数不超过该值）
+    pub threads: usize,
+    /// 等待输出的文件信息缓冲上限，写入方处理较慢时扫描线程会阻塞等待
+    pub buffer_size: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            threads: default_threads(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+}
+
+/// 默认的文件信息缓冲上限
+pub const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+/// 默认的扫描线程数（CPU 核心数，无法获取时为 4）
+///
+/// 返回值:
+///   - usize: 线程数
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// 并发扫描共享的目录队列
+struct WorkQueue {
+    /// 待扫描的目录
+    dirs: VecDeque<PathBuf>,
+    /// 正在扫描目录的线程数
+    active: usize,
+}
+
 /// 文件扫描器
 pub struct Scanner {
     /// 扫描规则列表
     rules: Vec<ScanRule>,
     /// 排除路径列表
     exclude_paths: Vec<String>,
+    /// 包含模式列表（非空时只保留至少匹配一个模式的文件）
+    include_patterns: Vec<String>,
 }
 
 /// 扫描规则结构体
@@ -70,9 +116,25 @@ impl Scanner {
         Scanner {
             rules,
             exclude_paths,
+            include_patterns: Vec::new(),
         }
     }
 
+    /// 设置包含模式
+    ///
+    /// 包含模式在规则匹配之外进一步筛选文件：文件必须同时匹配某条扫描规则
+    /// 和至少一个包含模式。模式中含路径分隔符时匹配完整路径，否则只匹配文件名。
+    ///
+    /// 参数:
+    ///   - patterns: 通配符模式列表（支持 `*` 和 `?`）
+    ///
+    /// 返回值:
+    ///   - 设置了包含模式的扫描器
+    pub fn with_include_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.include_patterns = patterns;
+        self
+    }
+
     /// 扫描指定目录
     ///
     /// 参数:
@@ -81,28 +143,127 @@ impl Scanner {
     /// 返回值:
     ///   - Ok(Vec<FileInfo>): 扫描到的文件列表
     ///   - Err(String): 错误信息
+    #[allow(dead_code)]
     pub fn scan_directory(&self, path: &str) -> Result<Vec<FileInfo>, String> {
         let mut files = Vec::new();
-        let path = Path::new(path);
+        self.scan_directory_streaming(path, &ScanOptions::default(), |file_info| files.push(file_info))?;
+        Ok(files)
+    }
 
-        if !path.exists() {
-            return Err(format!("路径不存在: {}", path.display()));
+    /// 使用多个线程并发扫描目录，并把结果逐个交给回调处理
+    ///
+    /// 目录由有界数量的工作线程从共享队列中取出扫描，匹配的文件经过
+    /// 容量为 `buffer_size` 的通道传给调用线程，不会在内存中累积全部结果。
+    ///
+    /// 参数:
+    ///   - path: 要扫描的目录路径
+    ///   - options: 并发扫描选项
+    ///   - on_file: 处理每个匹配文件的回调（在调用线程中执行）
+    ///
+    /// 返回值:
+    ///   - Ok(usize): 匹配的文件数
+    ///   - Err(String): 错误信息（遇到错误后停止扫描，已回调的文件不会撤回）
+    pub fn scan_directory_streaming<F: FnMut(FileInfo)>(
+        &self,
+        path: &str,
+        options: &ScanOptions,
+        mut on_file: F,
+    ) -> Result<usize, String> {
+        let root = Path::new(path);
+
+        if !root.exists() {
+            return Err(format!("路径不存在: {}", root.display()));
         }
 
-        self.scan_recursive(path, &mut files)?;
-        Ok(files)
+        let queue = Mutex::new(WorkQueue {
+            dirs: VecDeque::from([root.to_path_buf()]),
+            active: 0,
+        });
+        let ready = Condvar::new();
+        let stop = AtomicBool::new(false);
+        let first_error: Mutex<Option<String>> = Mutex::new(None);
+        let (sender, receiver) = mpsc::sync_channel::<FileInfo>(options.buffer_size.max(1));
+
+        let mut count = 0;
+        std::thread::scope(|scope| {
+            for _ in 0..options.threads.max(1) {
+                let sender = sender.clone();
+                let (queue, ready, stop, first_error) = (&queue, &ready, &stop, &first_error);
+                scope.spawn(move || {
+                    while let Some(dir) = self.next_dir(queue, ready, stop) {
+                        if let Err(e) = self.scan_dir(&dir, queue, ready, &sender) {
+                            first_error.lock().unwrap().get_or_insert(e);
+                            stop.store(true, Ordering::SeqCst);
+                        }
+                        let mut state = queue.lock().unwrap();
+                        state.active -= 1;
+                        if state.active == 0 && state.dirs.is_empty() {
+                            ready.notify_all();
+                        }
+                    }
+                    // 唤醒仍在等待的线程，使其看到停止标志
+                    ready.notify_all();
+                });
+            }
+            drop(sender);
+
+            for file_info in receiver {
+                on_file(file_info);
+                count += 1;
+            }
+        });
+
+        match first_error.into_inner().unwrap() {
+            Some(e) => Err(e),
+            None => Ok(count),
+        }
     }
 
-    /// 递归扫描目录
+    /// 从共享队列中取出下一个待扫描的目录
+    ///
+    /// 参数:
+    ///   - queue: 共享目录队列
+    ///   - ready: 队列变化通知
+    ///   - stop: 停止标志
+    ///
+    /// 返回值:
+    ///   - Some(PathBuf): 待扫描的目录
+    ///   - None: 所有目录已扫描完或已停止
+    fn next_dir(&self, queue: &Mutex<WorkQueue>, ready: &Condvar, stop: &AtomicBool) -> Option<PathBuf> {
+        let mut state = queue.lock().unwrap();
+        loop {
+            if stop.load(Ordering::SeqCst) {
+                return None;
+            }
+            if let Some(dir) = state.dirs.pop_front() {
+                state.active += 1;
+                return Some(dir);
+            }
+            if state.active == 0 {
+                return None;
+            }
+            state = ready.wait(state).unwrap();
+        }
+    }
+
+    /// 扫描单个目录：子目录放回队列，匹配的文件发送给调用线程
     ///
     /// 参数:
     ///   - path: 要扫描的目录路径
-    ///   - files: 文件列表的引用，用于存储扫描结果
+    ///   - queue: 共享目录队列
+    ///   - ready: 队列变化通知
+    ///   - sender: 文件信息通道
     ///
     /// 返回值:
     ///   - Ok(()): 扫描成功
     ///   - Err(String): 错误信息
-    fn scan_recursive(&self, path: &Path, files: &mut Vec<FileInfo>) -> Result<(), String> {
+    fn scan_dir(
+        &self,
+        path: &Path,
+        queue: &Mutex<WorkQueue>,
+        ready: &Condvar,
+        sender: &mpsc::SyncSender<FileInfo>,
+    ) -> Result<(), String> {
         let entries = fs::read_dir(path)
             .map_err(|e| format!("读取目录失败: {}", e))?;
 
@@ -116,9 +277,13 @@ impl Scanner {
             }
 
             if file_path.is_dir() {
-                self.scan_recursive(&file_path, files)?;
+                queue.lock().unwrap().dirs.push_back(file_path);
+                ready.notify_one();
             } else if let Some(file_info) = self.check_file(&file_path) {
-                files.push(file_info);
+                // 接收方已停止时直接结束
+                if sender.send(file_info).is_err() {
+                    return Ok(());
+                }
             }
         }
 
@@ -137,6 +302,10 @@ impl Scanner {
         let file_name = path.file_name()?.to_str()?;
         let file_path = path.to_str()?;
 
+        if !self.is_included(file_name, file_path) {
+            return None;
+        }
+
         for rule in &self.rules {
             if self.match_pattern(file_name, &rule.pattern) {
                 let metadata = fs::metadata(path).ok()?;
@@ -192,6 +361,28 @@ impl Scanner {
         file_name == pattern
     }
 
+    /// 检查文件是否匹配包含模式
+    ///
+    /// 参数:
+    ///   - file_name: 文件名
+    ///   - file_path: 文件完整路径
+    ///
+    /// 返回值:
+    ///   - true: 未设置包含模式，或至少匹配一个包含模式
+    ///   - false: 不匹配任何包含模式
+    fn is_included(&self, file_name: &str, file_path: &str) -> bool {
+        if self.include_patterns.is_empty() {
+            return true;
+        }
+        self.include_patterns.iter().any(|pattern| {
+            if pattern.contains('/') || pattern.contains('\\') {
+                glob_match(file_path, pattern)
+            } else {
+                glob_match(file_name, pattern)
+            }
+        })
+    }
+
     /// 检查路径是否在排除列表中
     ///
     /// 参数:
@@ -210,3 +401,107 @@ impl Scanner {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str) -> ScanRule {
+        ScanRule {
+            pattern: pattern.to_string(),
+            file_type: FileType::TempFile,
+            max_age_days: None,
+        }
+    }
+
+    /// 在临时目录下建立多层目录，每层放一个 .tmp 文件和一个 .txt 文件
+    fn build_tree(root: &Path, depth: usize, width: usize) -> Vec<String> {
+        let mut expected = Vec::new();
+        let mut dirs = vec![root.to_path_buf()];
+        for level in 0..depth {
+            let mut next = Vec::new();
+            for dir in &dirs {
+                for i in 0..width {
+                    let tmp = dir.join(format!("f{}_{}.tmp", level, i));
+                    fs::write(&tmp, b"x").unwrap();
+                    fs::write(dir.join(format!("keep{}_{}.txt", level, i)), b"x").unwrap();
+                    expected.push(tmp.to_str().unwrap().to_string());
+
+                    let sub = dir.join(format!("d{}_{}", level, i));
+                    fs::create_dir(&sub).unwrap();
+                    next.push(sub);
+                }
+            }
+            dirs = next;
+        }
+        expected.sort();
+        expected
+    }
+
+    #[test]
+    fn streaming_scan_is_complete_with_a_small_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let expected = build_tree(dir.path(), 4, 3);
+        let scanner = Scanner::new(vec![rule("*.tmp")], Vec::new());
+
+        for (threads, buffer_size) in [(1, 1), (4, 1), (8, 2)] {
+            let options = ScanOptions { threads, buffer_size };
+            let mut found = Vec::new();
+            let count = scanner
+                .scan_directory_streaming(dir.path().to_str().unwrap(), &options, |f| found.push(f.path))
+                .unwrap();
+            found.sort();
+            assert_eq!(count, expected.len());
+            assert_eq!(found, expected, "threads={} buffer={}", threads, buffer_size);
+        }
+    }
+
+    fn scan_names(scanner: &Scanner, root: &Path) -> Vec<String> {
+        let mut names: Vec<String> = scanner
+            .scan_directory(root.to_str().unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|f| Path::new(&f.path).strip_prefix(root).unwrap().to_str().unwrap().replace('\\', "/"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn include_patterns_narrow_rule_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("logs")).unwrap();
+        for name in ["a.tmp", "b.log", "c.txt", "logs/d.log", "logs/e.tmp"] {
+            fs::write(root.join(name), b"x").unwrap();
+        }
+        let rules = vec![rule("*.tmp"), rule("*.log")];
+
+        let scanner = Scanner::new(rules.clone(), Vec::new());
+        assert_eq!(scan_names(&scanner, root), ["a.tmp", "b.log", "logs/d.log", "logs/e.tmp"]);
+
+        // 文件名模式：仍需匹配规则，c.txt 虽匹配包含模式但不匹配任何规则
+        let scanner = Scanner::new(rules.clone(), Vec::new())
+            .with_include_patterns(vec!["*.LOG".to_string(), "c.*".to_string()]);
+        assert_eq!(scan_names(&scanner, root), ["b.log", "logs/d.log"]);
+
+        // 含路径分隔符的模式匹配完整路径
+        let scanner = Scanner::new(rules.clone(), Vec::new())
+            .with_include_patterns(vec!["*/logs/*".to_string()]);
+        assert_eq!(scan_names(&scanner, root), ["logs/d.log", "logs/e.tmp"]);
+
+        // 排除路径优先于包含模式
+        let scanner = Scanner::new(rules, vec!["logs".to_string()])
+            .with_include_patterns(vec!["*.log".to_string()]);
+        assert_eq!(scan_names(&scanner, root), ["b.log"]);
+    }
+
+    #[test]
+    fn streaming_scan_reports_missing_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let scanner = Scanner::new(vec![rule("*.tmp")], Vec::new());
+        let result = scanner.scan_directory_streaming(missing.to_str().unwrap(), &ScanOptions::default(), |_| {});
+        assert!(result.is_err());
+    }
+}
@@ -127,3 +127,40 @@ pub fn get_current_timestamp() -> u64 {
         .unwrap_or_default()
         .as_secs()
 }
+
+/// 通配符匹配（支持 `*` 匹配任意个字符，`?` 匹配单个字符，不区分大小写）
+///
+/// 参数:
+///   - text: 待匹配的文本
+///   - pattern: 通配符模式
+///
+/// 返回值:
+///   - true: 匹配成功
+///   - false: 匹配失败
+pub fn glob_match(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+
+    let (mut t, mut p) = (0, 0);
+    // 最近一个 `*` 在模式中的位置，以及它当时对应的文本位置
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // 回溯：让 `*` 多吞一个字符
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}